@@ -0,0 +1,11 @@
+//! Exports arbitrary documents to SVG, which must not panic however strange the shapes are.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tvg::document::Document;
+use tvg::export::svg::{to_svg, SvgOptions};
+
+fuzz_target!(|document: Document| {
+    let _ = to_svg(&document.tags, &SvgOptions::default());
+});
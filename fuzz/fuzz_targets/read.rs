@@ -0,0 +1,17 @@
+//! Reads arbitrary bytes with each parse profile. Reading may fail, but must not panic.
+
+#![no_main]
+
+use libfuzzer_sys::fuzz_target;
+use tvg::document::Document;
+use tvg::read::{ParseProfile, ReadOptions};
+
+fuzz_target!(|data: &[u8]| {
+    for profile in [
+        ParseProfile::Strict,
+        ParseProfile::Compatible,
+        ParseProfile::Forensic,
+    ] {
+        let _ = Document::read_with_options(data, &ReadOptions::with_profile(profile));
+    }
+});
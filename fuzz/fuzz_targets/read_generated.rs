@@ -0,0 +1,46 @@
+//! Reads valid files from `testgen` with some bytes changed. Since most random inputs already
+//! fail at the file header, this gets much further into the layer and palette data than `read`.
+
+#![no_main]
+
+use libfuzzer_sys::arbitrary::{self, Arbitrary};
+use libfuzzer_sys::fuzz_target;
+use tvg::document::{Art, Document};
+use tvg::layer::ComponentType;
+use tvg::read::{EncodingTag, ParseProfile, ReadOptions};
+use tvg::testgen::{generate, Curves, TestDrawing, TestLayer};
+
+#[derive(Debug, Arbitrary)]
+struct Input {
+    shapes: u8,
+    segments: u8,
+    fill: bool,
+    seed: u64,
+    /// Positions (modulo the file length) and the values they're XORed with.
+    changes: Vec<(u16, u8)>,
+}
+
+fuzz_target!(|input: Input| {
+    let layer = TestLayer {
+        curves: Curves::Mixed,
+        component: match input.fill {
+            true => ComponentType::Fill,
+            false => ComponentType::Pencil,
+        },
+        ..TestLayer::new(Art::Line, input.shapes as usize % 8, input.segments as usize % 16)
+    };
+    let drawing = TestDrawing {
+        // uncompressed, so the changes are in the decoded data
+        encoding: EncodingTag::Unco,
+        layers: vec![layer],
+        seed: input.seed,
+        ..Default::default()
+    };
+    let mut file = generate(&drawing).expect("UNCO doesn't need a zlib backend");
+    for (position, value) in input.changes {
+        let len = file.len();
+        file[position as usize % len] ^= value;
+    }
+    let options = ReadOptions::with_profile(ParseProfile::Forensic);
+    let _ = Document::read_with_options(&file[..], &options);
+});
@@ -0,0 +1,129 @@
+#ifndef TVG_H
+#define TVG_H
+
+/* Generated with cbindgen from tvg-capi. Do not edit by hand. */
+
+#include <stdarg.h>
+#include <stdbool.h>
+#include <stddef.h>
+#include <stdint.h>
+#include <stdlib.h>
+
+/**
+ * One of the four art layers of a drawing.
+ */
+typedef enum TvgArt {
+  TVG_ART_UNDERLAY,
+  TVG_ART_COLOR,
+  TVG_ART_LINE,
+  TVG_ART_OVERLAY,
+} TvgArt;
+
+/**
+ * A TVG file.
+ */
+typedef struct TvgDocument TvgDocument;
+
+typedef struct TvgPoint {
+  float x;
+  float y;
+} TvgPoint;
+
+typedef struct TvgRgba {
+  uint8_t r;
+  uint8_t g;
+  uint8_t b;
+  uint8_t a;
+} TvgRgba;
+
+#ifdef __cplusplus
+extern "C" {
+#endif // __cplusplus
+
+/**
+ * Returns the message of the last error on this thread, or NULL if there was none.
+ *
+ * The string is valid until the next failing call on this thread.
+ */
+const char *tvg_last_error(void);
+
+/**
+ * Reads a TVG file from memory. Returns NULL on error.
+ */
+struct TvgDocument *tvg_read(const uint8_t *data, size_t len);
+
+/**
+ * Reads a TVG file from a path (UTF-8). Returns NULL on error.
+ */
+struct TvgDocument *tvg_read_file(const char *path);
+
+/**
+ * Frees a document. Does nothing if it's NULL.
+ */
+void tvg_free(struct TvgDocument *doc);
+
+/**
+ * Returns the number of art layers in the document.
+ */
+size_t tvg_document_layer_count(const struct TvgDocument *doc);
+
+/**
+ * Writes the kind of art layer to `out`. Returns false if the layer doesn't exist.
+ */
+bool tvg_layer_art(const struct TvgDocument *doc, size_t layer, enum TvgArt *out);
+
+/**
+ * Returns the number of shapes in a layer.
+ */
+size_t tvg_layer_shape_count(const struct TvgDocument *doc, size_t layer);
+
+/**
+ * Returns the raw shape type (2 for fills, 3 for strokes, 6 for lines), or 0 if the shape
+ * doesn't exist.
+ */
+uint16_t tvg_shape_type(const struct TvgDocument *doc, size_t layer, size_t shape);
+
+/**
+ * Returns the number of paths in a shape.
+ */
+size_t tvg_shape_path_count(const struct TvgDocument *doc, size_t layer, size_t shape);
+
+/**
+ * Flattens a path into a polyline with the given tolerance (in drawing units).
+ *
+ * Writes at most `capacity` points to `out` and returns the total number of points, so the
+ * required capacity can be queried by passing NULL. Returns 0 if the path doesn't exist.
+ */
+size_t tvg_shape_points(const struct TvgDocument *doc,
+                        size_t layer,
+                        size_t shape,
+                        size_t path,
+                        float tolerance,
+                        struct TvgPoint *out,
+                        size_t capacity);
+
+/**
+ * Writes the palette color ID of a path to `out`. Returns false if the path doesn't exist or has
+ * no color.
+ */
+bool tvg_shape_color_id(const struct TvgDocument *doc,
+                        size_t layer,
+                        size_t shape,
+                        size_t path,
+                        uint64_t *out);
+
+/**
+ * Writes the color of a path, looked up in the palette, to `out`. Returns false if the path
+ * doesn't exist or its color isn't in the palette.
+ */
+bool tvg_shape_color(const struct TvgDocument *doc,
+                     size_t layer,
+                     size_t shape,
+                     size_t path,
+                     struct TvgRgba *out);
+
+#ifdef __cplusplus
+}  // extern "C"
+#endif  // __cplusplus
+
+#endif  /* TVG_H */
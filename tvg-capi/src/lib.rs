@@ -0,0 +1,352 @@
+//! C API for reading TVG files.
+//!
+//! Documents are opaque handles created by [tvg_read] or [tvg_read_file] and freed with
+//! [tvg_free]. Layers, shapes and paths are addressed by index:
+//!
+//! - layers are the art layers that exist in the file, in drawing order
+//! - shapes are in file order
+//! - paths are the non-empty paths of a shape, in file order
+//!
+//! Functions that can fail return NULL or false, and set a message that can be retrieved with
+//! [tvg_last_error]. Out-of-range indices are not errors: counts are 0 and lookups fail.
+//!
+//! Panics never unwind into the caller: if one happens (which would be a bug in this library),
+//! the function fails as if it had returned an error (returning NULL, 0 or false), and the
+//! message starts with `internal error:`.
+//!
+//! The header is generated with `cbindgen --config cbindgen.toml --output include/tvg.h`.
+
+#![allow(clippy::missing_safety_doc)]
+
+use std::any::Any;
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{self, AssertUnwindSafe};
+use std::ptr;
+use tvg::color::Rgba;
+use tvg::document::{Art, Document};
+use tvg::geometry;
+use tvg::layer::{LayerData, ShapeComponentData, ShapePath, VectorShape};
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: impl ToString) {
+    let message = CString::new(message.to_string().replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|error| *error.borrow_mut() = Some(message));
+}
+
+/// Runs the body of an exported function. If it panics, sets the last error and returns
+/// `fallback` instead, since unwinding into C is undefined behavior.
+fn catch_panic<T>(fallback: T, f: impl FnOnce() -> T) -> T {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        set_last_error(format!("internal error: {}", panic_message(&*payload)));
+        fallback
+    })
+}
+
+fn panic_message(payload: &(dyn Any + Send)) -> &str {
+    if let Some(message) = payload.downcast_ref::<&str>() {
+        message
+    } else if let Some(message) = payload.downcast_ref::<String>() {
+        message
+    } else {
+        "panic"
+    }
+}
+
+/// A TVG file.
+pub struct TvgDocument {
+    document: Document,
+}
+
+/// One of the four art layers of a drawing.
+#[repr(C)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TvgArt {
+    Underlay,
+    Color,
+    Line,
+    Overlay,
+}
+
+impl From<Art> for TvgArt {
+    fn from(art: Art) -> Self {
+        match art {
+            Art::Underlay => TvgArt::Underlay,
+            Art::Color => TvgArt::Color,
+            Art::Line => TvgArt::Line,
+            Art::Overlay => TvgArt::Overlay,
+        }
+    }
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TvgPoint {
+    pub x: f32,
+    pub y: f32,
+}
+
+#[repr(C)]
+#[derive(Debug, Clone, Copy, Default)]
+pub struct TvgRgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl TvgDocument {
+    fn layer(&self, layer: usize) -> Option<(Art, &LayerData)> {
+        self.document.layers().nth(layer)
+    }
+
+    fn shape(&self, layer: usize, shape: usize) -> Option<&VectorShape> {
+        match self.layer(layer)?.1 {
+            LayerData::Vector { shapes, .. } => shapes.get(shape),
+            _ => None,
+        }
+    }
+
+    /// Returns the non-empty paths of a shape, with the color ID of their component.
+    fn paths(&self, layer: usize, shape: usize) -> Vec<(&ShapePath, Option<u64>)> {
+        let Some(shape) = self.shape(layer, shape) else {
+            return Vec::new();
+        };
+        let mut paths = Vec::new();
+        for component in &shape.components {
+            let mut color_id = None;
+            for tag in &component.tags {
+                match tag {
+                    ShapeComponentData::Info(info) => color_id = info.color_id,
+                    ShapeComponentData::Path(path) if !path.segments.is_empty() => {
+                        paths.push((path, color_id))
+                    }
+                    _ => (),
+                }
+            }
+        }
+        paths
+    }
+
+    fn path(&self, layer: usize, shape: usize, path: usize) -> Option<(&ShapePath, Option<u64>)> {
+        self.paths(layer, shape).get(path).copied()
+    }
+}
+
+/// Returns the message of the last error on this thread, or NULL if there was none.
+///
+/// The string is valid until the next failing call on this thread.
+#[no_mangle]
+pub extern "C" fn tvg_last_error() -> *const c_char {
+    LAST_ERROR.with(|error| match &*error.borrow() {
+        Some(message) => message.as_ptr(),
+        None => ptr::null(),
+    })
+}
+
+/// Reads a TVG file from memory. Returns NULL on error.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_read(data: *const u8, len: usize) -> *mut TvgDocument {
+    catch_panic(ptr::null_mut(), || {
+        if data.is_null() {
+            set_last_error("data is NULL");
+            return ptr::null_mut();
+        }
+        let data = std::slice::from_raw_parts(data, len);
+        match Document::read(data) {
+            Ok(document) => Box::into_raw(Box::new(TvgDocument { document })),
+            Err(e) => {
+                set_last_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Reads a TVG file from a path (UTF-8). Returns NULL on error.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_read_file(path: *const c_char) -> *mut TvgDocument {
+    catch_panic(ptr::null_mut(), || {
+        if path.is_null() {
+            set_last_error("path is NULL");
+            return ptr::null_mut();
+        }
+        let path = match CStr::from_ptr(path).to_str() {
+            Ok(path) => path,
+            Err(e) => {
+                set_last_error(e);
+                return ptr::null_mut();
+            }
+        };
+        match std::fs::read(path) {
+            Ok(data) => tvg_read(data.as_ptr(), data.len()),
+            Err(e) => {
+                set_last_error(e);
+                ptr::null_mut()
+            }
+        }
+    })
+}
+
+/// Frees a document. Does nothing if it's NULL.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_free(doc: *mut TvgDocument) {
+    catch_panic((), || {
+        if !doc.is_null() {
+            drop(Box::from_raw(doc));
+        }
+    })
+}
+
+/// Returns the number of art layers in the document.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_document_layer_count(doc: *const TvgDocument) -> usize {
+    catch_panic(0, || match doc.as_ref() {
+        Some(doc) => doc.document.layers().count(),
+        None => 0,
+    })
+}
+
+/// Writes the kind of art layer to `out`. Returns false if the layer doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_layer_art(
+    doc: *const TvgDocument,
+    layer: usize,
+    out: *mut TvgArt,
+) -> bool {
+    catch_panic(false, || {
+        match (doc.as_ref().and_then(|doc| doc.layer(layer)), out.as_mut()) {
+            (Some((art, _)), Some(out)) => {
+                *out = art.into();
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Returns the number of shapes in a layer.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_layer_shape_count(doc: *const TvgDocument, layer: usize) -> usize {
+    catch_panic(0, || match doc.as_ref().and_then(|doc| doc.layer(layer)) {
+        Some((_, LayerData::Vector { shapes, .. })) => shapes.len(),
+        _ => 0,
+    })
+}
+
+/// Returns the raw shape type (2 for fills, 3 for strokes, 6 for lines), or 0 if the shape
+/// doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_shape_type(
+    doc: *const TvgDocument,
+    layer: usize,
+    shape: usize,
+) -> u16 {
+    catch_panic(0, || {
+        match doc.as_ref().and_then(|doc| doc.shape(layer, shape)) {
+            Some(shape) => shape.ty.into(),
+            None => 0,
+        }
+    })
+}
+
+/// Returns the number of paths in a shape.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_shape_path_count(
+    doc: *const TvgDocument,
+    layer: usize,
+    shape: usize,
+) -> usize {
+    catch_panic(0, || match doc.as_ref() {
+        Some(doc) => doc.paths(layer, shape).len(),
+        None => 0,
+    })
+}
+
+/// Flattens a path into a polyline with the given tolerance (in drawing units).
+///
+/// Writes at most `capacity` points to `out` and returns the total number of points, so the
+/// required capacity can be queried by passing NULL. Returns 0 if the path doesn't exist.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_shape_points(
+    doc: *const TvgDocument,
+    layer: usize,
+    shape: usize,
+    path: usize,
+    tolerance: f32,
+    out: *mut TvgPoint,
+    capacity: usize,
+) -> usize {
+    catch_panic(0, || {
+        let Some((path, _)) = doc.as_ref().and_then(|doc| doc.path(layer, shape, path)) else {
+            return 0;
+        };
+        let points = geometry::flatten(path, tolerance);
+        if !out.is_null() {
+            let out = std::slice::from_raw_parts_mut(out, capacity);
+            for (out, &(x, y)) in out.iter_mut().zip(&points) {
+                *out = TvgPoint { x, y };
+            }
+        }
+        points.len()
+    })
+}
+
+/// Writes the palette color ID of a path to `out`. Returns false if the path doesn't exist or has
+/// no color.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_shape_color_id(
+    doc: *const TvgDocument,
+    layer: usize,
+    shape: usize,
+    path: usize,
+    out: *mut u64,
+) -> bool {
+    catch_panic(false, || {
+        let color_id = doc.as_ref().and_then(|doc| doc.path(layer, shape, path)?.1);
+        match (color_id, out.as_mut()) {
+            (Some(color_id), Some(out)) => {
+                *out = color_id;
+                true
+            }
+            _ => false,
+        }
+    })
+}
+
+/// Writes the color of a path, looked up in the palette, to `out`. Returns false if the path
+/// doesn't exist or its color isn't in the palette.
+#[no_mangle]
+pub unsafe extern "C" fn tvg_shape_color(
+    doc: *const TvgDocument,
+    layer: usize,
+    shape: usize,
+    path: usize,
+    out: *mut TvgRgba,
+) -> bool {
+    catch_panic(false, || {
+        let Some(doc) = doc.as_ref() else {
+            return false;
+        };
+        let Some(color_id) = doc.path(layer, shape, path).and_then(|(_, id)| id) else {
+            return false;
+        };
+        let Some(palette) = doc.document.palette() else {
+            return false;
+        };
+        let rgba = palette
+            .find(color_id)
+            .and_then(|color| color.rgba())
+            .map(|Rgba { r, g, b, a }| TvgRgba { r, g, b, a });
+        match (rgba, out.as_mut()) {
+            (Some(rgba), Some(out)) => {
+                *out = rgba;
+                true
+            }
+            _ => false,
+        }
+    })
+}
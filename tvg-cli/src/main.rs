@@ -0,0 +1,495 @@
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use std::time::Duration;
+use tvg::pretty::{PrettyOptions, PrettyPrint};
+use tvg::read::{FileData, FileTag, ParseProfile, ReadOptions};
+
+const USAGE: &str = "usage:
+    tvg dump <file> [--max-points <count>] [--profile strict|compatible|forensic]
+        [--trace <output.json>]
+    tvg diff <a> <b> [--epsilon <distance>]
+    tvg inspect <file> [--format text|html] [--summary]
+    tvg sizes <file>
+    tvg stats <file>
+    tvg sanitize <input> <output>
+    tvg grep <color name or ID> [<folder>]
+    tvg convert <folder or pattern> --to svg|png|pdf|lottie [--out <folder>] [--recursive]
+        [--watch]";
+
+fn main() {
+    let args: Vec<String> = std::env::args().skip(1).collect();
+    let Some((command, args)) = args.split_first() else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+
+    match command.as_str() {
+        "dump" => dump(args),
+        "diff" => diff(args),
+        "inspect" => inspect(args),
+        "sizes" => sizes(args),
+        "stats" => stats(args),
+        "sanitize" => sanitize(args),
+        "grep" => grep(args),
+        "convert" => convert(args),
+        _ => {
+            eprintln!("unknown command: {command}\n{USAGE}");
+            exit(-1);
+        }
+    }
+}
+
+/// Splits arguments into positional arguments and `--flag value` pairs.
+fn parse_args<'a>(args: &'a [String], flags: &[&str]) -> (Vec<&'a str>, Vec<(&'a str, &'a str)>) {
+    let mut positional = Vec::new();
+    let mut values = Vec::new();
+    let mut args = args.iter();
+    while let Some(arg) = args.next() {
+        if let Some(flag) = arg.strip_prefix("--") {
+            if !flags.contains(&flag) {
+                eprintln!("unknown option: {arg}\n{USAGE}");
+                exit(-1);
+            }
+            let Some(value) = args.next() else {
+                eprintln!("missing value for {arg}");
+                exit(-1);
+            };
+            values.push((flag, value.as_str()));
+        } else {
+            positional.push(arg.as_str());
+        }
+    }
+    (positional, values)
+}
+
+fn read_file(path: &str) -> Vec<FileData> {
+    read_file_with_options(path, &ReadOptions::default())
+}
+
+fn read_file_with_options(path: &str, options: &ReadOptions) -> Vec<FileData> {
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("failed to open {path}: {e}");
+            exit(-1);
+        }
+    };
+    match tvg::read::read_with_options(file, options) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("failed to read {path}: {e}");
+            exit(-1);
+        }
+    }
+}
+
+fn dump(args: &[String]) {
+    let (paths, flags) = parse_args(args, &["max-points", "profile", "trace"]);
+    let [path] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+    let mut options = PrettyOptions::default();
+    let mut read_options = ReadOptions::default();
+    let mut trace_path = None;
+    for (flag, value) in flags {
+        match flag {
+            "trace" => trace_path = Some(value),
+            "max-points" => {
+                let Ok(max) = value.parse() else {
+                    eprintln!("invalid point count: {value}");
+                    exit(-1);
+                };
+                options.max_points = Some(max);
+            }
+            _ => {
+                let profile = match value {
+                    "strict" => ParseProfile::Strict,
+                    "compatible" => ParseProfile::Compatible,
+                    "forensic" => ParseProfile::Forensic,
+                    _ => {
+                        eprintln!("unknown profile: {value}");
+                        exit(-1);
+                    }
+                };
+                read_options = ReadOptions::with_profile(profile);
+            }
+        }
+    }
+
+    let trace = trace_path.map(|_| tvg::parse_trace::ParseTrace::new());
+    read_options.trace = trace.clone();
+    for item in read_file_with_options(path, &read_options) {
+        print!("{}", item.pretty().options(options));
+    }
+
+    if let (Some(trace), Some(trace_path)) = (trace, trace_path) {
+        let result = std::fs::File::create(trace_path)
+            .and_then(|file| trace.write_chrome_json(std::io::BufWriter::new(file)));
+        if let Err(e) = result {
+            eprintln!("failed to write {trace_path}: {e}");
+            exit(-1);
+        }
+    }
+}
+
+fn diff(args: &[String]) {
+    let (paths, flags) = parse_args(args, &["epsilon"]);
+    let [a, b] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+
+    let mut options = tvg::diff::DiffOptions::default();
+    for (flag, value) in flags {
+        if flag == "epsilon" {
+            options.epsilon = value.parse().unwrap_or_else(|_| {
+                eprintln!("invalid epsilon: {value}");
+                exit(-1);
+            });
+        }
+    }
+
+    let differences = tvg::diff::diff(&read_file(a), &read_file(b), &options);
+    for difference in &differences {
+        println!("{difference}");
+    }
+    if !differences.is_empty() {
+        exit(1);
+    }
+}
+
+fn inspect(args: &[String]) {
+    // the only option without a value
+    let summary = args.iter().any(|arg| arg == "--summary");
+    let args: Vec<_> = args
+        .iter()
+        .filter(|arg| *arg != "--summary")
+        .cloned()
+        .collect();
+    let (paths, flags) = parse_args(&args, &["format"]);
+    let [path] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+    if summary {
+        print_outline(path);
+        return;
+    }
+
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("failed to read {path}: {e}");
+            exit(-1);
+        }
+    };
+    let dump = tvg::inspect::inspect(&data);
+    match flags.iter().find(|(flag, _)| *flag == "format") {
+        None | Some((_, "text")) => print!("{dump}"),
+        Some((_, "html")) => print!("{}", dump.to_html()),
+        Some((_, format)) => {
+            eprintln!("unknown format: {format}");
+            exit(-1);
+        }
+    }
+}
+
+/// Prints the outline of a file as an indented tree, reading as much of it as possible.
+fn print_outline(path: &str) {
+    let options = ReadOptions::with_profile(ParseProfile::Forensic);
+    let document = tvg::document::Document::from(read_file_with_options(path, &options));
+    document.outline().walk(&mut |node, depth| {
+        let mut line = format!("{}{}", "  ".repeat(depth), node.name);
+        if !node.summary.is_empty() {
+            line += &format!(": {}", node.summary);
+        }
+        if node.size > 0 {
+            line += &format!(" [{}]", node.size);
+        }
+        println!("{line}");
+    });
+}
+
+fn sizes(args: &[String]) {
+    let (paths, _) = parse_args(args, &[]);
+    let [path] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+
+    let file = match std::fs::File::open(path) {
+        Ok(file) => file,
+        Err(e) => {
+            eprintln!("failed to open {path}: {e}");
+            exit(-1);
+        }
+    };
+    let blocks = match tvg::read::read_blocks(file, &Default::default()) {
+        Ok(blocks) => blocks,
+        Err(e) => {
+            eprintln!("failed to read {path}: {e}");
+            exit(-1);
+        }
+    };
+
+    println!("{:<8} {:<8} {:>10} {:>10} {:>6}", "tag", "encoding", "stored", "decoded", "ratio");
+    for tvg::read::TagBlock { tag, in_main, block } in blocks {
+        let name = match tag {
+            FileTag::MainData => "main".to_string(),
+            tag => String::from_utf8_lossy(&u32::from(tag).to_be_bytes()).into_owned(),
+        };
+        let name = if in_main { format!("  {name}") } else { name };
+        let (stored, decoded) = (block.encoded_len(), block.decoded_len());
+        let ratio = if decoded > 0 {
+            format!("{:.2}", stored as f64 / decoded as f64)
+        } else {
+            "-".to_string()
+        };
+        let encoding = format!("{:?}", block.encoding).to_uppercase();
+        println!("{name:<8} {encoding:<8} {stored:>10} {decoded:>10} {ratio:>6}");
+    }
+}
+
+fn stats(args: &[String]) {
+    let (paths, _) = parse_args(args, &[]);
+    let [path] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+
+    let data = match std::fs::read(path) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("failed to read {path}: {e}");
+            exit(-1);
+        }
+    };
+    let stats = match tvg::stats::analyze_file(&data, &Default::default()) {
+        Ok(stats) => stats,
+        Err(e) => {
+            eprintln!("failed to read {path}: {e}");
+            exit(-1);
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&stats).unwrap());
+}
+
+fn sanitize(args: &[String]) {
+    let (paths, _) = parse_args(args, &[]);
+    let [input, output] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+
+    let data = match std::fs::read(input) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("failed to read {input}: {e}");
+            exit(-1);
+        }
+    };
+    // there's no writer yet, so this works on the raw bytes
+    let anonymized = match tvg::anonymize::anonymize(&data) {
+        Ok(anonymized) => anonymized,
+        Err(e) => {
+            eprintln!("failed to sanitize {input}: {e}");
+            exit(-1);
+        }
+    };
+    if !anonymized.offsets_preserved() {
+        eprintln!(
+            "note: offsets after these re-encoded blocks differ from the original: {:?}",
+            anonymized.resized
+        );
+    }
+    if let Err(e) = std::fs::write(output, anonymized.data) {
+        eprintln!("failed to write {output}: {e}");
+        exit(-1);
+    }
+}
+
+fn grep(args: &[String]) {
+    let (paths, _) = parse_args(args, &[]);
+    let (query, root) = match paths[..] {
+        [query] => (query, "."),
+        [query, root] => (query, root),
+        _ => {
+            eprintln!("{USAGE}");
+            exit(-1);
+        }
+    };
+
+    let query = tvg::search::ColorQuery::parse(query);
+    let results = tvg::search::search(root, &query, &ReadOptions::default());
+    for error in &results.errors {
+        eprintln!("{error}");
+    }
+    for tvg::search::ColorMatch { path, color } in &results.matches {
+        let id = match color.id() {
+            Some(id) => format!("{id:016x}"),
+            None => "-".to_string(),
+        };
+        let name = color.name().unwrap_or("-");
+        println!("{}\t{id}\t{name}", path.display());
+    }
+    // like grep: 1 if nothing was found
+    if results.matches.is_empty() {
+        exit(1);
+    }
+}
+
+fn convert(args: &[String]) {
+    // the only options without a value
+    let switches = ["--recursive", "--watch"];
+    let recursive = args.iter().any(|arg| arg == "--recursive");
+    let watch = args.iter().any(|arg| arg == "--watch");
+    let args: Vec<_> = args
+        .iter()
+        .filter(|arg| !switches.contains(&arg.as_str()))
+        .cloned()
+        .collect();
+    let (paths, flags) = parse_args(&args, &["to", "out"]);
+    let [input] = paths[..] else {
+        eprintln!("{USAGE}");
+        exit(-1);
+    };
+
+    let mut exporter = None;
+    let mut options = tvg::batch::BatchOptions::default();
+    for (flag, value) in flags {
+        match flag {
+            "to" => {
+                exporter = Some(match value {
+                    "svg" => tvg::batch::Exporter::Svg(Default::default()),
+                    "png" => tvg::batch::Exporter::Png(Default::default()),
+                    "pdf" => tvg::batch::Exporter::Pdf(Default::default()),
+                    "lottie" => tvg::batch::Exporter::Lottie(Default::default()),
+                    _ => {
+                        eprintln!("unknown format: {value}");
+                        exit(-1);
+                    }
+                });
+            }
+            _ => options.output_dir = Some(value.into()),
+        }
+    }
+    let Some(exporter) = exporter else {
+        eprintln!("missing output format (--to)\n{USAGE}");
+        exit(-1);
+    };
+
+    if watch {
+        let dir = Path::new(input).canonicalize().ok();
+        let Some(dir) = dir.filter(|dir| dir.is_dir()) else {
+            eprintln!("--watch needs a folder: {input}");
+            exit(-1);
+        };
+        watch_folder(&dir, recursive, &exporter, &options);
+        return;
+    }
+
+    // a folder means all drawings in it
+    let pattern = if Path::new(input).is_dir() {
+        tvg::batch::folder_pattern(input, recursive)
+    } else {
+        input.to_string()
+    };
+    let report = match tvg::batch::convert(&pattern, &exporter, &options) {
+        Ok(report) => report,
+        Err(e) => {
+            eprintln!("{e}");
+            exit(-1);
+        }
+    };
+    println!("{}", serde_json::to_string_pretty(&report).unwrap());
+    eprintln!(
+        "converted {} files, {} failed",
+        report.converted(),
+        report.failed()
+    );
+    if report.failed() > 0 {
+        exit(1);
+    }
+}
+
+/// Converts the drawings in a folder whenever they're saved, and prints a report for each file as
+/// one line of JSON.
+fn watch_folder(
+    dir: &Path,
+    recursive: bool,
+    exporter: &tvg::batch::Exporter,
+    options: &tvg::batch::BatchOptions,
+) {
+    use notify::{EventKind, RecursiveMode, Watcher};
+
+    let (sender, receiver) = std::sync::mpsc::channel();
+    let mut watcher = match notify::recommended_watcher(sender) {
+        Ok(watcher) => watcher,
+        Err(e) => {
+            eprintln!("failed to watch {}: {e}", dir.display());
+            exit(-1);
+        }
+    };
+    let mode = match recursive {
+        true => RecursiveMode::Recursive,
+        false => RecursiveMode::NonRecursive,
+    };
+    if let Err(e) = watcher.watch(dir, mode) {
+        eprintln!("failed to watch {}: {e}", dir.display());
+        exit(-1);
+    }
+    eprintln!("watching {}", dir.display());
+
+    // drawings that were saved before watching started
+    let pattern = tvg::batch::folder_pattern(dir, recursive);
+    if let Ok(report) = tvg::batch::convert(&pattern, exporter, options) {
+        print_lines(&report);
+    }
+
+    // hashes of the file contents that were converted last, to skip saves without changes
+    let mut converted: HashMap<PathBuf, u64> = HashMap::new();
+    while let Ok(event) = receiver.recv() {
+        // saving a file causes several events, so collect them until it's quiet for a moment
+        let mut paths = HashSet::new();
+        let mut event = Some(event);
+        while let Some(result) = event {
+            match result {
+                Ok(event) if matches!(event.kind, EventKind::Create(_) | EventKind::Modify(_)) => {
+                    paths.extend(event.paths)
+                }
+                Ok(_) => (),
+                Err(e) => eprintln!("watch error: {e}"),
+            }
+            event = receiver.recv_timeout(Duration::from_millis(200)).ok();
+        }
+
+        let mut ready = Vec::new();
+        for path in paths {
+            let is_tvg = path
+                .extension()
+                .is_some_and(|extension| extension.eq_ignore_ascii_case("tvg"));
+            // it may have been deleted again already
+            let Some(data) = is_tvg.then(|| std::fs::read(&path).ok()).flatten() else {
+                continue;
+            };
+            // files that are still being written are converted after a later event
+            if !tvg::read::is_complete(&data) {
+                continue;
+            }
+            let hash = tvg::thumbnail::file_hash(&data);
+            if converted.insert(path.clone(), hash) != Some(hash) {
+                ready.push(path);
+            }
+        }
+        ready.sort();
+        print_lines(&tvg::batch::convert_files(dir, &ready, exporter, options));
+    }
+}
+
+fn print_lines(report: &tvg::batch::BatchReport) {
+    for file in &report.files {
+        println!("{}", serde_json::to_string(file).unwrap());
+    }
+}
@@ -0,0 +1,93 @@
+//! Generated drawings for the benchmarks, from small to pathological, plus the `.tvg` files in the
+//! folder named by the `TVG_BENCH_CORPUS` environment variable, if it's set.
+//!
+//! The generated files are the same on every run, so results can be compared between commits.
+
+use std::path::Path;
+use tvg::document::Art;
+use tvg::layer::ComponentType;
+use tvg::read::EncodingTag;
+use tvg::testgen::{generate, Curves, TestDrawing, TestLayer};
+
+/// A file to benchmark.
+pub struct Fixture {
+    pub name: String,
+    pub data: Vec<u8>,
+}
+
+/// Returns the generated fixtures, and then the corpus files sorted by name.
+pub fn all() -> Vec<Fixture> {
+    let fixture = |name: &str, drawing| Fixture {
+        name: name.into(),
+        data: generate(&drawing).unwrap(),
+    };
+    let fills = |art, shapes, segments| TestLayer {
+        component: ComponentType::Fill,
+        ..TestLayer::new(art, shapes, segments)
+    };
+    let uncompressed = |palette_colors, layers| TestDrawing {
+        encoding: EncodingTag::Unco,
+        palette_colors,
+        layers,
+        ..Default::default()
+    };
+    let mut fixtures = vec![
+        // a quick sketch
+        fixture(
+            "small",
+            TestDrawing {
+                palette_colors: 8,
+                layers: vec![TestLayer::new(Art::Line, 20, 20)],
+                ..Default::default()
+            },
+        ),
+        // a cleaned up character drawing
+        fixture(
+            "medium",
+            TestDrawing {
+                palette_colors: 100,
+                layers: vec![
+                    fills(Art::Color, 500, 20),
+                    TestLayer::new(Art::Line, 500, 40),
+                ],
+                ..Default::default()
+            },
+        ),
+        fixture(
+            "long stroke",
+            uncompressed(
+                1,
+                vec![TestLayer {
+                    curves: Curves::Cubics,
+                    ..TestLayer::new(Art::Line, 1, 50_000)
+                }],
+            ),
+        ),
+        fixture(
+            "many shapes",
+            uncompressed(1, vec![TestLayer::new(Art::Line, 5_000, 10)]),
+        ),
+        fixture(
+            "huge palette",
+            uncompressed(20_000, vec![TestLayer::new(Art::Line, 1, 1)]),
+        ),
+    ];
+    if let Some(dir) = std::env::var_os("TVG_BENCH_CORPUS") {
+        fixtures.extend(corpus(Path::new(&dir)));
+    }
+    fixtures
+}
+
+fn corpus(dir: &Path) -> Vec<Fixture> {
+    let entries = std::fs::read_dir(dir).expect("failed to list TVG_BENCH_CORPUS");
+    let mut fixtures: Vec<_> = entries
+        .map(|entry| entry.expect("failed to list TVG_BENCH_CORPUS").path())
+        .filter(|path| path.extension().is_some_and(|extension| extension == "tvg"))
+        .map(|path| Fixture {
+            name: path.file_name().unwrap().to_string_lossy().into(),
+            data: std::fs::read(&path).expect("failed to read corpus file"),
+        })
+        .collect();
+    fixtures.sort_by(|a, b| a.name.cmp(&b.name));
+    fixtures
+}
@@ -0,0 +1,101 @@
+//! Reading benchmarks on the fixtures (see `fixtures/mod.rs`): whole files, and the layer and
+//! palette decoders on their own, without decompression.
+//!
+//! Run with `cargo bench -p tvg --features testgen`. To add real files, set `TVG_BENCH_CORPUS` to a folder of
+//! `.tvg` files.
+
+mod fixtures;
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use tvg::layer::read_layer_data;
+use tvg::palette::read_palette_data;
+use tvg::read::{read_blocks, FileTag, ReadOptions};
+
+/// Returns the decoded blocks of the tags for which `select` is true, each as an uncompressed
+/// block, so that reading them doesn't include decompression.
+fn uncompressed_blocks(file: &[u8], select: impl Fn(FileTag) -> bool) -> Vec<Vec<u8>> {
+    let options = ReadOptions::default();
+    let blocks = read_blocks(file, &options).unwrap();
+    blocks
+        .into_iter()
+        .filter(|block| select(block.tag))
+        .map(|block| {
+            let data = block.block.decode(&options).unwrap();
+            let mut out = b"UNCO".to_vec();
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend(data);
+            out
+        })
+        .collect()
+}
+
+fn total_len(blocks: &[Vec<u8>]) -> u64 {
+    blocks.iter().map(|block| block.len() as u64).sum()
+}
+
+fn read(c: &mut Criterion) {
+    let mut group = c.benchmark_group("read");
+    for fixture in fixtures::all() {
+        group.throughput(Throughput::Bytes(fixture.data.len() as u64));
+        group.bench_with_input(
+            BenchmarkId::new("file", &fixture.name),
+            &fixture.data,
+            |b, file| b.iter(|| tvg::read::read(&file[..]).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+fn layers(c: &mut Criterion) {
+    let is_layer = |tag| {
+        matches!(
+            tag,
+            FileTag::LayerUnderlay
+                | FileTag::LayerColor
+                | FileTag::LayerLine
+                | FileTag::LayerOverlay
+        )
+    };
+    let options = ReadOptions::default();
+    let mut group = c.benchmark_group("layers");
+    for fixture in fixtures::all() {
+        let blocks = uncompressed_blocks(&fixture.data, is_layer);
+        group.throughput(Throughput::Bytes(total_len(&blocks)));
+        group.bench_with_input(
+            BenchmarkId::new("decode", &fixture.name),
+            &blocks,
+            |b, blocks| {
+                b.iter(|| {
+                    for block in blocks {
+                        read_layer_data(&block[..], &options).unwrap();
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+fn palette(c: &mut Criterion) {
+    let options = ReadOptions::default();
+    let mut group = c.benchmark_group("palette");
+    for fixture in fixtures::all() {
+        let blocks = uncompressed_blocks(&fixture.data, |tag| tag == FileTag::Palette);
+        group.throughput(Throughput::Bytes(total_len(&blocks)));
+        group.bench_with_input(
+            BenchmarkId::new("decode", &fixture.name),
+            &blocks,
+            |b, blocks| {
+                b.iter(|| {
+                    for block in blocks {
+                        read_palette_data(&block[..], &options).unwrap();
+                    }
+                })
+            },
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, read, layers, palette);
+criterion_main!(benches);
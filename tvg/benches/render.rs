@@ -0,0 +1,98 @@
+//! Rendering benchmarks comparing the render qualities on a synthetic drawing.
+//!
+//! Run with `cargo bench -p tvg --features render`.
+
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use tvg::layer::{
+    ComponentInfo, ComponentType, LayerData, LayerTrailer, PathSegment, ShapeComponent,
+    ShapeComponentData, ShapePath, ShapeType, VectorShape,
+};
+use tvg::read::FileData;
+use tvg::render::{render, RenderOptions, RenderQuality};
+
+fn shape(ty: ShapeType, component_type: ComponentType, segments: Vec<PathSegment>) -> VectorShape {
+    let info = ComponentInfo {
+        ty: component_type,
+        color_id: None,
+        pencil_value: None,
+        unknown_prefix: None,
+        unknown_suffix: None,
+    };
+    let path = ShapePath {
+        segments,
+        extra: None,
+    };
+    VectorShape {
+        ty,
+        components: vec![ShapeComponent {
+            tags: vec![
+                ShapeComponentData::Info(info),
+                ShapeComponentData::Path(path),
+            ],
+            trailer: None,
+        }],
+    }
+}
+
+/// A closed path of cubic segments approximating a circle.
+fn circle((cx, cy): (f32, f32), r: f32) -> Vec<PathSegment> {
+    const K: f32 = 0.552_284_8;
+    let point = |x: f32, y: f32| (cx + x * r, cy + y * r);
+    vec![
+        PathSegment::Line(point(1., 0.)),
+        PathSegment::Cubic(point(1., K), point(K, 1.), point(0., 1.)),
+        PathSegment::Cubic(point(-K, 1.), point(-1., K), point(-1., 0.)),
+        PathSegment::Cubic(point(-1., -K), point(-K, -1.), point(0., -1.)),
+        PathSegment::Cubic(point(K, -1.), point(1., -K), point(1., 0.)),
+    ]
+}
+
+/// A drawing with a grid of filled circles in the color art, and wavy strokes in the line art.
+fn drawing() -> Vec<FileData> {
+    let mut fills = Vec::new();
+    let mut strokes = Vec::new();
+    for i in 0..20 {
+        for j in 0..20 {
+            let center = (i as f32 * 100., j as f32 * 100.);
+            fills.push(shape(
+                ShapeType::Fill,
+                ComponentType::Fill,
+                circle(center, 40.),
+            ));
+        }
+        let mut segments = vec![PathSegment::Line((0., i as f32 * 100.))];
+        for x in 1..40 {
+            let (x, y) = (x as f32 * 50., i as f32 * 100. + 50.);
+            segments.push(PathSegment::Cubic((x - 35., y), (x - 15., y - 80.), (x, y)));
+        }
+        strokes.push(shape(ShapeType::Stroke, ComponentType::Pencil, segments));
+    }
+    let layer = |shapes| LayerData::Vector {
+        shapes,
+        trailer: LayerTrailer::default(),
+    };
+    vec![FileData::Main(vec![
+        FileData::LayerColor(layer(fills)),
+        FileData::LayerLine(layer(strokes)),
+    ])]
+}
+
+fn quality(c: &mut Criterion) {
+    let data = drawing();
+    let mut group = c.benchmark_group("render");
+    for quality in [RenderQuality::Fast, RenderQuality::High] {
+        let options = RenderOptions {
+            quality,
+            ..Default::default()
+        };
+        group.bench_with_input(
+            BenchmarkId::new("quality", format!("{quality:?}")),
+            &options,
+            |b, options| b.iter(|| render(&data, options)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, quality);
+criterion_main!(benches);
@@ -0,0 +1,17 @@
+fn main() {
+    let mut args = std::env::args();
+    args.next().expect("no exec arg");
+    let in_path = args.next().expect("missing input file path argument");
+    let out_path = args.next().expect("missing output file path argument");
+
+    let data = std::fs::read(in_path).expect("failed to read file");
+    let data = match tvg::anonymize::anonymize(&data) {
+        Ok(anonymized) => anonymized.data,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(-1);
+        }
+    };
+
+    std::fs::write(out_path, data).expect("failed to write file");
+}
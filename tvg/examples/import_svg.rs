@@ -0,0 +1,24 @@
+//! Imports an SVG file and exports it again, to check what survived the round trip.
+
+use tvg::export::svg::{to_svg, SvgOptions};
+use tvg::import::svg::{import_svg, SvgImportOptions};
+
+fn main() {
+    let mut args = std::env::args();
+    args.next().expect("no exec arg");
+    let file_path = args.next().expect("missing file path argument");
+
+    let svg = std::fs::read_to_string(file_path).expect("failed to read file");
+    let import = match import_svg(&svg, &SvgImportOptions::default()) {
+        Ok(import) => import,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(-1);
+        }
+    };
+
+    let document = import.into_document();
+    let (svg, report) = to_svg(&document.tags, &SvgOptions::default());
+    eprint!("{}", report);
+    print!("{}", svg);
+}
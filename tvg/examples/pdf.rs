@@ -0,0 +1,20 @@
+use tvg::export::pdf::{to_pdf, PdfOptions};
+
+fn main() {
+    let mut args = std::env::args();
+    args.next().expect("no exec arg");
+    let file_path = args.next().expect("missing file path argument");
+
+    let file = std::fs::File::open(file_path).expect("failed to read file");
+    let tvg = match tvg::read::read(file) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(-1);
+        }
+    };
+
+    let (pdf, report) = to_pdf(&tvg, &PdfOptions::default());
+    eprint!("{}", report);
+    std::io::Write::write_all(&mut std::io::stdout(), &pdf).expect("failed to write output");
+}
@@ -1,5 +1,3 @@
-use tvg::read::FileData;
-
 fn main() {
     let mut args = std::env::args();
     args.next().expect("no exec arg");
@@ -15,15 +13,6 @@ fn main() {
     };
 
     for item in tvg {
-        match item {
-            FileData::Main(items) => {
-                println!("<main>");
-                for item in items {
-                    println!("{item:02x?}");
-                }
-                println!("</main>");
-            }
-            item => println!("{item:02x?}"),
-        }
+        print!("{item}");
     }
 }
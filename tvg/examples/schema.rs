@@ -0,0 +1,7 @@
+fn main() {
+    let schema = tvg::schema::document_schema();
+    println!(
+        "{}",
+        serde_json::to_string_pretty(&schema).expect("failed to serialize schema")
+    );
+}
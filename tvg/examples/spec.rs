@@ -0,0 +1,3 @@
+fn main() {
+    print!("{}", tvg::spec::describe().to_markdown());
+}
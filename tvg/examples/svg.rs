@@ -0,0 +1,28 @@
+use tvg::export::svg::{to_svg, SvgOptions};
+use tvg::export::FieldGrid;
+
+fn main() {
+    let mut args = std::env::args();
+    args.next().expect("no exec arg");
+    let file_path = args.next().expect("missing file path argument");
+    let field_grid = args.next().map(|fields| FieldGrid {
+        fields: fields.parse().expect("invalid field grid size"),
+    });
+
+    let file = std::fs::File::open(file_path).expect("failed to read file");
+    let tvg = match tvg::read::read(file) {
+        Ok(data) => data,
+        Err(e) => {
+            eprintln!("{}", e);
+            std::process::exit(-1);
+        }
+    };
+
+    let options = SvgOptions {
+        field_grid,
+        ..Default::default()
+    };
+    let (svg, report) = to_svg(&tvg, &options);
+    eprint!("{}", report);
+    print!("{}", svg);
+}
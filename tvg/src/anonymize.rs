@@ -0,0 +1,335 @@
+//! Replaces identifying data in TVG files with placeholders, for sharing files in bug reports.
+//!
+//! Unlike parsing and re-writing the file, this works on the raw bytes and replaces every
+//! identifying field with a placeholder *of the same length*, so that offsets in a hex editor
+//! still line up with the original file.
+//!
+//! The following fields are replaced:
+//!
+//! - `CERT`: the certificate text
+//! - `SIGN`: the signature bytes
+//! - `TVCI`: the device name (the software name is kept, since it's useful for debugging)
+//! - `TPAL`: the names of the project palettes that colors come from
+//!
+//! Blocks that contain nothing to replace are copied as they are.
+//! If the main data is zlib-compressed, it must be compressed again after replacing the `TVCI`
+//! fields, which usually changes its compressed size and shifts the offsets of all top-level tags
+//! that follow it. The same goes for the palette. [Anonymized::resized] lists the blocks where
+//! this happened.
+//!
+//! [Document::sanitize] removes the same information from a parsed document.
+
+use crate::document::Document;
+use crate::read::{EncodingTag, FileData, FileTag, ReadError, ReadOptions, MAGIC};
+use crate::util::write_encoded_data;
+use byteorder::{ByteOrder, BE, LE};
+
+/// Header length: magic, version, and two mystery u32s.
+const HEADER_LEN: usize = 8 + 4 + 4 + 4;
+
+/// Placeholder text for text fields. Repeated and truncated as needed.
+const TEXT_PLACEHOLDER: &[u8] = b"anonymized ";
+/// Placeholder byte for binary fields.
+const BINARY_PLACEHOLDER: u8 = 0;
+
+/// `TCID` palette color tag, with the color name, ID, and project palette name. The `palette`
+/// module isn't always available, so this isn't `PaletteColorTag::ColorId`.
+const TCID: u32 = 0x54434944;
+
+/// An anonymized file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Anonymized {
+    pub data: Vec<u8>,
+    /// The tags whose blocks were encoded again with a different length than in the original, in
+    /// file order, so that the tags after them moved. Tags in the main data come before the main
+    /// data itself.
+    pub resized: Vec<FileTag>,
+}
+
+impl Anonymized {
+    /// Returns true if every byte is at the same offset as in the original file.
+    pub fn offsets_preserved(&self) -> bool {
+        self.resized.is_empty()
+    }
+}
+
+/// Anonymizes a TVG file.
+pub fn anonymize(input: &[u8]) -> Result<Anonymized, ReadError> {
+    let header = input.get(..HEADER_LEN).ok_or_else(eof)?;
+    if header[..8] != MAGIC {
+        let mut magic = [0; 8];
+        magic.copy_from_slice(&header[..8]);
+        return Err(ReadError::UnexpectedMagic(magic));
+    }
+
+    let mut out = header.to_vec();
+    let mut resized = Vec::new();
+    anonymize_tags(&input[HEADER_LEN..], &mut out, &mut resized)?;
+    Ok(Anonymized { data: out, resized })
+}
+
+impl Document {
+    /// Removes identifying information while keeping the artwork intact, like [anonymize] does
+    /// for raw files:
+    ///
+    /// - the certificate and signature are removed (see [Document::strip_signing])
+    /// - the device name in `TVCI` is cleared, along with its unknown bytes, since it's not known
+    ///   what they contain
+    /// - the project palette names of palette colors are cleared
+    pub fn sanitize(&mut self) {
+        self.strip_signing();
+        sanitize_tags(&mut self.tags);
+    }
+}
+
+fn sanitize_tags(tags: &mut [FileData]) {
+    for tag in tags {
+        match tag {
+            FileData::Main(tags) => sanitize_tags(tags),
+            FileData::Identity {
+                device,
+                unknown,
+                extra,
+                ..
+            } => {
+                device.clear();
+                *unknown = None;
+                *extra = None;
+            }
+            #[cfg(feature = "palette")]
+            FileData::Palette(palette) => {
+                for color in &mut palette.colors {
+                    for tag in &mut color.tags {
+                        if let crate::palette::ColorData::ColorId { palette, .. } = tag {
+                            palette.clear();
+                        }
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+}
+
+fn eof() -> ReadError {
+    ReadError::Io(std::io::ErrorKind::UnexpectedEof.into())
+}
+
+fn get(input: &[u8], start: usize, len: usize) -> Result<&[u8], ReadError> {
+    start
+        .checked_add(len)
+        .and_then(|end| input.get(start..end))
+        .ok_or_else(eof)
+}
+
+fn fill_text(data: &mut [u8]) {
+    for (i, byte) in data.iter_mut().enumerate() {
+        *byte = TEXT_PLACEHOLDER[i % TEXT_PLACEHOLDER.len()];
+    }
+}
+
+fn anonymize_tags(
+    mut input: &[u8],
+    out: &mut Vec<u8>,
+    resized: &mut Vec<FileTag>,
+) -> Result<(), ReadError> {
+    while !input.is_empty() {
+        if input == [0] {
+            // trailing 0 byte at EOF
+            out.push(0);
+            break;
+        }
+
+        let tag = BE::read_u32(get(input, 0, 4)?);
+        let tag = FileTag::try_from(tag).map_err(|e| ReadError::UnknownFileTag(e.number))?;
+
+        let len = match tag {
+            FileTag::Cert => {
+                let len = LE::read_u32(get(input, 4, 4)?) as usize;
+                let mut data = get(input, 0, len.saturating_add(8))?.to_vec();
+                if len >= 8 {
+                    let cert_len = LE::read_u32(&data[12..16]) as usize;
+                    let cert_end = cert_len.saturating_add(16).min(data.len());
+                    fill_text(&mut data[16..cert_end]);
+                }
+                out.extend_from_slice(&data);
+                data.len()
+            }
+            FileTag::Sign => {
+                let data = get(input, 0, 4 + 74)?;
+                out.extend_from_slice(&data[..4]);
+                out.extend(std::iter::repeat_n(BINARY_PLACEHOLDER, 74));
+                data.len()
+            }
+            FileTag::Endt => {
+                out.extend_from_slice(get(input, 0, 4)?);
+                4
+            }
+            FileTag::Ttoc => {
+                let count = LE::read_u32(get(input, 4, 4)?) as usize;
+                let len = count
+                    .checked_mul(8)
+                    .and_then(|n| n.checked_add(16))
+                    .ok_or_else(eof)?;
+                out.extend_from_slice(get(input, 0, len)?);
+                len
+            }
+            FileTag::MainData | FileTag::Tvci | FileTag::Palette => {
+                let (encoding, len, original) = decode_block(&input[4..])?;
+                let mut data = original.clone();
+                match tag {
+                    FileTag::MainData => {
+                        let mut inner = Vec::with_capacity(data.len());
+                        anonymize_tags(&data, &mut inner, resized)?;
+                        data = inner;
+                    }
+                    FileTag::Tvci => anonymize_tvci(&mut data)?,
+                    _ => anonymize_palette(&mut data)?,
+                }
+                if data == original {
+                    // encoding it again could change its length, e.g. with another zlib encoder
+                    out.extend_from_slice(&input[..4 + len]);
+                } else {
+                    let start = out.len();
+                    out.extend_from_slice(&input[..4]);
+                    write_encoded_data(encoding, &data, out)?;
+                    if out.len() - start != 4 + len {
+                        resized.push(tag);
+                    }
+                }
+                4 + len
+            }
+            FileTag::Crea
+            | FileTag::LayerUnderlay
+            | FileTag::LayerColor
+            | FileTag::LayerLine
+            | FileTag::LayerOverlay => {
+                let (_, len, _) = decode_block(&input[4..])?;
+                out.extend_from_slice(&input[..4 + len]);
+                4 + len
+            }
+        };
+
+        input = &input[len..];
+    }
+
+    Ok(())
+}
+
+/// Decodes encoded data. Returns the encoding, the length of the encoded block, and the data.
+fn decode_block(input: &[u8]) -> Result<(EncodingTag, usize, Vec<u8>), ReadError> {
+    let tag = BE::read_u32(get(input, 0, 4)?);
+    let encoding = EncodingTag::try_from(tag).map_err(|e| ReadError::UnknownEncoding(e.number))?;
+    let len = LE::read_u32(get(input, 4, 4)?) as usize;
+    let block = get(input, 0, len.saturating_add(8))?;
+    let data = crate::util::read_encoded_data(block, &ReadOptions::default())?;
+    Ok((encoding, block.len(), data))
+}
+
+/// Replaces the device name in `TVCI` data.
+fn anonymize_tvci(data: &mut [u8]) -> Result<(), ReadError> {
+    // skip 13 mystery bytes
+    let device = data.get_mut(13..).ok_or_else(eof)?;
+    let device_len = device.iter().position(|b| *b == 0).ok_or_else(eof)?;
+    fill_text(&mut device[..device_len]);
+    Ok(())
+}
+
+/// Replaces the project palette names in `TPAL` data.
+fn anonymize_palette(data: &mut [u8]) -> Result<(), ReadError> {
+    let color_count = LE::read_u32(get(data, 0, 4)?);
+    // color count, 0x79
+    let mut pos = 8;
+    for _ in 0..color_count {
+        // mystery header
+        pos += 2;
+        loop {
+            if pos >= data.len() {
+                return Ok(());
+            }
+            let tag = BE::read_u32(get(data, pos, 4)?);
+            if tag == 0x79_00_00_00 {
+                pos += 4;
+                break;
+            }
+            let len = LE::read_u32(get(data, pos + 4, 4)?) as usize;
+            pos += 8;
+            let end = pos.checked_add(len).ok_or_else(eof)?;
+            if tag == TCID {
+                let id_data = data.get_mut(pos..end).ok_or_else(eof)?;
+                // name length, name, ID, project name length, project name
+                let name_chars = LE::read_u32(get(id_data, 0, 4)?) as usize;
+                let project_start = name_chars
+                    .checked_mul(2)
+                    .and_then(|n| n.checked_add(4 + 8))
+                    .ok_or_else(eof)?;
+                let project_chars = LE::read_u32(get(id_data, project_start, 4)?) as usize;
+                let project = project_start + 4;
+                let project_end = project_chars
+                    .checked_mul(2)
+                    .and_then(|n| n.checked_add(project))
+                    .ok_or_else(eof)?;
+                fill_utf16_text(id_data.get_mut(project..project_end).ok_or_else(eof)?);
+            }
+            pos = end;
+        }
+    }
+    Ok(())
+}
+
+/// Like [fill_text], for UTF-16LE text.
+fn fill_utf16_text(data: &mut [u8]) {
+    for (i, unit) in data.chunks_exact_mut(2).enumerate() {
+        let c = TEXT_PLACEHOLDER[i % TEXT_PLACEHOLDER.len()];
+        LE::write_u16(unit, c.into());
+    }
+}
+
+#[cfg(all(test, feature = "layers"))]
+mod tests {
+    use super::*;
+    use crate::document::Art;
+    use crate::read::read;
+    use crate::testgen::{generate, TestDrawing, TestLayer};
+
+    fn file(encoding: EncodingTag) -> Vec<u8> {
+        generate(&TestDrawing {
+            encoding,
+            layers: vec![TestLayer::new(Art::Line, 3, 10)],
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// Checks that the anonymized file can be read, without the identifying fields.
+    fn assert_anonymized(data: &[u8]) {
+        let tags = format!("{:?}", read(data).unwrap());
+        for identifying in ["TESTGEN", "testgen certificate", "palette: \"testgen\""] {
+            assert!(!tags.contains(identifying), "{identifying} in {tags}");
+        }
+        assert!(tags.contains("tvg testgen"), "no software name in {tags}");
+    }
+
+    #[test]
+    fn uncompressed_keeps_offsets() {
+        let file = file(EncodingTag::Unco);
+        let anonymized = anonymize(&file).unwrap();
+        assert_eq!(anonymized.data.len(), file.len());
+        assert_eq!(anonymized.resized, []);
+        assert!(anonymized.offsets_preserved());
+        assert_anonymized(&anonymized.data);
+    }
+
+    #[test]
+    fn compressed_blocks_are_resized() {
+        if crate::zlib::BACKEND.is_none() {
+            return;
+        }
+        let anonymized = anonymize(&file(EncodingTag::Zlib)).unwrap();
+        assert_eq!(
+            anonymized.resized,
+            [FileTag::Tvci, FileTag::Palette, FileTag::MainData]
+        );
+        assert_anonymized(&anonymized.data);
+    }
+}
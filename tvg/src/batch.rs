@@ -0,0 +1,266 @@
+//! Converting many files at once, in parallel.
+//!
+//! Files that fail to convert are recorded in the report, and don't stop the others.
+
+#[cfg(feature = "export-lottie")]
+use crate::export::lottie::{self, LottieOptions};
+#[cfg(feature = "export-pdf")]
+use crate::export::pdf::{self, PdfOptions};
+#[cfg(feature = "export-svg")]
+use crate::export::svg::{self, SvgOptions};
+use crate::export::ConversionReport;
+use crate::read::{self, ReadError, ReadOptions};
+#[cfg(feature = "png")]
+use crate::render::{self, RenderOptions};
+use rayon::prelude::*;
+use std::io;
+use std::path::{Component, Path, PathBuf};
+use thiserror::Error;
+
+/// Output format and its options.
+#[derive(Debug, Clone)]
+pub enum Exporter {
+    #[cfg(feature = "export-svg")]
+    Svg(SvgOptions),
+    #[cfg(feature = "png")]
+    Png(RenderOptions),
+    #[cfg(feature = "export-pdf")]
+    Pdf(PdfOptions),
+    #[cfg(feature = "export-lottie")]
+    Lottie(LottieOptions),
+}
+
+impl Exporter {
+    /// The file extension of the output, without the dot.
+    pub fn extension(&self) -> &'static str {
+        match *self {
+            #[cfg(feature = "export-svg")]
+            Exporter::Svg(_) => "svg",
+            #[cfg(feature = "png")]
+            Exporter::Png(_) => "png",
+            #[cfg(feature = "export-pdf")]
+            Exporter::Pdf(_) => "pdf",
+            #[cfg(feature = "export-lottie")]
+            Exporter::Lottie(_) => "json",
+        }
+    }
+
+    /// Converts one file's data.
+    fn export(&self, data: &[read::FileData]) -> Result<(Vec<u8>, ConversionReport), FileError> {
+        Ok(match self {
+            #[cfg(feature = "export-svg")]
+            Exporter::Svg(options) => {
+                let (svg, report) = svg::to_svg(data, options);
+                (svg.into_bytes(), report)
+            }
+            #[cfg(feature = "png")]
+            Exporter::Png(options) => {
+                let (image, report) = render::render(data, options);
+                let mut png = Vec::new();
+                image.write_png(&mut png)?;
+                (png, report)
+            }
+            #[cfg(feature = "export-pdf")]
+            Exporter::Pdf(options) => pdf::to_pdf(data, options),
+            #[cfg(feature = "export-lottie")]
+            Exporter::Lottie(options) => {
+                let (json, report) = lottie::to_lottie(data, options);
+                (json.into_bytes(), report)
+            }
+        })
+    }
+}
+
+#[derive(Debug, Clone, Default)]
+pub struct BatchOptions {
+    /// Where to write the converted files. If None, each file is written next to its input.
+    /// Otherwise, files are written at the same path relative to this folder as their inputs are
+    /// relative to the start of the pattern (the folders before the first wildcard).
+    pub output_dir: Option<PathBuf>,
+    pub read_options: ReadOptions,
+}
+
+#[derive(Debug, Error)]
+pub enum BatchError {
+    #[error("invalid pattern: {0}")]
+    Pattern(#[from] glob::PatternError),
+}
+
+/// Why a single file couldn't be converted.
+#[derive(Debug, Error)]
+enum FileError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to read: {0}")]
+    Read(#[from] ReadError),
+    #[cfg(feature = "png")]
+    #[error("png error: {0}")]
+    Png(#[from] png::EncodingError),
+}
+
+/// What happened to each file.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatchReport {
+    /// The files, in the order they were listed, and then folders that couldn't be listed.
+    pub files: Vec<FileReport>,
+}
+
+impl BatchReport {
+    /// Number of files that were converted, with or without warnings.
+    pub fn converted(&self) -> usize {
+        self.files.len() - self.failed()
+    }
+
+    /// Number of files that couldn't be converted.
+    pub fn failed(&self) -> usize {
+        let failed = |file: &&FileReport| matches!(file.result, FileResult::Failed { .. });
+        self.files.iter().filter(failed).count()
+    }
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct FileReport {
+    pub input: PathBuf,
+    #[cfg_attr(feature = "serde", serde(flatten))]
+    pub result: FileResult,
+}
+
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "status", rename_all = "snake_case"))]
+pub enum FileResult {
+    Converted {
+        output: PathBuf,
+        /// What the exporter couldn't represent faithfully (see [ConversionReport]).
+        warnings: Vec<BatchWarning>,
+    },
+    Failed {
+        error: String,
+    },
+}
+
+/// A conversion warning, with the number of times it occurred in the file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct BatchWarning {
+    pub message: String,
+    pub count: usize,
+}
+
+/// Converts all files that match a glob pattern (e.g. `scenes/**/*.tvg`), using all CPUs.
+///
+/// Returns an error only if the pattern is invalid; everything else is in the report.
+pub fn convert(
+    pattern: &str,
+    exporter: &Exporter,
+    options: &BatchOptions,
+) -> Result<BatchReport, BatchError> {
+    let mut inputs = Vec::new();
+    let mut failed = Vec::new();
+    for path in glob::glob(pattern)? {
+        match path {
+            Ok(path) => inputs.push(path),
+            // a folder that couldn't be listed
+            Err(e) => failed.push(FileReport {
+                input: e.path().to_path_buf(),
+                result: FileResult::Failed {
+                    error: e.error().to_string(),
+                },
+            }),
+        }
+    }
+    let mut report = convert_files(&pattern_base(pattern), &inputs, exporter, options);
+    report.files.extend(failed);
+    Ok(report)
+}
+
+/// Converts a list of files, using all CPUs.
+///
+/// If there's an [output folder](BatchOptions::output_dir), the outputs are placed relative to it
+/// like the inputs are relative to `base`.
+pub fn convert_files(
+    base: &Path,
+    inputs: &[PathBuf],
+    exporter: &Exporter,
+    options: &BatchOptions,
+) -> BatchReport {
+    let files = inputs
+        .par_iter()
+        .map(|input| FileReport {
+            input: input.clone(),
+            result: convert_file(input, base, exporter, options),
+        })
+        .collect();
+    BatchReport { files }
+}
+
+/// Returns a pattern for all `.tvg` files in a folder, and in its subfolders if `recursive` is
+/// true.
+pub fn folder_pattern(dir: impl AsRef<Path>, recursive: bool) -> String {
+    let dir = glob::Pattern::escape(&dir.as_ref().to_string_lossy());
+    let files = if recursive { "**/*.tvg" } else { "*.tvg" };
+    format!("{dir}/{files}")
+}
+
+fn convert_file(
+    input: &Path,
+    base: &Path,
+    exporter: &Exporter,
+    options: &BatchOptions,
+) -> FileResult {
+    let output = output_path(input, base, exporter, options);
+    match export_file(input, &output, exporter, options) {
+        Ok(report) => FileResult::Converted {
+            output,
+            warnings: report
+                .warnings
+                .into_iter()
+                .map(|(warning, count)| BatchWarning {
+                    message: warning.to_string(),
+                    count,
+                })
+                .collect(),
+        },
+        Err(e) => FileResult::Failed {
+            error: e.to_string(),
+        },
+    }
+}
+
+fn export_file(
+    input: &Path,
+    output: &Path,
+    exporter: &Exporter,
+    options: &BatchOptions,
+) -> Result<ConversionReport, FileError> {
+    let file = std::fs::read(input)?;
+    let data = read::read_with_options(&file[..], &options.read_options)?;
+    let (bytes, report) = exporter.export(&data)?;
+    if let Some(parent) = output.parent() {
+        std::fs::create_dir_all(parent)?;
+    }
+    std::fs::write(output, bytes)?;
+    Ok(report)
+}
+
+fn output_path(input: &Path, base: &Path, exporter: &Exporter, options: &BatchOptions) -> PathBuf {
+    let path = match &options.output_dir {
+        Some(dir) => dir.join(input.strip_prefix(base).unwrap_or(input)),
+        None => input.to_path_buf(),
+    };
+    path.with_extension(exporter.extension())
+}
+
+/// Returns the folders at the start of a pattern, before the first one with a wildcard.
+fn pattern_base(pattern: &str) -> PathBuf {
+    let has_wildcard = |c: &Component| c.as_os_str().to_string_lossy().contains(['*', '?', '[']);
+    let mut components: Vec<_> = Path::new(pattern).components().collect();
+    // the last component is the file name part of the pattern
+    components.pop();
+    components
+        .into_iter()
+        .take_while(|c| !has_wildcard(c))
+        .collect()
+}
@@ -0,0 +1,412 @@
+//! Canonical form of documents, for comparing and content-addressing files.
+//!
+//! Two drawings with the same artwork can differ in ways that don't change what they look like:
+//! which machine and software saved them, the license they were saved with, the order of palette
+//! colors, and `-0.0` vs. `0.0` in coordinates. [Document::canonicalize] removes these
+//! differences, so that documents with identical artwork are also identical as data (and, once
+//! written, as bytes).
+//!
+//! The canonical form is not meant to be opened in Harmony: the certificate and signature are
+//! blanked out. The table of contents (`TTOC`) is left as it is and has to be recomputed when the
+//! document is written.
+//!
+//! [Document::content_hash] goes further and only hashes what the drawing looks like, for finding
+//! identical drawings in different files.
+
+#[cfg(feature = "palette")]
+use crate::color::Rgba;
+#[cfg(feature = "layers")]
+use crate::document::Art;
+use crate::document::Document;
+#[cfg(feature = "layers")]
+use crate::layer::{LayerData, PathSegment, Point, ShapeComponent, ShapeComponentData, ShapePath};
+#[cfg(feature = "palette")]
+use crate::palette::{ColorData, PaletteColor, PaletteData};
+#[cfg(feature = "pencil")]
+use crate::pencil::{StrokeThickness, StrokeThicknessSide};
+use crate::read::{FileData, FileTag};
+#[cfg(feature = "palette")]
+use alloc::vec::Vec;
+
+/// The order in which Harmony writes tags, at the top level and in the main data.
+pub const TAG_ORDER: [FileTag; 12] = [
+    FileTag::Cert,
+    FileTag::MainData,
+    FileTag::Tvci,
+    FileTag::Crea,
+    FileTag::Palette,
+    FileTag::LayerUnderlay,
+    FileTag::LayerColor,
+    FileTag::LayerLine,
+    FileTag::LayerOverlay,
+    FileTag::Ttoc,
+    FileTag::Endt,
+    FileTag::Sign,
+];
+
+fn tag_rank(tag: Option<FileTag>) -> usize {
+    TAG_ORDER
+        .iter()
+        .position(|t| Some(*t) == tag)
+        .unwrap_or(TAG_ORDER.len())
+}
+
+impl Document {
+    /// Converts the document to its canonical form (see the [module documentation](self)):
+    ///
+    /// - tags are sorted into the order Harmony writes them in ([TAG_ORDER])
+    /// - palette colors are sorted by ID (colors without an ID go last), and the tags of each
+    ///   color are sorted by type
+    /// - `-0.0` becomes `0.0` and all NaNs become the same NaN, in paths and stroke thickness
+    /// - the device name, software name and unknown bytes in `TVCI` are cleared
+    /// - the certificate is cleared and the signature is zeroed
+    ///
+    /// Everything else, including undecoded data, is kept as it is.
+    pub fn canonicalize(&mut self) {
+        canonicalize_tags(&mut self.tags);
+    }
+
+    /// Returns a hash of the artwork in the document: the art layers and the palette colors they
+    /// can use.
+    ///
+    /// The hash ignores the certificate, signature and `TVCI` data, the order of tags and palette
+    /// colors, color names, and undecoded data (except for undecoded palette color tags and layer
+    /// data types, which presumably contain gradients and bitmaps).
+    /// Only decoded data contributes to the hash, so the hash also depends on the enabled `layers`,
+    /// `palette` and `pencil` features.
+    ///
+    /// The hash is 128-bit FNV-1a and doesn't depend on the platform, so it can be stored (for
+    /// example, as `format!("{hash:032x}")`). It will only change between versions of this crate
+    /// if more of the file gets decoded.
+    pub fn content_hash(&self) -> u128 {
+        let mut hasher = ContentHasher::new();
+        // bumped whenever more of the file contributes to the hash
+        hasher.write(b"tvg content 1");
+        #[cfg(feature = "palette")]
+        if let Some(palette) = self.palette() {
+            let mut colors: Vec<_> = palette.colors.iter().collect();
+            colors.sort_by_key(|color| (color.id().is_none(), color.id()));
+            hasher.write_len(colors.len());
+            for color in colors {
+                hash_color(&mut hasher, color);
+            }
+        }
+        #[cfg(feature = "layers")]
+        for art in Art::ALL {
+            if let Some(layer) = self.layer(art) {
+                hasher.write(&[art as u8]);
+                hash_layer(&mut hasher, layer);
+            }
+        }
+        hasher.finish()
+    }
+}
+
+/// 128-bit FNV-1a.
+struct ContentHasher(u128);
+
+impl ContentHasher {
+    const OFFSET_BASIS: u128 = 0x6c62272e07bb014262b821756295c58d;
+    const PRIME: u128 = 0x0000000001000000000000000000013b;
+
+    fn new() -> Self {
+        ContentHasher(Self::OFFSET_BASIS)
+    }
+
+    fn write(&mut self, bytes: &[u8]) {
+        for byte in bytes {
+            self.0 ^= *byte as u128;
+            self.0 = self.0.wrapping_mul(Self::PRIME);
+        }
+    }
+
+    #[cfg(any(feature = "layers", feature = "palette"))]
+    fn write_len(&mut self, len: usize) {
+        self.write(&(len as u64).to_le_bytes());
+    }
+
+    #[cfg(feature = "layers")]
+    fn write_f32(&mut self, mut value: f32) {
+        canonicalize_float(&mut value);
+        self.write(&value.to_le_bytes());
+    }
+
+    #[cfg(feature = "layers")]
+    fn write_point(&mut self, p: Point) {
+        self.write_f32(p.0);
+        self.write_f32(p.1);
+    }
+
+    fn finish(&self) -> u128 {
+        self.0
+    }
+}
+
+#[cfg(feature = "palette")]
+fn hash_color(hasher: &mut ContentHasher, color: &PaletteColor) {
+    match color.id() {
+        Some(id) => {
+            hasher.write(&[1]);
+            hasher.write(&id.to_le_bytes());
+        }
+        None => hasher.write(&[0]),
+    }
+    match color.rgba() {
+        Some(Rgba { r, g, b, a }) => hasher.write(&[1, r, g, b, a]),
+        None => hasher.write(&[0]),
+    }
+    let mut unknown: Vec<_> = color
+        .tags
+        .iter()
+        .filter_map(|tag| match tag {
+            ColorData::Unknown { tag, data } => Some((*tag, data)),
+            _ => None,
+        })
+        .collect();
+    unknown.sort_by_key(|(tag, _)| *tag);
+    hasher.write_len(unknown.len());
+    for (tag, data) in unknown {
+        hasher.write(&tag.to_le_bytes());
+        hasher.write_len(data.0.len());
+        hasher.write(&data.0);
+    }
+}
+
+#[cfg(feature = "layers")]
+fn hash_layer(hasher: &mut ContentHasher, layer: &LayerData) {
+    match layer {
+        LayerData::Empty => hasher.write(&[0]),
+        LayerData::Vector { shapes, .. } => {
+            hasher.write(&[1]);
+            hasher.write_len(shapes.len());
+            for shape in shapes {
+                hasher.write(&u16::from(shape.ty).to_le_bytes());
+                hasher.write_len(shape.components.len());
+                for component in &shape.components {
+                    hash_component(hasher, component);
+                }
+            }
+        }
+        LayerData::Unknown { ty, data } => {
+            hasher.write(&[2]);
+            hasher.write(&ty.to_le_bytes());
+            hasher.write_len(data.0.len());
+            hasher.write(&data.0);
+        }
+    }
+}
+
+#[cfg(feature = "layers")]
+fn hash_component(hasher: &mut ContentHasher, component: &ShapeComponent) {
+    for tag in &component.tags {
+        match tag {
+            ShapeComponentData::Info(info) => {
+                hasher.write(&[0, info.ty.into()]);
+                match info.color_id {
+                    Some(id) => {
+                        hasher.write(&[1]);
+                        hasher.write(&id.to_le_bytes());
+                    }
+                    None => hasher.write(&[0]),
+                }
+            }
+            ShapeComponentData::Path(path) => {
+                hasher.write(&[1]);
+                hasher.write_len(path.segments.len());
+                for segment in &path.segments {
+                    match *segment {
+                        PathSegment::Line(p) => {
+                            hasher.write(&[0]);
+                            hasher.write_point(p);
+                        }
+                        PathSegment::Cubic(a, b, c) => {
+                            hasher.write(&[1]);
+                            hasher.write_point(a);
+                            hasher.write_point(b);
+                            hasher.write_point(c);
+                        }
+                    }
+                }
+            }
+            #[cfg(feature = "pencil")]
+            ShapeComponentData::Thickness(thickness) => {
+                hasher.write(&[2]);
+                hasher.write_point(thickness.domain);
+                let points = thickness.definition.as_deref().unwrap_or(&[]);
+                hasher.write_len(points.len());
+                for point in points {
+                    hasher.write_f32(point.loc);
+                    for side in [&point.left, &point.right] {
+                        hasher.write_f32(side.offset);
+                        hasher.write_point(side.ctrl_back);
+                        hasher.write_point(side.ctrl_fwd);
+                    }
+                }
+            }
+            _ => (),
+        }
+    }
+    // marks the end of the component, so tags can't shift between components
+    hasher.write(&[0xff]);
+}
+
+fn canonicalize_tags(tags: &mut [FileData]) {
+    tags.sort_by_key(|tag| tag_rank(tag.tag()));
+    for tag in tags {
+        match tag {
+            FileData::Main(tags) => canonicalize_tags(tags),
+            FileData::Certificate(certificate) => certificate.clear(),
+            FileData::Signature(signature) => signature.fill(0),
+            FileData::Identity {
+                device,
+                software_name,
+                unknown,
+                extra,
+            } => {
+                device.clear();
+                software_name.clear();
+                *unknown = None;
+                *extra = None;
+            }
+            FileData::MainOffsets { offsets, .. } => {
+                offsets.sort_by_key(|(tag, _)| tag_rank(Some(*tag)));
+            }
+            #[cfg(feature = "layers")]
+            FileData::LayerUnderlay(layer)
+            | FileData::LayerColor(layer)
+            | FileData::LayerLine(layer)
+            | FileData::LayerOverlay(layer) => canonicalize_layer(layer),
+            #[cfg(feature = "palette")]
+            FileData::Palette(palette) => canonicalize_palette(palette),
+            FileData::Crea(_) | FileData::Endt | FileData::Unreadable { .. } => (),
+        }
+    }
+}
+
+#[cfg(feature = "palette")]
+fn canonicalize_palette(palette: &mut PaletteData) {
+    palette
+        .colors
+        .sort_by_key(|color| (color.id().is_none(), color.id()));
+    for color in &mut palette.colors {
+        color.tags.sort_by_key(|tag| match tag {
+            ColorData::ColorRgba(..) => (0, 0),
+            ColorData::ColorId { .. } => (1, 0),
+            ColorData::Unknown { tag, .. } => (2, *tag),
+        });
+    }
+}
+
+#[cfg(feature = "layers")]
+fn canonicalize_layer(layer: &mut LayerData) {
+    let LayerData::Vector { shapes, .. } = layer else {
+        return;
+    };
+    for tag in shapes
+        .iter_mut()
+        .flat_map(|shape| &mut shape.components)
+        .flat_map(|component| &mut component.tags)
+    {
+        match tag {
+            ShapeComponentData::Path(path) => canonicalize_path(path),
+            #[cfg(feature = "pencil")]
+            ShapeComponentData::Thickness(thickness) => canonicalize_thickness(thickness),
+            _ => (),
+        }
+    }
+}
+
+#[cfg(feature = "layers")]
+fn canonicalize_path(path: &mut ShapePath) {
+    for segment in &mut path.segments {
+        match segment {
+            PathSegment::Line(p) => canonicalize_point(p),
+            PathSegment::Cubic(a, b, c) => {
+                canonicalize_point(a);
+                canonicalize_point(b);
+                canonicalize_point(c);
+            }
+        }
+    }
+}
+
+#[cfg(feature = "pencil")]
+fn canonicalize_thickness(thickness: &mut StrokeThickness) {
+    canonicalize_point(&mut thickness.domain);
+    let side = |side: &mut StrokeThicknessSide| {
+        canonicalize_float(&mut side.offset);
+        canonicalize_point(&mut side.ctrl_back);
+        canonicalize_point(&mut side.ctrl_fwd);
+    };
+    for point in thickness.definition.iter_mut().flatten() {
+        canonicalize_float(&mut point.loc);
+        side(&mut point.left);
+        side(&mut point.right);
+    }
+}
+
+#[cfg(feature = "layers")]
+fn canonicalize_point(p: &mut Point) {
+    canonicalize_float(&mut p.0);
+    canonicalize_float(&mut p.1);
+}
+
+#[cfg(feature = "layers")]
+fn canonicalize_float(value: &mut f32) {
+    if value.is_nan() {
+        *value = f32::NAN;
+    } else if *value == 0. {
+        // also turns -0.0 into 0.0
+        *value = 0.;
+    }
+}
+
+#[cfg(all(test, feature = "std", feature = "layers"))]
+mod tests {
+    use super::*;
+    use crate::testgen::{generate, TestDrawing, TestLayer};
+
+    fn document(seed: u64) -> Document {
+        let file = generate(&TestDrawing {
+            layers: vec![
+                TestLayer::new(Art::Line, 5, 10),
+                TestLayer::new(Art::Color, 2, 4),
+            ],
+            seed,
+            ..Default::default()
+        })
+        .unwrap();
+        Document::read(&file[..]).unwrap()
+    }
+
+    #[test]
+    fn canonicalize_keeps_content_hash() {
+        let mut document = document(1);
+        // differences that canonicalization removes
+        #[cfg(feature = "palette")]
+        document.palette_mut().unwrap().colors.reverse();
+        if let Some(LayerData::Vector { shapes, .. }) = document.layer_mut(Art::Line) {
+            for tag in &mut shapes[0].components[0].tags {
+                if let ShapeComponentData::Path(path) = tag {
+                    path.segments[0] = PathSegment::Line((-0., -0.));
+                }
+            }
+        }
+
+        let hash = document.content_hash();
+        let mut canonical = document.clone();
+        canonical.canonicalize();
+        assert_ne!(format!("{canonical:?}"), format!("{document:?}"));
+        assert_eq!(canonical.content_hash(), hash);
+
+        let mut twice = canonical.clone();
+        twice.canonicalize();
+        assert_eq!(format!("{twice:?}"), format!("{canonical:?}"));
+    }
+
+    #[test]
+    fn different_drawings_hash_differently() {
+        assert_eq!(document(1).content_hash(), document(1).content_hash());
+        assert_ne!(document(1).content_hash(), document(2).content_hash());
+    }
+}
@@ -0,0 +1,282 @@
+//! The license certificate (`CERT`) and signature (`SIGN`).
+//!
+//! The certificate is stored as text. If it's a PEM-encoded X.509 certificate, [Certificate]
+//! gives access to its issuer, subject and validity. How `SIGN` relates to it isn't known yet
+//! (see `notes/sign.md`).
+
+use crate::document::Document;
+use crate::read::FileData;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+use core::fmt::Write;
+
+/// Fields of a PEM-encoded X.509 certificate.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Certificate {
+    /// The serial number, as hexadecimal digits.
+    pub serial_number: String,
+    pub issuer: Vec<NameAttribute>,
+    pub subject: Vec<NameAttribute>,
+    /// Start of the validity period, as stored (`YYMMDDHHMMSSZ` or `YYYYMMDDHHMMSSZ`).
+    pub not_before: String,
+    /// End of the validity period, as stored.
+    pub not_after: String,
+    /// The whole certificate in DER encoding.
+    pub der: Vec<u8>,
+}
+
+/// One attribute of a distinguished name, e.g. `CN=Example`.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct NameAttribute {
+    /// The short name of the attribute type (`CN`, `O`, ...), or its OID in dotted form if it
+    /// doesn't have one.
+    pub name: String,
+    pub value: String,
+}
+
+impl Certificate {
+    /// Parses the text of a `CERT` tag. Returns None if it isn't a PEM-encoded X.509
+    /// certificate.
+    pub fn parse(text: &str) -> Option<Self> {
+        let der = decode_pem(text)?;
+        let mut data = &der[..];
+        let mut certificate = expect(&mut data, SEQUENCE)?;
+        let mut tbs = expect(&mut certificate, SEQUENCE)?;
+
+        if tbs.first() == Some(&EXPLICIT_VERSION) {
+            read_tlv(&mut tbs)?;
+        }
+        let serial_number = expect(&mut tbs, INTEGER)?;
+        // signature algorithm
+        expect(&mut tbs, SEQUENCE)?;
+        let issuer = parse_name(expect(&mut tbs, SEQUENCE)?)?;
+        let mut validity = expect(&mut tbs, SEQUENCE)?;
+        let not_before = parse_time(&mut validity)?;
+        let not_after = parse_time(&mut validity)?;
+        let subject = parse_name(expect(&mut tbs, SEQUENCE)?)?;
+
+        let mut serial_hex = String::with_capacity(serial_number.len() * 2);
+        for byte in serial_number {
+            let _ = write!(serial_hex, "{byte:02x}");
+        }
+
+        Some(Certificate {
+            serial_number: serial_hex,
+            issuer,
+            subject,
+            not_before,
+            not_after,
+            der,
+        })
+    }
+
+    /// Returns the value of the first subject attribute with the given short name.
+    pub fn subject_attribute(&self, name: &str) -> Option<&str> {
+        find_attribute(&self.subject, name)
+    }
+
+    /// Returns the value of the first issuer attribute with the given short name.
+    pub fn issuer_attribute(&self, name: &str) -> Option<&str> {
+        find_attribute(&self.issuer, name)
+    }
+}
+
+fn find_attribute<'a>(attributes: &'a [NameAttribute], name: &str) -> Option<&'a str> {
+    attributes
+        .iter()
+        .find(|attribute| attribute.name == name)
+        .map(|attribute| attribute.value.as_str())
+}
+
+impl Document {
+    /// Returns the text of the certificate, if there is one.
+    pub fn certificate_text(&self) -> Option<&str> {
+        self.tags.iter().find_map(|tag| match tag {
+            FileData::Certificate(text) => Some(text.as_str()),
+            _ => None,
+        })
+    }
+
+    /// Returns the parsed certificate, if there is one and it can be parsed. See
+    /// [Certificate::parse].
+    pub fn certificate(&self) -> Option<Certificate> {
+        Certificate::parse(self.certificate_text()?)
+    }
+
+    /// Returns the contents of the signature, if there is one.
+    pub fn signature(&self) -> Option<&[u8]> {
+        self.tags.iter().find_map(|tag| match tag {
+            FileData::Signature(signature) => Some(&signature[..]),
+            _ => None,
+        })
+    }
+
+    /// Removes the certificate and signature, e.g. to share sample files without identifying the
+    /// license they were made with. Returns true if there was anything to remove.
+    ///
+    /// Whether Harmony opens files without these hasn't been tested yet.
+    pub fn strip_signing(&mut self) -> bool {
+        let len = self.tags.len();
+        self.tags
+            .retain(|tag| !matches!(tag, FileData::Certificate(_) | FileData::Signature(_)));
+        self.tags.len() != len
+    }
+}
+
+const INTEGER: u8 = 0x02;
+const OBJECT_IDENTIFIER: u8 = 0x06;
+const UTF8_STRING: u8 = 0x0c;
+const PRINTABLE_STRING: u8 = 0x13;
+const TELETEX_STRING: u8 = 0x14;
+const IA5_STRING: u8 = 0x16;
+const UTC_TIME: u8 = 0x17;
+const GENERALIZED_TIME: u8 = 0x18;
+const BMP_STRING: u8 = 0x1e;
+const SEQUENCE: u8 = 0x30;
+const SET: u8 = 0x31;
+/// `[0]` in the TBS certificate.
+const EXPLICIT_VERSION: u8 = 0xa0;
+
+/// Short names of common attribute types.
+const ATTRIBUTE_NAMES: &[(&str, &str)] = &[
+    ("2.5.4.3", "CN"),
+    ("2.5.4.5", "serialNumber"),
+    ("2.5.4.6", "C"),
+    ("2.5.4.7", "L"),
+    ("2.5.4.8", "ST"),
+    ("2.5.4.10", "O"),
+    ("2.5.4.11", "OU"),
+    ("1.2.840.113549.1.9.1", "emailAddress"),
+];
+
+/// Decodes the base64 data of the first PEM block.
+fn decode_pem(text: &str) -> Option<Vec<u8>> {
+    let start = text.find("-----BEGIN ")?;
+    let text = &text[start..];
+    let body_start = text.find('\n')? + 1;
+    let body_end = text.find("-----END ")?;
+    decode_base64(text.get(body_start..body_end)?)
+}
+
+fn decode_base64(text: &str) -> Option<Vec<u8>> {
+    let mut out = Vec::with_capacity(text.len() * 3 / 4);
+    let mut buffer = 0_u32;
+    let mut bits = 0;
+    for byte in text.bytes() {
+        let value = match byte {
+            b'A'..=b'Z' => byte - b'A',
+            b'a'..=b'z' => byte - b'a' + 26,
+            b'0'..=b'9' => byte - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            b'=' => break,
+            b' ' | b'\t' | b'\r' | b'\n' => continue,
+            _ => return None,
+        };
+        buffer = (buffer << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            out.push((buffer >> bits) as u8);
+        }
+    }
+    Some(out)
+}
+
+/// Reads a DER tag, and returns it and its contents.
+fn read_tlv<'a>(data: &mut &'a [u8]) -> Option<(u8, &'a [u8])> {
+    let (&tag, rest) = data.split_first()?;
+    let (&len, mut rest) = rest.split_first()?;
+    let len = if len < 0x80 {
+        usize::from(len)
+    } else {
+        let count = usize::from(len & 0x7f);
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let (bytes, after) = rest.split_at_checked(count)?;
+        rest = after;
+        bytes
+            .iter()
+            .fold(0, |len, &byte| (len << 8) | usize::from(byte))
+    };
+    let (contents, rest) = rest.split_at_checked(len)?;
+    *data = rest;
+    Some((tag, contents))
+}
+
+/// Reads a DER tag, and returns its contents if it's the expected one.
+fn expect<'a>(data: &mut &'a [u8], expected: u8) -> Option<&'a [u8]> {
+    let (tag, contents) = read_tlv(data)?;
+    (tag == expected).then_some(contents)
+}
+
+fn parse_name(mut data: &[u8]) -> Option<Vec<NameAttribute>> {
+    let mut attributes = Vec::new();
+    while !data.is_empty() {
+        let mut set = expect(&mut data, SET)?;
+        while !set.is_empty() {
+            let mut attribute = expect(&mut set, SEQUENCE)?;
+            let oid = parse_oid(expect(&mut attribute, OBJECT_IDENTIFIER)?)?;
+            let (tag, value) = read_tlv(&mut attribute)?;
+            let name = ATTRIBUTE_NAMES
+                .iter()
+                .find(|(known, _)| *known == oid)
+                .map_or(oid, |(_, name)| name.to_string());
+            attributes.push(NameAttribute {
+                name,
+                value: parse_string(tag, value)?,
+            });
+        }
+    }
+    Some(attributes)
+}
+
+fn parse_oid(data: &[u8]) -> Option<String> {
+    let (&first, rest) = data.split_first()?;
+    let mut out = String::new();
+    // the first byte combines the first two arcs; the first arc is at most 2
+    let (a, b) = if first >= 80 {
+        (2, first - 80)
+    } else {
+        (first / 40, first % 40)
+    };
+    let _ = write!(out, "{a}.{b}");
+    let mut value = 0_u64;
+    for &byte in rest {
+        value = value.checked_mul(128)? | u64::from(byte & 0x7f);
+        if byte & 0x80 == 0 {
+            let _ = write!(out, ".{value}");
+            value = 0;
+        }
+    }
+    Some(out)
+}
+
+fn parse_string(tag: u8, data: &[u8]) -> Option<String> {
+    match tag {
+        UTF8_STRING | PRINTABLE_STRING | TELETEX_STRING | IA5_STRING => {
+            Some(String::from_utf8_lossy(data).into_owned())
+        }
+        BMP_STRING => {
+            let units: Vec<u16> = data
+                .chunks_exact(2)
+                .map(|pair| u16::from_be_bytes([pair[0], pair[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        _ => None,
+    }
+}
+
+fn parse_time(data: &mut &[u8]) -> Option<String> {
+    let (tag, time) = read_tlv(data)?;
+    match tag {
+        UTC_TIME | GENERALIZED_TIME => Some(String::from_utf8_lossy(time).into_owned()),
+        _ => None,
+    }
+}
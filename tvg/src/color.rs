@@ -0,0 +1,149 @@
+//! Colors.
+
+use alloc::format;
+use alloc::string::String;
+
+/// An 8-bit sRGB color with straight (not premultiplied) alpha, which is how palette colors are
+/// stored.
+///
+/// With serde, this is an array `[r, g, b, a]`.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(from = "[u8; 4]", into = "[u8; 4]"))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+pub struct Rgba {
+    pub r: u8,
+    pub g: u8,
+    pub b: u8,
+    pub a: u8,
+}
+
+impl Rgba {
+    pub const BLACK: Rgba = Rgba::new(0, 0, 0, 255);
+    pub const TRANSPARENT: Rgba = Rgba::new(0, 0, 0, 0);
+
+    pub const fn new(r: u8, g: u8, b: u8, a: u8) -> Self {
+        Rgba { r, g, b, a }
+    }
+
+    /// Returns the color with a different alpha.
+    pub const fn with_alpha(self, a: u8) -> Self {
+        Rgba { a, ..self }
+    }
+
+    pub const fn is_opaque(self) -> bool {
+        self.a == 255
+    }
+
+    /// Returns the color as `#rrggbbaa`.
+    pub fn to_css_hex(self) -> String {
+        let Rgba { r, g, b, a } = self;
+        format!("#{r:02x}{g:02x}{b:02x}{a:02x}")
+    }
+
+    /// Returns the color without its alpha as `#rrggbb`, for formats that only accept that, like
+    /// SVG 1.1. The alpha has to be given separately.
+    pub fn to_css_hex_rgb(self) -> String {
+        let Rgba { r, g, b, .. } = self;
+        format!("#{r:02x}{g:02x}{b:02x}")
+    }
+
+    /// Returns the color with its color channels multiplied by its alpha.
+    pub fn premultiply(self) -> Self {
+        let multiply = |c: u8| ((c as u32 * self.a as u32 + 127) / 255) as u8;
+        Rgba::new(multiply(self.r), multiply(self.g), multiply(self.b), self.a)
+    }
+
+    /// Returns the color with its color channels divided by its alpha, undoing
+    /// [Rgba::premultiply] as far as the precision allows. Fully transparent colors become
+    /// [Rgba::TRANSPARENT].
+    pub fn unpremultiply(self) -> Self {
+        if self.a == 0 {
+            return Rgba::TRANSPARENT;
+        }
+        let divide = |c: u8| ((c as u32 * 255 + self.a as u32 / 2) / self.a as u32).min(255) as u8;
+        Rgba::new(divide(self.r), divide(self.g), divide(self.b), self.a)
+    }
+
+    /// Returns the channels from 0 to 1, without converting the color space.
+    pub fn to_f32(self) -> [f32; 4] {
+        [self.r, self.g, self.b, self.a].map(|c| c as f32 / 255.)
+    }
+
+    /// Returns the channels from 0 to 1, with the color channels converted to linear light. The
+    /// alpha stays the same.
+    #[cfg(feature = "std")]
+    pub fn to_linear(self) -> [f32; 4] {
+        let [r, g, b, a] = self.to_f32();
+        [srgb_to_linear(r), srgb_to_linear(g), srgb_to_linear(b), a]
+    }
+
+    /// Converts channels from 0 to 1 with color channels in linear light back to sRGB. Values
+    /// outside 0 to 1 are clamped.
+    #[cfg(feature = "std")]
+    pub fn from_linear([r, g, b, a]: [f32; 4]) -> Self {
+        let channel = |c: f32| (c.clamp(0., 1.) * 255.).round() as u8;
+        Rgba::new(
+            channel(linear_to_srgb(r)),
+            channel(linear_to_srgb(g)),
+            channel(linear_to_srgb(b)),
+            channel(a),
+        )
+    }
+}
+
+/// Converts an sRGB color channel from 0 to 1 to linear light.
+#[cfg(feature = "std")]
+pub fn srgb_to_linear(c: f32) -> f32 {
+    if c <= 0.04045 {
+        c / 12.92
+    } else {
+        ((c + 0.055) / 1.055).powf(2.4)
+    }
+}
+
+/// Converts a color channel in linear light from 0 to 1 to sRGB.
+#[cfg(feature = "std")]
+pub fn linear_to_srgb(c: f32) -> f32 {
+    if c <= 0.0031308 {
+        c * 12.92
+    } else {
+        1.055 * c.powf(1. / 2.4) - 0.055
+    }
+}
+
+impl From<[u8; 4]> for Rgba {
+    fn from([r, g, b, a]: [u8; 4]) -> Self {
+        Rgba::new(r, g, b, a)
+    }
+}
+
+impl From<Rgba> for [u8; 4] {
+    fn from(Rgba { r, g, b, a }: Rgba) -> Self {
+        [r, g, b, a]
+    }
+}
+
+impl From<(u8, u8, u8, u8)> for Rgba {
+    fn from((r, g, b, a): (u8, u8, u8, u8)) -> Self {
+        Rgba::new(r, g, b, a)
+    }
+}
+
+impl From<Rgba> for (u8, u8, u8, u8) {
+    fn from(Rgba { r, g, b, a }: Rgba) -> Self {
+        (r, g, b, a)
+    }
+}
+
+/// Describes the serialized form, `[r, g, b, a]`.
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Rgba {
+    fn schema_name() -> String {
+        "Rgba".into()
+    }
+
+    fn json_schema(gen: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        <[u8; 4]>::json_schema(gen)
+    }
+}
@@ -0,0 +1,308 @@
+//! Structural comparison of two TVG files.
+
+use crate::layer::{LayerData, PathSegment, Point, ShapeComponent, ShapeComponentData, ShapePath};
+use crate::palette::PaletteData;
+use crate::read::FileData;
+use std::fmt;
+
+#[derive(Debug, Clone)]
+pub struct DiffOptions {
+    /// Points that moved less than this distance (in drawing units) are considered unchanged.
+    pub epsilon: f32,
+}
+
+impl Default for DiffOptions {
+    fn default() -> Self {
+        DiffOptions { epsilon: 1e-4 }
+    }
+}
+
+/// A difference between two files.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Difference {
+    /// Where the difference is, e.g. `main/layer_line/shape 2/component 0/path 0/segment 5`.
+    pub location: String,
+    pub kind: DifferenceKind,
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub enum DifferenceKind {
+    /// Only exists in the second file.
+    Added,
+    /// Only exists in the first file.
+    Removed,
+    /// Exists in both files but is different.
+    Changed { before: String, after: String },
+    /// A point moved by the given distance.
+    PointMoved {
+        before: Point,
+        after: Point,
+        distance: f32,
+    },
+}
+
+impl fmt::Display for Difference {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match &self.kind {
+            DifferenceKind::Added => write!(f, "+ {}", self.location),
+            DifferenceKind::Removed => write!(f, "- {}", self.location),
+            DifferenceKind::Changed { before, after } => {
+                write!(f, "~ {}: {before} -> {after}", self.location)
+            }
+            DifferenceKind::PointMoved {
+                before,
+                after,
+                distance,
+            } => write!(
+                f,
+                "~ {}: moved {distance} ({}, {}) -> ({}, {})",
+                self.location, before.0, before.1, after.0, after.1
+            ),
+        }
+    }
+}
+
+struct Differ<'a> {
+    options: &'a DiffOptions,
+    out: Vec<Difference>,
+}
+
+impl Differ<'_> {
+    fn push(&mut self, location: &str, kind: DifferenceKind) {
+        self.out.push(Difference {
+            location: location.to_string(),
+            kind,
+        });
+    }
+
+    fn changed(&mut self, location: &str, before: impl fmt::Debug, after: impl fmt::Debug) {
+        self.changed_if_different(location, format!("{before:?}"), format!("{after:?}"));
+    }
+
+    fn changed_if_different(&mut self, location: &str, before: String, after: String) {
+        if before != after {
+            self.push(location, DifferenceKind::Changed { before, after });
+        }
+    }
+
+    fn diff_tags(&mut self, location: &str, a: &[FileData], b: &[FileData]) {
+        // tags are matched by their name, in order
+        let mut b_used = vec![false; b.len()];
+        for tag_a in a {
+            let name = tag_name(tag_a);
+            let location = join(location, name);
+            let tag_b = b
+                .iter()
+                .enumerate()
+                .find(|(i, tag)| !b_used[*i] && tag_name(tag) == name);
+            match tag_b {
+                Some((i, tag_b)) => {
+                    b_used[i] = true;
+                    self.diff_tag(&location, tag_a, tag_b);
+                }
+                None => self.push(&location, DifferenceKind::Removed),
+            }
+        }
+        for (tag_b, _) in b.iter().zip(b_used).filter(|(_, used)| !used) {
+            self.push(&join(location, tag_name(tag_b)), DifferenceKind::Added);
+        }
+    }
+
+    fn diff_tag(&mut self, location: &str, a: &FileData, b: &FileData) {
+        match (a, b) {
+            (FileData::Main(a), FileData::Main(b)) => self.diff_tags(location, a, b),
+            (FileData::Palette(a), FileData::Palette(b)) => self.diff_palette(location, a, b),
+            _ => match (a.layer(), b.layer()) {
+                (Some((art_a, a)), Some((art_b, b))) if art_a == art_b => {
+                    self.diff_layer(location, a, b)
+                }
+                _ => self.changed(location, a, b),
+            },
+        }
+    }
+
+    fn diff_palette(&mut self, location: &str, a: &PaletteData, b: &PaletteData) {
+        for color_a in &a.colors {
+            let id = color_a.id();
+            let location = join(location, &format!("color {:016x}", id.unwrap_or(0)));
+            match b.colors.iter().find(|color| color.id() == id) {
+                Some(color_b) => self.changed(&location, &color_a.tags, &color_b.tags),
+                None => self.push(&location, DifferenceKind::Removed),
+            }
+        }
+        for color_b in &b.colors {
+            let id = color_b.id();
+            if !a.colors.iter().any(|color| color.id() == id) {
+                let location = join(location, &format!("color {:016x}", id.unwrap_or(0)));
+                self.push(&location, DifferenceKind::Added);
+            }
+        }
+    }
+
+    fn diff_layer(&mut self, location: &str, a: &LayerData, b: &LayerData) {
+        let (a, b) = match (a, b) {
+            (
+                LayerData::Vector {
+                    shapes: a,
+                    trailer: trailer_a,
+                },
+                LayerData::Vector {
+                    shapes: b,
+                    trailer: trailer_b,
+                },
+            ) => {
+                self.changed(&join(location, "trailer"), trailer_a, trailer_b);
+                (a, b)
+            }
+            (LayerData::Empty, LayerData::Empty) => return,
+            (
+                LayerData::Unknown { ty: ty_a, data: a },
+                LayerData::Unknown { ty: ty_b, data: b },
+            ) => {
+                self.changed(&join(location, "type"), ty_a, ty_b);
+                return self.changed(&join(location, "data"), a, b);
+            }
+            (a, b) => return self.changed(location, layer_kind(a), layer_kind(b)),
+        };
+
+        for (i, (shape_a, shape_b)) in a.iter().zip(b).enumerate() {
+            let location = join(location, &format!("shape {i}"));
+            if shape_a.ty != shape_b.ty {
+                self.changed(&location, shape_a.ty, shape_b.ty);
+                continue;
+            }
+            self.diff_list(
+                &location,
+                "component",
+                &shape_a.components,
+                &shape_b.components,
+                |differ, location, a, b| differ.diff_component(location, a, b),
+            );
+        }
+        self.diff_lengths(location, "shape", a.len(), b.len());
+    }
+
+    fn diff_component(&mut self, location: &str, a: &ShapeComponent, b: &ShapeComponent) {
+        self.diff_list(
+            location,
+            "tag",
+            &a.tags,
+            &b.tags,
+            |differ, location, a, b| match (a, b) {
+                (ShapeComponentData::Path(a), ShapeComponentData::Path(b)) => {
+                    differ.diff_path(location, a, b)
+                }
+                (a, b) => differ.changed(location, a, b),
+            },
+        );
+    }
+
+    fn diff_path(&mut self, location: &str, a: &ShapePath, b: &ShapePath) {
+        for (i, (seg_a, seg_b)) in a.segments.iter().zip(&b.segments).enumerate() {
+            let location = join(location, &format!("segment {i}"));
+            match (seg_a, seg_b) {
+                (PathSegment::Line(a), PathSegment::Line(b)) => self.diff_point(&location, *a, *b),
+                (PathSegment::Cubic(a1, a2, a3), PathSegment::Cubic(b1, b2, b3)) => {
+                    for (j, (a, b)) in [(a1, b1), (a2, b2), (a3, b3)].into_iter().enumerate() {
+                        self.diff_point(&join(&location, &format!("point {j}")), *a, *b);
+                    }
+                }
+                (a, b) => self.changed(&location, a, b),
+            }
+        }
+        self.diff_lengths(location, "segment", a.segments.len(), b.segments.len());
+    }
+
+    fn diff_point(&mut self, location: &str, a: Point, b: Point) {
+        let distance = (a.0 - b.0).hypot(a.1 - b.1);
+        if distance > self.options.epsilon || distance.is_nan() {
+            self.push(
+                location,
+                DifferenceKind::PointMoved {
+                    before: a,
+                    after: b,
+                    distance,
+                },
+            );
+        }
+    }
+
+    /// Compares items pairwise by index and reports added/removed items at the end.
+    fn diff_list<T>(
+        &mut self,
+        location: &str,
+        item_name: &str,
+        a: &[T],
+        b: &[T],
+        mut diff_item: impl FnMut(&mut Self, &str, &T, &T),
+    ) {
+        for (i, (item_a, item_b)) in a.iter().zip(b).enumerate() {
+            diff_item(
+                self,
+                &join(location, &format!("{item_name} {i}")),
+                item_a,
+                item_b,
+            );
+        }
+        self.diff_lengths(location, item_name, a.len(), b.len());
+    }
+
+    fn diff_lengths(&mut self, location: &str, item_name: &str, a: usize, b: usize) {
+        for i in b..a {
+            self.push(
+                &join(location, &format!("{item_name} {i}")),
+                DifferenceKind::Removed,
+            );
+        }
+        for i in a..b {
+            self.push(
+                &join(location, &format!("{item_name} {i}")),
+                DifferenceKind::Added,
+            );
+        }
+    }
+}
+
+fn join(location: &str, name: &str) -> String {
+    if location.is_empty() {
+        name.to_string()
+    } else {
+        format!("{location}/{name}")
+    }
+}
+
+fn tag_name(tag: &FileData) -> &'static str {
+    match tag {
+        FileData::Certificate(_) => "certificate",
+        FileData::Signature(_) => "signature",
+        FileData::Crea(_) => "crea",
+        FileData::Endt => "endt",
+        FileData::Main(_) => "main",
+        FileData::MainOffsets { .. } => "main_offsets",
+        FileData::Identity { .. } => "identity",
+        FileData::LayerUnderlay(_) => "layer_underlay",
+        FileData::LayerColor(_) => "layer_color",
+        FileData::LayerLine(_) => "layer_line",
+        FileData::LayerOverlay(_) => "layer_overlay",
+        FileData::Palette(_) => "palette",
+        FileData::Unreadable { .. } => "unreadable",
+    }
+}
+
+fn layer_kind(layer: &LayerData) -> &'static str {
+    match layer {
+        LayerData::Empty => "empty",
+        LayerData::Vector { .. } => "vector",
+        LayerData::Unknown { .. } => "unknown",
+    }
+}
+
+/// Compares two files and returns all differences.
+pub fn diff(a: &[FileData], b: &[FileData], options: &DiffOptions) -> Vec<Difference> {
+    let mut differ = Differ {
+        options,
+        out: Vec::new(),
+    };
+    differ.diff_tags("", a, b);
+    differ.out
+}
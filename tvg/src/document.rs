@@ -0,0 +1,285 @@
+//! A whole TVG file with convenient access to its contents.
+
+#[cfg(feature = "layers")]
+use crate::layer::{LayerData, ShapeType, VectorShape};
+#[cfg(feature = "palette")]
+use crate::palette::PaletteData;
+use crate::io::Read;
+use crate::read::{self, FileData, FileTag, ReadError, ReadOptions};
+use alloc::vec::Vec;
+
+/// One of the four art layers of a drawing.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, PartialOrd, Ord)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
+pub enum Art {
+    Underlay,
+    Color,
+    Line,
+    Overlay,
+}
+
+impl Art {
+    /// All art layers, in drawing order (bottom to top).
+    pub const ALL: [Art; 4] = [Art::Underlay, Art::Color, Art::Line, Art::Overlay];
+
+    /// Returns the tag that contains this layer.
+    pub fn file_tag(self) -> FileTag {
+        match self {
+            Art::Underlay => FileTag::LayerUnderlay,
+            Art::Color => FileTag::LayerColor,
+            Art::Line => FileTag::LayerLine,
+            Art::Overlay => FileTag::LayerOverlay,
+        }
+    }
+
+    /// Returns the art layer contained in a tag, if it's a layer tag.
+    pub fn from_file_tag(tag: FileTag) -> Option<Art> {
+        Art::ALL.into_iter().find(|art| art.file_tag() == tag)
+    }
+
+    /// Returns the name of the layer in lowercase (`underlay`, `color`, `line` or `overlay`), as
+    /// in the serde representation.
+    pub fn name(self) -> &'static str {
+        match self {
+            Art::Underlay => "underlay",
+            Art::Color => "color",
+            Art::Line => "line",
+            Art::Overlay => "overlay",
+        }
+    }
+
+    /// Returns the art layer with the given name. See [Art::name].
+    pub fn from_name(name: &str) -> Option<Art> {
+        Art::ALL.into_iter().find(|art| art.name() == name)
+    }
+}
+
+/// A shape in one of the art layers of a document.
+#[cfg(feature = "layers")]
+#[derive(Debug, Clone, Copy)]
+pub struct ShapeRef<'a> {
+    /// The art layer that contains the shape.
+    pub layer: Art,
+    /// The index of the shape in the layer.
+    pub index: usize,
+    pub shape: &'a VectorShape,
+}
+
+/// A TVG file.
+#[derive(Debug, Clone, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(transparent))]
+pub struct Document {
+    /// Top-level tags, in file order.
+    pub tags: Vec<FileData>,
+}
+
+impl Document {
+    /// Reads a TVG file.
+    pub fn read(input: impl Read) -> Result<Self, ReadError> {
+        Self::read_with_options(input, &ReadOptions::default())
+    }
+
+    /// Reads a TVG file with custom options.
+    pub fn read_with_options(input: impl Read, options: &ReadOptions) -> Result<Self, ReadError> {
+        Ok(Document {
+            tags: read::read_with_options(input, options)?,
+        })
+    }
+
+    /// Returns the tags in the main data, or None if there is no main data.
+    pub fn main(&self) -> Option<&[FileData]> {
+        self.tags.iter().find_map(|tag| match tag {
+            FileData::Main(tags) => Some(&tags[..]),
+            _ => None,
+        })
+    }
+
+    /// Returns the palette.
+    #[cfg(feature = "palette")]
+    pub fn palette(&self) -> Option<&PaletteData> {
+        find_palette(&self.tags)
+    }
+
+    /// Returns an art layer, or None if the file doesn't contain it at all.
+    ///
+    /// A layer that exists but has no contents is [LayerData::Empty] instead.
+    #[cfg(feature = "layers")]
+    pub fn layer(&self, art: Art) -> Option<&LayerData> {
+        find_layer(&self.tags, art)
+    }
+
+    /// Returns a mutable reference to the palette.
+    #[cfg(feature = "palette")]
+    pub fn palette_mut(&mut self) -> Option<&mut PaletteData> {
+        find_palette_mut(&mut self.tags)
+    }
+
+    /// Returns a mutable reference to an art layer.
+    #[cfg(feature = "layers")]
+    pub fn layer_mut(&mut self, art: Art) -> Option<&mut LayerData> {
+        find_layer_mut(&mut self.tags, art)
+    }
+
+    /// Returns all art layers that exist in the file, in drawing order (bottom to top).
+    #[cfg(feature = "layers")]
+    pub fn layers(&self) -> impl Iterator<Item = (Art, &LayerData)> {
+        Art::ALL
+            .into_iter()
+            .filter_map(|art| Some((art, self.layer(art)?)))
+    }
+
+    /// Returns all shapes in drawing order (bottom to top), which can be narrowed down with the
+    /// methods of [Shapes].
+    #[cfg(feature = "layers")]
+    pub fn shapes(&self) -> Shapes<'_> {
+        Shapes {
+            document: self,
+            layer: 0,
+            index: 0,
+            in_layer: None,
+            of_type: None,
+            with_color: None,
+        }
+    }
+
+    /// Returns the art layers that the file doesn't contain at all.
+    #[cfg(feature = "layers")]
+    pub fn missing_layers(&self) -> Vec<Art> {
+        Art::ALL
+            .into_iter()
+            .filter(|art| self.layer(*art).is_none())
+            .collect()
+    }
+}
+
+/// An iterator over the shapes of a document, in drawing order. See [Document::shapes].
+#[cfg(feature = "layers")]
+#[derive(Debug, Clone)]
+pub struct Shapes<'a> {
+    document: &'a Document,
+    /// Index of the current layer in [Art::ALL].
+    layer: usize,
+    /// Index of the next shape in the current layer.
+    index: usize,
+    in_layer: Option<Art>,
+    of_type: Option<ShapeType>,
+    with_color: Option<u64>,
+}
+
+#[cfg(feature = "layers")]
+impl Shapes<'_> {
+    /// Only yields shapes in the given layer.
+    pub fn in_layer(mut self, art: Art) -> Self {
+        self.in_layer = Some(art);
+        self
+    }
+
+    /// Only yields shapes of the given type.
+    pub fn of_type(mut self, ty: ShapeType) -> Self {
+        self.of_type = Some(ty);
+        self
+    }
+
+    /// Only yields shapes that use the given palette color in any of their components.
+    pub fn with_color(mut self, id: u64) -> Self {
+        self.with_color = Some(id);
+        self
+    }
+}
+
+#[cfg(feature = "layers")]
+impl<'a> Iterator for Shapes<'a> {
+    type Item = ShapeRef<'a>;
+
+    fn next(&mut self) -> Option<ShapeRef<'a>> {
+        while let Some(&art) = Art::ALL.get(self.layer) {
+            let wanted = self.in_layer.is_none_or(|a| a == art);
+            let shapes = match self.document.layer(art) {
+                Some(LayerData::Vector { shapes, .. }) if wanted => &shapes[..],
+                _ => &[],
+            };
+            let Some(shape) = shapes.get(self.index) else {
+                self.layer += 1;
+                self.index = 0;
+                continue;
+            };
+            let index = self.index;
+            self.index += 1;
+
+            if self.of_type.is_some_and(|ty| shape.ty != ty)
+                || self
+                    .with_color
+                    .is_some_and(|id| !shape.color_ids().any(|color_id| color_id == id))
+            {
+                continue;
+            }
+            return Some(ShapeRef {
+                layer: art,
+                index,
+                shape,
+            });
+        }
+        None
+    }
+}
+
+impl From<Vec<FileData>> for Document {
+    fn from(tags: Vec<FileData>) -> Self {
+        Document { tags }
+    }
+}
+
+/// Finds an art layer in the tags or in nested main data.
+#[cfg(feature = "layers")]
+pub(crate) fn find_layer(tags: &[FileData], art: Art) -> Option<&LayerData> {
+    tags.iter().find_map(|tag| match tag {
+        FileData::Main(tags) => find_layer(tags, art),
+        tag => tag.layer().filter(|(a, _)| *a == art).map(|(_, layer)| layer),
+    })
+}
+
+#[cfg(feature = "layers")]
+fn find_layer_mut(tags: &mut [FileData], art: Art) -> Option<&mut LayerData> {
+    for tag in tags {
+        let found = match tag {
+            FileData::Main(tags) => find_layer_mut(tags, art),
+            tag => tag.layer_mut().filter(|(a, _)| *a == art).map(|(_, layer)| layer),
+        };
+        if found.is_some() {
+            return found;
+        }
+    }
+    None
+}
+
+/// Finds the palette in the tags or in nested main data.
+#[cfg(feature = "palette")]
+pub(crate) fn find_palette(tags: &[FileData]) -> Option<&PaletteData> {
+    tags.iter().find_map(|tag| match tag {
+        FileData::Palette(palette) => Some(palette),
+        FileData::Main(tags) => find_palette(tags),
+        _ => None,
+    })
+}
+
+#[cfg(feature = "palette")]
+fn find_palette_mut(tags: &mut [FileData]) -> Option<&mut PaletteData> {
+    for tag in tags {
+        match tag {
+            FileData::Palette(palette) => return Some(palette),
+            FileData::Main(tags) => {
+                if let Some(palette) = find_palette_mut(tags) {
+                    return Some(palette);
+                }
+            }
+            _ => (),
+        }
+    }
+    None
+}
@@ -0,0 +1,461 @@
+//! Editing documents without breaking references between their parts.
+//!
+//! Edits through [DocumentMut] check that shapes only reference colors that exist in the palette,
+//! and that palette colors aren't removed while they're still used.
+//!
+//! The table of contents (`TTOC`, [FileData::MainOffsets](crate::read::FileData::MainOffsets))
+//! contains offsets into the encoded main data, which change with any edit. It's left as it is,
+//! and has to be recomputed when the document is written.
+
+use crate::document::{Art, Document, ShapeRef};
+use crate::layer::{
+    LayerData, LayerTrailer, ShapeComponent, ShapeComponentData, ShapePath, VectorShape,
+};
+use crate::palette::{ConflictPolicy, PaletteColor, PaletteData};
+use crate::validate::ComponentLocation;
+use std::collections::{BTreeMap, HashSet};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum EditError {
+    #[error("the document has no {0:?} layer")]
+    MissingLayer(Art),
+    #[error("the {0:?} layer is not a vector layer")]
+    NotVectorLayer(Art),
+    #[error("no shape {index} in the {art:?} layer")]
+    MissingShape { art: Art, index: usize },
+    #[error("no component at {0}")]
+    MissingComponent(ComponentLocation),
+    #[error("no path {index} at {location}")]
+    MissingPath {
+        location: ComponentLocation,
+        index: usize,
+    },
+    #[error("the document has no palette")]
+    MissingPalette,
+    #[error("color not in palette: {0:016x}")]
+    MissingColor(u64),
+    #[error("color already in palette: {0:016x}")]
+    DuplicateColor(u64),
+    #[error("palette color has no ID")]
+    ColorWithoutId,
+    #[error("color is still used: {0:016x}")]
+    ColorInUse(u64),
+}
+
+/// A document that is being edited. See the [module documentation](self).
+pub struct DocumentMut<'a> {
+    document: &'a mut Document,
+}
+
+impl Document {
+    /// Starts editing the document.
+    pub fn edit(&mut self) -> DocumentMut<'_> {
+        DocumentMut::new(self)
+    }
+
+    /// Moves all shapes from one layer to the top of another. See [DocumentMut::merge_layers].
+    pub fn merge_layers(&mut self, from: Art, into: Art) -> Result<usize, EditError> {
+        self.edit().merge_layers(from, into)
+    }
+
+    /// Replaces a layer with a copy of the same layer from another document. See
+    /// [DocumentMut::copy_layer_from].
+    pub fn copy_layer_from(
+        &mut self,
+        other: &Document,
+        art: Art,
+    ) -> Result<BTreeMap<u64, u64>, EditError> {
+        self.edit().copy_layer_from(other, art)
+    }
+
+    /// Returns a copy of the document that only contains the shapes matching the predicate.
+    ///
+    /// Layers without matching shapes become empty. Everything else, including the palette, is
+    /// copied as it is.
+    pub fn extract_shapes(&self, mut predicate: impl FnMut(&ShapeRef) -> bool) -> Document {
+        let keep: HashSet<(Art, usize)> = self
+            .shapes()
+            .filter(|shape| predicate(shape))
+            .map(|shape| (shape.layer, shape.index))
+            .collect();
+
+        let mut document = self.clone();
+        for art in Art::ALL {
+            let Some(layer) = document.layer_mut(art) else {
+                continue;
+            };
+            if let LayerData::Vector { shapes, .. } = layer {
+                let mut index = 0;
+                shapes.retain(|_| {
+                    index += 1;
+                    keep.contains(&(art, index - 1))
+                });
+                if !shapes.is_empty() {
+                    continue;
+                }
+                *layer = LayerData::Empty;
+            }
+        }
+        document
+    }
+
+    /// Removes the palette colors that no shape uses, and returns them in palette order.
+    ///
+    /// Colors without an ID can't be used by shapes, so they're removed too. Does nothing if there
+    /// is no palette.
+    pub fn prune_palette(&mut self) -> Vec<PaletteColor> {
+        let used: HashSet<u64> = self
+            .shapes()
+            .flat_map(|shape| shape.shape.color_ids())
+            .collect();
+        let Some(palette) = self.palette_mut() else {
+            return Vec::new();
+        };
+        let (kept, removed) = std::mem::take(&mut palette.colors)
+            .into_iter()
+            .partition(|color| color.id().is_some_and(|id| used.contains(&id)));
+        palette.colors = kept;
+        removed
+    }
+}
+
+impl<'a> DocumentMut<'a> {
+    pub fn new(document: &'a mut Document) -> Self {
+        DocumentMut { document }
+    }
+
+    /// Returns the document in its current state.
+    pub fn document(&self) -> &Document {
+        self.document
+    }
+
+    /// Checks that a layer can contain shapes, and returns how many it has.
+    fn check_layer(&self, art: Art) -> Result<usize, EditError> {
+        match self.document.layer(art) {
+            None => Err(EditError::MissingLayer(art)),
+            Some(LayerData::Empty) => Ok(0),
+            Some(LayerData::Vector { shapes, .. }) => Ok(shapes.len()),
+            Some(LayerData::Unknown { .. }) => Err(EditError::NotVectorLayer(art)),
+        }
+    }
+
+    /// Returns the shapes of a layer, turning an empty layer into a vector layer.
+    fn shapes_mut(&mut self, art: Art) -> Result<&mut Vec<VectorShape>, EditError> {
+        let layer = self
+            .document
+            .layer_mut(art)
+            .ok_or(EditError::MissingLayer(art))?;
+        if let LayerData::Empty = layer {
+            *layer = LayerData::Vector {
+                shapes: Vec::new(),
+                trailer: LayerTrailer::default(),
+            };
+        }
+        match layer {
+            LayerData::Vector { shapes, .. } => Ok(shapes),
+            _ => Err(EditError::NotVectorLayer(art)),
+        }
+    }
+
+    fn shape_mut(&mut self, art: Art, index: usize) -> Result<&mut VectorShape, EditError> {
+        if index >= self.check_layer(art)? {
+            return Err(EditError::MissingShape { art, index });
+        }
+        Ok(&mut self.shapes_mut(art)?[index])
+    }
+
+    fn component_mut(
+        &mut self,
+        location: ComponentLocation,
+    ) -> Result<&mut ShapeComponent, EditError> {
+        self.shape_mut(location.art, location.shape)?
+            .components
+            .get_mut(location.component)
+            .ok_or(EditError::MissingComponent(location))
+    }
+
+    fn check_colors(&self, shape: &VectorShape) -> Result<(), EditError> {
+        for tag in shape
+            .components
+            .iter()
+            .flat_map(|component| &component.tags)
+        {
+            if let ShapeComponentData::Info(info) = tag {
+                if let Some(id) = info.color_id {
+                    self.check_color(id)?;
+                }
+            }
+        }
+        Ok(())
+    }
+
+    fn check_color(&self, id: u64) -> Result<(), EditError> {
+        match self.document.palette().and_then(|palette| palette.find(id)) {
+            Some(_) => Ok(()),
+            None => Err(EditError::MissingColor(id)),
+        }
+    }
+
+    /// Removes a shape and returns it.
+    ///
+    /// If this removes the last shape of a layer, it becomes an empty layer, like Harmony writes
+    /// them.
+    pub fn remove_shape(&mut self, art: Art, index: usize) -> Result<VectorShape, EditError> {
+        if index >= self.check_layer(art)? {
+            return Err(EditError::MissingShape { art, index });
+        }
+        let shapes = self.shapes_mut(art)?;
+        let shape = shapes.remove(index);
+        if shapes.is_empty() {
+            if let Some(layer) = self.document.layer_mut(art) {
+                *layer = LayerData::Empty;
+            }
+        }
+        Ok(shape)
+    }
+
+    /// Inserts a shape at an index in a layer (shapes are drawn in order, so a larger index is
+    /// further up). All colors used by the shape must be in the palette.
+    pub fn insert_shape(
+        &mut self,
+        art: Art,
+        index: usize,
+        shape: VectorShape,
+    ) -> Result<(), EditError> {
+        self.check_colors(&shape)?;
+        if index > self.check_layer(art)? {
+            return Err(EditError::MissingShape { art, index });
+        }
+        self.shapes_mut(art)?.insert(index, shape);
+        Ok(())
+    }
+
+    /// Moves a shape to the top of another layer, and returns its new index.
+    pub fn move_shape_to_layer(
+        &mut self,
+        art: Art,
+        index: usize,
+        to: Art,
+    ) -> Result<usize, EditError> {
+        // make sure the target can take the shape before removing anything
+        self.check_layer(to)?;
+        let shape = self.remove_shape(art, index)?;
+        let shapes = self.shapes_mut(to)?;
+        shapes.push(shape);
+        Ok(shapes.len() - 1)
+    }
+
+    /// Moves all shapes from one layer to the top of another, keeping their order, and returns how
+    /// many were moved. The layer they came from becomes empty.
+    pub fn merge_layers(&mut self, from: Art, into: Art) -> Result<usize, EditError> {
+        let count = self.check_layer(from)?;
+        self.check_layer(into)?;
+        if from == into || count == 0 {
+            return Ok(0);
+        }
+        let shapes = std::mem::take(self.shapes_mut(from)?);
+        if let Some(layer) = self.document.layer_mut(from) {
+            *layer = LayerData::Empty;
+        }
+        self.shapes_mut(into)?.extend(shapes);
+        Ok(count)
+    }
+
+    /// Replaces a layer with a copy of the same layer from another document, e.g. to combine line
+    /// art and color fills from different files.
+    ///
+    /// Colors that the layer uses are added to the palette, with [ConflictPolicy::Rename]: if the
+    /// palette already has a different color with the same ID, the copied color gets a new ID and
+    /// the layer is changed to use it. Returns the IDs that were changed this way (see
+    /// [PaletteData::merge]).
+    /// Colors that aren't in the other document's palette are left as they are.
+    ///
+    /// Documents only contain decoded data, so the layer is encoded again when the document is
+    /// written.
+    pub fn copy_layer_from(
+        &mut self,
+        other: &Document,
+        art: Art,
+    ) -> Result<BTreeMap<u64, u64>, EditError> {
+        let mut layer = other
+            .layer(art)
+            .ok_or(EditError::MissingLayer(art))?
+            .clone();
+        if self.document.layer(art).is_none() {
+            return Err(EditError::MissingLayer(art));
+        }
+
+        let used: HashSet<u64> = other
+            .shapes()
+            .in_layer(art)
+            .flat_map(|shape| shape.shape.color_ids())
+            .collect();
+        let colors = PaletteData {
+            colors: other
+                .palette()
+                .into_iter()
+                .flat_map(|palette| &palette.colors)
+                .filter(|color| color.id().is_some_and(|id| used.contains(&id)))
+                .cloned()
+                .collect(),
+        };
+        let remap = match self.document.palette_mut() {
+            _ if colors.colors.is_empty() => BTreeMap::new(),
+            Some(palette) => palette
+                .merge(&colors, ConflictPolicy::Rename)
+                .map_err(|conflict| EditError::DuplicateColor(conflict.0))?,
+            None => return Err(EditError::MissingPalette),
+        };
+
+        if let LayerData::Vector { shapes, .. } = &mut layer {
+            for info in shapes
+                .iter_mut()
+                .flat_map(|shape| &mut shape.components)
+                .flat_map(|component| &mut component.tags)
+                .filter_map(|tag| match tag {
+                    ShapeComponentData::Info(info) => Some(info),
+                    _ => None,
+                })
+            {
+                if let Some(new_id) = info.color_id.and_then(|id| remap.get(&id)) {
+                    info.color_id = Some(*new_id);
+                }
+            }
+        }
+        if let Some(target) = self.document.layer_mut(art) {
+            *target = layer;
+        }
+        Ok(remap)
+    }
+
+    /// Replaces a path of a shape component, and returns the old path. `index` counts the
+    /// component's paths (`TGBP` tags); most components have only one.
+    pub fn replace_path(
+        &mut self,
+        location: ComponentLocation,
+        index: usize,
+        path: ShapePath,
+    ) -> Result<ShapePath, EditError> {
+        let old = self
+            .component_mut(location)?
+            .tags
+            .iter_mut()
+            .filter_map(|tag| match tag {
+                ShapeComponentData::Path(old) => Some(old),
+                _ => None,
+            })
+            .nth(index)
+            .ok_or(EditError::MissingPath { location, index })?;
+        Ok(std::mem::replace(old, path))
+    }
+
+    /// Sets the color of a shape component, and returns the old color. The color must be in the
+    /// palette.
+    pub fn set_color(
+        &mut self,
+        location: ComponentLocation,
+        color_id: u64,
+    ) -> Result<Option<u64>, EditError> {
+        self.check_color(color_id)?;
+        let info = self
+            .component_mut(location)?
+            .tags
+            .iter_mut()
+            .find_map(|tag| match tag {
+                ShapeComponentData::Info(info) => Some(info),
+                _ => None,
+            })
+            .ok_or(EditError::MissingComponent(location))?;
+        Ok(info.color_id.replace(color_id))
+    }
+
+    /// Adds a color to the palette, and returns its ID. The ID must not be in use yet.
+    pub fn add_palette_color(&mut self, color: PaletteColor) -> Result<u64, EditError> {
+        let id = color.id().ok_or(EditError::ColorWithoutId)?;
+        let palette = self
+            .document
+            .palette_mut()
+            .ok_or(EditError::MissingPalette)?;
+        if palette.find(id).is_some() {
+            return Err(EditError::DuplicateColor(id));
+        }
+        palette.colors.push(color);
+        Ok(id)
+    }
+
+    /// Removes a color from the palette and returns it. No shape may use the color.
+    pub fn remove_palette_color(&mut self, id: u64) -> Result<PaletteColor, EditError> {
+        if self.document.shapes().with_color(id).next().is_some() {
+            return Err(EditError::ColorInUse(id));
+        }
+        let palette = self
+            .document
+            .palette_mut()
+            .ok_or(EditError::MissingPalette)?;
+        palette.remove(id).map_err(|_| EditError::MissingColor(id))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::PathSegment;
+    use crate::testgen::{generate, TestDrawing, TestLayer};
+
+    fn line(x: f32) -> ShapePath {
+        ShapePath {
+            segments: vec![PathSegment::Line((x, 0.)), PathSegment::Line((x, 1.))],
+            extra: None,
+        }
+    }
+
+    /// A document with one shape, whose component has the paths `line(0.)` and `line(1.)`.
+    fn document() -> Document {
+        let file = generate(&TestDrawing {
+            layers: vec![TestLayer::new(Art::Line, 1, 1)],
+            ..Default::default()
+        })
+        .unwrap();
+        let mut document = Document::read(&file[..]).unwrap();
+        let Some(LayerData::Vector { shapes, .. }) = document.layer_mut(Art::Line) else {
+            panic!("no line art");
+        };
+        let tags = &mut shapes[0].components[0].tags;
+        tags.retain(|tag| !matches!(tag, ShapeComponentData::Path(_)));
+        tags.insert(1, ShapeComponentData::Path(line(0.)));
+        tags.insert(2, ShapeComponentData::Path(line(1.)));
+        document
+    }
+
+    #[test]
+    fn replace_second_path() {
+        let mut document = document();
+        let location = ComponentLocation {
+            art: Art::Line,
+            shape: 0,
+            component: 0,
+        };
+        let old = document.edit().replace_path(location, 1, line(2.)).unwrap();
+        assert_eq!(format!("{old:?}"), format!("{:?}", line(1.)));
+
+        let Some(LayerData::Vector { shapes, .. }) = document.layer(Art::Line) else {
+            panic!("no line art");
+        };
+        let paths: Vec<_> = shapes[0].components[0]
+            .tags
+            .iter()
+            .filter_map(|tag| match tag {
+                ShapeComponentData::Path(path) => Some(format!("{path:?}")),
+                _ => None,
+            })
+            .collect();
+        assert_eq!(paths, [line(0.), line(2.)].map(|path| format!("{path:?}")));
+
+        let result = document.edit().replace_path(location, 2, line(3.));
+        assert!(matches!(
+            result,
+            Err(EditError::MissingPath { index: 2, .. })
+        ));
+    }
+}
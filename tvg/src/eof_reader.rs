@@ -1,82 +1,117 @@
-use std::io::{self, Read};
+//! A reader that can look ahead.
 
-const PEEK_BUF_HALF_SIZE: usize = 32;
-const PEEK_BUF_SIZE: usize = PEEK_BUF_HALF_SIZE * 2;
+use crate::io::{self, Read};
+
+/// The maximum number of bytes that [EofReader::peek] can look ahead.
+pub const MAX_PEEK: usize = 64;
+
+/// A reader that can tell whether its input has ended, and peek at upcoming bytes without
+/// consuming them.
+///
+/// This is useful for formats that don't store how many items follow, where the end of the
+/// input is the only indication that there are no more.
 pub struct EofReader<R> {
     read: R,
-    buf: [u8; PEEK_BUF_SIZE],
+    buf: [u8; MAX_PEEK],
     buf_pos: usize,
     buf_read_pos: usize,
     at_eof: bool,
+    position: u64,
 }
 
 impl<R: Read> EofReader<R> {
     pub fn new(read: R) -> io::Result<Self> {
         let mut reader = Self {
             read,
-            buf: [0; PEEK_BUF_SIZE],
+            buf: [0; MAX_PEEK],
             buf_pos: 0,
             buf_read_pos: 0,
             at_eof: false,
+            position: 0,
         };
-        reader.fill_if_needed()?;
+        reader.fill(1)?;
         Ok(reader)
     }
 
-    fn fill_if_needed(&mut self) -> io::Result<()> {
-        if self.at_eof {
-            return Ok(());
-        }
+    /// Number of buffered bytes.
+    fn available(&self) -> usize {
+        self.buf_read_pos - self.buf_pos
+    }
 
-        // discard the first half of the buffer if we can
-        if self.buf_pos >= PEEK_BUF_HALF_SIZE {
-            let (a, b) = self.buf.split_at_mut(PEEK_BUF_HALF_SIZE);
-            a.copy_from_slice(b);
-            self.buf_pos -= PEEK_BUF_HALF_SIZE;
-            self.buf_read_pos -= PEEK_BUF_HALF_SIZE;
-        }
+    /// Buffers bytes until at least `n` are available or the input ends.
+    fn fill(&mut self, n: usize) -> io::Result<()> {
+        while self.available() < n && !self.at_eof {
+            // move buffered bytes to the start to make room
+            if self.buf_pos > 0 {
+                self.buf.copy_within(self.buf_pos..self.buf_read_pos, 0);
+                self.buf_read_pos -= self.buf_pos;
+                self.buf_pos = 0;
+            }
 
-        // buffer more bytes
-        if self.buf_read_pos < PEEK_BUF_SIZE {
-            let read_count = self.read.read(&mut self.buf[self.buf_read_pos..])?;
-            if read_count == 0 {
-                self.at_eof = true;
-            } else {
-                self.buf_read_pos += read_count;
+            match self.read.read(&mut self.buf[self.buf_read_pos..]) {
+                Ok(0) => self.at_eof = true,
+                Ok(read_count) => self.buf_read_pos += read_count,
+                Err(e) if e.kind() == io::ErrorKind::Interrupted => (),
+                Err(e) => return Err(e),
             }
         }
-
         Ok(())
     }
 
     /// Returns true if no more bytes can be read.
-    pub fn is_at_eof(&self) -> bool {
-        self.buf_pos == self.buf_read_pos && self.at_eof
+    pub fn is_at_eof(&mut self) -> io::Result<bool> {
+        self.fill(1)?;
+        Ok(self.available() == 0)
     }
 
-    /// Peeks at least two (unless at EOF) and up to four bytes.
-    pub fn peek_tag(&mut self, buf: &mut [u8; 4]) -> io::Result<usize> {
-        // fill twice (once here, once in read) so that it must have at least 2 bytes available
-        self.fill_if_needed()?;
-        let read = self.read(buf)?;
-        self.buf_pos -= read;
-        Ok(read)
+    /// Number of bytes that have been read (not counting peeked bytes).
+    pub fn position(&self) -> u64 {
+        self.position
+    }
+
+    /// Copies upcoming bytes into `buf` without consuming them, and returns how many there were.
+    ///
+    /// This only returns fewer bytes than requested if the input ends before that.
+    /// `buf` may be at most [MAX_PEEK] bytes long.
+    pub fn peek(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        if buf.len() > MAX_PEEK {
+            return Err(io::Error::new(
+                io::ErrorKind::InvalidInput,
+                "cannot peek more than 64 bytes",
+            ));
+        }
+        self.fill(buf.len())?;
+        let len = buf.len().min(self.available());
+        buf[..len].copy_from_slice(&self.buf[self.buf_pos..self.buf_pos + len]);
+        Ok(len)
     }
 }
 
 impl<R: Read> Read for EofReader<R> {
     fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
-        self.fill_if_needed()?;
-        if self.buf_pos < self.buf_read_pos {
-            let ready_len = self.buf_read_pos - self.buf_pos;
-            let read_len = buf.len().min(ready_len);
-            buf[..read_len].copy_from_slice(&self.buf[self.buf_pos..(self.buf_pos + read_len)]);
-            self.buf_pos += read_len;
-            Ok(read_len)
-        } else if self.at_eof {
-            Ok(0)
-        } else {
-            Err(io::Error::new(io::ErrorKind::UnexpectedEof, "invalid state probably"))
+        if buf.is_empty() {
+            return Ok(0);
         }
+        if self.available() == 0 {
+            if self.at_eof {
+                return Ok(0);
+            }
+            // skip the buffer for large reads
+            if buf.len() >= MAX_PEEK {
+                let read_count = self.read.read(buf)?;
+                if read_count == 0 {
+                    self.at_eof = true;
+                }
+                self.position += read_count as u64;
+                return Ok(read_count);
+            }
+            self.fill(1)?;
+        }
+
+        let len = buf.len().min(self.available());
+        buf[..len].copy_from_slice(&self.buf[self.buf_pos..self.buf_pos + len]);
+        self.buf_pos += len;
+        self.position += len as u64;
+        Ok(len)
     }
 }
@@ -0,0 +1,264 @@
+//! Lottie (bodymovin) export.
+//!
+//! Each art layer becomes a Lottie shape layer. Fill shapes become even-odd filled groups and
+//! pencil strokes become stroked groups with a constant width, like in the SVG export.
+//! Drawing sequences are exported with one drawing per animation frame.
+
+use crate::color::Rgba;
+use crate::document::{find_layer, find_palette, Art};
+use crate::export::{self, ConversionReport, CoordinateMapping, Framing, Item};
+use crate::geometry::Bounds;
+use crate::layer::{LayerData, PathSegment, Point, ShapePath};
+use crate::palette::PaletteData;
+use crate::read::FileData;
+use serde_json::{json, Value};
+
+/// Lottie format version the output is written for.
+const LOTTIE_VERSION: &str = "5.7.0";
+
+#[derive(Debug, Clone)]
+pub struct LottieOptions {
+    /// The area of the drawing to export, in drawing units. Defaults to the framed area.
+    pub view_box: Option<Bounds>,
+    /// How to frame the drawing if there's no explicit view box.
+    pub framing: Framing,
+    /// Padding around the drawing, in drawing units. Only used if there is no explicit view box.
+    pub padding: f32,
+    /// Stroke width of pencil lines, in drawing units.
+    pub stroke_width: f32,
+    /// Animation frames per second.
+    pub frame_rate: f32,
+    /// How many animation frames each drawing is shown for.
+    pub frames_per_drawing: u32,
+    /// How drawing units map to Lottie pixels. Defaults to drawing units with Y pointing down,
+    /// which Lottie needs, and the origin at the top left corner.
+    pub mapping: CoordinateMapping,
+}
+
+impl Default for LottieOptions {
+    fn default() -> Self {
+        LottieOptions {
+            view_box: None,
+            framing: Framing::Bounds,
+            padding: 10.,
+            stroke_width: 4.,
+            frame_rate: 24.,
+            frames_per_drawing: 1,
+            mapping: CoordinateMapping::default(),
+        }
+    }
+}
+
+impl LottieOptions {
+    /// Returns the exported area of a drawing, in drawing units.
+    pub fn view_box(&self, data: &[FileData]) -> Bounds {
+        let layers = Art::ALL.into_iter().filter_map(|art| find_layer(data, art));
+        export::frame(
+            layers,
+            self.view_box,
+            self.framing,
+            self.padding + self.stroke_width / 2.,
+        )
+    }
+}
+
+fn static_value(value: impl Into<Value>) -> Value {
+    json!({ "a": 0, "k": value.into() })
+}
+
+fn identity_transform() -> Value {
+    json!({
+        "o": static_value(100),
+        "r": static_value(0),
+        "p": static_value(json!([0, 0, 0])),
+        "a": static_value(json!([0, 0, 0])),
+        "s": static_value(json!([100, 100, 100])),
+    })
+}
+
+/// Converts a path to Lottie bezier data. Vertex tangents are relative to the vertex.
+fn path_shape(path: &ShapePath, to_lottie: impl Fn(Point) -> Point) -> Option<Value> {
+    let mut vertices: Vec<Point> = Vec::new();
+    let mut in_tangents: Vec<Point> = Vec::new();
+    let mut out_tangents: Vec<Point> = Vec::new();
+
+    for segment in &path.segments {
+        match *segment {
+            PathSegment::Line(p) => {
+                vertices.push(to_lottie(p));
+                in_tangents.push((0., 0.));
+                out_tangents.push((0., 0.));
+            }
+            PathSegment::Cubic(c1, c2, p) => {
+                let (c1, c2, p) = (to_lottie(c1), to_lottie(c2), to_lottie(p));
+                if let (Some(prev), Some(out)) = (vertices.last(), out_tangents.last_mut()) {
+                    *out = (c1.0 - prev.0, c1.1 - prev.1);
+                }
+                vertices.push(p);
+                in_tangents.push((c2.0 - p.0, c2.1 - p.1));
+                out_tangents.push((0., 0.));
+            }
+        }
+    }
+
+    if vertices.is_empty() {
+        return None;
+    }
+
+    // an explicitly closed path ends on its first point; Lottie closes it implicitly
+    let first = vertices[0];
+    let last = vertices[vertices.len() - 1];
+    let closed = vertices.len() > 2 && (first.0 - last.0).hypot(first.1 - last.1) < 1e-3;
+    if closed {
+        vertices.pop();
+        in_tangents[0] = in_tangents.pop().unwrap();
+        out_tangents.pop();
+    }
+
+    let points =
+        |points: Vec<Point>| -> Vec<[f32; 2]> { points.into_iter().map(|(x, y)| [x, y]).collect() };
+    Some(json!({
+        "ty": "sh",
+        "ks": static_value(json!({
+            "c": closed,
+            "v": points(vertices),
+            "i": points(in_tangents),
+            "o": points(out_tangents),
+        })),
+    }))
+}
+
+fn color_value(
+    palette: Option<&PaletteData>,
+    color: Option<u64>,
+    report: &mut ConversionReport,
+) -> (Value, f32) {
+    // no palette entry: draw it in black so it's at least visible
+    let [r, g, b, a] = export::resolve_rgba(palette, color, report)
+        .unwrap_or(Rgba::BLACK)
+        .to_f32();
+    (static_value(json!([r, g, b, 1])), a * 100.)
+}
+
+fn item_group(
+    item: &Item,
+    palette: Option<&PaletteData>,
+    options: &LottieOptions,
+    to_lottie: impl Fn(Point) -> Point + Copy,
+    report: &mut ConversionReport,
+) -> Value {
+    let (Item::Fill { paths, color } | Item::Stroke { paths, color, .. }) = item;
+    let mut items: Vec<Value> = paths
+        .iter()
+        .filter_map(|path| path_shape(path, to_lottie))
+        .collect();
+    let (color, opacity) = color_value(palette, *color, report);
+
+    items.push(match item {
+        Item::Fill { .. } => json!({
+            "ty": "fl",
+            "c": color,
+            "o": static_value(opacity),
+            // even-odd
+            "r": 2,
+        }),
+        Item::Stroke { .. } => json!({
+            "ty": "st",
+            "c": color,
+            "o": static_value(opacity),
+            "w": static_value(options.stroke_width * options.mapping.scale),
+            // round cap and join
+            "lc": 2,
+            "lj": 2,
+        }),
+    });
+    items.push(json!({
+        "ty": "tr",
+        "o": static_value(100),
+        "r": static_value(0),
+        "p": static_value(json!([0, 0])),
+        "a": static_value(json!([0, 0])),
+        "s": static_value(json!([100, 100])),
+    }));
+
+    json!({ "ty": "gr", "it": items })
+}
+
+/// Exports several drawings as consecutive frames of one animation.
+///
+/// All drawings use the same view box, which contains all of them.
+pub fn frames_to_lottie(
+    frames: &[&[FileData]],
+    options: &LottieOptions,
+) -> (String, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let view_box = frames
+        .iter()
+        .map(|data| options.view_box(data))
+        .reduce(Bounds::union)
+        .unwrap_or(Bounds {
+            min: (0., 0.),
+            max: (1., 1.),
+        });
+    let to_lottie = |point: Point| options.mapping.apply(view_box, point);
+    let (width, height) = options.mapping.output_size(view_box);
+
+    let mut layers = Vec::new();
+    for (i, data) in frames.iter().enumerate() {
+        let start = i as u32 * options.frames_per_drawing;
+        let end = start + options.frames_per_drawing;
+        let palette = find_palette(data);
+
+        for art in Art::ALL {
+            let Some(LayerData::Vector { shapes, .. }) = find_layer(data, art) else {
+                continue;
+            };
+            let mut groups = Vec::new();
+            for shape in shapes {
+                for item in export::shape_items(shape, &mut report) {
+                    groups.push(item_group(&item, palette, options, to_lottie, &mut report));
+                }
+            }
+            // in Lottie, the first shape is drawn on top
+            groups.reverse();
+
+            layers.push(json!({
+                "ddd": 0,
+                "ty": 4,
+                "nm": format!("{art:?} {i}"),
+                "sr": 1,
+                "ks": identity_transform(),
+                "ao": 0,
+                "shapes": groups,
+                "ip": start,
+                "op": end,
+                "st": 0,
+                "bm": 0,
+            }));
+        }
+    }
+    // in Lottie, the first layer is drawn on top
+    layers.reverse();
+    for (i, layer) in layers.iter_mut().enumerate() {
+        layer["ind"] = json!(i + 1);
+    }
+
+    let lottie = json!({
+        "v": LOTTIE_VERSION,
+        "fr": options.frame_rate,
+        "ip": 0,
+        "op": frames.len() as u32 * options.frames_per_drawing,
+        "w": width.ceil(),
+        "h": height.ceil(),
+        "nm": "tvg",
+        "ddd": 0,
+        "assets": [],
+        "layers": layers,
+    });
+    (lottie.to_string(), report)
+}
+
+/// Exports a drawing as a single-frame Lottie animation.
+pub fn to_lottie(data: &[FileData], options: &LottieOptions) -> (String, ConversionReport) {
+    frames_to_lottie(&[data], options)
+}
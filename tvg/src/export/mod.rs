@@ -0,0 +1,513 @@
+//! Exporters to other formats.
+
+// the shared helpers are only used by the exporters and the renderer
+#![cfg_attr(
+    not(any(
+        feature = "export-svg",
+        feature = "export-lottie",
+        feature = "export-pdf",
+        feature = "render"
+    )),
+    allow(dead_code)
+)]
+
+#[cfg(feature = "export-lottie")]
+pub mod lottie;
+#[cfg(feature = "export-pdf")]
+pub mod pdf;
+#[cfg(feature = "export-svg")]
+pub mod svg;
+
+use crate::color::Rgba;
+#[cfg(any(feature = "render", feature = "export-svg"))]
+use crate::document::Art;
+use crate::geometry::{self, Bounds};
+use crate::layer::{
+    ComponentType, LayerData, Point, ShapeComponentData, ShapePath, ShapeType, VectorShape,
+};
+use crate::palette::PaletteData;
+#[cfg(feature = "pencil")]
+use crate::pencil::StrokeThicknessPoint;
+use std::fmt;
+
+/// Something in the drawing that an exporter could not represent faithfully.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ConversionWarning {
+    /// Pencil stroke thickness was replaced with a constant width.
+    PencilThicknessIgnored,
+    /// Pencil texture data (`tGTI`) was ignored.
+    PencilTextureIgnored,
+    /// An invisible stroke was skipped.
+    InvisibleStrokeSkipped,
+    /// A shape of an unknown type was skipped.
+    UnknownShapeTypeSkipped(ShapeType),
+    /// A shape component tag we don't understand was ignored.
+    UnknownComponentTagIgnored(u32),
+    /// A shape references a color that is not in the palette.
+    MissingColor(u64),
+    /// A shape references a color that isn't a solid color (e.g. a gradient or texture), which we
+    /// can't decode yet.
+    UnsupportedColor(u64),
+    /// A shape has no color at all.
+    NoColor,
+    /// The image was rendered at a smaller scale than requested, to stay within the renderer's
+    /// pixel limit (`render::RenderOptions::max_pixels`).
+    ImageScaledDown,
+}
+
+impl fmt::Display for ConversionWarning {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ConversionWarning::PencilThicknessIgnored => write!(f, "pencil thickness ignored"),
+            ConversionWarning::PencilTextureIgnored => write!(f, "pencil texture ignored"),
+            ConversionWarning::InvisibleStrokeSkipped => write!(f, "invisible stroke skipped"),
+            ConversionWarning::UnknownShapeTypeSkipped(ty) => {
+                write!(f, "unknown shape type skipped: {ty:?}")
+            }
+            ConversionWarning::UnknownComponentTagIgnored(tag) => {
+                write!(f, "unknown shape component tag ignored: {tag:08x}")
+            }
+            ConversionWarning::MissingColor(id) => write!(f, "color not in palette: {id:016x}"),
+            ConversionWarning::UnsupportedColor(id) => {
+                write!(f, "unsupported (not solid) color: {id:016x}")
+            }
+            ConversionWarning::NoColor => write!(f, "shape without color"),
+            ConversionWarning::ImageScaledDown => {
+                write!(f, "image scaled down to fit the pixel limit")
+            }
+        }
+    }
+}
+
+/// Lists everything that an exporter could not represent faithfully.
+#[derive(Debug, Clone, Default)]
+pub struct ConversionReport {
+    /// Warnings, with the number of times they occurred.
+    pub warnings: Vec<(ConversionWarning, usize)>,
+}
+
+impl ConversionReport {
+    /// Records a warning.
+    pub fn warn(&mut self, warning: ConversionWarning) {
+        match self.warnings.iter_mut().find(|(w, _)| *w == warning) {
+            Some((_, count)) => *count += 1,
+            None => self.warnings.push((warning, 1)),
+        }
+    }
+
+    /// Adds all warnings from another report.
+    pub fn merge(&mut self, other: ConversionReport) {
+        for (warning, count) in other.warnings {
+            match self.warnings.iter_mut().find(|(w, _)| *w == warning) {
+                Some((_, c)) => *c += count,
+                None => self.warnings.push((warning, count)),
+            }
+        }
+    }
+
+    /// Returns true if nothing was lost during conversion.
+    pub fn is_lossless(&self) -> bool {
+        self.warnings.is_empty()
+    }
+}
+
+impl fmt::Display for ConversionReport {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        for (warning, count) in &self.warnings {
+            writeln!(f, "{warning} (x{count})")?;
+        }
+        Ok(())
+    }
+}
+
+/// How to pick the exported area of a drawing, if it's not given explicitly.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub enum Framing {
+    /// The bounds of all shapes.
+    Bounds,
+    /// The bounds of all shapes that are at least `min_size` wide or tall (in drawing units),
+    /// ignoring stray dots.
+    Significant { min_size: f32 },
+}
+
+impl Framing {
+    /// Returns the framed area of the given layers.
+    pub fn bounds<'a>(&self, layers: impl IntoIterator<Item = &'a LayerData>) -> Option<Bounds> {
+        match *self {
+            Framing::Bounds => layers
+                .into_iter()
+                .filter_map(geometry::layer_bounds)
+                .reduce(Bounds::union),
+            Framing::Significant { min_size } => geometry::significant_bounds(layers, min_size),
+        }
+    }
+}
+
+/// Width of one field of the Harmony field grid, in drawing units.
+#[allow(clippy::excessive_precision)] // exactly representable
+pub const FIELD_WIDTH: f32 = 208.328125;
+/// Height of one field of the Harmony field grid, in drawing units.
+pub const FIELD_HEIGHT: f32 = 156.25;
+
+/// A standard animation field grid, centered on the drawing origin.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct FieldGrid {
+    /// Number of fields from the center to each edge of the grid.
+    pub fields: u32,
+}
+
+impl FieldGrid {
+    /// The standard 12-field grid.
+    pub const TWELVE: FieldGrid = FieldGrid { fields: 12 };
+    /// The standard 16-field grid.
+    pub const SIXTEEN: FieldGrid = FieldGrid { fields: 16 };
+
+    /// Returns the grid lines as pairs of end points, in drawing units.
+    ///
+    /// The first two lines are the center axes (horizontal, then vertical).
+    pub fn lines(&self) -> Vec<((f32, f32), (f32, f32))> {
+        let n = self.fields as i32;
+        let x_max = FIELD_WIDTH * n as f32;
+        let y_max = FIELD_HEIGHT * n as f32;
+
+        let mut lines = vec![((-x_max, 0.), (x_max, 0.)), ((0., -y_max), (0., y_max))];
+        for i in (-n..=n).filter(|i| *i != 0) {
+            let y = FIELD_HEIGHT * i as f32;
+            lines.push(((-x_max, y), (x_max, y)));
+            let x = FIELD_WIDTH * i as f32;
+            lines.push(((x, -y_max), (x, y_max)));
+        }
+        lines
+    }
+}
+
+/// How drawing units map to the coordinates of an output format.
+///
+/// TVG coordinates are in drawing units (see [FIELD_WIDTH]) with Y pointing up, while most output
+/// formats are Y-down, and measured in pixels or points. The mapping is applied to the exported
+/// area (the view box), so the output area is the mapped view box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct CoordinateMapping {
+    /// Output units (e.g. pixels) per drawing unit.
+    pub scale: f32,
+    /// Whether X points the other way in the output.
+    pub flip_x: bool,
+    /// Whether Y points the other way in the output. True by default, since drawings are Y-up.
+    pub flip_y: bool,
+    /// Where the output origin is.
+    pub origin: Origin,
+}
+
+/// Where the origin of the output coordinates is.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Origin {
+    /// At the corner of the exported area, so the output area starts at 0, 0.
+    #[default]
+    Corner,
+    /// At the drawing origin (the center of the field grid).
+    Drawing,
+}
+
+impl Default for CoordinateMapping {
+    fn default() -> Self {
+        CoordinateMapping::new(1.)
+    }
+}
+
+impl CoordinateMapping {
+    /// A Y-down mapping with the given scale and the origin at the corner.
+    pub fn new(scale: f32) -> Self {
+        CoordinateMapping {
+            scale,
+            flip_x: false,
+            flip_y: true,
+            origin: Origin::Corner,
+        }
+    }
+
+    /// A Y-down mapping to pixels at a resolution of `dpi`, for drawings on paper where one field
+    /// of the field grid is `field_size` inches wide (0.5 for standard animation paper).
+    pub fn fields_to_pixels(dpi: f32, field_size: f32) -> Self {
+        CoordinateMapping::new(dpi * field_size / FIELD_WIDTH)
+    }
+
+    /// The point in drawing units that ends up at the output origin.
+    fn origin_point(&self, view_box: Bounds) -> Point {
+        match self.origin {
+            Origin::Corner => {
+                let (x_min, x_max) = (view_box.min.0, view_box.max.0);
+                let (y_min, y_max) = (view_box.min.1, view_box.max.1);
+                (
+                    if self.flip_x { x_max } else { x_min },
+                    if self.flip_y { y_max } else { y_min },
+                )
+            }
+            Origin::Drawing => (0., 0.),
+        }
+    }
+
+    /// Maps a point in drawing units to output coordinates.
+    pub fn apply(&self, view_box: Bounds, (x, y): Point) -> Point {
+        let (ox, oy) = self.origin_point(view_box);
+        let map = |v: f32, o: f32, flip: bool| match flip {
+            true => (o - v) * self.scale,
+            false => (v - o) * self.scale,
+        };
+        (map(x, ox, self.flip_x), map(y, oy, self.flip_y))
+    }
+
+    /// Returns the mapping as the 2×3 affine matrix `[a, b, c, d, e, f]`, so
+    /// `x' = a x + c y + e` and `y' = b x + d y + f`.
+    pub fn transform(&self, view_box: Bounds) -> [f32; 6] {
+        let sx = if self.flip_x { -self.scale } else { self.scale };
+        let sy = if self.flip_y { -self.scale } else { self.scale };
+        let (e, f) = self.apply(view_box, (0., 0.));
+        [sx, 0., 0., sy, e, f]
+    }
+
+    /// Returns the output area, in output coordinates.
+    pub fn output_bounds(&self, view_box: Bounds) -> Bounds {
+        let a = self.apply(view_box, view_box.min);
+        let b = self.apply(view_box, view_box.max);
+        Bounds {
+            min: (a.0.min(b.0), a.1.min(b.1)),
+            max: (a.0.max(b.0), a.1.max(b.1)),
+        }
+    }
+
+    /// Returns the size of the output area, in output units.
+    pub fn output_size(&self, view_box: Bounds) -> (f32, f32) {
+        (
+            view_box.width() * self.scale,
+            view_box.height() * self.scale,
+        )
+    }
+}
+
+/// The framed area of the drawing: the view box if there is one, or the framed bounds of the
+/// layers, inflated by `padding`.
+pub(crate) fn frame<'a>(
+    layers: impl IntoIterator<Item = &'a LayerData>,
+    view_box: Option<Bounds>,
+    framing: Framing,
+    padding: f32,
+) -> Bounds {
+    view_box.unwrap_or_else(|| {
+        framing
+            .bounds(layers)
+            .map(|bounds| bounds.inflate(padding))
+            .unwrap_or(Bounds {
+                min: (0., 0.),
+                max: (1., 1.),
+            })
+    })
+}
+
+/// Returns the art layers to draw, bottom to top: the ones in `arts`, in the order Harmony
+/// composites them in, but with the line art below the color art if `line_over_color` is false.
+#[cfg(any(feature = "render", feature = "export-svg"))]
+pub(crate) fn layer_order(arts: &[Art], line_over_color: bool) -> impl Iterator<Item = Art> + '_ {
+    let order = match line_over_color {
+        true => Art::ALL,
+        false => [Art::Underlay, Art::Line, Art::Color, Art::Overlay],
+    };
+    order.into_iter().filter(|art| arts.contains(art))
+}
+
+/// Returns the opacity of an art layer.
+#[cfg(any(feature = "render", feature = "export-svg"))]
+pub(crate) fn layer_opacity(art: Art, underlay_opacity: f32) -> f32 {
+    match art {
+        Art::Underlay => underlay_opacity.clamp(0., 1.),
+        _ => 1.,
+    }
+}
+
+/// Something that can be drawn.
+pub(crate) enum Item<'a> {
+    /// Paths filled together using the even-odd rule.
+    Fill {
+        paths: Vec<&'a ShapePath>,
+        color: Option<u64>,
+    },
+    /// Paths stroked along their center line.
+    Stroke {
+        paths: Vec<&'a ShapePath>,
+        color: Option<u64>,
+        /// The thickness of each path, if it has one and it was asked for (see
+        /// [shape_items_with_thickness]).
+        #[cfg(feature = "pencil")]
+        #[cfg_attr(not(feature = "export-svg"), allow(dead_code))]
+        thickness: Vec<Option<Thickness<'a>>>,
+    },
+}
+
+/// The thickness of a pencil stroke path: the thickness points, which can come from an earlier
+/// path of the shape, and the part of them that the path covers.
+#[cfg(feature = "pencil")]
+#[cfg_attr(not(feature = "export-svg"), allow(dead_code))]
+#[derive(Clone, Copy)]
+pub(crate) struct Thickness<'a> {
+    pub definition: &'a [StrokeThicknessPoint],
+    pub domain: (f32, f32),
+}
+
+/// Returns the drawable items of a shape, reporting anything that can't be drawn.
+// the renderer only uses shape_items_with_guides, and the SVG exporter uses
+// shape_items_with_thickness if it can
+#[cfg_attr(
+    not(any(
+        all(feature = "export-svg", not(feature = "pencil")),
+        feature = "export-lottie",
+        feature = "export-pdf",
+        feature = "lyon"
+    )),
+    allow(dead_code)
+)]
+pub(crate) fn shape_items<'a>(
+    shape: &'a VectorShape,
+    report: &mut ConversionReport,
+) -> Vec<Item<'a>> {
+    shape_items_with_guides(shape, None, report)
+}
+
+/// Like [shape_items], but adds the paths of invisible strokes to `guides` instead of skipping
+/// them, if it's given.
+pub(crate) fn shape_items_with_guides<'a>(
+    shape: &'a VectorShape,
+    guides: Option<&mut Vec<&'a ShapePath>>,
+    report: &mut ConversionReport,
+) -> Vec<Item<'a>> {
+    shape_items_with(shape, guides, false, report)
+}
+
+/// Like [shape_items], but keeps the thickness of pencil strokes instead of reporting it as
+/// ignored.
+#[cfg(feature = "pencil")]
+#[cfg_attr(not(feature = "export-svg"), allow(dead_code))]
+pub(crate) fn shape_items_with_thickness<'a>(
+    shape: &'a VectorShape,
+    report: &mut ConversionReport,
+) -> Vec<Item<'a>> {
+    shape_items_with(shape, None, true, report)
+}
+
+#[cfg_attr(not(feature = "pencil"), allow(unused_variables))]
+fn shape_items_with<'a>(
+    shape: &'a VectorShape,
+    mut guides: Option<&mut Vec<&'a ShapePath>>,
+    keep_thickness: bool,
+    report: &mut ConversionReport,
+) -> Vec<Item<'a>> {
+    match shape.ty {
+        ShapeType::Fill => {
+            let mut paths = Vec::new();
+            let mut color = None;
+            for component in &shape.components {
+                for tag in &component.tags {
+                    match tag {
+                        ShapeComponentData::Info(info) => color = color.or(info.color_id),
+                        ShapeComponentData::Path(path) => paths.push(path),
+                        ShapeComponentData::Unknown { tag, .. } => {
+                            report.warn(ConversionWarning::UnknownComponentTagIgnored(*tag))
+                        }
+                        _ => (),
+                    }
+                }
+            }
+            vec![Item::Fill { paths, color }]
+        }
+        ShapeType::Stroke | ShapeType::Line => {
+            let mut items = Vec::new();
+            // a tGTB without a definition uses the one before it, even in another component
+            #[cfg(feature = "pencil")]
+            let mut definition = None;
+            for component in &shape.components {
+                let mut color = None;
+                let mut visible = false;
+                for tag in &component.tags {
+                    match tag {
+                        ShapeComponentData::Info(info) => {
+                            visible = info.ty == ComponentType::Pencil;
+                            color = info.color_id;
+                        }
+                        #[cfg(feature = "pencil")]
+                        ShapeComponentData::Thickness(_) if !keep_thickness => {
+                            report.warn(ConversionWarning::PencilThicknessIgnored)
+                        }
+                        ShapeComponentData::Tgti(_) => {
+                            report.warn(ConversionWarning::PencilTextureIgnored)
+                        }
+                        ShapeComponentData::Unknown { tag, .. } => {
+                            report.warn(ConversionWarning::UnknownComponentTagIgnored(*tag))
+                        }
+                        _ => (),
+                    }
+                }
+                let groups = component.path_groups();
+                if groups.is_empty() {
+                    continue;
+                }
+                let paths: Vec<_> = groups.iter().map(|group| group.path).collect();
+                #[cfg(feature = "pencil")]
+                let thickness = groups
+                    .iter()
+                    .map(|group| {
+                        let thickness = group.thickness?;
+                        if let Some(points) = &thickness.definition {
+                            definition = Some(points.as_slice());
+                        }
+                        let thickness = Thickness {
+                            definition: definition?,
+                            domain: thickness.domain,
+                        };
+                        keep_thickness.then_some(thickness)
+                    })
+                    .collect();
+                if !visible {
+                    match guides.as_deref_mut() {
+                        Some(guides) => guides.extend(paths),
+                        None => report.warn(ConversionWarning::InvisibleStrokeSkipped),
+                    }
+                    continue;
+                }
+                items.push(Item::Stroke {
+                    paths,
+                    color,
+                    #[cfg(feature = "pencil")]
+                    thickness,
+                });
+            }
+            items
+        }
+        ty => {
+            report.warn(ConversionWarning::UnknownShapeTypeSkipped(ty));
+            Vec::new()
+        }
+    }
+}
+
+/// Looks up the RGBA value of a color, reporting missing colors.
+pub(crate) fn resolve_rgba(
+    palette: Option<&PaletteData>,
+    color_id: Option<u64>,
+    report: &mut ConversionReport,
+) -> Option<Rgba> {
+    match color_id {
+        Some(id) => {
+            let Some(color) = palette.and_then(|palette| palette.find(id)) else {
+                report.warn(ConversionWarning::MissingColor(id));
+                return None;
+            };
+            let rgba = color.rgba();
+            if rgba.is_none() {
+                report.warn(ConversionWarning::UnsupportedColor(id));
+            }
+            rgba
+        }
+        None => {
+            report.warn(ConversionWarning::NoColor);
+            None
+        }
+    }
+}
@@ -0,0 +1,258 @@
+//! PDF export.
+//!
+//! Each drawing becomes one page with vector contents. Colors are written as device RGB, and
+//! translucent colors use a graphics state with the corresponding alpha. Like in the SVG export,
+//! pencil strokes are drawn as their center lines with a constant width.
+
+use crate::color::Rgba;
+use crate::document::{find_layer, find_palette, Art};
+use crate::export::{self, ConversionReport, CoordinateMapping, Framing, Item};
+use crate::geometry::Bounds;
+use crate::layer::{LayerData, PathSegment, ShapePath};
+use crate::palette::PaletteData;
+use crate::read::FileData;
+use std::collections::BTreeSet;
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct PdfOptions {
+    /// The area of the drawing to export, in drawing units. Defaults to the framed area.
+    pub view_box: Option<Bounds>,
+    /// How to frame the drawing if there's no explicit view box.
+    pub framing: Framing,
+    /// Padding around the drawing, in drawing units. Only used if there is no explicit view box.
+    pub padding: f32,
+    /// How drawing units map to points (1/72 inch). PDF is Y-up like TVG, so by default Y isn't
+    /// flipped.
+    pub mapping: CoordinateMapping,
+    /// Stroke width of pencil lines, in drawing units.
+    pub stroke_width: f32,
+}
+
+impl Default for PdfOptions {
+    fn default() -> Self {
+        PdfOptions {
+            view_box: None,
+            framing: Framing::Bounds,
+            padding: 10.,
+            mapping: CoordinateMapping {
+                flip_y: false,
+                ..CoordinateMapping::new(0.25)
+            },
+            stroke_width: 4.,
+        }
+    }
+}
+
+impl PdfOptions {
+    /// Returns the exported area of a drawing, in drawing units.
+    pub fn view_box(&self, data: &[FileData]) -> Bounds {
+        let layers = Art::ALL.into_iter().filter_map(|art| find_layer(data, art));
+        export::frame(
+            layers,
+            self.view_box,
+            self.framing,
+            self.padding + self.stroke_width / 2.,
+        )
+    }
+}
+
+/// Appends path construction operators, in drawing units.
+fn write_path(out: &mut String, path: &ShapePath) {
+    let mut is_first = true;
+    for segment in &path.segments {
+        match segment {
+            PathSegment::Line((x, y)) => {
+                let op = if is_first { 'm' } else { 'l' };
+                writeln!(out, "{x} {y} {op}").unwrap();
+            }
+            PathSegment::Cubic((x1, y1), (x2, y2), (x, y)) => {
+                writeln!(out, "{x1} {y1} {x2} {y2} {x} {y} c").unwrap();
+            }
+        }
+        is_first = false;
+    }
+}
+
+/// Contents of one page.
+struct Page {
+    /// The media box, in points.
+    bounds: Bounds,
+    content: String,
+    /// Alpha values (0–255) used by the page, each of which needs a graphics state.
+    alphas: BTreeSet<u8>,
+}
+
+fn write_item(
+    page: &mut Page,
+    item: &Item,
+    palette: Option<&PaletteData>,
+    options: &PdfOptions,
+    report: &mut ConversionReport,
+) {
+    let (Item::Fill { paths, color } | Item::Stroke { paths, color, .. }) = item;
+    // no palette entry: draw it in black so it's at least visible
+    let rgba = export::resolve_rgba(palette, *color, report).unwrap_or(Rgba::BLACK);
+    let ([r, g, b, _], a) = (rgba.to_f32(), rgba.a);
+
+    let out = &mut page.content;
+    out.push_str("q\n");
+    if a != 255 {
+        page.alphas.insert(a);
+        writeln!(out, "/GS{a} gs").unwrap();
+    }
+    for path in paths {
+        write_path(out, path);
+    }
+    match item {
+        Item::Fill { .. } => writeln!(out, "{r} {g} {b} rg\nf*").unwrap(),
+        Item::Stroke { .. } => writeln!(
+            out,
+            "{r} {g} {b} RG\n{} w\n1 J\n1 j\nS",
+            options.stroke_width
+        )
+        .unwrap(),
+    }
+    out.push_str("Q\n");
+}
+
+fn page(
+    data: &[FileData],
+    view_box: Bounds,
+    options: &PdfOptions,
+    report: &mut ConversionReport,
+) -> Page {
+    let mut page = Page {
+        bounds: options.mapping.output_bounds(view_box),
+        content: String::new(),
+        alphas: BTreeSet::new(),
+    };
+    // map drawing units to page space
+    let [a, b, c, d, e, f] = options.mapping.transform(view_box);
+    writeln!(page.content, "{a} {b} {c} {d} {e} {f} cm").unwrap();
+
+    let palette = find_palette(data);
+    for art in Art::ALL {
+        if let Some(LayerData::Vector { shapes, .. }) = find_layer(data, art) {
+            for shape in shapes {
+                for item in export::shape_items(shape, report) {
+                    write_item(&mut page, &item, palette, options, report);
+                }
+            }
+        }
+    }
+    page
+}
+
+/// Writes PDF objects and keeps track of their offsets for the cross-reference table.
+struct PdfWriter {
+    out: Vec<u8>,
+    offsets: Vec<usize>,
+}
+
+impl PdfWriter {
+    fn object(&mut self, id: usize, contents: &str) {
+        self.begin_object(id);
+        self.out.extend_from_slice(contents.as_bytes());
+        self.out.extend_from_slice(b"\nendobj\n");
+    }
+
+    fn begin_object(&mut self, id: usize) {
+        if self.offsets.len() < id {
+            self.offsets.resize(id, 0);
+        }
+        self.offsets[id - 1] = self.out.len();
+        self.out
+            .extend_from_slice(format!("{id} 0 obj\n").as_bytes());
+    }
+
+    fn stream(&mut self, id: usize, data: &str) {
+        self.begin_object(id);
+        let header = format!("<< /Length {} >>\nstream\n", data.len());
+        self.out.extend_from_slice(header.as_bytes());
+        self.out.extend_from_slice(data.as_bytes());
+        self.out.extend_from_slice(b"\nendstream\nendobj\n");
+    }
+
+    fn finish(mut self, root: usize) -> Vec<u8> {
+        let xref = self.out.len();
+        let mut table = format!("xref\n0 {}\n0000000000 65535 f \n", self.offsets.len() + 1);
+        for offset in &self.offsets {
+            writeln!(table, "{offset:010} 00000 n ").unwrap();
+        }
+        write!(
+            table,
+            "trailer\n<< /Size {} /Root {root} 0 R >>\nstartxref\n{xref}\n%%EOF\n",
+            self.offsets.len() + 1
+        )
+        .unwrap();
+        self.out.extend_from_slice(table.as_bytes());
+        self.out
+    }
+}
+
+/// Exports several drawings as a PDF document with one page per drawing.
+///
+/// All pages use the same view box, which contains all drawings.
+pub fn pages_to_pdf(pages: &[&[FileData]], options: &PdfOptions) -> (Vec<u8>, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let view_box = pages
+        .iter()
+        .map(|data| options.view_box(data))
+        .reduce(Bounds::union)
+        .unwrap_or(Bounds {
+            min: (0., 0.),
+            max: (1., 1.),
+        });
+
+    let mut writer = PdfWriter {
+        // the second line marks the file as binary, as recommended by the spec
+        out: b"%PDF-1.4\n%\xe2\xe3\xcf\xd3\n".to_vec(),
+        offsets: Vec::new(),
+    };
+
+    // object 1 is the catalog, object 2 the page tree, then two objects per page
+    let page_ids: Vec<usize> = (0..pages.len()).map(|i| 3 + i * 2).collect();
+    writer.object(1, "<< /Type /Catalog /Pages 2 0 R >>");
+    let kids: Vec<_> = page_ids.iter().map(|id| format!("{id} 0 R")).collect();
+    writer.object(
+        2,
+        &format!(
+            "<< /Type /Pages /Kids [{}] /Count {} >>",
+            kids.join(" "),
+            pages.len()
+        ),
+    );
+
+    for (data, id) in pages.iter().zip(page_ids) {
+        let page = page(data, view_box, options, &mut report);
+        let states: String = page
+            .alphas
+            .iter()
+            .map(|a| {
+                let alpha = *a as f32 / 255.;
+                format!("/GS{a} << /Type /ExtGState /ca {alpha} /CA {alpha} >> ")
+            })
+            .collect();
+        writer.object(
+            id,
+            &format!(
+                "<< /Type /Page /Parent 2 0 R /MediaBox [{} {} {} {}] /Contents {} 0 R \
+                 /Resources << /ExtGState << {states}>> >> >>",
+                page.bounds.min.0,
+                page.bounds.min.1,
+                page.bounds.max.0,
+                page.bounds.max.1,
+                id + 1
+            ),
+        );
+        writer.stream(id + 1, &page.content);
+    }
+
+    (writer.finish(1), report)
+}
+
+/// Exports a drawing as a single-page PDF document.
+pub fn to_pdf(data: &[FileData], options: &PdfOptions) -> (Vec<u8>, ConversionReport) {
+    pages_to_pdf(&[data], options)
+}
@@ -0,0 +1,370 @@
+//! SVG export.
+//!
+//! Fill shapes are exported as filled paths. Pencil strokes with thickness data are exported as
+//! filled outlines, since SVG strokes have a constant width; the outlines can optionally be made
+//! editable as Inkscape power strokes (see [SvgOptions::inkscape_extensions]). Strokes without
+//! thickness data are exported as their center lines with a constant stroke width.
+
+use crate::color::Rgba;
+use crate::document::{find_layer, find_palette, Art};
+#[cfg(feature = "pencil")]
+use crate::export::Thickness;
+use crate::export::{self, ConversionReport, CoordinateMapping, FieldGrid, Framing, Item, Origin};
+use crate::geometry::Bounds;
+use crate::layer::{LayerData, PathSegment, Point, ShapePath};
+use crate::palette::PaletteData;
+#[cfg(feature = "pencil")]
+use crate::pencil;
+use crate::read::FileData;
+use std::fmt::Write;
+
+#[derive(Debug, Clone)]
+pub struct SvgOptions {
+    /// The area of the drawing to export, in drawing units. Defaults to the framed area.
+    pub view_box: Option<Bounds>,
+    /// How to frame the drawing if there's no explicit view box.
+    pub framing: Framing,
+    /// Padding around the drawing, in drawing units. Only used if there is no explicit view box.
+    pub padding: f32,
+    /// Stroke width of pencil lines without thickness data, in drawing units.
+    pub stroke_width: f32,
+    /// If set, a field grid will be drawn over the drawing.
+    pub field_grid: Option<FieldGrid>,
+    /// Background color. Transparent if None.
+    pub background: Option<Rgba>,
+    /// The art layers to export. Other layers are left out, and also don't count for framing.
+    pub layers: Vec<Art>,
+    /// Opacity of the underlay art, from 0 to 1.
+    pub underlay_opacity: f32,
+    /// If true (the default), line art is drawn over color art, like in Harmony's default
+    /// camera view. If false, color art is drawn over line art.
+    pub line_over_color: bool,
+    /// How drawing units map to SVG user units. Defaults to drawing units with Y pointing down and
+    /// the drawing origin at 0, 0.
+    pub mapping: CoordinateMapping,
+    /// If true, the outlines of pencil strokes with thickness data also get their center line and
+    /// an Inkscape power stroke path effect, so they can be edited as strokes in Inkscape. Other
+    /// programs show the outlines as usual. False by default.
+    pub inkscape_extensions: bool,
+}
+
+impl Default for SvgOptions {
+    fn default() -> Self {
+        SvgOptions {
+            view_box: None,
+            framing: Framing::Bounds,
+            padding: 10.,
+            stroke_width: 4.,
+            field_grid: None,
+            background: None,
+            layers: Art::ALL.to_vec(),
+            underlay_opacity: 1.,
+            line_over_color: true,
+            mapping: CoordinateMapping {
+                origin: Origin::Drawing,
+                ..CoordinateMapping::default()
+            },
+            inkscape_extensions: false,
+        }
+    }
+}
+
+impl SvgOptions {
+    /// Returns the exported area of a drawing, in drawing units.
+    pub fn view_box(&self, data: &[FileData]) -> Bounds {
+        let layers = Art::ALL
+            .into_iter()
+            .filter(|art| self.layers.contains(art))
+            .filter_map(|art| find_layer(data, art));
+        export::frame(
+            layers,
+            self.view_box,
+            self.framing,
+            self.padding + self.stroke_width / 2.,
+        )
+    }
+}
+
+fn write_color(
+    out: &mut String,
+    attr: &str,
+    palette: Option<&PaletteData>,
+    color_id: Option<u64>,
+    report: &mut ConversionReport,
+) {
+    match export::resolve_rgba(palette, color_id, report) {
+        Some(rgba) => {
+            write!(out, r#" {attr}="{}""#, rgba.to_css_hex_rgb()).unwrap();
+            if !rgba.is_opaque() {
+                write!(out, r#" {attr}-opacity="{}""#, rgba.a as f32 / 255.).unwrap();
+            }
+        }
+        // no palette entry or not a solid color: draw it in black so it's at least visible
+        None => write!(out, r#" {attr}="black""#).unwrap(),
+    }
+}
+
+/// Appends SVG path data for a path, with points mapped to SVG coordinates by `to_svg`.
+fn write_path_data(out: &mut String, path: &ShapePath, to_svg: impl Fn(Point) -> Point) {
+    let mut is_first = true;
+    for segment in &path.segments {
+        match *segment {
+            PathSegment::Line(p) => {
+                let cmd = if is_first { 'M' } else { 'L' };
+                let (x, y) = to_svg(p);
+                write!(out, "{cmd}{x} {y} ").unwrap();
+            }
+            PathSegment::Cubic(p1, p2, p) => {
+                let ((x1, y1), (x2, y2), (x, y)) = (to_svg(p1), to_svg(p2), to_svg(p));
+                write!(out, "C{x1} {y1} {x2} {y2} {x} {y} ").unwrap();
+            }
+        }
+        is_first = false;
+    }
+}
+
+fn write_item(
+    out: &mut String,
+    item: &Item,
+    palette: Option<&PaletteData>,
+    view_box: Bounds,
+    options: &SvgOptions,
+    report: &mut ConversionReport,
+) {
+    let to_svg = |p| options.mapping.apply(view_box, p);
+    match item {
+        Item::Fill { paths, color } => {
+            let mut d = String::new();
+            for path in paths {
+                write_path_data(&mut d, path, to_svg);
+            }
+            write!(out, r#"<path d="{}""#, d.trim_end()).unwrap();
+            write_color(out, "fill", palette, *color, report);
+            out.push_str("/>\n");
+        }
+        #[cfg(feature = "pencil")]
+        Item::Stroke {
+            paths,
+            color,
+            thickness,
+        } => {
+            let mut center_lines = Vec::new();
+            for (path, thickness) in paths.iter().zip(thickness) {
+                match thickness {
+                    Some(thickness) => {
+                        write_outline(out, path, thickness, view_box, options);
+                        write_color(out, "fill", palette, *color, report);
+                        out.push_str("/>\n");
+                    }
+                    None => center_lines.push(*path),
+                }
+            }
+            if !center_lines.is_empty() {
+                write_center_lines(out, &center_lines, view_box, options);
+                write_color(out, "stroke", palette, *color, report);
+                out.push_str("/>\n");
+            }
+        }
+        #[cfg(not(feature = "pencil"))]
+        Item::Stroke { paths, color } => {
+            write_center_lines(out, paths, view_box, options);
+            write_color(out, "stroke", palette, *color, report);
+            out.push_str("/>\n");
+        }
+    }
+}
+
+/// Appends a `<path` element for center lines with a constant width, without its color and end.
+fn write_center_lines(
+    out: &mut String,
+    paths: &[&ShapePath],
+    view_box: Bounds,
+    options: &SvgOptions,
+) {
+    let mut d = String::new();
+    for path in paths {
+        write_path_data(&mut d, path, |p| options.mapping.apply(view_box, p));
+    }
+    write!(
+        out,
+        r#"<path d="{}" fill="none" stroke-width="{}" stroke-linecap="round" stroke-linejoin="round""#,
+        d.trim_end(),
+        options.stroke_width * options.mapping.scale
+    )
+    .unwrap();
+}
+
+/// Length of the straight lines that the outlines of pencil strokes are made of, in drawing
+/// units.
+#[cfg(feature = "pencil")]
+const OUTLINE_STEP: f32 = 1.;
+/// The most points on each side of a pencil stroke outline.
+#[cfg(feature = "pencil")]
+const MAX_OUTLINE_SAMPLES: usize = 4096;
+
+/// Appends a `<path` element for the outline of a pencil stroke with thickness data, without its
+/// color and end. With [SvgOptions::inkscape_extensions], the path effect it refers to is written
+/// before it.
+#[cfg(feature = "pencil")]
+fn write_outline(
+    out: &mut String,
+    path: &ShapePath,
+    thickness: &Thickness,
+    view_box: Bounds,
+    options: &SvgOptions,
+) {
+    let to_svg = |p| options.mapping.apply(view_box, p);
+    // min also replaces NaN, for paths with non-finite points
+    let steps = (path.length() / OUTLINE_STEP)
+        .ceil()
+        .min(MAX_OUTLINE_SAMPLES as f32) as usize;
+    let samples = (steps + 1).clamp(2, MAX_OUTLINE_SAMPLES);
+    let outline = pencil::outline(path, thickness.definition, thickness.domain, samples);
+    let mut d = String::new();
+    for (i, point) in outline.into_iter().enumerate() {
+        let (x, y) = to_svg(point);
+        let cmd = if i == 0 { 'M' } else { 'L' };
+        write!(d, "{cmd}{x} {y} ").unwrap();
+    }
+    d.push('Z');
+
+    if !options.inkscape_extensions {
+        write!(out, r#"<path d="{d}""#).unwrap();
+        return;
+    }
+    // the offset in the output is unique, even with several drawings in one document
+    let id = format!("powerstroke{}", out.len());
+    writeln!(
+        out,
+        r#"<defs><inkscape:path-effect id="{id}" effect="powerstroke" is_visible="true" offset_points="{}" sort_points="true" interpolator_type="CubicBezierSmooth" interpolator_beta="0.2" start_linecap_type="butt" end_linecap_type="butt" linejoin_type="round" miter_limit="4" scale_width="1" not_jump="false"/></defs>"#,
+        powerstroke_points(path, thickness, options.mapping.scale),
+    )
+    .unwrap();
+    let mut original = String::new();
+    write_path_data(&mut original, path, to_svg);
+    write!(
+        out,
+        r##"<path d="{d}" inkscape:original-d="{}" inkscape:path-effect="#{id}""##,
+        original.trim_end()
+    )
+    .unwrap();
+}
+
+/// Returns the `offset_points` of an Inkscape power stroke: a point for each thickness point in
+/// the domain, as the path parameter (see [ShapePath::param_at_length]) and the average of the
+/// offsets on both sides, in output units.
+#[cfg(feature = "pencil")]
+fn powerstroke_points(path: &ShapePath, thickness: &Thickness, scale: f32) -> String {
+    let (start, end) = thickness.domain;
+    let length = path.length();
+    let points: Vec<_> = thickness
+        .definition
+        .iter()
+        .filter(|point| end > start && (start..=end).contains(&point.loc))
+        .map(|point| {
+            let param = path.param_at_length((point.loc - start) / (end - start) * length);
+            let width = (point.left.offset + point.right.offset) / 2. * scale;
+            format!("{param},{width}")
+        })
+        .collect();
+    points.join(" | ")
+}
+
+fn write_field_grid(out: &mut String, grid: &FieldGrid, to_svg: impl Fn(Point) -> Point) {
+    out.push_str(r#"<g class="field-grid" fill="none" vector-effect="non-scaling-stroke">"#);
+    out.push('\n');
+    for (i, (a, b)) in grid.lines().into_iter().enumerate() {
+        // the first two lines are the center axes
+        let stroke = if i < 2 { "#f00" } else { "#0007" };
+        let ((x1, y1), (x2, y2)) = (to_svg(a), to_svg(b));
+        writeln!(
+            out,
+            r#"<line x1="{x1}" y1="{y1}" x2="{x2}" y2="{y2}" stroke="{stroke}" vector-effect="non-scaling-stroke"/>"#,
+        )
+        .unwrap();
+    }
+    out.push_str("</g>\n");
+}
+
+/// Appends the opening `<svg>` tag for a view box (in drawing units), and the background if
+/// there is one.
+pub(crate) fn write_svg_start(out: &mut String, view_box: Bounds, options: &SvgOptions) {
+    let bounds = options.mapping.output_bounds(view_box);
+    let (width, height) = options.mapping.output_size(view_box);
+    write!(
+        out,
+        r#"<svg xmlns="http://www.w3.org/2000/svg" viewBox="{} {} {} {}" width="{}" height="{}""#,
+        bounds.min.0, bounds.min.1, width, height, width, height,
+    )
+    .unwrap();
+    if options.inkscape_extensions {
+        out.push_str(r#" xmlns:inkscape="http://www.inkscape.org/namespaces/inkscape""#);
+    }
+    out.push_str(">\n");
+
+    if let Some(background) = options.background {
+        write!(
+            out,
+            r#"<rect x="{}" y="{}" width="{}" height="{}" fill="{}""#,
+            bounds.min.0,
+            bounds.min.1,
+            width,
+            height,
+            background.to_css_hex_rgb(),
+        )
+        .unwrap();
+        if !background.is_opaque() {
+            write!(out, r#" fill-opacity="{}""#, background.a as f32 / 255.).unwrap();
+        }
+        out.push_str("/>\n");
+    }
+}
+
+/// Appends the art layers of a drawing, without the surrounding `<svg>` tag.
+pub(crate) fn write_drawing(
+    out: &mut String,
+    data: &[FileData],
+    view_box: Bounds,
+    options: &SvgOptions,
+    report: &mut ConversionReport,
+) {
+    let palette = find_palette(data);
+    for art in export::layer_order(&options.layers, options.line_over_color) {
+        if let Some(LayerData::Vector { shapes, .. }) = find_layer(data, art) {
+            let opacity = export::layer_opacity(art, options.underlay_opacity);
+            if opacity <= 0. {
+                continue;
+            }
+            out.push_str("<g fill-rule=\"evenodd\"");
+            if opacity < 1. {
+                write!(out, r#" opacity="{opacity}""#).unwrap();
+            }
+            out.push_str(">\n");
+            for shape in shapes {
+                #[cfg(feature = "pencil")]
+                let items = export::shape_items_with_thickness(shape, report);
+                #[cfg(not(feature = "pencil"))]
+                let items = export::shape_items(shape, report);
+                for item in items {
+                    write_item(out, &item, palette, view_box, options, report);
+                }
+            }
+            out.push_str("</g>\n");
+        }
+    }
+
+    if let Some(grid) = &options.field_grid {
+        write_field_grid(out, grid, |p| options.mapping.apply(view_box, p));
+    }
+}
+
+/// Exports a TVG file as an SVG document.
+pub fn to_svg(data: &[FileData], options: &SvgOptions) -> (String, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let mut out = String::new();
+    let view_box = options.view_box(data);
+    write_svg_start(&mut out, view_box, options);
+    write_drawing(&mut out, data, view_box, options, &mut report);
+    out.push_str("</svg>\n");
+    (out, report)
+}
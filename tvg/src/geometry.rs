@@ -0,0 +1,921 @@
+//! Geometric helpers for working with shapes as plain polylines.
+
+use crate::layer::{
+    LayerData, PathSegment, Point, ShapeComponentData, ShapePath, ShapeType, VectorShape,
+};
+use std::ops::{Add, Div, Mul, Sub};
+
+/// Default flattening tolerance, in drawing units.
+pub const FLATTEN_TOLERANCE: f32 = 0.5;
+
+/// Two points closer than this (in drawing units) are considered identical when checking whether
+/// a contour is closed.
+pub const CLOSE_EPSILON: f32 = 0.01;
+
+/// Iterations of Newton's method when fitting a cubic to points.
+const REPARAMETERIZE_ITERATIONS: usize = 4;
+
+/// A flattened path of a shape.
+#[derive(Debug, Clone)]
+pub struct Contour {
+    /// Polyline points. If the contour is closed, the last point equals the first point.
+    pub points: Vec<Point>,
+    /// Whether the last point coincides with the first point.
+    pub closed: bool,
+    /// Signed area (shoelace formula).
+    ///
+    /// TVG coordinates are Y-up, so a positive area means the contour runs counterclockwise.
+    /// Open contours are treated as if they were implicitly closed.
+    pub signed_area: f32,
+    /// The palette color ID of the component this contour belongs to.
+    pub color_id: Option<u64>,
+}
+
+impl Contour {
+    /// Returns true if the contour runs counterclockwise (in Y-up coordinates).
+    pub fn is_counter_clockwise(&self) -> bool {
+        self.signed_area > 0.
+    }
+}
+
+/// Returns the flattened contours of all paths in a shape, in file order.
+pub fn contours(shape: &VectorShape) -> Vec<Contour> {
+    contours_with_tolerance(shape, FLATTEN_TOLERANCE)
+}
+
+/// Like [contours], but with a custom flattening tolerance.
+pub fn contours_with_tolerance(shape: &VectorShape, tolerance: f32) -> Vec<Contour> {
+    let mut contours = Vec::new();
+
+    for component in &shape.components {
+        let mut color_id = None;
+        for tag in &component.tags {
+            match tag {
+                ShapeComponentData::Info(info) => color_id = info.color_id,
+                ShapeComponentData::Path(path) => {
+                    let points = flatten(path, tolerance);
+                    if points.is_empty() {
+                        continue;
+                    }
+                    let closed = is_closed(&points, CLOSE_EPSILON);
+                    let signed_area = signed_area(&points);
+                    contours.push(Contour {
+                        points,
+                        closed,
+                        signed_area,
+                        color_id,
+                    });
+                }
+                _ => (),
+            }
+        }
+    }
+
+    contours
+}
+
+/// Flattens a path into a polyline such that no point on the curve is further than `tolerance`
+/// away from it.
+///
+/// Paths start with a line segment whose point acts as the starting point.
+pub fn flatten(path: &ShapePath, tolerance: f32) -> Vec<Point> {
+    let mut points = Vec::with_capacity(path.segments.len());
+
+    for segment in &path.segments {
+        match *segment {
+            PathSegment::Line(p) => points.push(p),
+            PathSegment::Cubic(c1, c2, p) => {
+                let Some(&start) = points.last() else {
+                    // there is nowhere to start the curve from, so treat it as a jump
+                    points.push(p);
+                    continue;
+                };
+                flatten_cubic(start, c1, c2, p, tolerance, &mut points);
+            }
+        }
+    }
+
+    points
+}
+
+/// Appends a flattened cubic Bézier curve (excluding its start point) to `out`.
+pub fn flatten_cubic(
+    p0: Point,
+    p1: Point,
+    p2: Point,
+    p3: Point,
+    tolerance: f32,
+    out: &mut Vec<Point>,
+) {
+    // Wang's formula for the number of segments needed to stay within the tolerance
+    let dd = |a: Point, b: Point, c: Point| {
+        let x = a.0 - 2. * b.0 + c.0;
+        let y = a.1 - 2. * b.1 + c.1;
+        x.hypot(y)
+    };
+    let m = dd(p0, p1, p2).max(dd(p1, p2, p3));
+    let n = (0.75 * m / tolerance.max(f32::EPSILON)).sqrt().ceil();
+    let n = if n.is_finite() {
+        (n as usize).clamp(1, 1024)
+    } else {
+        1
+    };
+
+    for i in 1..=n {
+        out.push(cubic_point(p0, p1, p2, p3, i as f32 / n as f32));
+    }
+}
+
+/// Evaluates a cubic Bézier curve at `t`.
+pub fn cubic_point(p0: Point, p1: Point, p2: Point, p3: Point, t: f32) -> Point {
+    let mt = 1. - t;
+    let a = mt * mt * mt;
+    let b = 3. * mt * mt * t;
+    let c = 3. * mt * t * t;
+    let d = t * t * t;
+    (
+        a * p0.0 + b * p1.0 + c * p2.0 + d * p3.0,
+        a * p0.1 + b * p1.1 + c * p2.1 + d * p3.1,
+    )
+}
+
+/// Returns true if the first and last point of the polyline are within `epsilon` of each other.
+pub fn is_closed(points: &[Point], epsilon: f32) -> bool {
+    match (points.first(), points.last()) {
+        (Some(a), Some(b)) if points.len() > 2 => (a.0 - b.0).hypot(a.1 - b.1) <= epsilon,
+        _ => false,
+    }
+}
+
+/// Returns the signed area of a polygon (positive if counterclockwise in Y-up coordinates).
+pub fn signed_area(points: &[Point]) -> f32 {
+    let mut area = 0.;
+    for (i, a) in points.iter().enumerate() {
+        let b = points[(i + 1) % points.len()];
+        area += a.0 * b.1 - b.0 * a.1;
+    }
+    area / 2.
+}
+
+/// An axis-aligned bounding box.
+#[derive(Debug, Clone, Copy, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Bounds {
+    pub min: Point,
+    pub max: Point,
+}
+
+impl Bounds {
+    /// Creates bounds containing a single point.
+    pub fn from_point(p: Point) -> Self {
+        Bounds { min: p, max: p }
+    }
+
+    /// Returns the bounds of a set of points, or None if there are no points.
+    pub fn from_points(points: &[Point]) -> Option<Self> {
+        let (first, rest) = points.split_first()?;
+        let mut bounds = Bounds::from_point(*first);
+        for p in rest {
+            bounds.include(*p);
+        }
+        Some(bounds)
+    }
+
+    /// Extends the bounds to include a point.
+    pub fn include(&mut self, p: Point) {
+        self.min.0 = self.min.0.min(p.0);
+        self.min.1 = self.min.1.min(p.1);
+        self.max.0 = self.max.0.max(p.0);
+        self.max.1 = self.max.1.max(p.1);
+    }
+
+    /// Returns the smallest bounds containing both.
+    pub fn union(self, other: Bounds) -> Bounds {
+        Bounds {
+            min: (self.min.0.min(other.min.0), self.min.1.min(other.min.1)),
+            max: (self.max.0.max(other.max.0), self.max.1.max(other.max.1)),
+        }
+    }
+
+    /// Returns the bounds grown by `amount` on every side.
+    pub fn inflate(self, amount: f32) -> Bounds {
+        Bounds {
+            min: (self.min.0 - amount, self.min.1 - amount),
+            max: (self.max.0 + amount, self.max.1 + amount),
+        }
+    }
+
+    pub fn width(&self) -> f32 {
+        self.max.0 - self.min.0
+    }
+
+    pub fn height(&self) -> f32 {
+        self.max.1 - self.min.1
+    }
+}
+
+/// Returns the bounds of all paths in a shape.
+pub fn shape_bounds(shape: &VectorShape) -> Option<Bounds> {
+    contours(shape)
+        .iter()
+        .filter_map(|contour| Bounds::from_points(&contour.points))
+        .reduce(Bounds::union)
+}
+
+/// Returns the bounds of all shapes in a layer.
+pub fn layer_bounds(layer: &LayerData) -> Option<Bounds> {
+    match layer {
+        LayerData::Empty | LayerData::Unknown { .. } => None,
+        LayerData::Vector { shapes, .. } => {
+            shapes.iter().filter_map(shape_bounds).reduce(Bounds::union)
+        }
+    }
+}
+
+/// Returns the bounds of all shapes in the layers that are at least `min_size` wide or tall.
+///
+/// This ignores stray dots and specks, which are common in rough drawings and would otherwise
+/// make the bounds much larger than the actual drawing.
+pub fn significant_bounds<'a>(
+    layers: impl IntoIterator<Item = &'a LayerData>,
+    min_size: f32,
+) -> Option<Bounds> {
+    layers
+        .into_iter()
+        .flat_map(|layer| match layer {
+            LayerData::Empty | LayerData::Unknown { .. } => &[][..],
+            LayerData::Vector { shapes, .. } => &shapes[..],
+        })
+        .filter_map(shape_bounds)
+        .filter(|bounds| bounds.width() >= min_size || bounds.height() >= min_size)
+        .reduce(Bounds::union)
+}
+
+/// A path made only of cubic Bézier segments.
+///
+/// Line segments are represented as cubics with their control points at one and two thirds of
+/// the line, which describes exactly the same line. Since every segment has the same structure,
+/// two paths with the same number of segments can be interpolated point by point.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct PolyCubicBezier {
+    pub start: Point,
+    /// Control point 1, control point 2, and end point of each segment.
+    pub segments: Vec<(Point, Point, Point)>,
+}
+
+impl PolyCubicBezier {
+    /// Converts a path. Returns None if the path has no segments.
+    ///
+    /// Line segments other than the first (which is the starting point) are converted to cubics.
+    pub fn from_path(path: &ShapePath) -> Option<Self> {
+        let mut segments = path.segments.iter();
+        let start = match *segments.next()? {
+            PathSegment::Line(p) => p,
+            // no explicit starting point; this doesn't seem to happen in practice
+            PathSegment::Cubic(_, _, p) => p,
+        };
+
+        let mut current = start;
+        let segments = segments
+            .map(|segment| {
+                let segment = match *segment {
+                    PathSegment::Line(p) => {
+                        (lerp(current, p, 1. / 3.), lerp(current, p, 2. / 3.), p)
+                    }
+                    PathSegment::Cubic(c1, c2, p) => (c1, c2, p),
+                };
+                current = segment.2;
+                segment
+            })
+            .collect();
+
+        Some(PolyCubicBezier { start, segments })
+    }
+
+    /// Converts back to a path, with every segment as a cubic.
+    ///
+    /// Use [normalize] to turn straight segments back into lines.
+    pub fn to_path(&self) -> ShapePath {
+        let mut segments = Vec::with_capacity(self.segments.len() + 1);
+        segments.push(PathSegment::Line(self.start));
+        segments.extend(
+            self.segments
+                .iter()
+                .map(|&(c1, c2, p)| PathSegment::Cubic(c1, c2, p)),
+        );
+        ShapePath {
+            segments,
+            extra: None,
+        }
+    }
+
+    /// Interpolates between two poly-Béziers with the same number of segments.
+    /// Returns None if the segment counts differ.
+    pub fn interpolate(&self, other: &PolyCubicBezier, t: f32) -> Option<PolyCubicBezier> {
+        if self.segments.len() != other.segments.len() {
+            return None;
+        }
+        Some(PolyCubicBezier {
+            start: lerp(self.start, other.start, t),
+            segments: self
+                .segments
+                .iter()
+                .zip(&other.segments)
+                .map(|(a, b)| (lerp(a.0, b.0, t), lerp(a.1, b.1, t), lerp(a.2, b.2, t)))
+                .collect(),
+        })
+    }
+}
+
+fn lerp(a: Point, b: Point, t: f32) -> Point {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn distance(a: Point, b: Point) -> f32 {
+    (a.0 - b.0).hypot(a.1 - b.1)
+}
+
+pub(crate) fn sub(a: Point, b: Point) -> Point {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+pub(crate) fn add(a: Point, b: Point) -> Point {
+    (a.0 + b.0, a.1 + b.1)
+}
+
+pub(crate) fn scale(a: Point, s: f32) -> Point {
+    (a.0 * s, a.1 * s)
+}
+
+pub(crate) fn dot(a: Point, b: Point) -> f32 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+pub(crate) fn length(a: Point) -> f32 {
+    a.0.hypot(a.1)
+}
+
+/// Returns the vector scaled to length 1, or None if it has no length.
+fn normalized(a: Point) -> Option<Point> {
+    let len = length(a);
+    (len > 0.).then(|| scale(a, 1. / len))
+}
+
+/// Returns a copy of the path where cubic segments that are straight lines are replaced with line
+/// segments.
+///
+/// A cubic is considered a line if its control points are within `epsilon` of the points at one
+/// and two thirds of the line, i.e. if converting it to a line doesn't change its
+/// parametrization. This makes it the inverse of the conversion in [PolyCubicBezier::from_path].
+pub fn normalize(path: &ShapePath, epsilon: f32) -> ShapePath {
+    let mut segments = Vec::with_capacity(path.segments.len());
+    let mut current = None;
+    for segment in &path.segments {
+        let segment = match *segment {
+            PathSegment::Cubic(c1, c2, p)
+                if current.is_some_and(|start| {
+                    distance(c1, lerp(start, p, 1. / 3.)) <= epsilon
+                        && distance(c2, lerp(start, p, 2. / 3.)) <= epsilon
+                }) =>
+            {
+                PathSegment::Line(p)
+            }
+            ref segment => segment.clone(),
+        };
+        current = Some(match segment {
+            PathSegment::Line(p) | PathSegment::Cubic(_, _, p) => p,
+        });
+        segments.push(segment);
+    }
+    ShapePath {
+        segments,
+        extra: path.extra.clone(),
+    }
+}
+
+fn segment_end(segment: &PathSegment) -> Point {
+    match *segment {
+        PathSegment::Line(p) | PathSegment::Cubic(_, _, p) => p,
+    }
+}
+
+fn start_point(path: &ShapePath) -> Option<Point> {
+    path.segments.first().map(segment_end)
+}
+
+fn end_point(path: &ShapePath) -> Option<Point> {
+    path.segments.last().map(segment_end)
+}
+
+/// Returns a copy of the path that runs in the opposite direction.
+///
+/// Extra bytes are dropped, since it's unknown whether they depend on the direction.
+pub fn reverse(path: &ShapePath) -> ShapePath {
+    let mut segments = Vec::with_capacity(path.segments.len());
+    segments.extend(end_point(path).map(PathSegment::Line));
+    for pair in path.segments.windows(2).rev() {
+        let prev = segment_end(&pair[0]);
+        segments.push(match pair[1] {
+            PathSegment::Line(_) => PathSegment::Line(prev),
+            PathSegment::Cubic(c1, c2, _) => PathSegment::Cubic(c2, c1, prev),
+        });
+    }
+    ShapePath {
+        segments,
+        extra: None,
+    }
+}
+
+/// Appends `b` to `a` if `a` ends where `b` starts.
+fn join(a: &ShapePath, b: &ShapePath) -> Option<ShapePath> {
+    let (end, start) = (end_point(a)?, start_point(b)?);
+    if distance(end, start) > CLOSE_EPSILON {
+        return None;
+    }
+    let mut segments = a.segments.clone();
+    // the first segment of b is its starting point, which is where a ends
+    segments.extend(b.segments[1..].iter().cloned());
+    Some(ShapePath {
+        segments,
+        extra: None,
+    })
+}
+
+/// The direction a closed path runs in. See [ShapePath::direction].
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Clockwise,
+    CounterClockwise,
+}
+
+/// Paths are measured until the length of the control polygon and the chord of each part differ
+/// by less than this, in drawing units.
+const LENGTH_TOLERANCE: f32 = 0.01;
+
+/// The most times a cubic is split in half when measuring its length.
+const MAX_LENGTH_DEPTH: u32 = 16;
+
+impl ShapePath {
+    /// Returns true if the path ends within `epsilon` of where it starts. A path with a single
+    /// segment is just its starting point, so it's never closed.
+    pub fn is_closed(&self, epsilon: f32) -> bool {
+        match (start_point(self), end_point(self)) {
+            (Some(start), Some(end)) => self.segments.len() > 1 && distance(start, end) <= epsilon,
+            _ => false,
+        }
+    }
+
+    /// Returns the direction the path runs in (in Y-up coordinates, like TVG's), from the sign of
+    /// its area. Open paths are treated as if they were closed with a line. Returns None if the
+    /// path encloses no area, e.g. if it's a straight line.
+    pub fn direction(&self) -> Option<Direction> {
+        let area = signed_area(&flatten(self, FLATTEN_TOLERANCE));
+        if area > 0. {
+            Some(Direction::CounterClockwise)
+        } else if area < 0. {
+            Some(Direction::Clockwise)
+        } else {
+            None
+        }
+    }
+
+    /// Returns a copy of the path that runs in the opposite direction. See [reverse].
+    pub fn reverse(&self) -> ShapePath {
+        reverse(self)
+    }
+
+    /// Returns the parameter of the point at a distance along the path, in drawing units.
+    ///
+    /// The parameter is `i + t` for the point at `t` on the `i`-th segment after the starting
+    /// point, so it goes from 0 to the number of segments minus one. Distances outside the path
+    /// are clamped to its ends. See [ShapePath::point_at_param].
+    pub fn param_at_length(&self, length: f32) -> f32 {
+        self.param_at_length_in(&self.cumulative_lengths(), length)
+    }
+
+    /// Returns the distance along the path to the end of each segment after the starting point.
+    pub(crate) fn cumulative_lengths(&self) -> Vec<f32> {
+        let mut lengths = Vec::with_capacity(self.segments.len().saturating_sub(1));
+        let mut total = 0.;
+        for pair in self.segments.windows(2) {
+            let start = segment_end(&pair[0]);
+            total += match pair[1] {
+                PathSegment::Line(p) => distance(start, p),
+                PathSegment::Cubic(c1, c2, p) => cubic_length([start, c1, c2, p], 0),
+            };
+            lengths.push(total);
+        }
+        lengths
+    }
+
+    /// Like [ShapePath::param_at_length], with the lengths from [ShapePath::cumulative_lengths],
+    /// so they're only measured once when finding many points.
+    pub(crate) fn param_at_length_in(&self, cumulative: &[f32], length: f32) -> f32 {
+        let i = cumulative.partition_point(|&end| end < length);
+        let Some(&end) = cumulative.get(i) else {
+            return self.segments.len().saturating_sub(1) as f32;
+        };
+        let segment_start = if i == 0 { 0. } else { cumulative[i - 1] };
+        let segment_length = end - segment_start;
+        let remaining = (length - segment_start).max(0.);
+        let start = segment_end(&self.segments[i]);
+        let t = match self.segments[i + 1] {
+            _ if segment_length <= 0. => 0.,
+            PathSegment::Line(_) => remaining / segment_length,
+            PathSegment::Cubic(c1, c2, p) => {
+                cubic_param_at_fraction([start, c1, c2, p], remaining / segment_length)
+            }
+        };
+        i as f32 + t
+    }
+
+    /// Returns the point at a parameter (see [ShapePath::param_at_length]), clamped to the ends
+    /// of the path, or None if the path has no points.
+    pub fn point_at_param(&self, param: f32) -> Option<Point> {
+        let last = self.segments.len().checked_sub(1)?;
+        let param = param.clamp(0., last as f32);
+        let i = (param.floor() as usize).min(last.saturating_sub(1));
+        if last == 0 {
+            return Some(segment_end(&self.segments[0]));
+        }
+        let start = segment_end(&self.segments[i]);
+        let t = param - i as f32;
+        Some(match self.segments[i + 1] {
+            PathSegment::Line(p) => lerp(start, p, t),
+            PathSegment::Cubic(c1, c2, p) => cubic_point(start, c1, c2, p, t),
+        })
+    }
+
+    /// Returns the length of the path, measuring curves by adaptive subdivision.
+    pub fn length(&self) -> f32 {
+        let mut length = 0.;
+        let mut current = None;
+        for segment in &self.segments {
+            let end = segment_end(segment);
+            if let Some(start) = current {
+                length += match *segment {
+                    PathSegment::Line(p) => distance(start, p),
+                    PathSegment::Cubic(c1, c2, p) => cubic_length([start, c1, c2, p], 0),
+                };
+            }
+            current = Some(end);
+        }
+        length
+    }
+}
+
+/// Number of parts a cubic is measured in when finding a point by arc length.
+const ARC_LENGTH_STEPS: usize = 32;
+
+/// Returns the `t` at which a fraction of the length of a cubic is reached, interpolating between
+/// points at evenly spaced values of `t`.
+fn cubic_param_at_fraction(cubic: Cubic, fraction: f32) -> f32 {
+    let [p0, p1, p2, p3] = cubic;
+    let mut lengths = [0.; ARC_LENGTH_STEPS + 1];
+    let mut previous = p0;
+    for i in 1..=ARC_LENGTH_STEPS {
+        let point = cubic_point(p0, p1, p2, p3, i as f32 / ARC_LENGTH_STEPS as f32);
+        lengths[i] = lengths[i - 1] + distance(previous, point);
+        previous = point;
+    }
+    let target = fraction.clamp(0., 1.) * lengths[ARC_LENGTH_STEPS];
+    let i = lengths[1..].partition_point(|&length| length < target);
+    if i >= ARC_LENGTH_STEPS {
+        return 1.;
+    }
+    let (a, b) = (lengths[i], lengths[i + 1]);
+    let part = if b > a { (target - a) / (b - a) } else { 0. };
+    (i as f32 + part) / ARC_LENGTH_STEPS as f32
+}
+
+fn cubic_length(cubic: Cubic, depth: u32) -> f32 {
+    let [p0, p1, p2, p3] = cubic;
+    let chord = distance(p0, p3);
+    let polygon = distance(p0, p1) + distance(p1, p2) + distance(p2, p3);
+    if polygon - chord <= LENGTH_TOLERANCE || depth >= MAX_LENGTH_DEPTH {
+        return (chord + polygon) / 2.;
+    }
+
+    // de Casteljau at t = 0.5
+    let (a, b, c) = (lerp(p0, p1, 0.5), lerp(p1, p2, 0.5), lerp(p2, p3, 0.5));
+    let (d, e) = (lerp(a, b, 0.5), lerp(b, c, 0.5));
+    let mid = lerp(d, e, 0.5);
+    cubic_length([p0, a, d, mid], depth + 1) + cubic_length([mid, e, c, p3], depth + 1)
+}
+
+/// Tests whether a point is inside polygons using the even-odd rule. The polygons are implicitly
+/// closed. Generic so it works for both [Point] and the f64 points some algorithms need.
+pub(crate) fn contains_even_odd<'a, T>(
+    polygons: impl IntoIterator<Item = &'a [(T, T)]>,
+    p: (T, T),
+) -> bool
+where
+    T: Copy + PartialOrd + Add<Output = T> + Sub<Output = T> + Mul<Output = T> + Div<Output = T>,
+    T: 'a,
+{
+    let mut inside = false;
+    for polygon in polygons {
+        for (i, a) in polygon.iter().enumerate() {
+            let b = polygon[(i + 1) % polygon.len()];
+            if (a.1 > p.1) != (b.1 > p.1) && p.0 < a.0 + (p.1 - a.1) * (b.0 - a.0) / (b.1 - a.1) {
+                inside = !inside;
+            }
+        }
+    }
+    inside
+}
+
+impl VectorShape {
+    /// Returns the filled area of a fill shape, in square drawing units. Other shapes have no
+    /// area, since strokes don't enclose anything.
+    ///
+    /// Paths are filled using the even-odd rule, so a contour inside another one is a hole (and a
+    /// contour inside a hole fills again). This assumes that contours don't cross each other.
+    /// Open paths are implicitly closed.
+    pub fn area(&self) -> f32 {
+        if self.ty != ShapeType::Fill {
+            return 0.;
+        }
+
+        let contours = contours(self);
+        let mut area = 0.;
+        for (i, contour) in contours.iter().enumerate() {
+            let depth = contours
+                .iter()
+                .enumerate()
+                .filter(|&(j, other)| {
+                    j != i && contains_even_odd([&other.points[..]], contour.points[0])
+                })
+                .count();
+            let sign = if depth % 2 == 0 { 1. } else { -1. };
+            area += sign * contour.signed_area.abs();
+        }
+        area
+    }
+
+    /// Returns the total length of all paths in the shape. See [ShapePath::length].
+    ///
+    /// For strokes and lines, this is the length of the center lines. For fills, it's the
+    /// perimeter, not counting the closing edge of paths that aren't explicitly closed.
+    pub fn length(&self) -> f32 {
+        self.components
+            .iter()
+            .flat_map(|component| &component.tags)
+            .map(|tag| match tag {
+                ShapeComponentData::Path(path) => path.length(),
+                _ => 0.,
+            })
+            .sum()
+    }
+
+    /// Returns the center lines of a stroke or line shape, with paths that share end points
+    /// (within [CLOSE_EPSILON]) joined into continuous paths. Paths may be reversed to join them.
+    ///
+    /// Where more than two paths meet, which ones are joined is arbitrary. Joined paths have no
+    /// extra bytes. Returns nothing for other shape types.
+    pub fn centerlines(&self) -> Vec<ShapePath> {
+        if !matches!(self.ty, ShapeType::Stroke | ShapeType::Line) {
+            return Vec::new();
+        }
+
+        let mut lines: Vec<ShapePath> = self
+            .components
+            .iter()
+            .flat_map(|component| &component.tags)
+            .filter_map(|tag| match tag {
+                ShapeComponentData::Path(path) if !path.segments.is_empty() => Some(path.clone()),
+                _ => None,
+            })
+            .collect();
+
+        'outer: loop {
+            for i in 0..lines.len() {
+                if lines[i].is_closed(CLOSE_EPSILON) {
+                    continue;
+                }
+                for j in i + 1..lines.len() {
+                    if lines[j].is_closed(CLOSE_EPSILON) {
+                        continue;
+                    }
+                    let (a, b) = (&lines[i], &lines[j]);
+                    let joined = join(a, b)
+                        .or_else(|| join(b, a))
+                        .or_else(|| join(a, &reverse(b)))
+                        .or_else(|| join(&reverse(b), a));
+                    if let Some(joined) = joined {
+                        lines[i] = joined;
+                        lines.remove(j);
+                        continue 'outer;
+                    }
+                }
+            }
+            break lines;
+        }
+    }
+}
+
+/// A cubic Bézier segment: start point, two control points, and end point.
+pub(crate) type Cubic = [Point; 4];
+
+/// Fits smooth cubic Bézier segments to a polyline, e.g. a dense auto-traced outline.
+///
+/// Every point is within about `max_error` of the result. Consecutive segments share their
+/// tangents, so the result has no corners except where the polyline starts and ends; closed
+/// polylines are smooth everywhere but at their first point. Repeated points and points with
+/// NaN or infinite coordinates are ignored.
+///
+/// Returns a path that starts with a line segment to the first point, followed by cubics. For a
+/// polyline with fewer than two distinct points, it has only the starting point (or nothing).
+/// Where points are too close together to have a direction, the polyline is kept as lines.
+pub fn fit_cubics(points: &[Point], max_error: f32) -> ShapePath {
+    let mut points: Vec<_> = points
+        .iter()
+        .copied()
+        .filter(|(x, y)| x.is_finite() && y.is_finite())
+        .collect();
+    points.dedup();
+
+    let mut segments = Vec::new();
+    segments.extend(points.first().map(|&p| PathSegment::Line(p)));
+    let lines =
+        |range: &[Point]| -> Vec<_> { range[1..].iter().map(|&p| PathSegment::Line(p)).collect() };
+    if points.len() >= 2 {
+        let n = points.len();
+        let start_tangent = normalized(sub(points[1], points[0]));
+        let end_tangent = normalized(sub(points[n - 2], points[n - 1]));
+        let (Some(start_tangent), Some(end_tangent)) = (start_tangent, end_tangent) else {
+            segments.extend(lines(&points));
+            return ShapePath {
+                segments,
+                extra: None,
+            };
+        };
+
+        // explicit stack, since a long polyline can be split many times; the next range to fit
+        // is on top
+        let mut ranges = vec![(0, n - 1, start_tangent, end_tangent)];
+        while let Some((start, end, start_tangent, end_tangent)) = ranges.pop() {
+            let range = &points[start..=end];
+            let (cubic, error, worst) = fit_cubic(range, start_tangent, end_tangent);
+            if error <= max_error || range.len() <= 2 {
+                segments.push(PathSegment::Cubic(cubic[1], cubic[2], cubic[3]));
+                continue;
+            }
+
+            // split at the worst point, with a tangent that continues smoothly on both sides
+            let split = start + worst.clamp(1, range.len() - 2);
+            let center = normalized(sub(points[split - 1], points[split + 1]))
+                .or_else(|| normalized(sub(points[split - 1], points[split])));
+            let Some(center) = center else {
+                segments.extend(lines(range));
+                continue;
+            };
+            ranges.push((split, end, scale(center, -1.), end_tangent));
+            ranges.push((start, split, start_tangent, center));
+        }
+    }
+
+    ShapePath {
+        segments,
+        extra: None,
+    }
+}
+
+/// Fits a cubic from the first to the last point, with the given unit tangents at the ends
+/// (both pointing into the curve).
+///
+/// Returns the cubic, the largest distance from a point, and the index of that point.
+pub(crate) fn fit_cubic(
+    points: &[Point],
+    start_tangent: Point,
+    end_tangent: Point,
+) -> (Cubic, f32, usize) {
+    let mut params = chord_length_params(points);
+    let mut cubic = least_squares_cubic(points, &params, start_tangent, end_tangent);
+    let (mut error, mut worst) = max_error(points, &params, &cubic);
+    for _ in 0..REPARAMETERIZE_ITERATIONS {
+        for (t, &p) in params.iter_mut().zip(points) {
+            *t = newton_step(&cubic, p, *t);
+        }
+        let refitted = least_squares_cubic(points, &params, start_tangent, end_tangent);
+        let (refitted_error, refitted_worst) = max_error(points, &params, &refitted);
+        if refitted_error >= error {
+            break;
+        }
+        cubic = refitted;
+        error = refitted_error;
+        worst = refitted_worst;
+    }
+    (cubic, error, worst)
+}
+
+fn chord_length_params(points: &[Point]) -> Vec<f32> {
+    let mut params = Vec::with_capacity(points.len());
+    let mut total = 0.;
+    params.push(0.);
+    for pair in points.windows(2) {
+        total += length(sub(pair[1], pair[0]));
+        params.push(total);
+    }
+    if total > 0. {
+        for t in &mut params {
+            *t /= total;
+        }
+    }
+    params
+}
+
+/// Finds the control point distances that best fit the points at the given parameters
+/// (Schneider, "An Algorithm for Automatically Fitting Digitized Curves", 1990).
+fn least_squares_cubic(
+    points: &[Point],
+    params: &[f32],
+    start_tangent: Point,
+    end_tangent: Point,
+) -> Cubic {
+    let p0 = points[0];
+    let p3 = points[points.len() - 1];
+
+    let mut c = [[0.; 2]; 2];
+    let mut x = [0.; 2];
+    for (&p, &t) in points.iter().zip(params) {
+        let s = 1. - t;
+        let (b0, b1, b2, b3) = (s * s * s, 3. * s * s * t, 3. * s * t * t, t * t * t);
+        let a1 = scale(start_tangent, b1);
+        let a2 = scale(end_tangent, b2);
+        c[0][0] += dot(a1, a1);
+        c[0][1] += dot(a1, a2);
+        c[1][1] += dot(a2, a2);
+        let rest = sub(p, add(scale(p0, b0 + b1), scale(p3, b2 + b3)));
+        x[0] += dot(a1, rest);
+        x[1] += dot(a2, rest);
+    }
+
+    let det = c[0][0] * c[1][1] - c[0][1] * c[0][1];
+    let chord = length(sub(p3, p0));
+    let (mut alpha1, mut alpha2) = if det.abs() > f32::EPSILON {
+        (
+            (x[0] * c[1][1] - x[1] * c[0][1]) / det,
+            (c[0][0] * x[1] - c[0][1] * x[0]) / det,
+        )
+    } else {
+        (0., 0.)
+    };
+    // control points on the wrong side or right on the end points: fall back to a third of the
+    // chord, which gives a reasonable curve in the tangent directions
+    let min_alpha = chord * 1e-6;
+    if alpha1 <= min_alpha || alpha2 <= min_alpha {
+        alpha1 = chord / 3.;
+        alpha2 = chord / 3.;
+    }
+
+    [
+        p0,
+        add(p0, scale(start_tangent, alpha1)),
+        add(p3, scale(end_tangent, alpha2)),
+        p3,
+    ]
+}
+
+/// Returns the largest distance between a point and the cubic at its parameter, and the index of
+/// that point.
+fn max_error(points: &[Point], params: &[f32], cubic: &Cubic) -> (f32, usize) {
+    let [p0, p1, p2, p3] = *cubic;
+    let mut worst = (0., 0);
+    for (i, (&p, &t)) in points.iter().zip(params).enumerate() {
+        let error = distance(p, cubic_point(p0, p1, p2, p3, t));
+        if error > worst.0 {
+            worst = (error, i);
+        }
+    }
+    worst
+}
+
+/// Moves the parameter `t` closer to the point on the cubic nearest to `p`.
+fn newton_step(cubic: &Cubic, p: Point, t: f32) -> f32 {
+    let [p0, p1, p2, p3] = *cubic;
+    let s = 1. - t;
+    let q = cubic_point(p0, p1, p2, p3, t);
+    // first and second derivatives
+    let d1 = add(
+        add(
+            scale(sub(p1, p0), 3. * s * s),
+            scale(sub(p2, p1), 6. * s * t),
+        ),
+        scale(sub(p3, p2), 3. * t * t),
+    );
+    let d2 = add(
+        scale(add(sub(p2, scale(p1, 2.)), p0), 6. * s),
+        scale(add(sub(p3, scale(p2, 2.)), p1), 6. * t),
+    );
+    let diff = sub(q, p);
+    let numerator = dot(diff, d1);
+    let denominator = dot(d1, d1) + dot(diff, d2);
+    if denominator.abs() <= f32::EPSILON {
+        return t;
+    }
+    (t - numerator / denominator).clamp(0., 1.)
+}
@@ -0,0 +1,175 @@
+//! Finding the shapes under a point.
+
+use crate::document::{Art, Document, ShapeRef};
+use crate::geometry;
+use crate::layer::{ComponentType, LayerData, Point, ShapeComponentData, ShapeType, VectorShape};
+#[cfg(feature = "pencil")]
+use crate::pencil::{offset_at, StrokeThicknessPoint};
+
+#[derive(Debug, Clone)]
+pub struct HitTestOptions {
+    /// Stroke width of pencil lines without thickness data, in drawing units.
+    pub stroke_width: f32,
+    /// Additional distance from shapes that still counts as a hit, in drawing units.
+    pub tolerance: f32,
+    /// Whether invisible strokes can be hit.
+    pub include_invisible: bool,
+}
+
+impl Default for HitTestOptions {
+    fn default() -> Self {
+        HitTestOptions {
+            stroke_width: 4.,
+            tolerance: 0.,
+            include_invisible: false,
+        }
+    }
+}
+
+impl Document {
+    /// Returns the shapes under a point (in drawing units), topmost first.
+    ///
+    /// Fills are hit using the even-odd rule, like they're drawn by the exporters. Pencil lines
+    /// are hit within their thickness.
+    pub fn hit_test(&self, x: f32, y: f32) -> Vec<ShapeRef<'_>> {
+        self.hit_test_with_options(x, y, &HitTestOptions::default())
+    }
+
+    /// Like [Document::hit_test], but with custom options.
+    pub fn hit_test_with_options(
+        &self,
+        x: f32,
+        y: f32,
+        options: &HitTestOptions,
+    ) -> Vec<ShapeRef<'_>> {
+        let mut hits = Vec::new();
+        for art in Art::ALL.into_iter().rev() {
+            let Some(LayerData::Vector { shapes, .. }) = self.layer(art) else {
+                continue;
+            };
+            for (index, shape) in shapes.iter().enumerate().rev() {
+                if hits_shape(shape, (x, y), options) {
+                    hits.push(ShapeRef {
+                        layer: art,
+                        index,
+                        shape,
+                    });
+                }
+            }
+        }
+        hits
+    }
+}
+
+fn hits_shape(shape: &VectorShape, p: Point, options: &HitTestOptions) -> bool {
+    let tolerance = geometry::FLATTEN_TOLERANCE;
+    match shape.ty {
+        ShapeType::Fill => {
+            let polygons: Vec<Vec<Point>> = shape
+                .components
+                .iter()
+                .flat_map(|component| &component.tags)
+                .filter_map(|tag| match tag {
+                    ShapeComponentData::Path(path) => Some(geometry::flatten(path, tolerance)),
+                    _ => None,
+                })
+                .collect();
+            geometry::contains_even_odd(polygons.iter().map(|polygon| &polygon[..]), p)
+                || (options.tolerance > 0.
+                    && polygons.iter().any(|polygon| {
+                        let closed: Vec<Point> =
+                            polygon.iter().chain(polygon.first()).copied().collect();
+                        distance_to_polyline(&closed, p).0 <= options.tolerance
+                    }))
+        }
+        ShapeType::Stroke | ShapeType::Line => {
+            #[cfg(feature = "pencil")]
+            let mut definition: Option<&[StrokeThicknessPoint]> = None;
+
+            for component in &shape.components {
+                let visible = component
+                    .info()
+                    .is_some_and(|info| info.ty == ComponentType::Pencil);
+                let groups = component.path_groups();
+                #[cfg(feature = "pencil")]
+                let mut thicknesses = Vec::new();
+                #[cfg(feature = "pencil")]
+                for group in &groups {
+                    // definitions carry over to later paths, also through invisible strokes
+                    if let Some(points) = group.thickness.and_then(|t| t.definition.as_deref()) {
+                        definition = Some(points);
+                    }
+                    thicknesses.push(definition.zip(group.thickness.map(|t| t.domain)));
+                }
+                if !visible && !options.include_invisible {
+                    continue;
+                }
+
+                for (i, group) in groups.iter().enumerate() {
+                    let polyline = geometry::flatten(group.path, tolerance);
+                    let (distance, position, left) = distance_to_polyline(&polyline, p);
+                    let half_width = {
+                        #[cfg(feature = "pencil")]
+                        {
+                            match thicknesses[i] {
+                                Some((points, (start, end))) if visible => {
+                                    let loc = start + (end - start) * position;
+                                    offset_at(points, loc, left)
+                                }
+                                _ => options.stroke_width / 2.,
+                            }
+                        }
+                        #[cfg(not(feature = "pencil"))]
+                        {
+                            let _ = (i, position, left);
+                            options.stroke_width / 2.
+                        }
+                    };
+                    if distance <= half_width + options.tolerance {
+                        return true;
+                    }
+                }
+            }
+            false
+        }
+        _ => false,
+    }
+}
+
+/// Returns the distance from a point to a polyline, the position of the closest point along the
+/// polyline (0 to 1, by length), and whether the point is on the left side (in Y-up coordinates).
+fn distance_to_polyline(points: &[Point], p: Point) -> (f32, f32, bool) {
+    let lengths: Vec<f32> = points
+        .windows(2)
+        .map(|w| (w[1].0 - w[0].0).hypot(w[1].1 - w[0].1))
+        .collect();
+    let total: f32 = lengths.iter().sum();
+
+    let mut best = match points.first() {
+        Some(a) => ((a.0 - p.0).hypot(a.1 - p.1), 0., true),
+        None => return (f32::INFINITY, 0., true),
+    };
+    let mut offset = 0.;
+    for (w, length) in points.windows(2).zip(lengths) {
+        let (a, b) = (w[0], w[1]);
+        let d = (b.0 - a.0, b.1 - a.1);
+        let t = if length > 0. {
+            (((p.0 - a.0) * d.0 + (p.1 - a.1) * d.1) / (length * length)).clamp(0., 1.)
+        } else {
+            0.
+        };
+        let closest = (a.0 + d.0 * t, a.1 + d.1 * t);
+        let distance = (closest.0 - p.0).hypot(closest.1 - p.1);
+        if distance < best.0 {
+            let position = if total > 0. {
+                (offset + length * t) / total
+            } else {
+                0.
+            };
+            let left = d.0 * (p.1 - a.1) - d.1 * (p.0 - a.0) >= 0.;
+            best = (distance, position, left);
+        }
+        offset += length;
+    }
+    best
+}
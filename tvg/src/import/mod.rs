@@ -0,0 +1,4 @@
+//! Importers from other formats.
+
+#[cfg(feature = "import-svg")]
+pub mod svg;
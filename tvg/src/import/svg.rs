@@ -0,0 +1,516 @@
+//! SVG import.
+//!
+//! Supports a subset of SVG: `path`, `rect` (without rounded corners), `circle`, `ellipse`,
+//! `line`, `polyline` and `polygon` elements in nested groups, with transforms and solid fills
+//! and strokes. Anything else that would affect the result is an error, rather than being
+//! silently dropped.
+//!
+//! Filled elements become fill shapes in the color art layer, and stroked elements become pencil
+//! lines in the line art layer. Stroke widths are not imported, since pencil thickness can't be
+//! written yet. Colors are collected into a generated palette.
+
+use crate::color::Rgba;
+use crate::document::Document;
+use crate::layer::{
+    ComponentInfo, ComponentType, LayerData, LayerTrailer, PathSegment, Point, ShapeComponent,
+    ShapeComponentData, ShapePath, ShapeType, VectorShape,
+};
+use crate::palette::{PaletteColor, PaletteData};
+use crate::read::FileData;
+use std::str::FromStr;
+use svgtypes::{Paint, SimplePathSegment, SimplifyingPathParser, Transform};
+use thiserror::Error;
+
+/// Name of the palette that generated colors are put in.
+const PALETTE_NAME: &str = "svg import";
+
+/// Distance of cubic control points from the end points when approximating a quarter circle.
+const CIRCLE_KAPPA: f64 = 0.552_284_749_831;
+
+#[derive(Debug, Error)]
+pub enum SvgImportError {
+    #[error("xml error: {0}")]
+    Xml(#[from] roxmltree::Error),
+    #[error("unsupported element: <{0}>")]
+    UnsupportedElement(String),
+    #[error("unsupported attribute on <{element}>: {name}=\"{value}\"")]
+    UnsupportedAttribute {
+        element: String,
+        name: String,
+        value: String,
+    },
+    #[error("invalid value for {name}: {value:?}")]
+    InvalidValue { name: String, value: String },
+}
+
+#[derive(Debug, Clone)]
+pub struct SvgImportOptions {
+    /// Drawing units per SVG user unit.
+    pub scale: f32,
+}
+
+impl Default for SvgImportOptions {
+    fn default() -> Self {
+        SvgImportOptions { scale: 1. }
+    }
+}
+
+/// The result of importing an SVG file.
+#[derive(Debug, Clone)]
+pub struct SvgImport {
+    /// All colors used by the shapes.
+    pub palette: PaletteData,
+    /// Filled elements, in document order (bottom to top).
+    pub color_art: Vec<VectorShape>,
+    /// Stroked elements, in document order (bottom to top).
+    pub line_art: Vec<VectorShape>,
+}
+
+impl SvgImport {
+    /// Converts the import to a document with the color and line art layers.
+    pub fn into_document(self) -> Document {
+        let layer = |shapes: Vec<VectorShape>| match shapes.is_empty() {
+            true => LayerData::Empty,
+            false => LayerData::Vector {
+                shapes,
+                trailer: LayerTrailer::default(),
+            },
+        };
+        Document {
+            tags: vec![FileData::Main(vec![
+                FileData::Palette(self.palette),
+                FileData::LayerUnderlay(LayerData::Empty),
+                FileData::LayerColor(layer(self.color_art)),
+                FileData::LayerLine(layer(self.line_art)),
+                FileData::LayerOverlay(LayerData::Empty),
+            ])],
+        }
+    }
+}
+
+/// Inherited presentation attributes.
+#[derive(Debug, Clone, Copy)]
+struct Style {
+    fill: Option<(u8, u8, u8)>,
+    stroke: Option<(u8, u8, u8)>,
+    fill_opacity: f32,
+    stroke_opacity: f32,
+    /// Group opacity, multiplied through nested groups.
+    opacity: f32,
+    transform: Transform,
+}
+
+struct Importer {
+    options: SvgImportOptions,
+    import: SvgImport,
+}
+
+fn invalid(name: &str, value: &str) -> SvgImportError {
+    SvgImportError::InvalidValue {
+        name: name.to_string(),
+        value: value.to_string(),
+    }
+}
+
+fn parse_paint(name: &str, value: &str) -> Result<Option<(u8, u8, u8)>, SvgImportError> {
+    match Paint::from_str(value).map_err(|_| invalid(name, value))? {
+        Paint::None => Ok(None),
+        Paint::Color(c) => Ok(Some((c.red, c.green, c.blue))),
+        // gradients, patterns, currentColor, etc.
+        _ => Err(invalid(name, value)),
+    }
+}
+
+fn parse_number(name: &str, value: &str) -> Result<f64, SvgImportError> {
+    let number = svgtypes::Number::from_str(value).map_err(|_| invalid(name, value))?;
+    Ok(number.0)
+}
+
+fn parse_opacity(name: &str, value: &str) -> Result<f32, SvgImportError> {
+    Ok((parse_number(name, value)? as f32).clamp(0., 1.))
+}
+
+/// Returns the presentation attributes of a node, including those in its `style` attribute.
+fn attributes<'a>(node: roxmltree::Node<'a, '_>) -> Vec<(&'a str, &'a str)> {
+    let mut attributes: Vec<_> = node
+        .attributes()
+        .filter(|attr| attr.namespace().is_none())
+        .map(|attr| (attr.name(), attr.value()))
+        .filter(|(name, _)| *name != "style")
+        .collect();
+    if let Some(style) = node.attribute("style") {
+        // style declarations override attributes
+        attributes.extend(style.split(';').filter_map(|declaration| {
+            let (name, value) = declaration.split_once(':')?;
+            Some((name.trim(), value.trim()))
+        }));
+    }
+    attributes
+}
+
+/// Attributes that don't affect the imported geometry or color.
+const IGNORED_ATTRIBUTES: &[&str] = &[
+    "id",
+    "class",
+    "version",
+    "width",
+    "height",
+    "viewBox",
+    "preserveAspectRatio",
+    "x",
+    "y",
+    "x1",
+    "y1",
+    "x2",
+    "y2",
+    "cx",
+    "cy",
+    "r",
+    "rx",
+    "ry",
+    "d",
+    "points",
+    "fill-rule",
+    "clip-rule",
+    "stroke-width",
+    "stroke-linecap",
+    "stroke-linejoin",
+    "stroke-miterlimit",
+    "vector-effect",
+    "shape-rendering",
+    "color",
+];
+
+impl Importer {
+    fn style(&self, node: roxmltree::Node, parent: &Style) -> Result<Style, SvgImportError> {
+        let mut style = *parent;
+        for (name, value) in attributes(node) {
+            match name {
+                "fill" => style.fill = parse_paint(name, value)?,
+                "stroke" => style.stroke = parse_paint(name, value)?,
+                "fill-opacity" => style.fill_opacity = parse_opacity(name, value)?,
+                "stroke-opacity" => style.stroke_opacity = parse_opacity(name, value)?,
+                "opacity" => style.opacity *= parse_opacity(name, value)?,
+                "transform" => {
+                    let t = Transform::from_str(value).map_err(|_| invalid(name, value))?;
+                    style.transform = multiply(parent.transform, t);
+                }
+                // hidden elements are skipped before getting here
+                "display" | "visibility" => {}
+                "stroke-dasharray" if value == "none" => {}
+                name if IGNORED_ATTRIBUTES.contains(&name) => {}
+                _ => {
+                    return Err(SvgImportError::UnsupportedAttribute {
+                        element: node.tag_name().name().to_string(),
+                        name: name.to_string(),
+                        value: value.to_string(),
+                    })
+                }
+            }
+        }
+        Ok(style)
+    }
+
+    fn is_hidden(node: roxmltree::Node) -> bool {
+        attributes(node)
+            .iter()
+            .any(|attr| matches!(*attr, ("display", "none") | ("visibility", "hidden")))
+    }
+
+    fn visit(&mut self, node: roxmltree::Node, parent: &Style) -> Result<(), SvgImportError> {
+        if !node.is_element() || Self::is_hidden(node) {
+            return Ok(());
+        }
+        // editor-specific elements, e.g. sodipodi:namedview
+        if node.tag_name().namespace() != Some(SVG_NAMESPACE) {
+            return Ok(());
+        }
+
+        let name = node.tag_name().name();
+        match name {
+            "title" | "desc" | "metadata" | "defs" | "style" => return Ok(()),
+            "svg" | "g" => {
+                let style = self.style(node, parent)?;
+                for child in node.children() {
+                    self.visit(child, &style)?;
+                }
+                return Ok(());
+            }
+            _ => (),
+        }
+
+        let style = self.style(node, parent)?;
+        let subpaths = element_subpaths(node)?;
+        let subpaths: Vec<ShapePath> = subpaths
+            .into_iter()
+            .filter(|path| path.len() > 1)
+            .map(|path| self.to_shape_path(&path, style.transform))
+            .collect();
+        if subpaths.is_empty() {
+            return Ok(());
+        }
+
+        if let Some(fill) = style.fill {
+            let alpha = style.fill_opacity * style.opacity;
+            let color_id = self.color_id(fill, alpha);
+            let shape = shape(ShapeType::Fill, ComponentType::Fill, color_id, &subpaths);
+            self.import.color_art.push(shape);
+        }
+        if let Some(stroke) = style.stroke {
+            let alpha = style.stroke_opacity * style.opacity;
+            let color_id = self.color_id(stroke, alpha);
+            let shape = shape(ShapeType::Line, ComponentType::Pencil, color_id, &subpaths);
+            self.import.line_art.push(shape);
+        }
+        Ok(())
+    }
+
+    fn to_shape_path(&self, path: &[PathSegment], transform: Transform) -> ShapePath {
+        let scale = self.options.scale;
+        // apply the transform, then flip Y since TVG coordinates are Y-up
+        let map = |(x, y): Point| {
+            let (x, y) = (x as f64, y as f64);
+            let tx = transform.a * x + transform.c * y + transform.e;
+            let ty = transform.b * x + transform.d * y + transform.f;
+            (tx as f32 * scale, -ty as f32 * scale)
+        };
+        let segments = path
+            .iter()
+            .map(|segment| match *segment {
+                PathSegment::Line(p) => PathSegment::Line(map(p)),
+                PathSegment::Cubic(c1, c2, p) => PathSegment::Cubic(map(c1), map(c2), map(p)),
+            })
+            .collect();
+        ShapePath {
+            segments,
+            extra: None,
+        }
+    }
+
+    /// Returns the ID of a palette color, adding it to the palette if needed.
+    fn color_id(&mut self, (r, g, b): (u8, u8, u8), alpha: f32) -> u64 {
+        let rgba = Rgba::new(r, g, b, (alpha * 255.).round() as u8);
+        // derive the ID from the color so that imports are deterministic
+        let id = u64::from_be_bytes([0x5f, 0x53, 0x56, 0x47, r, g, b, rgba.a]);
+
+        if self.import.palette.find(id).is_none() {
+            let color = PaletteColor::solid(id, rgba, rgba.to_css_hex(), PALETTE_NAME);
+            self.import.palette.colors.push(color);
+        }
+        id
+    }
+}
+
+const SVG_NAMESPACE: &str = "http://www.w3.org/2000/svg";
+
+fn multiply(a: Transform, b: Transform) -> Transform {
+    Transform::new(
+        a.a * b.a + a.c * b.b,
+        a.b * b.a + a.d * b.b,
+        a.a * b.c + a.c * b.d,
+        a.b * b.c + a.d * b.d,
+        a.a * b.e + a.c * b.f + a.e,
+        a.b * b.e + a.d * b.f + a.f,
+    )
+}
+
+fn shape(
+    ty: ShapeType,
+    component_type: ComponentType,
+    color_id: u64,
+    paths: &[ShapePath],
+) -> VectorShape {
+    let components = paths
+        .iter()
+        .map(|path| ShapeComponent {
+            tags: vec![
+                ShapeComponentData::Info(ComponentInfo {
+                    ty: component_type,
+                    color_id: Some(color_id),
+                    // the usual value
+                    pencil_value: (component_type == ComponentType::Pencil).then_some(10.),
+                    unknown_prefix: None,
+                    unknown_suffix: None,
+                }),
+                ShapeComponentData::Path(path.clone()),
+            ],
+            trailer: None,
+        })
+        .collect();
+    VectorShape { ty, components }
+}
+
+fn number_attr(node: roxmltree::Node, name: &str) -> Result<f64, SvgImportError> {
+    match node.attribute(name) {
+        Some(value) => parse_number(name, value),
+        None => Ok(0.),
+    }
+}
+
+fn point(x: f64, y: f64) -> Point {
+    (x as f32, y as f32)
+}
+
+/// Returns the subpaths of an element in SVG user units. Each starts with a line segment.
+fn element_subpaths(node: roxmltree::Node) -> Result<Vec<Vec<PathSegment>>, SvgImportError> {
+    let name = node.tag_name().name();
+    let num = |name: &str| number_attr(node, name);
+    match name {
+        "path" => path_data(node.attribute("d").unwrap_or_default()),
+        "rect" => {
+            let (x, y, w, h) = (num("x")?, num("y")?, num("width")?, num("height")?);
+            if num("rx")? != 0. || num("ry")? != 0. {
+                return Err(SvgImportError::UnsupportedAttribute {
+                    element: name.to_string(),
+                    name: "rx".to_string(),
+                    value: node
+                        .attribute("rx")
+                        .or(node.attribute("ry"))
+                        .unwrap()
+                        .into(),
+                });
+            }
+            Ok(vec![polygon(
+                &[(x, y), (x + w, y), (x + w, y + h), (x, y + h)],
+                true,
+            )])
+        }
+        "circle" => {
+            let r = num("r")?;
+            Ok(vec![ellipse(num("cx")?, num("cy")?, r, r)])
+        }
+        "ellipse" => Ok(vec![ellipse(
+            num("cx")?,
+            num("cy")?,
+            num("rx")?,
+            num("ry")?,
+        )]),
+        "line" => Ok(vec![polygon(
+            &[(num("x1")?, num("y1")?), (num("x2")?, num("y2")?)],
+            false,
+        )]),
+        "polyline" | "polygon" => {
+            let points: Vec<_> =
+                svgtypes::PointsParser::from(node.attribute("points").unwrap_or_default())
+                    .collect();
+            Ok(vec![polygon(&points, name == "polygon")])
+        }
+        _ => Err(SvgImportError::UnsupportedElement(name.to_string())),
+    }
+}
+
+fn polygon(points: &[(f64, f64)], closed: bool) -> Vec<PathSegment> {
+    let mut segments: Vec<_> = points
+        .iter()
+        .map(|&(x, y)| PathSegment::Line(point(x, y)))
+        .collect();
+    if closed {
+        if let Some(&(x, y)) = points.first() {
+            segments.push(PathSegment::Line(point(x, y)));
+        }
+    }
+    segments
+}
+
+fn ellipse(cx: f64, cy: f64, rx: f64, ry: f64) -> Vec<PathSegment> {
+    let (kx, ky) = (rx * CIRCLE_KAPPA, ry * CIRCLE_KAPPA);
+    vec![
+        PathSegment::Line(point(cx + rx, cy)),
+        PathSegment::Cubic(
+            point(cx + rx, cy + ky),
+            point(cx + kx, cy + ry),
+            point(cx, cy + ry),
+        ),
+        PathSegment::Cubic(
+            point(cx - kx, cy + ry),
+            point(cx - rx, cy + ky),
+            point(cx - rx, cy),
+        ),
+        PathSegment::Cubic(
+            point(cx - rx, cy - ky),
+            point(cx - kx, cy - ry),
+            point(cx, cy - ry),
+        ),
+        PathSegment::Cubic(
+            point(cx + kx, cy - ry),
+            point(cx + rx, cy - ky),
+            point(cx + rx, cy),
+        ),
+    ]
+}
+
+/// Parses SVG path data into subpaths.
+fn path_data(d: &str) -> Result<Vec<Vec<PathSegment>>, SvgImportError> {
+    let mut subpaths: Vec<Vec<PathSegment>> = Vec::new();
+    let mut start = (0., 0.);
+    let mut current = (0., 0.);
+    for segment in SimplifyingPathParser::from(d) {
+        let segment = segment.map_err(|_| invalid("d", d))?;
+        let end = match segment {
+            SimplePathSegment::MoveTo { x, y } => {
+                start = point(x, y);
+                subpaths.push(vec![PathSegment::Line(start)]);
+                current = start;
+                continue;
+            }
+            SimplePathSegment::LineTo { x, y } => PathSegment::Line(point(x, y)),
+            SimplePathSegment::CurveTo {
+                x1,
+                y1,
+                x2,
+                y2,
+                x,
+                y,
+            } => PathSegment::Cubic(point(x1, y1), point(x2, y2), point(x, y)),
+            SimplePathSegment::Quadratic { x1, y1, x, y } => {
+                // elevate to a cubic
+                let (q, p) = (point(x1, y1), point(x, y));
+                let c1 = (
+                    current.0 + 2. / 3. * (q.0 - current.0),
+                    current.1 + 2. / 3. * (q.1 - current.1),
+                );
+                let c2 = (p.0 + 2. / 3. * (q.0 - p.0), p.1 + 2. / 3. * (q.1 - p.1));
+                PathSegment::Cubic(c1, c2, p)
+            }
+            SimplePathSegment::ClosePath => {
+                if current != start {
+                    PathSegment::Line(start)
+                } else {
+                    continue;
+                }
+            }
+        };
+        current = match end {
+            PathSegment::Line(p) | PathSegment::Cubic(_, _, p) => p,
+        };
+        match subpaths.last_mut() {
+            Some(subpath) => subpath.push(end),
+            None => return Err(invalid("d", d)),
+        }
+    }
+    Ok(subpaths)
+}
+
+/// Imports an SVG document.
+pub fn import_svg(svg: &str, options: &SvgImportOptions) -> Result<SvgImport, SvgImportError> {
+    let tree = roxmltree::Document::parse(svg)?;
+    let mut importer = Importer {
+        options: options.clone(),
+        import: SvgImport {
+            palette: PaletteData { colors: Vec::new() },
+            color_art: Vec::new(),
+            line_art: Vec::new(),
+        },
+    };
+    let style = Style {
+        // SVG defaults
+        fill: Some((0, 0, 0)),
+        stroke: None,
+        fill_opacity: 1.,
+        stroke_opacity: 1.,
+        opacity: 1.,
+        transform: Transform::default(),
+    };
+    importer.visit(tree.root_element(), &style)?;
+    Ok(importer.import)
+}
@@ -0,0 +1,602 @@
+//! Annotated hex dumps of TVG files, for reverse engineering.
+//!
+//! The dump interleaves the raw bytes with their interpretation (tag names, lengths, decoded
+//! values). Unlike [read](crate::read), this never fails: if something can't be interpreted, the
+//! error is noted in the dump and the remaining bytes of the enclosing block are dumped as-is.
+
+use crate::read::{EncodingTag, FileTag, MAGIC};
+use crate::zlib;
+use std::fmt::{self, Write};
+
+/// Number of bytes per line in the text dump.
+const BYTES_PER_LINE: usize = 16;
+
+/// A run of bytes (or a heading, if there are no bytes) with its interpretation.
+#[derive(Debug, Clone)]
+pub struct Entry {
+    /// Nesting depth.
+    pub depth: usize,
+    /// Offset of the bytes in the enclosing buffer. This is the file, except inside
+    /// zlib-compressed data, where it's the decompressed buffer.
+    pub offset: usize,
+    pub bytes: Vec<u8>,
+    pub label: String,
+}
+
+/// An annotated hex dump.
+#[derive(Debug, Clone, Default)]
+pub struct Dump {
+    pub entries: Vec<Entry>,
+}
+
+impl Dump {
+    /// Returns the dump as plain text, with offsets, up to 16 bytes per line, and labels.
+    pub fn to_text(&self) -> String {
+        self.to_string()
+    }
+
+    /// Returns the dump as a standalone HTML document.
+    pub fn to_html(&self) -> String {
+        let mut out = String::new();
+        out.push_str("<!doctype html>\n<meta charset=\"utf-8\">\n<title>tvg dump</title>\n");
+        out.push_str(
+            "<style>\
+             table { border-collapse: collapse; font: 12px monospace; }\
+             td { padding: 1px 8px; vertical-align: top; }\
+             tr:hover { background: #eef; }\
+             .offset { color: #888; }\
+             .hex { white-space: pre-wrap; word-break: break-all; max-width: 48ch; }\
+             .heading td { font-weight: bold; }\
+             </style>\n",
+        );
+        out.push_str("<table>\n");
+        for entry in &self.entries {
+            let class = if entry.bytes.is_empty() {
+                " class=\"heading\""
+            } else {
+                ""
+            };
+            let offset = if entry.bytes.is_empty() {
+                String::new()
+            } else {
+                format!("{:08x}", entry.offset)
+            };
+            writeln!(
+                out,
+                "<tr{class}><td class=\"offset\">{offset}</td><td class=\"hex\">{}</td>\
+                 <td style=\"padding-left: {}em\">{}</td></tr>",
+                hex(&entry.bytes),
+                entry.depth * 2 + 1,
+                escape_html(&entry.label),
+            )
+            .unwrap();
+        }
+        out.push_str("</table>\n");
+        out
+    }
+}
+
+impl fmt::Display for Dump {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        let hex_width = BYTES_PER_LINE * 3 - 1;
+        for entry in &self.entries {
+            let indent = "  ".repeat(entry.depth);
+            if entry.bytes.is_empty() {
+                writeln!(f, "{:8}  {:hex_width$}  {indent}{}", "", "", entry.label)?;
+                continue;
+            }
+            for (i, line) in entry.bytes.chunks(BYTES_PER_LINE).enumerate() {
+                let offset = entry.offset + i * BYTES_PER_LINE;
+                let label = if i == 0 { &entry.label[..] } else { "" };
+                writeln!(f, "{offset:08x}  {:hex_width$}  {indent}{label}", hex(line))?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn hex(bytes: &[u8]) -> String {
+    let mut out = String::with_capacity(bytes.len() * 3);
+    for (i, byte) in bytes.iter().enumerate() {
+        if i > 0 {
+            out.push(' ');
+        }
+        write!(out, "{byte:02x}").unwrap();
+    }
+    out
+}
+
+fn escape_html(text: &str) -> String {
+    text.replace('&', "&amp;")
+        .replace('<', "&lt;")
+        .replace('>', "&gt;")
+}
+
+/// Formats a tag as its four characters if they're printable, or as hex otherwise.
+fn fourcc(tag: u32) -> String {
+    let bytes = tag.to_be_bytes();
+    if bytes.iter().all(|b| b.is_ascii_graphic()) {
+        String::from_utf8_lossy(&bytes).into_owned()
+    } else {
+        format!("{tag:08x}")
+    }
+}
+
+/// Why interpretation stopped.
+type Result<T> = std::result::Result<T, String>;
+
+/// Walks a buffer, recording entries.
+struct Walker<'d, 'e> {
+    data: &'d [u8],
+    pos: usize,
+    /// Offset of `data` in the enclosing buffer.
+    base: usize,
+    depth: usize,
+    entries: &'e mut Vec<Entry>,
+}
+
+impl<'d> Walker<'d, '_> {
+    fn remaining(&self) -> usize {
+        self.data.len() - self.pos
+    }
+
+    fn note(&mut self, label: impl Into<String>) {
+        self.entries.push(Entry {
+            depth: self.depth,
+            offset: self.base + self.pos,
+            bytes: Vec::new(),
+            label: label.into(),
+        });
+    }
+
+    fn peek(&self, n: usize) -> Result<&'d [u8]> {
+        let end = self.pos.checked_add(n);
+        end.and_then(|end| self.data.get(self.pos..end)).ok_or_else(|| {
+            format!(
+                "expected {n} more bytes at {:08x}, found {}",
+                self.base + self.pos,
+                self.remaining()
+            )
+        })
+    }
+
+    /// Takes `n` bytes without recording them.
+    fn skip(&mut self, n: usize) -> Result<&'d [u8]> {
+        let bytes = self.peek(n)?;
+        self.pos += n;
+        Ok(bytes)
+    }
+
+    fn bytes(&mut self, n: usize, label: impl Into<String>) -> Result<&'d [u8]> {
+        let offset = self.base + self.pos;
+        let bytes = self.skip(n)?;
+        self.entries.push(Entry {
+            depth: self.depth,
+            offset,
+            bytes: bytes.to_vec(),
+            label: label.into(),
+        });
+        Ok(bytes)
+    }
+
+    /// Records all remaining bytes, if there are any.
+    fn rest(&mut self, label: &str) {
+        if self.remaining() > 0 {
+            // can't fail
+            let _ = self.bytes(self.remaining(), label);
+        }
+    }
+
+    fn u8(&mut self, label: &str) -> Result<u8> {
+        let value = self.peek(1)?[0];
+        self.bytes(1, format!("{label}: {value}"))?;
+        Ok(value)
+    }
+
+    fn u16(&mut self, label: &str) -> Result<u16> {
+        let value = u16::from_le_bytes(self.peek(2)?.try_into().unwrap());
+        self.bytes(2, format!("{label}: {value}"))?;
+        Ok(value)
+    }
+
+    fn u32(&mut self, label: &str) -> Result<u32> {
+        let value = u32::from_le_bytes(self.peek(4)?.try_into().unwrap());
+        self.bytes(4, format!("{label}: {value}"))?;
+        Ok(value)
+    }
+
+    fn u64_hex(&mut self, label: &str) -> Result<u64> {
+        let value = u64::from_le_bytes(self.peek(8)?.try_into().unwrap());
+        self.bytes(8, format!("{label}: {value:016x}"))?;
+        Ok(value)
+    }
+
+    fn point(&mut self, label: &str) -> Result<()> {
+        let bytes = self.peek(8)?;
+        let x = f32::from_le_bytes(bytes[..4].try_into().unwrap());
+        let y = f32::from_le_bytes(bytes[4..].try_into().unwrap());
+        self.bytes(8, format!("{label} ({x}, {y})"))?;
+        Ok(())
+    }
+
+    /// Reads a big-endian tag.
+    fn tag(&mut self, name: impl Fn(u32) -> Option<String>) -> Result<u32> {
+        let tag = u32::from_be_bytes(self.peek(4)?.try_into().unwrap());
+        let label = match name(tag) {
+            Some(name) => format!("tag {} ({name})", fourcc(tag)),
+            None => format!("tag {}", fourcc(tag)),
+        };
+        self.bytes(4, label)?;
+        Ok(tag)
+    }
+
+    /// Reads a null-terminated string.
+    fn c_string(&mut self, label: &str) -> Result<()> {
+        let len = self.data[self.pos..]
+            .iter()
+            .position(|b| *b == 0)
+            .ok_or_else(|| format!("unterminated {label}"))?;
+        let text = String::from_utf8_lossy(&self.data[self.pos..self.pos + len]).into_owned();
+        self.bytes(len + 1, format!("{label}: {text:?}"))?;
+        Ok(())
+    }
+
+    /// Reads a UTF-16 string prefixed with its length in code units.
+    fn utf16_string(&mut self, label: &str) -> Result<()> {
+        let chars = self.u32(&format!("{label} length"))? as usize;
+        let len = chars.checked_mul(2).ok_or("string length overflows")?;
+        let bytes = self.peek(len)?;
+        let units: Vec<u16> = bytes
+            .chunks(2)
+            .map(|c| u16::from_le_bytes([c[0], c[1]]))
+            .collect();
+        let text = String::from_utf16_lossy(&units);
+        self.bytes(len, format!("{label}: {text:?}"))?;
+        Ok(())
+    }
+
+    /// Runs `f` with increased depth.
+    fn indented<T>(&mut self, f: impl FnOnce(&mut Self) -> Result<T>) -> Result<T> {
+        self.depth += 1;
+        let result = f(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Walks the next `len` bytes as a block of their own.
+    ///
+    /// Errors inside the block are recorded and don't stop the walk of the enclosing data.
+    fn block(&mut self, len: usize, f: impl FnOnce(&mut Walker) -> Result<()>) -> Result<()> {
+        let base = self.base + self.pos;
+        let data = self.skip(len)?;
+        walk_buffer(data, base, self.depth, self.entries, f);
+        Ok(())
+    }
+}
+
+/// Walks a buffer, recording an error and the remaining bytes if `f` fails.
+fn walk_buffer(
+    data: &[u8],
+    base: usize,
+    depth: usize,
+    entries: &mut Vec<Entry>,
+    f: impl FnOnce(&mut Walker) -> Result<()>,
+) {
+    let mut walker = Walker {
+        data,
+        pos: 0,
+        base,
+        depth,
+        entries,
+    };
+    if let Err(error) = f(&mut walker) {
+        walker.note(format!("error: {error}"));
+        walker.rest("not interpreted");
+    } else {
+        walker.rest("unexpected trailing bytes");
+    }
+}
+
+/// Creates an annotated dump of a TVG file.
+pub fn inspect(data: &[u8]) -> Dump {
+    let mut dump = Dump::default();
+    walk_buffer(data, 0, 0, &mut dump.entries, walk_file);
+    dump
+}
+
+fn walk_file(w: &mut Walker) -> Result<()> {
+    let magic = w.peek(MAGIC.len())?;
+    let label = format!("magic {:?}", String::from_utf8_lossy(magic));
+    w.bytes(MAGIC.len(), label)?;
+    w.u32("version")?;
+    w.u32("unknown (usually 2, 1 in some older files)")?;
+    w.u32("unknown (usually 1)")?;
+    walk_tags(w)
+}
+
+fn file_tag_name(tag: u32) -> Option<String> {
+    FileTag::try_from(tag).ok().map(|tag| format!("{tag:?}"))
+}
+
+fn walk_tags(w: &mut Walker) -> Result<()> {
+    while w.remaining() > 0 {
+        if w.remaining() == 1 && w.peek(1)? == [0] {
+            w.bytes(1, "trailing 0 byte")?;
+            break;
+        }
+        let tag = w.tag(file_tag_name)?;
+        w.indented(|w| walk_tag(w, tag))?;
+    }
+    Ok(())
+}
+
+fn walk_tag(w: &mut Walker, tag: u32) -> Result<()> {
+    match FileTag::try_from(tag) {
+        Ok(FileTag::Cert) => {
+            let len = w.u32("length")?;
+            w.block(len as usize, |w| {
+                w.u32("unknown (usually 1)")?;
+                let cert_len = w.u32("certificate length")?;
+                w.bytes(cert_len as usize, "certificate")?;
+                Ok(())
+            })
+        }
+        Ok(FileTag::MainData) => walk_encoded(w, walk_tags),
+        Ok(FileTag::Endt) => Ok(()),
+        Ok(FileTag::Crea) => walk_encoded(w, |w| w.u32("value (usually 2)").map(drop)),
+        Ok(FileTag::Tvci) => walk_encoded(w, |w| {
+            w.bytes(13, "unknown")?;
+            w.c_string("device")?;
+            w.c_string("software name")?;
+            w.rest("unknown");
+            Ok(())
+        }),
+        Ok(
+            FileTag::LayerUnderlay
+            | FileTag::LayerColor
+            | FileTag::LayerLine
+            | FileTag::LayerOverlay,
+        ) => walk_encoded(w, walk_layer),
+        Ok(FileTag::Palette) => walk_encoded(w, walk_palette),
+        Ok(FileTag::Ttoc) => {
+            let count = w.u32("count")?;
+            for _ in 0..count {
+                w.tag(file_tag_name)?;
+                w.indented(|w| w.u32("offset"))?;
+            }
+            w.bytes(8, "unknown").map(drop)
+        }
+        Ok(FileTag::Sign) => w.bytes(74, "signature").map(drop),
+        Err(_) => Err(format!("unknown file tag {}", fourcc(tag))),
+    }
+}
+
+/// Walks encoded data (see [EncodingTag]), interpreting its contents with `f`.
+fn walk_encoded(w: &mut Walker, f: impl FnOnce(&mut Walker) -> Result<()>) -> Result<()> {
+    let encoding = w.tag(|tag| {
+        EncodingTag::try_from(tag)
+            .ok()
+            .map(|tag| format!("{tag:?}"))
+    })?;
+    match EncodingTag::try_from(encoding) {
+        Ok(EncodingTag::Unco) => {
+            let len = w.u32("length")?;
+            w.block(len as usize, f)
+        }
+        Ok(EncodingTag::Zlib) => {
+            let len = w.u32("length")?;
+            let decompressed_len = w.u32("decompressed length")?;
+            let compressed = w.bytes(len.saturating_sub(4) as usize, "zlib data")?;
+
+            let data = zlib::decompress(compressed, decompressed_len as usize, u64::MAX)
+                .map_err(|e| format!("failed to decompress: {e}"))?;
+
+            w.note(format!(
+                "decompressed data ({} bytes, offsets relative to it)",
+                data.len()
+            ));
+            walk_buffer(&data, 0, w.depth + 1, w.entries, f);
+            Ok(())
+        }
+        Err(_) => Err(format!("unknown encoding {}", fourcc(encoding))),
+    }
+}
+
+fn walk_layer(w: &mut Walker) -> Result<()> {
+    match w.u16("layer data type (0: empty, 256: vector)")? {
+        0 => return Ok(()),
+        0x0100 => (),
+        _ => {
+            w.rest("unknown layer data");
+            return Ok(());
+        }
+    }
+
+    let shape_count = w.u32("shape count")?;
+    for i in 0..shape_count {
+        w.note(format!("shape {i}"));
+        w.indented(|w| {
+            w.u32("unknown (usually 2)")?;
+            w.tag(|_| None)?;
+            let len = w.u32("length")?;
+            w.block(len as usize, walk_shape)
+        })?;
+    }
+    w.rest("layer trailer");
+    Ok(())
+}
+
+fn shape_type_name(ty: u16) -> &'static str {
+    match ty {
+        2 => "fill",
+        3 => "stroke",
+        6 => "line",
+        _ => "unknown",
+    }
+}
+
+fn walk_shape(w: &mut Walker) -> Result<()> {
+    let ty = u16::from_le_bytes(w.peek(2)?.try_into().unwrap());
+    w.bytes(2, format!("shape type: {ty} ({})", shape_type_name(ty)))?;
+    let component_count = w.u32("component count")?;
+    for i in 0..component_count {
+        w.note(format!("component {i}"));
+        w.indented(|w| {
+            w.tag(|_| None)?;
+            let len = w.u32("length")?;
+            w.block(len as usize, walk_component)
+        })?;
+    }
+    Ok(())
+}
+
+const TGSD: u32 = u32::from_be_bytes(*b"TGSD");
+const TGBP: u32 = u32::from_be_bytes(*b"TGBP");
+
+fn walk_component(w: &mut Walker) -> Result<()> {
+    while w.remaining() > 0 {
+        let tag = w.tag(|tag| match tag {
+            TGSD => Some("component info".into()),
+            TGBP => Some("path".into()),
+            _ => None,
+        })?;
+        let stop = w.indented(|w| {
+            let len = w.u32("length")? as usize;
+            match tag {
+                TGSD => {
+                    w.block(len, |w| walk_tgsd(w, len))?;
+                    match w.u8("continue (1) or stop (0)")? {
+                        0 => {
+                            w.bytes(4, "component trailer")?;
+                            Ok(true)
+                        }
+                        _ => Ok(false),
+                    }
+                }
+                TGBP => w.block(len, walk_tgbp).map(|_| false),
+                _ => w.bytes(len, "data").map(|_| false),
+            }
+        })?;
+        if stop {
+            break;
+        }
+    }
+    Ok(())
+}
+
+fn walk_tgsd(w: &mut Walker, len: usize) -> Result<()> {
+    let ty = w.peek(1)?[0];
+    let name = match ty {
+        0 => "fill",
+        2 => "stroke",
+        4 => "pencil",
+        _ => "unknown",
+    };
+    w.bytes(1, format!("component type: {ty} ({name})"))?;
+    match ty {
+        0 => {
+            let has_color = w.u8("has color")?;
+            if has_color == 1 {
+                let prefix_len = len.saturating_sub(24).saturating_sub(2);
+                w.bytes(prefix_len, "unknown")?;
+                w.u64_hex("color ID")?;
+            }
+        }
+        4 => {
+            w.bytes(4, "unknown (usually 00 00 20 41)")?;
+            w.u64_hex("color ID")?;
+        }
+        _ => (),
+    }
+    w.rest("unknown (usually zeros)");
+    Ok(())
+}
+
+fn walk_tgbp(w: &mut Walker) -> Result<()> {
+    let point_count = w.u32("point count")?;
+
+    // segment types are encoded as a bit stream from LSB to MSB: 1 for lines, 001 for cubics
+    let mut segments = Vec::new();
+    let mut bits = 0;
+    let mut points_left = point_count;
+    let mut zeros = 0;
+    while points_left > 0 {
+        let byte = *w
+            .data
+            .get(w.pos + bits / 8)
+            .ok_or("segment types end early")?;
+        let bit = byte & (1 << (bits % 8)) != 0;
+        bits += 1;
+        if !bit {
+            zeros += 1;
+            continue;
+        }
+        match zeros {
+            0 => {
+                segments.push(false);
+                points_left = points_left.saturating_sub(1);
+            }
+            2 => {
+                segments.push(true);
+                points_left = points_left.saturating_sub(3);
+            }
+            n => return Err(format!("unknown segment type {n}")),
+        }
+        zeros = 0;
+    }
+    let type_bytes = bits.div_ceil(8).max(1);
+    let cubics = segments.iter().filter(|is_cubic| **is_cubic).count();
+    let label = format!(
+        "segment types: {} lines, {cubics} cubics",
+        segments.len() - cubics
+    );
+    w.bytes(type_bytes, label)?;
+
+    for is_cubic in segments {
+        if is_cubic {
+            w.point("cubic control point")?;
+            w.point("cubic control point")?;
+            w.point("cubic to")?;
+        } else {
+            w.point("line to")?;
+        }
+    }
+    w.rest("extra");
+    Ok(())
+}
+
+fn walk_palette(w: &mut Walker) -> Result<()> {
+    let color_count = w.u32("color count")?;
+    w.u32("unknown (usually 0x79)")?;
+    for i in 0..color_count {
+        w.note(format!("color {i}"));
+        w.indented(|w| {
+            w.u16("header (usually 0)")?;
+            while w.remaining() >= 4 {
+                if w.peek(4)? == [0x79, 0, 0, 0] {
+                    w.bytes(4, "end of color")?;
+                    break;
+                }
+                let tag = w.tag(|_| None)?;
+                w.indented(|w| {
+                    let len = w.u32("length")? as usize;
+                    match &tag.to_be_bytes() {
+                        b"TCSC" => w.block(len, |w| {
+                            let rgba = w.peek(4)?;
+                            let label = format!("RGBA #{}", hex(rgba).replace(' ', ""));
+                            w.bytes(4, label).map(drop)
+                        }),
+                        b"TCID" => w.block(len, |w| {
+                            w.utf16_string("name")?;
+                            w.u64_hex("color ID")?;
+                            w.utf16_string("palette name")
+                        }),
+                        _ => w.bytes(len, "data").map(drop),
+                    }
+                })?;
+            }
+            Ok(())
+        })?;
+    }
+    Ok(())
+}
@@ -0,0 +1,111 @@
+//! Conversions between paths and [kurbo] paths.
+//!
+//! The first segment of a [ShapePath] is its starting point, so it becomes a `MoveTo`.
+
+use crate::layer::{PathSegment, Point, ShapePath};
+use kurbo::{BezPath, PathEl};
+
+fn to_kurbo(p: Point) -> kurbo::Point {
+    kurbo::Point::new(p.0 as f64, p.1 as f64)
+}
+
+fn from_kurbo(p: kurbo::Point) -> Point {
+    (p.x as f32, p.y as f32)
+}
+
+impl From<&PathSegment> for PathEl {
+    fn from(segment: &PathSegment) -> Self {
+        match *segment {
+            PathSegment::Line(p) => PathEl::LineTo(to_kurbo(p)),
+            PathSegment::Cubic(c1, c2, p) => {
+                PathEl::CurveTo(to_kurbo(c1), to_kurbo(c2), to_kurbo(p))
+            }
+        }
+    }
+}
+
+impl From<PathSegment> for PathEl {
+    fn from(segment: PathSegment) -> Self {
+        (&segment).into()
+    }
+}
+
+impl From<&ShapePath> for BezPath {
+    fn from(path: &ShapePath) -> Self {
+        let mut bez_path = BezPath::new();
+        for (i, segment) in path.segments.iter().enumerate() {
+            match (i, segment) {
+                (0, PathSegment::Line(p) | PathSegment::Cubic(_, _, p)) => {
+                    bez_path.move_to(to_kurbo(*p))
+                }
+                (_, segment) => bez_path.push(segment.into()),
+            }
+        }
+        bez_path
+    }
+}
+
+impl From<ShapePath> for BezPath {
+    fn from(path: ShapePath) -> Self {
+        (&path).into()
+    }
+}
+
+impl From<&BezPath> for ShapePath {
+    /// Converts a kurbo path.
+    ///
+    /// Since a path can only have one starting point, any further `MoveTo`s become line segments.
+    /// Quadratic curves are converted to cubics, and `ClosePath` becomes a line back to the start
+    /// of the subpath (if it's not already there).
+    fn from(bez_path: &BezPath) -> Self {
+        let mut segments = Vec::new();
+        let mut start = kurbo::Point::ZERO;
+        let mut current = kurbo::Point::ZERO;
+        for el in bez_path.elements() {
+            match *el {
+                PathEl::MoveTo(p) => {
+                    segments.push(PathSegment::Line(from_kurbo(p)));
+                    start = p;
+                    current = p;
+                }
+                PathEl::LineTo(p) => {
+                    segments.push(PathSegment::Line(from_kurbo(p)));
+                    current = p;
+                }
+                PathEl::QuadTo(q, p) => {
+                    let cubic = kurbo::QuadBez::new(current, q, p).raise();
+                    segments.push(PathSegment::Cubic(
+                        from_kurbo(cubic.p1),
+                        from_kurbo(cubic.p2),
+                        from_kurbo(p),
+                    ));
+                    current = p;
+                }
+                PathEl::CurveTo(c1, c2, p) => {
+                    segments.push(PathSegment::Cubic(
+                        from_kurbo(c1),
+                        from_kurbo(c2),
+                        from_kurbo(p),
+                    ));
+                    current = p;
+                }
+                PathEl::ClosePath => {
+                    if current != start {
+                        segments.push(PathSegment::Line(from_kurbo(start)));
+                    }
+                    current = start;
+                }
+            }
+        }
+        ShapePath {
+            segments,
+            extra: None,
+        }
+    }
+}
+
+impl From<BezPath> for ShapePath {
+    fn from(bez_path: BezPath) -> Self {
+        (&bez_path).into()
+    }
+}
@@ -1,20 +1,135 @@
+#[cfg(feature = "pencil")]
 use crate::pencil::{read_tgtb, StrokeThickness};
-use crate::read::ReadError;
-use crate::util::{read_encoded_data, Bytes};
-use byteorder::{ReadBytesExt, LE};
+use crate::read::{check_limit, ReadError, ReadOptions};
+use crate::trace;
+use crate::util::{all_values, read_encoded_data, Bytes};
+use crate::io::{self, Read, ReadBytesExt};
+use alloc::{format, vec::Vec};
+use byteorder::{ByteOrder, LE};
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use std::io::{self, Read};
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(tag = "type", content = "content", rename_all = "snake_case"))]
 pub enum LayerData {
     Empty,
-    Vector(Vec<VectorShape>),
+    Vector {
+        shapes: Vec<VectorShape>,
+        trailer: LayerTrailer,
+    },
+    /// Layer data of a type we don't know how to decode yet (such as bitmap layers), kept as-is
+    /// so it can be written back out. See `notes/bitmap-layers.md`.
+    Unknown {
+        #[cfg_attr(feature = "serde", serde(rename = "type"))]
+        ty: u16,
+        /// The layer data after the type.
+        data: Bytes,
+    },
 }
 
+/// The data at the end of a vector layer.
+///
+/// It looks like a tagged block: a 0 byte (possibly a terminator like the one after `TGSD`), a
+/// `TGRV` tag, a u32 length and a value. In most files, it's exactly [LAYER_TRAILER], but files
+/// from some Harmony versions (e.g. Harmony 20) have different bytes after the tag.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+#[cfg_attr(feature = "serde", serde(tag = "type", content = "content", rename_all = "snake_case"))]
+pub enum LayerTrailer {
+    /// A `TGRV` block.
+    Tagged {
+        /// The byte before the tag. So far always 0.
+        marker: u8,
+        /// The tag, always [TGRV].
+        tag: u32,
+        /// The length after the tag. So far always 8, though fewer bytes than that may follow.
+        len: u32,
+        /// The 4 bytes after the length as a little-endian f32, if there are that many. Usually
+        /// `3d df 4f 8d`, which is about -6.4e-31. What it means is unknown (see
+        /// `notes/pivot.md`).
+        value: Option<f32>,
+        /// Everything after the value until the end of the layer data. Usually empty.
+        data: Bytes,
+    },
+    /// A trailer that doesn't start with a marker byte and a `TGRV` tag and length, kept as it
+    /// is. This is an error with [ParseProfile::Strict](crate::read::ParseProfile::Strict).
+    Raw(Bytes),
+}
+
+/// `TGRV`: the tag in the layer trailer.
+pub const TGRV: u32 = 0x54475256;
+
+impl LayerTrailer {
+    /// Decodes a trailer. Returns [LayerTrailer::Raw] if it doesn't have a `TGRV` tag.
+    pub fn parse(data: &[u8]) -> Self {
+        let tagged = || {
+            let (&marker, rest) = data.split_first()?;
+            let tag = byteorder::BE::read_u32(rest.get(..4)?);
+            let len = LE::read_u32(rest.get(4..8)?);
+            let rest = &rest[8..];
+            if tag != TGRV {
+                return None;
+            }
+            let value = rest.get(..4).map(LE::read_f32);
+            let data = rest.get(4..).unwrap_or_default();
+            Some(LayerTrailer::Tagged {
+                marker,
+                tag,
+                len,
+                value,
+                data: Bytes(data.to_vec()),
+            })
+        };
+        tagged().unwrap_or_else(|| LayerTrailer::Raw(Bytes(data.to_vec())))
+    }
+}
+
+impl Default for LayerTrailer {
+    /// Returns the trailer as it appears in most files ([LAYER_TRAILER]).
+    fn default() -> Self {
+        LayerTrailer::parse(LAYER_TRAILER)
+    }
+}
+
+/// Compares values by their bits, so that trailers with a NaN value are equal to themselves.
+impl PartialEq for LayerTrailer {
+    fn eq(&self, other: &Self) -> bool {
+        match (self, other) {
+            (
+                LayerTrailer::Tagged {
+                    marker,
+                    tag,
+                    len,
+                    value,
+                    data,
+                },
+                LayerTrailer::Tagged {
+                    marker: other_marker,
+                    tag: other_tag,
+                    len: other_len,
+                    value: other_value,
+                    data: other_data,
+                },
+            ) => {
+                (marker, tag, len, data) == (other_marker, other_tag, other_len, other_data)
+                    && value.map(f32::to_bits) == other_value.map(f32::to_bits)
+            }
+            (LayerTrailer::Raw(a), LayerTrailer::Raw(b)) => a == b,
+            _ => false,
+        }
+    }
+}
+
+impl Eq for LayerTrailer {}
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u16)]
 pub enum ShapeType {
@@ -26,8 +141,19 @@ pub enum ShapeType {
     Unknown7 = 7,
 }
 
+all_values!(ShapeType {
+    Unknown0,
+    Unknown1,
+    Fill,
+    Stroke,
+    Line,
+    Unknown7
+});
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct VectorShape {
     #[cfg_attr(feature = "serde", serde(rename = "type"))]
     pub ty: ShapeType,
@@ -36,12 +162,19 @@ pub struct VectorShape {
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ShapeComponent {
     pub tags: Vec<ShapeComponentData>,
+    /// The 4 bytes that end the component after the last `TGSD` tag, if
+    /// [ReadOptions::preserve_unknown].
+    pub trailer: Option<Bytes>,
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[repr(u32)]
 pub enum ShapeComponentTag {
     /// `TGSD`: seems to contain metadata
@@ -54,18 +187,36 @@ pub enum ShapeComponentTag {
     Tgti = 0x74475449,
 }
 
+all_values!(ShapeComponentTag {
+    Tgsd,
+    Tgbp,
+    Tgtb,
+    Tgti
+});
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(tag = "type", content = "content", rename_all = "snake_case"))]
 pub enum ShapeComponentData {
     Info(ComponentInfo),
-    Path(Path),
+    Path(ShapePath),
+    #[cfg(feature = "pencil")]
     Thickness(StrokeThickness),
+    /// Undecoded `tGTI` data (see `notes/tgti.md`).
     Tgti(Bytes),
+    /// A tag we don't know how to decode yet, kept as-is so it can be written back out.
+    Unknown {
+        tag: u32,
+        data: Bytes,
+    },
 }
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(rename_all = "snake_case"))]
 #[repr(u8)]
 pub enum ComponentType {
@@ -75,24 +226,56 @@ pub enum ComponentType {
     Pencil = 4,
 }
 
+all_values!(ComponentType {
+    Fill,
+    Unknown1,
+    Stroke,
+    Pencil
+});
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct ComponentInfo {
     #[cfg_attr(feature = "serde", serde(rename = "type"))]
     pub ty: ComponentType,
     pub color_id: Option<u64>,
+    /// For pencil strokes, the f32 between the component type and the color ID. It's usually
+    /// 10.0 (`00 00 20 41`), but not always, and doesn't seem to change how the stroke looks.
+    pub pencil_value: Option<f32>,
+    /// Undecoded bytes between the component type and the color ID of fills: everything between
+    /// the "has color" flag and the color ID, if [ReadOptions::preserve_unknown]. Empty for
+    /// other components.
+    pub unknown_prefix: Option<Bytes>,
+    /// Undecoded bytes after the color ID (or after the component type, if there's no color ID),
+    /// if [ReadOptions::preserve_unknown]. These usually seem to be all zeros.
+    pub unknown_suffix: Option<Bytes>,
 }
 
 pub type Point = (f32, f32);
 
+/// A Bézier path in a `TGBP` tag.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
-pub struct Path {
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ShapePath {
     pub segments: Vec<PathSegment>,
+    /// Any bytes in the `TGBP` tag that follow the point data.
+    ///
+    /// So far, these have never been observed, but they are kept so that they survive a round trip.
+    pub extra: Option<Bytes>,
 }
 
+/// Old name of [ShapePath], which was renamed to avoid confusion with `std::path::Path`.
+#[deprecated(note = "renamed to ShapePath")]
+pub type Path = ShapePath;
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(tag = "type", content = "content", rename_all = "snake_case"))]
 pub enum PathSegment {
     Line(Point),
@@ -101,6 +284,8 @@ pub enum PathSegment {
 
 #[derive(Debug)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 enum PathSegmentType {
     Line,
     Cubic,
@@ -135,11 +320,16 @@ impl PathSegmentType {
             if is_1 {
                 match zeros {
                     0 => {
-                        points_left -= 1;
+                        points_left = points_left
+                            .checked_sub(1)
+                            .ok_or(ReadError::InvalidLength("path point count", points))?;
                         out.push(PathSegmentType::Line);
                     }
                     2 => {
-                        points_left -= 3;
+                        // a cubic with fewer points left than it needs
+                        points_left = points_left
+                            .checked_sub(3)
+                            .ok_or(ReadError::InvalidLength("path point count", points))?;
                         out.push(PathSegmentType::Cubic);
                     }
                     n => {
@@ -152,61 +342,235 @@ impl PathSegmentType {
                 zeros = 0;
             } else {
                 zeros += 1;
+                if zeros > 2 {
+                    // no known code has more than two zeros
+                    return Err(ReadError::UnknownMystery(format!(
+                        "unknown curve segment type {}",
+                        zeros
+                    )));
+                }
             }
         }
         Ok(out)
     }
 }
 
-impl Path {
-    fn read<R>(mut input: R) -> Result<Self, ReadError>
-    where
-        R: Read,
-    {
+impl ShapePath {
+    /// The number of points stored in the path, counting cubic control points.
+    pub fn point_count(&self) -> usize {
+        self.segments
+            .iter()
+            .map(|segment| match segment {
+                PathSegment::Line(_) => 1,
+                PathSegment::Cubic(..) => 3,
+            })
+            .sum()
+    }
+
+    /// Reads a path from the contents of a `TGBP` tag.
+    ///
+    /// Paths can have tens of thousands of points, so the points are decoded from the slice
+    /// directly instead of going through [Read] one coordinate at a time.
+    fn read(mut input: &[u8], options: &ReadOptions) -> Result<Self, ReadError> {
         let point_count = input.read_u32::<LE>()?;
+        check_limit("path point count", point_count, options.max_point_count)?;
 
         let segment_types = PathSegmentType::read(&mut input, point_count)?;
-        let mut segments = Vec::new();
-
-        macro_rules! read_point {
-            () => {{
-                let x = input.read_f32::<LE>()?;
-                let y = input.read_f32::<LE>()?;
-                (x, y)
-            }};
+        let point_count: usize = segment_types
+            .iter()
+            .map(|segment| match segment {
+                PathSegmentType::Line => 1,
+                PathSegmentType::Cubic => 3,
+            })
+            .sum();
+        if input.len() < point_count * 8 {
+            return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
         }
+        let (point_data, extra) = input.split_at(point_count * 8);
 
+        let point = |i: usize| {
+            let data = &point_data[i * 8..i * 8 + 8];
+            (LE::read_f32(&data[..4]), LE::read_f32(&data[4..]))
+        };
+
+        let mut segments = Vec::with_capacity(segment_types.len());
+        let mut i = 0;
         for segment in segment_types {
             match segment {
                 PathSegmentType::Line => {
-                    segments.push(PathSegment::Line(read_point!()));
+                    segments.push(PathSegment::Line(point(i)));
+                    i += 1;
                 }
                 PathSegmentType::Cubic => {
-                    segments.push(PathSegment::Cubic(
-                        read_point!(),
-                        read_point!(),
-                        read_point!(),
-                    ));
+                    segments.push(PathSegment::Cubic(point(i), point(i + 1), point(i + 2)));
+                    i += 3;
+                }
+            }
+        }
+
+        let extra = if extra.is_empty() {
+            None
+        } else {
+            Some(Bytes(extra.to_vec()))
+        };
+
+        Ok(ShapePath { segments, extra })
+    }
+}
+
+impl ShapeComponent {
+    /// Returns the component info (`TGSD`), if there is one.
+    pub fn info(&self) -> Option<&ComponentInfo> {
+        self.tags.iter().find_map(|tag| match tag {
+            ShapeComponentData::Info(info) => Some(info),
+            _ => None,
+        })
+    }
+
+    /// Returns the first path (`TGBP`), if there is one. See [ShapeComponent::paths] for
+    /// components with more than one.
+    pub fn path(&self) -> Option<&ShapePath> {
+        self.paths().next()
+    }
+
+    /// Returns all paths (`TGBP`) in order.
+    pub fn paths(&self) -> impl Iterator<Item = &ShapePath> {
+        self.tags.iter().filter_map(|tag| match tag {
+            ShapeComponentData::Path(path) => Some(path),
+            _ => None,
+        })
+    }
+
+    /// Returns the paths (`TGBP`) in order, each with the pencil thickness (`tGTB`) that applies
+    /// to it.
+    ///
+    /// A component can contain more than one path, and a thickness applies to the path before it.
+    /// [ShapeComponent::tags] keeps the tags in file order so they can be written back out as they
+    /// were, so this pairs them up.
+    pub fn path_groups(&self) -> Vec<PathGroup<'_>> {
+        let mut groups: Vec<PathGroup> = Vec::new();
+        for tag in &self.tags {
+            match tag {
+                ShapeComponentData::Path(path) => groups.push(PathGroup {
+                    path,
+                    #[cfg(feature = "pencil")]
+                    thickness: None,
+                }),
+                #[cfg(feature = "pencil")]
+                ShapeComponentData::Thickness(thickness) => {
+                    if let Some(group) = groups.last_mut().filter(|g| g.thickness.is_none()) {
+                        group.thickness = Some(thickness);
+                    }
                 }
+                _ => (),
             }
         }
+        groups
+    }
+
+    /// Returns the first pencil thickness (`tGTB`), if there is one. See
+    /// [ShapeComponent::path_groups] for components with more than one path.
+    #[cfg(feature = "pencil")]
+    pub fn thickness(&self) -> Option<&StrokeThickness> {
+        self.tags.iter().find_map(|tag| match tag {
+            ShapeComponentData::Thickness(thickness) => Some(thickness),
+            _ => None,
+        })
+    }
+
+    /// Returns the undecoded `tGTI` data, if there is any.
+    pub fn tgti(&self) -> Option<&[u8]> {
+        self.tags.iter().find_map(|tag| match tag {
+            ShapeComponentData::Tgti(data) => Some(&data.0[..]),
+            _ => None,
+        })
+    }
+}
+
+/// A path of a component, with the thickness that applies to it. See
+/// [ShapeComponent::path_groups].
+#[derive(Debug, Clone, Copy)]
+pub struct PathGroup<'a> {
+    pub path: &'a ShapePath,
+    /// The thickness that follows the path, if any.
+    #[cfg(feature = "pencil")]
+    pub thickness: Option<&'a StrokeThickness>,
+}
+
+/// A path of a shape, with the info of the component that contains it and the thickness that
+/// applies to it. See [VectorShape::paths].
+#[derive(Debug, Clone, Copy)]
+pub struct ResolvedComponent<'a> {
+    /// The index of the component in [VectorShape::components].
+    pub index: usize,
+    pub info: Option<&'a ComponentInfo>,
+    pub path: &'a ShapePath,
+    #[cfg(feature = "pencil")]
+    pub thickness: Option<&'a StrokeThickness>,
+}
+
+impl ResolvedComponent<'_> {
+    /// The component type, if the component has info.
+    pub fn ty(&self) -> Option<ComponentType> {
+        self.info.map(|info| info.ty)
+    }
 
-        Ok(Path { segments })
+    /// The palette color ID, if the component has one.
+    pub fn color_id(&self) -> Option<u64> {
+        self.info.and_then(|info| info.color_id)
     }
 }
 
-// what does this mean?
-const LAYER_TRAILER: &[u8] = &[
+impl VectorShape {
+    /// Returns the IDs of the palette colors that the components use, in component order. A color
+    /// used by several components is returned once for each.
+    pub fn color_ids(&self) -> impl Iterator<Item = u64> + '_ {
+        self.components
+            .iter()
+            .flat_map(|component| &component.tags)
+            .filter_map(|tag| match tag {
+                ShapeComponentData::Info(info) => info.color_id,
+                _ => None,
+            })
+    }
+
+    /// Returns all paths of the shape in order, each with the info of its component and the
+    /// thickness that applies to it (see [ShapeComponent::path_groups]). Components without a path
+    /// are skipped.
+    pub fn paths(&self) -> impl Iterator<Item = ResolvedComponent<'_>> {
+        self.components
+            .iter()
+            .enumerate()
+            .flat_map(|(index, component)| {
+                let info = component.info();
+                component
+                    .path_groups()
+                    .into_iter()
+                    .map(move |group| ResolvedComponent {
+                        index,
+                        info,
+                        path: group.path,
+                        #[cfg(feature = "pencil")]
+                        thickness: group.thickness,
+                    })
+            })
+    }
+}
+
+/// The layer trailer as it appears in most files. With
+/// [ParseProfile::Strict](crate::read::ParseProfile::Strict), the trailer must start with these
+/// bytes.
+pub const LAYER_TRAILER: &[u8] = &[
     0x00, 0x54, 0x47, 0x52, 0x56, 0x08, 0x00, 0x00, 0x00, 0x3d, 0xdf, 0x4f, 0x8d,
 ];
 
-pub fn read_layer_data<R>(mut input: R) -> Result<LayerData, ReadError>
+pub fn read_layer_data<R>(mut input: R, options: &ReadOptions) -> Result<LayerData, ReadError>
 where
     R: Read,
 {
-    let data = read_encoded_data(&mut input)?;
-    println!("layer:\n{:?}", Bytes(data.clone()));
-    let mut input = io::BufReader::new(io::Cursor::new(data));
+    let data = read_encoded_data(&mut input, options)?;
+    trace::debug!(len = data.len(), data = ?Bytes(data.clone()), "layer data");
+    let mut input = &data[..];
 
     let data_type = input.read_u16::<LE>()?;
     match data_type {
@@ -216,29 +580,32 @@ where
         }
         0x0100 => {
             // vector layer
-            read_vector_layer(input)
+            read_vector_layer(input, options)
+        }
+        ty => {
+            let mut data = Vec::new();
+            input.read_to_end(&mut data)?;
+            Ok(LayerData::Unknown {
+                ty,
+                data: Bytes(data),
+            })
         }
-        ty => Err(ReadError::UnknownMystery(format!(
-            "unexpected value of layer data type: {:04x?}",
-            ty
-        ))),
     }
 }
 
-fn read_vector_layer<R>(mut input: R) -> Result<LayerData, ReadError>
+fn read_vector_layer<R>(mut input: R, options: &ReadOptions) -> Result<LayerData, ReadError>
 where
     R: Read,
 {
     let mut shapes = Vec::new();
 
     let shape_count = input.read_u32::<LE>()?;
+    check_limit("shape count", shape_count, options.max_shape_count)?;
     for _ in 0..shape_count {
+        trace::span!("shape", index = shapes.len());
         let layer_ty = input.read_u32::<LE>()?;
         if layer_ty != 2 {
-            return Err(ReadError::UnknownMystery(format!(
-                "unexpected layer type: {:?}",
-                layer_ty
-            )));
+            options.unexpected(|| format!("unexpected layer type: {:?}", layer_ty))?;
         }
         let tgly = input.read_u32::<byteorder::BE>()?;
         if tgly != 0x54474c59 {
@@ -252,18 +619,15 @@ where
 
         let shape_type = match ShapeType::try_from(input.read_u16::<LE>()?) {
             Ok(ty) => ty,
-            Err(err) => {
-                let mut data = Vec::new();
-                input.read_to_end(&mut data)?;
-                println!("{:?}", Bytes(data));
-                return Err(ReadError::UnknownShapeType(err.number));
-            }
+            Err(err) => return Err(ReadError::UnknownShapeType(err.number)),
         };
 
         let mut paths = Vec::new();
 
         let component_count = input.read_u32::<LE>()?;
+        check_limit("component count", component_count, options.max_component_count)?;
         for _ in 0..component_count {
+            trace::span!("component", index = paths.len());
             let tag = input.read_u32::<byteorder::BE>()?;
             if tag != 0x54475653 {
                 // not TGVS
@@ -277,11 +641,25 @@ where
             let mut input = (&mut input).take(len as u64);
 
             let mut tags = Vec::new();
+            let mut trailer = None;
             loop {
                 let tag = match input.read_u32::<byteorder::BE>() {
                     Ok(tag) => match ShapeComponentTag::try_from(tag) {
                         Ok(tag) => tag,
-                        Err(err) => return Err(ReadError::UnknownComponentTag(err.number)),
+                        Err(err) => {
+                            // assume it's length-prefixed like all the other tags
+                            let len = input.read_u32::<LE>()?;
+                            let mut data = Vec::new();
+                            (&mut input).take(len as u64).read_to_end(&mut data)?;
+                            if data.len() != len as usize {
+                                return Err(ReadError::UnknownComponentTag(err.number));
+                            }
+                            tags.push(ShapeComponentData::Unknown {
+                                tag: err.number,
+                                data: Bytes(data),
+                            });
+                            continue;
+                        }
                     },
                     Err(err) if err.kind() == io::ErrorKind::UnexpectedEof => break,
                     Err(err) => return Err(ReadError::Io(err)),
@@ -298,15 +676,28 @@ where
 
                             // TODO: find out what all the other stuff means (“TGCO”?)
                             // there may be information about the graph structure in this tag
+                            let mut unknown_prefix = Vec::new();
+                            let mut pencil_value = None;
                             let color_id = match component_type {
                                 ComponentType::Fill => {
                                     // fill
                                     let color_id = match input.read_u8()? {
                                         0x00 => None,
                                         0x01 => {
-                                            let color_pos = len - 24;
-                                            for _ in 2..color_pos {
-                                                input.read_u8()?;
+                                            let color_pos = len.checked_sub(24).ok_or(
+                                                ReadError::InvalidLength("TGSD", len),
+                                            )?;
+                                            let prefix_len = color_pos.saturating_sub(2);
+                                            // read instead of allocating the length up front,
+                                            // since it comes from the file
+                                            (&mut input)
+                                                .take(prefix_len as u64)
+                                                .read_to_end(&mut unknown_prefix)?;
+                                            if unknown_prefix.len() != prefix_len as usize {
+                                                return Err(io::Error::from(
+                                                    io::ErrorKind::UnexpectedEof,
+                                                )
+                                                .into());
                                             }
                                             Some(input.read_u64::<LE>()?)
                                         }
@@ -326,25 +717,29 @@ where
                                 }
                                 ComponentType::Pencil => {
                                     // pencil stroke
-                                    let v = input.read_u32::<LE>()?;
-                                    if v != 0x41200000 {
-                                        // TODO: figure out what this means
-                                        // (maybe some kind of ID, because it does not seem important for the data)
-                                        /*return Err(ReadError::UnknownMystery(format!(
-                                            "unexpected bytes in TGSD pencil: {v:08x} (expected 41200000)",
-                                        )));*/
-                                    }
+                                    // TODO: figure out what this means. It's usually 10.0, but
+                                    // not always (maybe some kind of ID, because it does not seem
+                                    // important for the data)
+                                    pencil_value = Some(input.read_f32::<LE>()?);
                                     Some(input.read_u64::<LE>()?)
                                 }
                             };
 
                             // FIXME: is there any interesting data here, ever?
                             // seems to just be a bunch of 0 bytes, usually...
-                            input.read_to_end(&mut Vec::new())?;
+                            let mut unknown_suffix = Vec::new();
+                            input.read_to_end(&mut unknown_suffix)?;
 
                             tags.push(ShapeComponentData::Info(ComponentInfo {
                                 ty: component_type,
                                 color_id,
+                                pencil_value,
+                                unknown_prefix: options
+                                    .preserve_unknown()
+                                    .then_some(Bytes(unknown_prefix)),
+                                unknown_suffix: options
+                                    .preserve_unknown()
+                                    .then_some(Bytes(unknown_suffix)),
                             }));
                         };
 
@@ -354,8 +749,12 @@ where
                         match extra_byte {
                             0 => {
                                 // stop
-                                let trailer = input.read_u32::<LE>()?;
-                                println!("trailer: {:08x?}", trailer);
+                                let mut data = [0; 4];
+                                input.read_exact(&mut data)?;
+                                trace::debug!(trailer = ?data, "component trailer");
+                                if options.preserve_unknown() {
+                                    trailer = Some(Bytes(data.into()));
+                                }
                                 break;
                             }
                             1 => {
@@ -371,13 +770,25 @@ where
                     }
                     ShapeComponentTag::Tgbp => {
                         let len = input.read_u32::<LE>()?;
-                        let mut input = (&mut input).take(len as u64);
-                        tags.push(ShapeComponentData::Path(Path::read(&mut input)?));
+                        let mut data = Vec::new();
+                        (&mut input).take(len as u64).read_to_end(&mut data)?;
+                        tags.push(ShapeComponentData::Path(ShapePath::read(&data, options)?));
                     }
+                    #[cfg(feature = "pencil")]
                     ShapeComponentTag::Tgtb => {
-                        let thickness = read_tgtb(&mut input)?;
+                        let thickness = read_tgtb(&mut input, options)?;
                         tags.push(ShapeComponentData::Thickness(thickness));
                     }
+                    #[cfg(not(feature = "pencil"))]
+                    ShapeComponentTag::Tgtb => {
+                        let len = input.read_u32::<LE>()?;
+                        let mut data = Vec::new();
+                        (&mut input).take(len as u64).read_to_end(&mut data)?;
+                        tags.push(ShapeComponentData::Unknown {
+                            tag: tag.into(),
+                            data: Bytes(data),
+                        });
+                    }
                     ShapeComponentTag::Tgti => {
                         let len = input.read_u32::<LE>()?;
                         let mut input = (&mut input).take(len as u64);
@@ -389,7 +800,7 @@ where
                 }
             }
 
-            paths.push(ShapeComponent { tags });
+            paths.push(ShapeComponent { tags, trailer });
         }
 
         shapes.push(VectorShape {
@@ -398,14 +809,105 @@ where
         });
     }
 
-    let mut trailer = [0; LAYER_TRAILER.len()];
-    input.read_exact(&mut trailer)?;
-    if trailer != LAYER_TRAILER {
+    let mut trailer = Vec::new();
+    input.read_to_end(&mut trailer)?;
+    if options.is_strict() && !trailer.starts_with(LAYER_TRAILER) {
         return Err(ReadError::UnknownMystery(format!(
-            "unexpected layer trailer: {:02?}",
+            "unexpected layer trailer: {:02x?}",
             trailer
         )));
     }
 
-    Ok(LayerData::Vector(shapes))
+    Ok(LayerData::Vector {
+        shapes,
+        trailer: LayerTrailer::parse(&trailer),
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use alloc::vec;
+
+    fn tag(out: &mut Vec<u8>, tag: &[u8; 4], data: &[u8]) {
+        out.extend_from_slice(tag);
+        out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+        out.extend_from_slice(data);
+    }
+
+    /// A vector layer (without the data type) with one fill shape with one component.
+    fn layer(component: &[u8]) -> Vec<u8> {
+        let mut shape = Vec::new();
+        shape.extend_from_slice(&u16::from(ShapeType::Fill).to_le_bytes());
+        shape.extend_from_slice(&1_u32.to_le_bytes());
+        tag(&mut shape, b"TGVS", component);
+
+        let mut out = Vec::new();
+        out.extend_from_slice(&1_u32.to_le_bytes());
+        out.extend_from_slice(&2_u32.to_le_bytes());
+        tag(&mut out, b"TGLY", &shape);
+        out.extend_from_slice(LAYER_TRAILER);
+        out
+    }
+
+    fn read(data: &[u8]) -> Result<LayerData, ReadError> {
+        read_vector_layer(data, &ReadOptions::default())
+    }
+
+    #[test]
+    fn fill_info_shorter_than_color_id() {
+        // a fill with a color, but too short for the 24 bytes around the color ID
+        let mut component = Vec::new();
+        tag(&mut component, b"TGSD", &[0, 1, 0, 0, 0, 0, 0, 0, 0, 0]);
+        component.extend_from_slice(&[0, 0, 0, 0, 0]);
+        assert!(matches!(
+            read(&layer(&component)),
+            Err(ReadError::InvalidLength("TGSD", 10))
+        ));
+    }
+
+    #[test]
+    fn cubic_past_point_count() {
+        // a line and a cubic (1 001), but only 2 points
+        let mut path = Vec::new();
+        path.extend_from_slice(&2_u32.to_le_bytes());
+        path.push(0b1001);
+        path.extend_from_slice(&[0; 32]);
+        let mut component = Vec::new();
+        tag(&mut component, b"TGBP", &path);
+        assert!(matches!(
+            read(&layer(&component)),
+            Err(ReadError::InvalidLength("path point count", 2))
+        ));
+    }
+
+    #[test]
+    fn fill_with_path() {
+        let mut info = vec![0, 1];
+        info.extend_from_slice(&[0; 14]);
+        info.extend_from_slice(&7_u64.to_le_bytes());
+        info.extend_from_slice(&[0; 16]);
+        let mut path = Vec::new();
+        path.extend_from_slice(&2_u32.to_le_bytes());
+        path.push(0b11);
+        for value in [1_f32, 2., 3., 4.] {
+            path.extend_from_slice(&value.to_le_bytes());
+        }
+
+        let mut component = Vec::new();
+        tag(&mut component, b"TGSD", &info);
+        component.push(1);
+        tag(&mut component, b"TGBP", &path);
+
+        let LayerData::Vector { shapes, .. } = read(&layer(&component)).unwrap() else {
+            panic!("not a vector layer");
+        };
+        let component = &shapes[0].components[0];
+        assert_eq!(component.info().and_then(|info| info.color_id), Some(7));
+        let segments = &component.path().unwrap().segments;
+        assert!(matches!(
+            segments[..],
+            [PathSegment::Line((1., 2.)), PathSegment::Line((3., 4.))]
+        ));
+    }
 }
@@ -1,6 +1,84 @@
-mod eof_reader;
+#![cfg_attr(not(feature = "std"), no_std)]
+
+extern crate alloc;
+
+#[cfg(feature = "std")]
+pub mod anonymize;
+#[cfg(feature = "batch")]
+pub mod batch;
+pub mod canonical;
+pub mod certificate;
+pub mod color;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod diff;
+pub mod document;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod edit;
+pub mod eof_reader;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod export;
+#[cfg(all(feature = "std", feature = "layers"))]
+pub mod geometry;
+#[cfg(all(feature = "std", feature = "layers"))]
+pub mod hit_test;
+#[cfg(feature = "import-svg")]
+pub mod import;
+#[cfg(feature = "std")]
+pub mod inspect;
+pub mod io;
+#[cfg(feature = "kurbo")]
+mod kurbo_interop;
+#[cfg(feature = "layers")]
 pub mod layer;
+#[cfg(feature = "mesh")]
+pub mod mesh;
+pub mod outline;
+#[cfg(feature = "palette")]
 pub mod palette;
-mod pencil;
+#[cfg(feature = "std")]
+pub mod parse_trace;
+#[cfg(feature = "std")]
+pub mod patch;
+#[cfg(feature = "pencil")]
+pub mod pencil;
+pub mod prelude;
+pub mod pretty;
+#[cfg(feature = "project")]
+pub mod project;
 pub mod read;
+#[cfg(feature = "tokio")]
+mod read_async;
+mod read_captured;
+mod read_cursor;
+mod read_forensic;
+mod read_incremental;
+mod read_located;
+#[cfg(all(feature = "std", feature = "layers"))]
+pub mod regions;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod roundtrip;
+#[cfg(feature = "render")]
+pub mod render;
+#[cfg(feature = "schema")]
+pub mod schema;
+#[cfg(all(feature = "std", feature = "palette"))]
+pub mod search;
+#[cfg(all(feature = "std", feature = "palette"))]
+pub mod sequence;
+#[cfg(all(feature = "std", feature = "layers"))]
+pub mod simplify;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod spec;
+#[cfg(feature = "lyon")]
+pub mod tessellation;
+#[cfg(any(feature = "testgen", all(test, feature = "std", feature = "layers")))]
+pub mod testgen;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod stats;
+#[cfg(feature = "render")]
+pub mod thumbnail;
+mod trace;
 pub mod util;
+#[cfg(all(feature = "std", feature = "layers", feature = "palette"))]
+pub mod validate;
+pub mod zlib;
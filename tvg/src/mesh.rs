@@ -0,0 +1,357 @@
+//! Triangulation of fill shapes into indexed triangle meshes.
+//!
+//! Like the renderer and the exporters, fills use the even-odd rule: a contour inside an odd
+//! number of other contours is a hole. Each hole is connected to the contour around it by a
+//! bridge, and the resulting polygon is triangulated using ear clipping.
+
+use crate::geometry::{self, Contour};
+use crate::layer::{LayerData, Point, ShapeType, VectorShape};
+
+/// An indexed triangle mesh.
+#[derive(Debug, Clone, Default)]
+pub struct Mesh {
+    /// Vertex positions.
+    pub vertices: Vec<Point>,
+    /// Vertex indices, three per triangle.
+    pub indices: Vec<u32>,
+    /// The palette color ID of each triangle.
+    pub colors: Vec<Option<u64>>,
+}
+
+impl Mesh {
+    /// Returns the number of triangles.
+    pub fn triangle_count(&self) -> usize {
+        self.indices.len() / 3
+    }
+
+    /// Appends all fill shapes in a layer to the mesh.
+    pub fn add_layer(&mut self, layer: &LayerData) {
+        if let LayerData::Vector { shapes, .. } = layer {
+            for shape in shapes {
+                self.add_shape(shape);
+            }
+        }
+    }
+
+    /// Appends a shape to the mesh, if it's a fill shape. All triangles get the color of the
+    /// shape, which is the color of its first component that has one.
+    pub fn add_shape(&mut self, shape: &VectorShape) {
+        if shape.ty != ShapeType::Fill {
+            return;
+        }
+        let contours = geometry::contours(shape);
+        let rings: Vec<&[Point]> = contours.iter().map(ring).collect();
+        let inside = |i: usize, j: usize| {
+            i != j
+                && rings[i]
+                    .first()
+                    .is_some_and(|&p| geometry::contains_even_odd([rings[j]], p))
+        };
+        // how many other contours each contour is inside
+        let depths: Vec<usize> = (0..rings.len())
+            .map(|i| (0..rings.len()).filter(|&j| inside(i, j)).count())
+            .collect();
+
+        let color_id = shape.color_ids().next();
+        for (i, depth) in depths.iter().enumerate() {
+            if depth % 2 == 1 {
+                continue;
+            }
+            let holes = (0..rings.len())
+                .filter(|&j| depths[j] == depth + 1 && inside(j, i))
+                .map(|j| rings[j]);
+            self.add_polygon(rings[i], holes, color_id);
+        }
+    }
+
+    /// Appends a single contour to the mesh, without any holes.
+    pub fn add_contour(&mut self, contour: &Contour) {
+        self.add_polygon(ring(contour), [], contour.color_id);
+    }
+
+    /// Appends a polygon with holes to the mesh. Rings with fewer than 3 points are ignored.
+    fn add_polygon<'a>(
+        &mut self,
+        outline: &[Point],
+        holes: impl IntoIterator<Item = &'a [Point]>,
+        color_id: Option<u64>,
+    ) {
+        if outline.len() < 3 {
+            return;
+        }
+        let mut points = outline.to_vec();
+        let mut polygon: Vec<u32> = (0..points.len() as u32).collect();
+        if geometry::signed_area(outline) < 0. {
+            polygon.reverse();
+        }
+
+        // holes are bridged from right to left, so earlier bridges can't block later ones
+        let mut holes: Vec<Vec<u32>> = holes
+            .into_iter()
+            .filter(|hole| hole.len() >= 3)
+            .map(|hole| {
+                let start = points.len() as u32;
+                points.extend_from_slice(hole);
+                let mut ring: Vec<u32> = (start..points.len() as u32).collect();
+                // holes go clockwise
+                if geometry::signed_area(hole) > 0. {
+                    ring.reverse();
+                }
+                ring
+            })
+            .collect();
+        let max_x = |ring: &Vec<u32>| {
+            ring.iter()
+                .map(|&i| points[i as usize].0)
+                .fold(f32::MIN, f32::max)
+        };
+        holes.sort_by(|a, b| max_x(b).total_cmp(&max_x(a)));
+        for hole in holes {
+            bridge_hole(&points, &mut polygon, &hole);
+        }
+
+        let base = self.vertices.len() as u32;
+        self.vertices.extend_from_slice(&points);
+        for [a, b, c] in ear_clip(&points, polygon) {
+            self.indices.extend([base + a, base + b, base + c]);
+            self.colors.push(color_id);
+        }
+    }
+}
+
+/// Returns the points of a contour without the repeated first point of closed contours.
+fn ring(contour: &Contour) -> &[Point] {
+    match contour.points.split_last() {
+        Some((_, rest)) if contour.closed => rest,
+        _ => &contour.points,
+    }
+}
+
+/// Tessellates all fill shapes in a layer.
+pub fn tessellate_layer(layer: &LayerData) -> Mesh {
+    let mut mesh = Mesh::default();
+    mesh.add_layer(layer);
+    mesh
+}
+
+/// Tessellates a single fill shape.
+pub fn tessellate_shape(shape: &VectorShape) -> Mesh {
+    let mut mesh = Mesh::default();
+    mesh.add_shape(shape);
+    mesh
+}
+
+fn cross(o: Point, a: Point, b: Point) -> f32 {
+    (a.0 - o.0) * (b.1 - o.1) - (a.1 - o.1) * (b.0 - o.0)
+}
+
+fn in_triangle(p: Point, a: Point, b: Point, c: Point) -> bool {
+    cross(a, b, p) >= 0. && cross(b, c, p) >= 0. && cross(c, a, p) >= 0.
+}
+
+/// Connects a clockwise hole to a counterclockwise polygon that contains it, by going from a
+/// vertex of the polygon to the rightmost vertex of the hole, around the hole, and back. The hole
+/// is left out if no vertex of the polygon is to its right.
+fn bridge_hole(points: &[Point], polygon: &mut Vec<u32>, hole: &[u32]) {
+    let point = |i: u32| points[i as usize];
+    let Some(start) =
+        (0..hole.len()).max_by(|&a, &b| point(hole[a]).0.total_cmp(&point(hole[b]).0))
+    else {
+        return;
+    };
+    let m = point(hole[start]);
+
+    // the closest edge to the right of the hole along a horizontal ray, and the endpoint of that
+    // edge that's furthest to the right
+    let mut closest: Option<(f32, usize)> = None;
+    for k in 0..polygon.len() {
+        let next = (k + 1) % polygon.len();
+        let (a, b) = (point(polygon[k]), point(polygon[next]));
+        if a.1.min(b.1) > m.1 || a.1.max(b.1) < m.1 || a.1 == b.1 {
+            continue;
+        }
+        let x = a.0 + (m.1 - a.1) * (b.0 - a.0) / (b.1 - a.1);
+        if x >= m.0 && closest.is_none_or(|(closest_x, _)| x < closest_x) {
+            closest = Some((x, if a.0 > b.0 { k } else { next }));
+        }
+    }
+    let Some((x, mut target)) = closest else {
+        return;
+    };
+
+    // another vertex in the triangle between the hole, the ray hit and the edge endpoint would
+    // be in the way, so use the one closest in angle to the ray instead
+    let hit = (x, m.1);
+    let p = point(polygon[target]);
+    if p != hit {
+        let angle = |q: Point| (q.1 - m.1).abs() / (q.0 - m.0);
+        let mut best = angle(p);
+        for (k, &i) in polygon.iter().enumerate() {
+            let q = point(i);
+            if q == p || q.0 <= m.0 {
+                continue;
+            }
+            let (a, b, c) = if p.1 < m.1 { (m, p, hit) } else { (m, hit, p) };
+            if in_triangle(q, a, b, c) && angle(q) < best {
+                best = angle(q);
+                target = k;
+            }
+        }
+    }
+
+    let bridge = hole[start..]
+        .iter()
+        .chain(&hole[..=start])
+        .copied()
+        .chain([polygon[target]]);
+    polygon.splice(target + 1..target + 1, bridge.collect::<Vec<_>>());
+}
+
+/// Triangulates a counterclockwise polygon, given as indices into `points`. Returns triangles as
+/// counterclockwise index triples.
+fn ear_clip(points: &[Point], mut remaining: Vec<u32>) -> Vec<[u32; 3]> {
+    let point = |i: u32| points[i as usize];
+
+    let mut triangles = Vec::with_capacity(points.len().saturating_sub(2));
+    let mut i = 0;
+    let mut since_last_ear = 0;
+
+    while remaining.len() > 3 {
+        let n = remaining.len();
+        let prev = remaining[(i + n - 1) % n];
+        let curr = remaining[i % n];
+        let next = remaining[(i + 1) % n];
+        let (a, b, c) = (point(prev), point(curr), point(next));
+
+        let is_convex = cross(a, b, c) > 0.;
+        // vertices at the same position as a corner are the ends of hole bridges
+        let is_ear = is_convex
+            && !remaining.iter().any(|&j| {
+                let p = point(j);
+                p != a && p != b && p != c && in_triangle(p, a, b, c)
+            });
+
+        // if we've gone around once without finding an ear, the polygon is degenerate
+        // (e.g. self-intersecting), so just clip whatever vertex we're at
+        if is_ear || since_last_ear > n {
+            if is_convex {
+                triangles.push([prev, curr, next]);
+            }
+            remaining.remove(i % n);
+            since_last_ear = 0;
+        } else {
+            i += 1;
+            since_last_ear += 1;
+        }
+        i %= remaining.len();
+    }
+
+    if let [a, b, c] = remaining[..] {
+        if cross(point(a), point(b), point(c)) > 0. {
+            triangles.push([a, b, c]);
+        }
+    }
+
+    triangles
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{
+        ComponentInfo, ComponentType, PathSegment, ShapeComponent, ShapeComponentData, ShapePath,
+    };
+
+    /// A fill shape with a closed path for each ring.
+    fn fill(rings: &[Vec<Point>]) -> VectorShape {
+        let info = ComponentInfo {
+            ty: ComponentType::Fill,
+            color_id: Some(3),
+            pencil_value: None,
+            unknown_prefix: None,
+            unknown_suffix: None,
+        };
+        let paths = rings.iter().map(|ring| {
+            ShapeComponentData::Path(ShapePath {
+                segments: ring
+                    .iter()
+                    .chain(ring.first())
+                    .map(|p| PathSegment::Line(*p))
+                    .collect(),
+                extra: None,
+            })
+        });
+        VectorShape {
+            ty: ShapeType::Fill,
+            components: vec![ShapeComponent {
+                tags: std::iter::once(ShapeComponentData::Info(info))
+                    .chain(paths)
+                    .collect(),
+                trailer: None,
+            }],
+        }
+    }
+
+    fn square(min: f32, max: f32) -> Vec<Point> {
+        vec![(min, min), (max, min), (max, max), (min, max)]
+    }
+
+    fn triangles(mesh: &Mesh) -> impl Iterator<Item = [Point; 3]> + '_ {
+        mesh.indices
+            .chunks_exact(3)
+            .map(|t| [t[0], t[1], t[2]].map(|i| mesh.vertices[i as usize]))
+    }
+
+    fn area(mesh: &Mesh) -> f32 {
+        triangles(mesh).map(|[a, b, c]| cross(a, b, c) / 2.).sum()
+    }
+
+    /// Whether any triangle covers the point.
+    fn covers(mesh: &Mesh, p: Point) -> bool {
+        triangles(mesh).any(|[a, b, c]| in_triangle(p, a, b, c))
+    }
+
+    #[test]
+    fn clockwise_contour() {
+        let mut ring = square(0., 10.);
+        ring.reverse();
+        let mesh = tessellate_shape(&fill(&[ring]));
+        assert_eq!(mesh.triangle_count(), 2);
+        assert_eq!(area(&mesh), 100.);
+        assert_eq!(mesh.colors, [Some(3), Some(3)]);
+    }
+
+    #[test]
+    fn holes_are_left_out() {
+        let mut hole = square(2., 4.);
+        hole.reverse();
+        let shape = fill(&[square(0., 10.), hole, square(6., 8.)]);
+        let mesh = tessellate_shape(&shape);
+        assert!((area(&mesh) - 92.).abs() < 1e-3);
+        assert!(triangles(&mesh).all(|[a, b, c]| cross(a, b, c) > 0.));
+        assert!(!covers(&mesh, (3., 3.)));
+        assert!(!covers(&mesh, (7., 7.)));
+        assert!(covers(&mesh, (5., 5.)));
+    }
+
+    #[test]
+    fn islands_in_holes_are_filled() {
+        let shape = fill(&[square(0., 10.), square(2., 8.), square(4., 6.)]);
+        let mesh = tessellate_shape(&shape);
+        assert!((area(&mesh) - 68.).abs() < 1e-3);
+        assert!(!covers(&mesh, (3., 3.)));
+        assert!(covers(&mesh, (5., 5.)));
+    }
+
+    #[test]
+    fn empty_closed_contour() {
+        let mut mesh = Mesh::default();
+        mesh.add_contour(&Contour {
+            points: Vec::new(),
+            closed: true,
+            signed_area: 0.,
+            color_id: None,
+        });
+        assert_eq!(mesh.triangle_count(), 0);
+    }
+}
@@ -0,0 +1,192 @@
+//! A lightweight tree of the contents of a document, for tree views. See [Document::outline].
+
+use crate::document::Document;
+#[cfg(feature = "layers")]
+use crate::layer::{ComponentType, LayerData, ShapeComponentData, VectorShape};
+#[cfg(feature = "palette")]
+use crate::palette::{PaletteColor, PaletteData};
+use crate::read::FileData;
+use alloc::format;
+use alloc::string::{String, ToString};
+use alloc::vec::Vec;
+
+/// A node in the outline of a document: a tag, a shape or a palette color.
+///
+/// Nodes only contain short descriptions, not the data itself, so the outline of a large drawing
+/// is still cheap to build and to send elsewhere.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct OutlineNode {
+    /// The kind of node in snake case, e.g. `layer_line`, `shape` or `color`.
+    pub name: String,
+    /// A short description of the contents, e.g. `12 shapes` or `Skin #f2c7a5ff`.
+    pub summary: String,
+    /// How much data the node contains, to compare nodes with each other: the number of points in
+    /// paths for geometry, and the number of bytes for undecoded data. Includes the children.
+    pub size: usize,
+    pub children: Vec<OutlineNode>,
+}
+
+impl OutlineNode {
+    fn new(name: &str, summary: impl Into<String>) -> Self {
+        OutlineNode {
+            name: name.into(),
+            summary: summary.into(),
+            size: 0,
+            children: Vec::new(),
+        }
+    }
+
+    fn with_size(mut self, size: usize) -> Self {
+        self.size = size;
+        self
+    }
+
+    fn with_children(mut self, children: Vec<OutlineNode>) -> Self {
+        self.size += children.iter().map(|child| child.size).sum::<usize>();
+        self.children = children;
+        self
+    }
+
+    /// The number of direct children.
+    pub fn child_count(&self) -> usize {
+        self.children.len()
+    }
+
+    /// Calls `f` with each node and its depth, starting with this one at depth 0, parents before
+    /// children.
+    pub fn walk(&self, f: &mut impl FnMut(&OutlineNode, usize)) {
+        self.walk_at(0, f);
+    }
+
+    fn walk_at(&self, depth: usize, f: &mut impl FnMut(&OutlineNode, usize)) {
+        f(self, depth);
+        for child in &self.children {
+            child.walk_at(depth + 1, f);
+        }
+    }
+}
+
+impl Document {
+    /// Returns an outline of the document: a node for the document, with a node for each tag,
+    /// and nodes for the shapes of art layers and the colors of the palette.
+    pub fn outline(&self) -> OutlineNode {
+        OutlineNode::new("document", plural(self.tags.len(), "tag"))
+            .with_children(self.tags.iter().map(tag_node).collect())
+    }
+}
+
+fn tag_node(tag: &FileData) -> OutlineNode {
+    match tag {
+        FileData::Certificate(certificate) => {
+            OutlineNode::new("certificate", plural(certificate.len(), "byte"))
+                .with_size(certificate.len())
+        }
+        FileData::Signature(signature) => {
+            OutlineNode::new("signature", plural(signature.len(), "byte"))
+                .with_size(signature.len())
+        }
+        FileData::Crea(value) => OutlineNode::new("crea", value.to_string()),
+        FileData::Endt => OutlineNode::new("endt", ""),
+        FileData::Main(tags) => OutlineNode::new("main", plural(tags.len(), "tag"))
+            .with_children(tags.iter().map(tag_node).collect()),
+        FileData::MainOffsets { offsets, .. } => {
+            OutlineNode::new("main_offsets", plural(offsets.len(), "offset"))
+        }
+        FileData::Identity {
+            device,
+            software_name,
+            ..
+        } => OutlineNode::new("identity", format!("{software_name} on {device}")),
+        #[cfg(feature = "layers")]
+        FileData::LayerUnderlay(layer) => layer_node("layer_underlay", layer),
+        #[cfg(feature = "layers")]
+        FileData::LayerColor(layer) => layer_node("layer_color", layer),
+        #[cfg(feature = "layers")]
+        FileData::LayerLine(layer) => layer_node("layer_line", layer),
+        #[cfg(feature = "layers")]
+        FileData::LayerOverlay(layer) => layer_node("layer_overlay", layer),
+        #[cfg(feature = "palette")]
+        FileData::Palette(palette) => palette_node(palette),
+        FileData::Unreadable { tag, data, error } => {
+            let tag = match crate::read::FileTag::try_from(*tag) {
+                Ok(tag) => format!("{tag:?}"),
+                Err(_) => format!("{tag:08x}"),
+            };
+            OutlineNode::new("unreadable", format!("{tag}: {error}")).with_size(data.0.len())
+        }
+    }
+}
+
+#[cfg(feature = "layers")]
+fn layer_node(name: &str, layer: &LayerData) -> OutlineNode {
+    match layer {
+        LayerData::Empty => OutlineNode::new(name, "empty"),
+        LayerData::Vector { shapes, .. } => OutlineNode::new(name, plural(shapes.len(), "shape"))
+            .with_children(shapes.iter().map(shape_node).collect()),
+        LayerData::Unknown { ty, data } => OutlineNode::new(
+            name,
+            format!("unknown type {ty}, {}", plural(data.0.len(), "byte")),
+        )
+        .with_size(data.0.len()),
+    }
+}
+
+/// Describes a shape by its type and components, e.g. `Stroke: pencil (color 3), 24 points`.
+#[cfg(feature = "layers")]
+fn shape_node(shape: &VectorShape) -> OutlineNode {
+    let mut points = 0;
+    let mut components = Vec::new();
+    for component in &shape.components {
+        let mut description = String::from("?");
+        for tag in &component.tags {
+            match tag {
+                ShapeComponentData::Info(info) => {
+                    description = String::from(match info.ty {
+                        ComponentType::Fill => "fill",
+                        ComponentType::Unknown1 => "unknown1",
+                        ComponentType::Stroke => "stroke",
+                        ComponentType::Pencil => "pencil",
+                    });
+                    if let Some(id) = info.color_id {
+                        description += &format!(" (color {id})");
+                    }
+                }
+                ShapeComponentData::Path(path) => points += path.point_count(),
+                _ => (),
+            }
+        }
+        components.push(description);
+    }
+    let summary = format!(
+        "{:?}: {}, {}",
+        shape.ty,
+        components.join(" + "),
+        plural(points, "point")
+    );
+    OutlineNode::new("shape", summary).with_size(points)
+}
+
+#[cfg(feature = "palette")]
+fn palette_node(palette: &PaletteData) -> OutlineNode {
+    OutlineNode::new("palette", plural(palette.colors.len(), "color"))
+        .with_children(palette.colors.iter().map(color_node).collect())
+}
+
+/// Describes a color by its name and RGBA value, e.g. `Skin #f2c7a5ff`.
+#[cfg(feature = "palette")]
+fn color_node(color: &PaletteColor) -> OutlineNode {
+    let mut summary = String::from(color.name().unwrap_or("(unnamed)"));
+    if let Some(rgba) = color.rgba() {
+        summary += &format!(" {}", rgba.to_css_hex());
+    }
+    OutlineNode::new("color", summary)
+}
+
+fn plural(count: usize, noun: &str) -> String {
+    match count {
+        1 => format!("1 {noun}"),
+        _ => format!("{count} {noun}s"),
+    }
+}
@@ -1,12 +1,17 @@
-use crate::read::ReadError;
-use crate::util::read_encoded_data;
-use byteorder::{LE, ReadBytesExt};
+use crate::color::Rgba;
+use crate::read::{check_limit, ReadError, ReadOptions};
+use crate::util::{all_values, read_encoded_data, Bytes};
+use crate::io::{self, Read, ReadBytesExt};
+use alloc::collections::BTreeMap;
+use alloc::{format, string::String, vec, vec::Vec};
+use byteorder::LE;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use std::io;
-use std::io::Read;
+use thiserror::Error;
 
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[repr(u32)]
 pub enum PaletteColorTag {
     /// `TCSC`: contains the color value
@@ -15,55 +20,381 @@ pub enum PaletteColorTag {
     ColorId = 0x54434944,
 }
 
-#[derive(Debug, Clone)]
+all_values!(PaletteColorTag { Tcsc, ColorId });
+
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PaletteData {
     pub colors: Vec<PaletteColor>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct PaletteColor {
     pub tags: Vec<ColorData>,
 }
 
-#[derive(Debug, Clone)]
+#[derive(Debug, Clone, PartialEq)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(tag = "type", content = "content", rename_all = "snake_case"))]
 pub enum ColorData {
-    ColorRgba(u8, u8, u8, u8),
+    ColorRgba(Rgba),
     ColorId {
         id: u64,
         name: String,
         palette: String,
     },
+    /// A tag we don't know how to decode yet, kept as-is so it can be written back out.
+    ///
+    /// Gradient and texture colors presumably store their data in tags like this
+    /// (see `notes/palette-gradients.md`).
+    Unknown {
+        tag: u32,
+        data: Bytes,
+    },
+}
+
+impl PaletteData {
+    /// Returns the color with the given ID.
+    pub fn find(&self, id: u64) -> Option<&PaletteColor> {
+        self.colors.iter().find(|color| color.id() == Some(id))
+    }
+
+    /// Returns all colors that have both an ID and a value, in palette order.
+    pub fn resolved(&self) -> impl Iterator<Item = ResolvedColor> + '_ {
+        self.colors.iter().filter_map(PaletteColor::resolve)
+    }
+
+    /// Adds the colors of another palette that aren't in this one yet.
+    ///
+    /// Colors that have the same ID and are otherwise the same (see [PaletteColor::same_as]) are
+    /// only kept once. What happens to colors with the same ID that are different depends on
+    /// `policy`. Returns the IDs of the other palette that have to be changed in shapes that use
+    /// them, to the IDs their colors have in this palette.
+    ///
+    /// Nothing is changed if this returns an error.
+    pub fn merge(
+        &mut self,
+        other: &PaletteData,
+        policy: ConflictPolicy,
+    ) -> Result<BTreeMap<u64, u64>, ColorConflict> {
+        if policy == ConflictPolicy::Error {
+            for color in &other.colors {
+                let Some(id) = color.id() else { continue };
+                if self
+                    .find(id)
+                    .is_some_and(|existing| !existing.same_as(color))
+                {
+                    return Err(ColorConflict(id));
+                }
+            }
+        }
+
+        let mut remap = BTreeMap::new();
+        for color in &other.colors {
+            let Some(id) = color.id() else {
+                // these can't be used by shapes, so only their values matter
+                if !self.colors.contains(color) {
+                    self.colors.push(color.clone());
+                }
+                continue;
+            };
+            match self.find(id) {
+                None => self.colors.push(color.clone()),
+                Some(existing) if existing.same_as(color) => (),
+                Some(_) if policy != ConflictPolicy::Rename => (),
+                Some(_) => {
+                    // e.g. when merging the same palette twice
+                    let copy = self.colors.iter().find(|c| c.same_as(color));
+                    if let Some(new_id) = copy.and_then(PaletteColor::id) {
+                        remap.insert(id, new_id);
+                        continue;
+                    }
+                    let mut new_id = id.wrapping_add(1);
+                    while self.find(new_id).is_some() || other.find(new_id).is_some() {
+                        new_id = new_id.wrapping_add(1);
+                    }
+                    let mut color = color.clone();
+                    color.set_id(new_id);
+                    self.colors.push(color);
+                    remap.insert(id, new_id);
+                }
+            }
+        }
+        Ok(remap)
+    }
+
+    /// Returns the color with the given ID, for changing it.
+    pub fn find_mut(&mut self, id: u64) -> Option<&mut PaletteColor> {
+        self.colors.iter_mut().find(|color| color.id() == Some(id))
+    }
+
+    /// Returns the position of the color with the given ID.
+    pub fn position(&self, id: u64) -> Option<usize> {
+        self.colors.iter().position(|color| color.id() == Some(id))
+    }
+
+    /// Returns an ID that no color in the palette has, one more than the largest one.
+    pub fn unused_id(&self) -> u64 {
+        let largest = self.colors.iter().filter_map(PaletteColor::id).max();
+        let mut id = largest.map_or(1, |id| id.wrapping_add(1));
+        while self.find(id).is_some() {
+            id = id.wrapping_add(1);
+        }
+        id
+    }
+
+    /// Adds a solid color at the end of the palette. See [PaletteColor::solid].
+    pub fn add_color(
+        &mut self,
+        id: u64,
+        rgba: Rgba,
+        name: impl Into<String>,
+        project: impl Into<String>,
+    ) -> Result<(), PaletteEditError> {
+        if self.find(id).is_some() {
+            return Err(PaletteEditError::DuplicateColor(id));
+        }
+        self.colors
+            .push(PaletteColor::solid(id, rgba, name, project));
+        Ok(())
+    }
+
+    /// Changes the name of a color, and returns the old name.
+    pub fn rename(
+        &mut self,
+        id: u64,
+        new_name: impl Into<String>,
+    ) -> Result<String, PaletteEditError> {
+        let name = self
+            .find_mut(id)
+            .and_then(|color| {
+                color.tags.iter_mut().find_map(|tag| match tag {
+                    ColorData::ColorId { name, .. } => Some(name),
+                    _ => None,
+                })
+            })
+            .ok_or(PaletteEditError::MissingColor(id))?;
+        Ok(core::mem::replace(name, new_name.into()))
+    }
+
+    /// Changes the value of a solid color, and returns the old value.
+    ///
+    /// Colors without a value, like gradients, can't be turned into solid colors, since it's
+    /// unknown what else Harmony expects of those.
+    pub fn set_rgba(&mut self, id: u64, rgba: Rgba) -> Result<Rgba, PaletteEditError> {
+        let color = self
+            .find_mut(id)
+            .ok_or(PaletteEditError::MissingColor(id))?;
+        let value = color
+            .tags
+            .iter_mut()
+            .find_map(|tag| match tag {
+                ColorData::ColorRgba(value) => Some(value),
+                _ => None,
+            })
+            .ok_or(PaletteEditError::NotSolidColor(id))?;
+        Ok(core::mem::replace(value, rgba))
+    }
+
+    /// Removes a color and returns it.
+    ///
+    /// Shapes that use the color aren't changed. To check for those, use
+    /// [DocumentMut::remove_palette_color](crate::edit::DocumentMut::remove_palette_color).
+    pub fn remove(&mut self, id: u64) -> Result<PaletteColor, PaletteEditError> {
+        let index = self
+            .position(id)
+            .ok_or(PaletteEditError::MissingColor(id))?;
+        Ok(self.colors.remove(index))
+    }
+
+    /// Moves a color to another position in the palette, shifting the colors in between.
+    pub fn move_color(&mut self, id: u64, index: usize) -> Result<(), PaletteEditError> {
+        let from = self
+            .position(id)
+            .ok_or(PaletteEditError::MissingColor(id))?;
+        if index >= self.colors.len() {
+            return Err(PaletteEditError::InvalidIndex(index));
+        }
+        let color = self.colors.remove(from);
+        self.colors.insert(index, color);
+        Ok(())
+    }
 }
 
-pub fn read_palette_data<R>(mut input: R) -> Result<PaletteData, ReadError>
+/// Why a palette edit failed. Nothing is changed if an edit fails.
+#[derive(Debug, Error, PartialEq, Eq)]
+pub enum PaletteEditError {
+    #[error("color not in palette: {0:016x}")]
+    MissingColor(u64),
+    #[error("color already in palette: {0:016x}")]
+    DuplicateColor(u64),
+    #[error("not a solid color: {0:016x}")]
+    NotSolidColor(u64),
+    #[error("no position {0} in the palette")]
+    InvalidIndex(usize),
+}
+
+/// What [PaletteData::merge] does with two different colors that have the same ID.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ConflictPolicy {
+    /// Keeps the color that's already in the palette, so shapes that used the other color will
+    /// use it instead.
+    #[default]
+    Keep,
+    /// Adds the other color with a new ID.
+    Rename,
+    /// Returns an error.
+    Error,
+}
+
+/// Two palettes contain different colors with the same ID.
+#[derive(Debug, Error)]
+#[error("palettes contain different colors with the same ID: {0:016x}")]
+pub struct ColorConflict(pub u64);
+
+impl PaletteColor {
+    /// Creates a solid color, with its value (`TCSC`) followed by its ID, name and project
+    /// palette name (`TCID`), like Harmony writes them.
+    pub fn solid(id: u64, rgba: Rgba, name: impl Into<String>, project: impl Into<String>) -> Self {
+        PaletteColor {
+            tags: vec![
+                ColorData::ColorRgba(rgba),
+                ColorData::ColorId {
+                    id,
+                    name: name.into(),
+                    palette: project.into(),
+                },
+            ],
+        }
+    }
+
+    /// Returns the color value, if it's a solid color.
+    pub fn rgba(&self) -> Option<Rgba> {
+        self.tags.iter().find_map(|tag| match *tag {
+            ColorData::ColorRgba(rgba) => Some(rgba),
+            _ => None,
+        })
+    }
+
+    /// Returns the color ID, which shapes use to refer to the color.
+    pub fn id(&self) -> Option<u64> {
+        self.tags.iter().find_map(|tag| match *tag {
+            ColorData::ColorId { id, .. } => Some(id),
+            _ => None,
+        })
+    }
+
+    /// Returns the color name.
+    pub fn name(&self) -> Option<&str> {
+        self.tags.iter().find_map(|tag| match tag {
+            ColorData::ColorId { name, .. } => Some(&name[..]),
+            _ => None,
+        })
+    }
+
+    /// Returns the name of the project palette that the color comes from.
+    pub fn project(&self) -> Option<&str> {
+        self.tags.iter().find_map(|tag| match tag {
+            ColorData::ColorId { palette, .. } => Some(&palette[..]),
+            _ => None,
+        })
+    }
+
+    /// Changes the color ID.
+    fn set_id(&mut self, new_id: u64) {
+        for tag in &mut self.tags {
+            if let ColorData::ColorId { id, .. } = tag {
+                *id = new_id;
+            }
+        }
+    }
+
+    /// Returns true if the colors are the same, except for their IDs.
+    pub fn same_as(&self, other: &PaletteColor) -> bool {
+        self.tags.len() == other.tags.len()
+            && self.tags.iter().zip(&other.tags).all(|(a, b)| match (a, b) {
+                (
+                    ColorData::ColorId { name, palette, .. },
+                    ColorData::ColorId {
+                        name: other_name,
+                        palette: other_palette,
+                        ..
+                    },
+                ) => name == other_name && palette == other_palette,
+                _ => a == b,
+            })
+    }
+
+    /// Returns true if the color has tags that we can't decode, such as gradient or texture data.
+    pub fn has_unknown_data(&self) -> bool {
+        self.tags
+            .iter()
+            .any(|tag| matches!(tag, ColorData::Unknown { .. }))
+    }
+
+    /// Collects the color's data, if it has both an ID and a value.
+    pub fn resolve(&self) -> Option<ResolvedColor> {
+        self.tags.iter().find_map(|tag| match tag {
+            ColorData::ColorId { id, name, palette } => Some(ResolvedColor {
+                id: *id,
+                rgba: self.rgba()?,
+                name: name.clone(),
+                project: palette.clone(),
+            }),
+            _ => None,
+        })
+    }
+}
+
+/// A palette color with all of its data in one place.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ResolvedColor {
+    pub id: u64,
+    pub rgba: Rgba,
+    pub name: String,
+    /// The name of the project palette that the color comes from.
+    pub project: String,
+}
+
+pub fn read_palette_data<R>(mut input: R, options: &ReadOptions) -> Result<PaletteData, ReadError>
 where
     R: Read,
 {
-    let data = read_encoded_data(&mut input)?;
-    let mut input = io::BufReader::new(io::Cursor::new(data));
+    let data = read_encoded_data(&mut input, options)?;
+    let mut input = &data[..];
 
     let color_count = input.read_u32::<LE>()?;
+    check_limit("palette color count", color_count, options.max_color_count)?;
 
     let first_end_tag = input.read_u32::<LE>()?;
     if first_end_tag != 0x79 {
-        return Err(ReadError::UnknownMystery(format!(
-            "expected palette color to start with 0x79, but found {}",
-            first_end_tag
-        )));
+        options.unexpected(|| {
+            format!(
+                "expected palette color to start with 0x79, but found {}",
+                first_end_tag
+            )
+        })?;
     }
 
     let mut colors = Vec::new();
     for _ in 0..color_count {
         let mystery_header = input.read_u16::<LE>()?;
         if mystery_header != 0 {
-            return Err(ReadError::UnknownMystery(format!(
-                "expected palette color header to be 0, but found {}",
-                mystery_header
-            )));
+            options.unexpected(|| {
+                format!(
+                    "expected palette color header to be 0, but found {}",
+                    mystery_header
+                )
+            })?;
         }
 
         let mut tags = Vec::new();
@@ -91,14 +422,14 @@ where
                     let b = input.read_u8()?;
                     let a = input.read_u8()?;
 
-                    tags.push(ColorData::ColorRgba(r, g, b, a));
+                    tags.push(ColorData::ColorRgba(Rgba::new(r, g, b, a)));
                 }
                 Ok(PaletteColorTag::ColorId) => {
                     let len = input.read_u32::<LE>()?;
                     let mut input = (&mut input).take(len as u64);
                     let name_chars = input.read_u32::<LE>()?;
 
-                    let mut name = Vec::with_capacity(name_chars as usize);
+                    let mut name = Vec::new();
                     for _ in 0..name_chars {
                         name.push(input.read_u16::<LE>()?);
                     }
@@ -108,7 +439,7 @@ where
                     let color_id = input.read_u64::<LE>()?;
 
                     let proj_chars = input.read_u32::<LE>()?;
-                    let mut project = Vec::with_capacity(proj_chars as usize);
+                    let mut project = Vec::new();
                     for _ in 0..proj_chars {
                         project.push(input.read_u16::<LE>()?);
                     }
@@ -122,7 +453,17 @@ where
                     });
                 }
                 Err(err) => {
-                    return Err(ReadError::UnknownPaletteTag(err.number));
+                    // assume it's length-prefixed like the other tags
+                    let len = input.read_u32::<LE>()?;
+                    let mut data = Vec::new();
+                    (&mut input).take(len as u64).read_to_end(&mut data)?;
+                    if data.len() != len as usize {
+                        return Err(ReadError::UnknownPaletteTag(err.number));
+                    }
+                    tags.push(ColorData::Unknown {
+                        tag: err.number,
+                        data: Bytes(data),
+                    });
                 }
             }
         }
@@ -132,3 +473,75 @@ where
 
     Ok(PaletteData { colors })
 }
+
+#[cfg(all(test, feature = "std", feature = "layers"))]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+    use crate::testgen::{generate, TestDrawing};
+
+    /// A palette with colors 1 to `colors`. Palettes with more colors start with the same ones.
+    fn palette(colors: usize) -> PaletteData {
+        let file = generate(&TestDrawing {
+            palette_colors: colors,
+            ..Default::default()
+        })
+        .unwrap();
+        let document = Document::read(&file[..]).unwrap();
+        document.palette().unwrap().clone()
+    }
+
+    const CHANGED: Rgba = Rgba::new(1, 2, 3, 255);
+
+    /// Colors 1 to 6, where color 2 is [CHANGED] and different from the one in [palette].
+    fn conflicting() -> PaletteData {
+        let mut other = palette(6);
+        other.set_rgba(2, CHANGED).unwrap();
+        other
+    }
+
+    #[test]
+    fn merge_same_colors() {
+        use ConflictPolicy::*;
+        for policy in [Keep, Rename, Error] {
+            let mut merged = palette(4);
+            let remap = merged.merge(&palette(6), policy).unwrap();
+            assert!(remap.is_empty());
+            assert_eq!(merged, palette(6));
+        }
+    }
+
+    #[test]
+    fn merge_keep() {
+        let mut merged = palette(4);
+        let remap = merged.merge(&conflicting(), ConflictPolicy::Keep).unwrap();
+        assert!(remap.is_empty());
+        assert_eq!(merged, palette(6));
+    }
+
+    #[test]
+    fn merge_rename() {
+        let other = conflicting();
+        let mut merged = palette(4);
+        let remap = merged.merge(&other, ConflictPolicy::Rename).unwrap();
+        // IDs up to 6 are used by one of the palettes
+        assert_eq!(remap, BTreeMap::from([(2, 7)]));
+        assert_eq!(merged.colors.len(), 7);
+        assert_eq!(merged.find(2), palette(4).find(2));
+        assert_eq!(merged.find(7).unwrap().rgba(), Some(CHANGED));
+
+        // the renamed color is reused when merging again
+        let before = merged.clone();
+        let remap = merged.merge(&other, ConflictPolicy::Rename).unwrap();
+        assert_eq!(remap, BTreeMap::from([(2, 7)]));
+        assert_eq!(merged, before);
+    }
+
+    #[test]
+    fn merge_error() {
+        let mut merged = palette(4);
+        let result = merged.merge(&conflicting(), ConflictPolicy::Error);
+        assert!(matches!(result, Err(ColorConflict(2))));
+        assert_eq!(merged, palette(4));
+    }
+}
@@ -0,0 +1,183 @@
+//! Timing of the steps of reading a file, to find out where the time goes (e.g. whether zlib or
+//! decoding paths dominates).
+//!
+//! Tracing is opt-in: put a [ParseTrace] in [ReadOptions::trace], read the file, and then look at
+//! [ParseTrace::events], or write them out with [ParseTrace::write_chrome_json] to open them in
+//! `chrome://tracing` or [Perfetto](https://ui.perfetto.dev).
+//!
+//! [ReadOptions::trace]: crate::read::ReadOptions::trace
+
+use crate::read::FileTag;
+use std::io::{self, Write};
+use std::sync::{Arc, Mutex};
+use std::thread::{self, ThreadId};
+use std::time::{Duration, Instant};
+
+/// Collects timing events while reading.
+///
+/// Clones share the same events, so a clone can be passed to the reader in the options and the
+/// events retrieved from the original afterwards. Several files (even on several threads) can be
+/// traced with the same collector.
+#[derive(Clone)]
+pub struct ParseTrace {
+    inner: Arc<Mutex<TraceInner>>,
+}
+
+#[derive(Debug)]
+struct TraceInner {
+    origin: Instant,
+    threads: Vec<ThreadId>,
+    events: Vec<TraceEvent>,
+}
+
+/// A step of reading.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct TraceEvent {
+    pub kind: TraceEventKind,
+    /// When the step started, relative to when the trace was created.
+    pub start: Duration,
+    pub duration: Duration,
+    /// Number of bytes read: all bytes of a tag, or the compressed data.
+    pub bytes: u64,
+    /// For decompression, the size of the output.
+    pub decoded_bytes: Option<u64>,
+    /// The thread the step ran on, numbered in the order the threads first recorded an event.
+    pub thread: usize,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TraceEventKind {
+    /// Reading and decoding a tag, including any decompression and the tags inside it. The tag
+    /// number may be unknown if reading it failed.
+    Tag(u32),
+    /// Decompressing a `ZLIB` block.
+    Decompress,
+}
+
+impl TraceEventKind {
+    /// A name for display, e.g. `LayerLine` or `zlib`.
+    pub fn name(&self) -> String {
+        match *self {
+            TraceEventKind::Tag(number) => match FileTag::try_from(number) {
+                Ok(tag) => format!("{tag:?}"),
+                Err(_) => format!("{number:08x}"),
+            },
+            TraceEventKind::Decompress => "zlib".into(),
+        }
+    }
+}
+
+impl ParseTrace {
+    pub fn new() -> Self {
+        ParseTrace {
+            inner: Arc::new(Mutex::new(TraceInner {
+                origin: Instant::now(),
+                threads: Vec::new(),
+                events: Vec::new(),
+            })),
+        }
+    }
+
+    /// Returns the events recorded so far, in the order they ended. Tags inside the main data end
+    /// before the main data tag, and decompression ends before the tag it's in.
+    pub fn events(&self) -> Vec<TraceEvent> {
+        self.lock().events.clone()
+    }
+
+    /// Total time spent decompressing.
+    pub fn decompression_time(&self) -> Duration {
+        let inner = self.lock();
+        let decompress = inner
+            .events
+            .iter()
+            .filter(|event| event.kind == TraceEventKind::Decompress);
+        decompress.map(|event| event.duration).sum()
+    }
+
+    /// Removes all events.
+    pub fn clear(&self) {
+        self.lock().events.clear();
+    }
+
+    /// Writes the events in the Chrome trace event format, as complete (`X`) events with the
+    /// byte counts as arguments.
+    pub fn write_chrome_json(&self, mut out: impl Write) -> io::Result<()> {
+        let micros = |duration: Duration| duration.as_secs_f64() * 1e6;
+        let inner = self.lock();
+        write!(out, "{{\"traceEvents\":[")?;
+        for (i, event) in inner.events.iter().enumerate() {
+            let category = match event.kind {
+                TraceEventKind::Tag(_) => "tag",
+                TraceEventKind::Decompress => "decompress",
+            };
+            if i > 0 {
+                write!(out, ",")?;
+            }
+            write!(
+                out,
+                "\n{{\"name\":\"{}\",\"cat\":\"{}\",\"ph\":\"X\",\"ts\":{:.3},\"dur\":{:.3},\
+                 \"pid\":0,\"tid\":{},\"args\":{{\"bytes\":{}",
+                event.kind.name(),
+                category,
+                micros(event.start),
+                micros(event.duration),
+                event.thread,
+                event.bytes,
+            )?;
+            if let Some(decoded) = event.decoded_bytes {
+                write!(out, ",\"decoded_bytes\":{decoded}")?;
+            }
+            write!(out, "}}}}")?;
+        }
+        writeln!(out, "\n],\"displayTimeUnit\":\"ms\"}}")
+    }
+
+    /// Records a step that started at `start` and ends now.
+    pub(crate) fn record(
+        &self,
+        kind: TraceEventKind,
+        start: Instant,
+        bytes: u64,
+        decoded_bytes: Option<u64>,
+    ) {
+        let end = Instant::now();
+        let current = thread::current().id();
+        let mut inner = self.lock();
+        let thread = match inner.threads.iter().position(|id| *id == current) {
+            Some(thread) => thread,
+            None => {
+                inner.threads.push(current);
+                inner.threads.len() - 1
+            }
+        };
+        let event = TraceEvent {
+            kind,
+            start: start.saturating_duration_since(inner.origin),
+            duration: end - start,
+            bytes,
+            decoded_bytes,
+            thread,
+        };
+        inner.events.push(event);
+    }
+
+    fn lock(&self) -> std::sync::MutexGuard<'_, TraceInner> {
+        // the events are still valid if another thread panicked while recording
+        self.inner.lock().unwrap_or_else(|e| e.into_inner())
+    }
+}
+
+impl core::fmt::Debug for ParseTrace {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        let events = self.lock().events.len();
+        f.debug_struct("ParseTrace")
+            .field("events", &events)
+            .finish()
+    }
+}
+
+impl Default for ParseTrace {
+    fn default() -> Self {
+        Self::new()
+    }
+}
@@ -0,0 +1,240 @@
+//! Replaces the contents of one tag in a TVG file, copying the other tags' blocks as they are.
+//!
+//! Like [anonymize](crate::anonymize), this works on the raw bytes. The new contents are encoded
+//! the same way (`UNCO` or `ZLIB`) as the old ones, and the other top-level tags are copied as
+//! they are. Most tags are inside the main data tag, though, so if the main data is
+//! zlib-compressed, the whole main data has to be compressed again (see
+//! [Patched::main_data_recompressed]). The blocks of the other tags inside it are still copied as
+//! they are.
+//!
+//! The offsets in `TTOC` are left as they are, since it isn't known what they point at (see
+//! `notes/writer.md`), so they may not match the patched file (see [Patched::ttoc_stale]).
+
+use crate::read::{EncodingTag, FileTag, ReadError, ReadOptions, TagCursor};
+use crate::read_incremental::{tag_len, HEADER_LEN};
+use crate::util::{read_encoded_data, write_encoded_data};
+use byteorder::{ByteOrder, BE};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum PatchError {
+    #[error(transparent)]
+    Read(#[from] ReadError),
+    #[error("tag not found: {0:?}")]
+    NotFound(FileTag),
+    #[error("tag doesn't contain an encoded block: {0:?}")]
+    NotEncoded(FileTag),
+}
+
+/// A file with a patched tag.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Patched {
+    pub data: Vec<u8>,
+    /// Whether the tag is in zlib-compressed main data, which had to be compressed again.
+    pub main_data_recompressed: bool,
+    /// Whether the file has a `TTOC` tag and the patched tag (or the main data containing it)
+    /// changed length, so that the offsets in `TTOC` may no longer match the tags they did.
+    pub ttoc_stale: bool,
+}
+
+/// What changed while patching.
+#[derive(Default)]
+struct Changes {
+    main_data_recompressed: bool,
+    resized: bool,
+}
+
+/// Replaces the decoded contents of the first tag of a kind, e.g. the palette data after an edit.
+pub fn patch_tag(input: &[u8], tag: FileTag, contents: &[u8]) -> Result<Patched, PatchError> {
+    match tag {
+        FileTag::Cert | FileTag::Endt | FileTag::Ttoc | FileTag::Sign | FileTag::MainData => {
+            return Err(PatchError::NotEncoded(tag));
+        }
+        _ => (),
+    }
+
+    let options = ReadOptions::default();
+    crate::read::read_header(input.get(..HEADER_LEN).unwrap_or(input), &options)?;
+
+    let mut out = Vec::with_capacity(input.len());
+    out.extend_from_slice(&input[..HEADER_LEN]);
+    let mut changes = Changes::default();
+    if !patch_tags(
+        &input[HEADER_LEN..],
+        tag,
+        contents,
+        &options,
+        &mut out,
+        &mut changes,
+    )? {
+        return Err(PatchError::NotFound(tag));
+    }
+    Ok(Patched {
+        data: out,
+        main_data_recompressed: changes.main_data_recompressed,
+        ttoc_stale: changes.resized && has_ttoc(input),
+    })
+}
+
+/// Copies a sequence of tags, replacing the first one of a kind. Returns whether it was found;
+/// if it wasn't, `out` contains a copy of `input`.
+fn patch_tags(
+    input: &[u8],
+    tag: FileTag,
+    contents: &[u8],
+    options: &ReadOptions,
+    out: &mut Vec<u8>,
+    changes: &mut Changes,
+) -> Result<bool, ReadError> {
+    let mut pos = 0;
+    let mut found = false;
+    while pos < input.len() && !found {
+        let rest = &input[pos..];
+        let tag_data = match tag_len(rest, options)? {
+            Some(len) if len <= rest.len() => &rest[..len],
+            _ => break,
+        };
+        pos += tag_data.len();
+
+        let (tag_number, block) = tag_data.split_at(4);
+        let this_tag = FileTag::try_from(BE::read_u32(tag_number)).ok();
+        let start = out.len();
+        if this_tag == Some(tag) {
+            out.extend_from_slice(tag_number);
+            write_encoded_data(block_encoding(block)?, contents, out)?;
+            found = true;
+        } else if this_tag == Some(FileTag::MainData) {
+            let main = read_encoded_data(block, options)?;
+            let mut patched = Vec::with_capacity(main.len());
+            if patch_tags(&main, tag, contents, options, &mut patched, changes)? {
+                let encoding = block_encoding(block)?;
+                out.extend_from_slice(tag_number);
+                write_encoded_data(encoding, &patched, out)?;
+                changes.main_data_recompressed = encoding == EncodingTag::Zlib;
+                found = true;
+            } else {
+                out.extend_from_slice(tag_data);
+            }
+        } else {
+            out.extend_from_slice(tag_data);
+        }
+        changes.resized |= out.len() - start != tag_data.len();
+    }
+    // the rest, including any trailing 0 byte or data that couldn't be framed
+    out.extend_from_slice(&input[pos..]);
+    Ok(found)
+}
+
+/// Returns true if the file has a `TTOC` tag at the top level, where it's always been seen.
+fn has_ttoc(file: &[u8]) -> bool {
+    let Ok(mut cursor) = TagCursor::new(file) else {
+        return false;
+    };
+    while !cursor.is_empty() {
+        if cursor.file_tag() == Some(FileTag::Ttoc) {
+            return true;
+        }
+        if cursor.advance().is_err() {
+            break;
+        }
+    }
+    false
+}
+
+fn block_encoding(block: &[u8]) -> Result<EncodingTag, ReadError> {
+    EncodingTag::try_from(BE::read_u32(block)).map_err(|e| ReadError::UnknownEncoding(e.number))
+}
+
+#[cfg(all(test, feature = "layers"))]
+mod tests {
+    use super::*;
+    use crate::document::Art;
+    use crate::testgen::{generate, TestDrawing, TestLayer};
+
+    fn generated(encoding: EncodingTag, palette_colors: usize, metadata: bool) -> Vec<u8> {
+        generate(&TestDrawing {
+            encoding,
+            palette_colors,
+            layers: vec![TestLayer::new(Art::Line, 3, 10)],
+            metadata,
+            ..Default::default()
+        })
+        .unwrap()
+    }
+
+    /// The decoded contents of the first tag of a kind in a sequence of tags, looking inside the
+    /// main data.
+    fn contents(tags: &[u8], tag: FileTag) -> Option<Vec<u8>> {
+        let options = ReadOptions::default();
+        let mut rest = tags;
+        while let Some(len) = tag_len(rest, &options).unwrap() {
+            let (tag_data, next) = rest.split_at(len);
+            let (tag_number, block) = tag_data.split_at(4);
+            let this_tag = FileTag::try_from(BE::read_u32(tag_number)).ok();
+            if this_tag == Some(tag) {
+                return Some(read_encoded_data(block, &options).unwrap());
+            } else if this_tag == Some(FileTag::MainData) {
+                let main = read_encoded_data(block, &options).unwrap();
+                if let Some(found) = contents(&main, tag) {
+                    return Some(found);
+                }
+            }
+            rest = next;
+        }
+        None
+    }
+
+    #[test]
+    fn same_contents_same_file() {
+        let mut encodings = vec![EncodingTag::Unco];
+        if crate::zlib::BACKEND.is_some() {
+            encodings.push(EncodingTag::Zlib);
+        }
+        for encoding in encodings {
+            let file = generated(encoding, 4, true);
+            for tag in [FileTag::Tvci, FileTag::Palette, FileTag::LayerLine] {
+                let old = contents(&file[HEADER_LEN..], tag).unwrap();
+                let patched = patch_tag(&file, tag, &old).unwrap();
+                assert_eq!(patched.data, file, "{tag:?} in {encoding:?}");
+                let zlib = encoding == EncodingTag::Zlib;
+                assert_eq!(patched.main_data_recompressed, zlib);
+                assert!(!patched.ttoc_stale);
+            }
+        }
+    }
+
+    #[test]
+    fn resized_tag_makes_ttoc_stale() {
+        let larger = generated(EncodingTag::Unco, 5, true);
+        let palette = contents(&larger[HEADER_LEN..], FileTag::Palette).unwrap();
+
+        let file = generated(EncodingTag::Unco, 4, true);
+        let patched = patch_tag(&file, FileTag::Palette, &palette).unwrap();
+        assert!(patched.ttoc_stale);
+        crate::read::read(&patched.data[..]).unwrap();
+        let patched_palette = contents(&patched.data[HEADER_LEN..], FileTag::Palette);
+        assert_eq!(patched_palette, Some(palette.clone()));
+
+        // without a table of contents, nothing is stale
+        let file = generated(EncodingTag::Unco, 4, false);
+        let patched = patch_tag(&file, FileTag::Palette, &palette).unwrap();
+        assert!(!patched.ttoc_stale);
+    }
+
+    #[test]
+    fn missing_tag() {
+        let file = generated(EncodingTag::Unco, 4, false);
+        let result = patch_tag(&file, FileTag::Tvci, b"");
+        assert!(matches!(result, Err(PatchError::NotFound(FileTag::Tvci))));
+    }
+
+    #[test]
+    fn tag_without_encoded_block() {
+        use FileTag::*;
+        let file = generated(EncodingTag::Unco, 4, true);
+        for tag in [Cert, Ttoc, Sign, MainData] {
+            let result = patch_tag(&file, tag, b"");
+            assert!(matches!(result, Err(PatchError::NotEncoded(t)) if t == tag));
+        }
+    }
+}
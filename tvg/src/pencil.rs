@@ -1,20 +1,32 @@
+//! Pencil stroke thickness (`tGTB`).
+
 use crate::layer::Point;
-use crate::read::ReadError;
+#[cfg(feature = "std")]
+use crate::layer::ShapePath;
+use crate::read::{check_limit, ReadError, ReadOptions};
 use crate::util::Bytes;
-use byteorder::{ReadBytesExt, LE};
-use std::io::Read;
+use crate::io::{Read, ReadBytesExt};
+use alloc::{format, vec::Vec};
+use byteorder::LE;
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct StrokeThickness {
     /// Optional definition of a new thickness path.
     pub definition: Option<Vec<StrokeThicknessPoint>>,
     /// The domain of the thickness path that we're using for the current shape.
     pub domain: (f32, f32),
+    /// The 4 bytes after the definition type (some kind of ID?), if
+    /// [ReadOptions::preserve_unknown].
+    pub unknown: Option<Bytes>,
 }
 
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct StrokeThicknessPoint {
     /// The location on the entire curve, from 0 to 1.
     pub loc: f32,
@@ -38,6 +50,8 @@ pub struct StrokeThicknessPoint {
 /// coordinate goes in the direction of the end cap.
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 pub struct StrokeThicknessSide {
     /// The offset from the center line.
     pub offset: f32,
@@ -50,30 +64,32 @@ pub struct StrokeThicknessSide {
 }
 
 /// The `tGTB` tag ends with information about the domain for the current shape component.
-fn read_tgtb_domain(input: &mut impl Read) -> Result<(f32, f32), ReadError> {
+fn read_tgtb_domain(
+    input: &mut impl Read,
+    options: &ReadOptions,
+) -> Result<(f32, f32), ReadError> {
     let domain_start = input.read_f32::<LE>()?;
 
     let unknown = input.read_u64::<LE>()?;
     if unknown != 0 {
-        return Err(ReadError::UnknownMystery(format!(
-            "unexpected tGTB bytes after domain start: {unknown:16x}",
-        )));
+        options.unexpected(|| format!("unexpected tGTB bytes after domain start: {unknown:16x}"))?;
     }
 
     let domain_end = input.read_f32::<LE>()?;
 
     let unknown = input.read_u64::<LE>()?;
     if unknown != 0 {
-        return Err(ReadError::UnknownMystery(format!(
-            "unexpected tGTB bytes after domain end: {unknown:16x}",
-        )));
+        options.unexpected(|| format!("unexpected tGTB bytes after domain end: {unknown:16x}"))?;
     }
 
     Ok((domain_start, domain_end))
 }
 
 /// Reads pencil thickness data in a `tGTB` tag.
-pub fn read_tgtb(input: &mut impl Read) -> Result<StrokeThickness, ReadError> {
+pub(crate) fn read_tgtb(
+    input: &mut impl Read,
+    options: &ReadOptions,
+) -> Result<StrokeThickness, ReadError> {
     let len = input.read_u32::<LE>()?;
 
     let mut input = input.take(len as u64);
@@ -81,7 +97,8 @@ pub fn read_tgtb(input: &mut impl Read) -> Result<StrokeThickness, ReadError> {
     let defn_type = input.read_u8()?;
 
     // probably??
-    let _some_kind_of_id = input.read_u32::<LE>()?;
+    let mut some_kind_of_id = [0; 4];
+    input.read_exact(&mut some_kind_of_id)?;
 
     let definition = match defn_type {
         0x00 => {
@@ -93,13 +110,16 @@ pub fn read_tgtb(input: &mut impl Read) -> Result<StrokeThickness, ReadError> {
 
             let cf = input.read_u16::<LE>()?;
             if cf != 0xCF {
-                return Err(ReadError::UnknownMystery(format!(
-                    "unexpected mystery value in tGTB definition: {:02x?} (expected CF)",
-                    cf,
-                )));
+                options.unexpected(|| {
+                    format!(
+                        "unexpected mystery value in tGTB definition: {:02x?} (expected CF)",
+                        cf,
+                    )
+                })?;
             }
 
             let point_count = input.read_u32::<LE>()?;
+            check_limit("thickness point count", point_count, options.max_point_count)?;
             let mut points = Vec::with_capacity(point_count as usize);
 
             for _ in 0..point_count {
@@ -134,10 +154,12 @@ pub fn read_tgtb(input: &mut impl Read) -> Result<StrokeThickness, ReadError> {
             let mut trailer_read = [0; 5];
             input.read_exact(&mut trailer_read)?;
             if trailer != trailer_read {
-                return Err(ReadError::UnknownMystery(format!(
-                    "unexpected tGTB definition trailer: {:?}",
-                    Bytes(trailer_read.into()),
-                )));
+                options.unexpected(|| {
+                    format!(
+                        "unexpected tGTB definition trailer: {:?}",
+                        Bytes(trailer_read.into()),
+                    )
+                })?;
             }
 
             Some(points)
@@ -150,7 +172,156 @@ pub fn read_tgtb(input: &mut impl Read) -> Result<StrokeThickness, ReadError> {
         }
     };
 
-    let domain = read_tgtb_domain(&mut input)?;
+    let domain = read_tgtb_domain(&mut input, options)?;
+
+    Ok(StrokeThickness {
+        definition,
+        domain,
+        unknown: options.preserve_unknown().then(|| Bytes(some_kind_of_id.into())),
+    })
+}
+
+/// Returns the offset of one side of a stroke at a location on the thickness path, following the
+/// Bézier curves between thickness points. Locations outside the points get the offset of the
+/// nearest one.
+#[cfg(feature = "std")]
+pub(crate) fn offset_at(points: &[StrokeThicknessPoint], loc: f32, left: bool) -> f32 {
+    let side = |point| side(point, left);
+    let Some(next) = points.iter().position(|point| point.loc >= loc) else {
+        return points.last().map_or(0., |point| side(point).offset);
+    };
+    if next == 0 {
+        return side(&points[0]).offset;
+    }
+    let (a, b) = (&points[next - 1], &points[next]);
+    let (side_a, side_b) = (side(a), side(b));
+    let span = b.loc - a.loc;
+    let xs = [
+        a.loc,
+        a.loc + side_a.ctrl_fwd.0 * span,
+        b.loc - side_b.ctrl_back.0 * span,
+        b.loc,
+    ];
+    let ys = [
+        side_a.offset,
+        side_a.ctrl_fwd.1,
+        side_b.ctrl_back.1,
+        side_b.offset,
+    ];
+
+    // the control points are between the thickness points, so x increases along the curve
+    let (mut low, mut high) = (0., 1.);
+    for _ in 0..24 {
+        let mid = (low + high) / 2.;
+        if cubic(xs, mid) < loc {
+            low = mid;
+        } else {
+            high = mid;
+        }
+    }
+    cubic(ys, (low + high) / 2.)
+}
+
+#[cfg(feature = "std")]
+fn side(point: &StrokeThicknessPoint, left: bool) -> &StrokeThicknessSide {
+    match left {
+        true => &point.left,
+        false => &point.right,
+    }
+}
+
+/// Evaluates a one-dimensional cubic Bézier curve.
+#[cfg(feature = "std")]
+fn cubic([a, b, c, d]: [f32; 4], t: f32) -> f32 {
+    let mt = 1. - t;
+    mt * mt * mt * a + 3. * mt * mt * t * b + 3. * mt * t * t * c + t * t * t * d
+}
+
+/// Samples the thickness of a pencil stroke at `n` points evenly spaced along its path, including
+/// both ends. Returns each point with the width of the stroke to its left and to its right (in the
+/// drawing direction).
+///
+/// The thickness [domain](StrokeThickness::domain) is mapped onto the path by arc length. If the
+/// thickness has no definition of its own, it uses one from an earlier `tGTB` in the shape; use
+/// [sample_thickness_with] for that.
+#[cfg(feature = "std")]
+pub fn sample_thickness(
+    path: &ShapePath,
+    thickness: &StrokeThickness,
+    n: usize,
+) -> Vec<(Point, f32, f32)> {
+    match &thickness.definition {
+        Some(definition) => sample_thickness_with(path, definition, thickness.domain, n),
+        None => Vec::new(),
+    }
+}
+
+/// Like [sample_thickness], but with a definition from elsewhere.
+#[cfg(feature = "std")]
+pub fn sample_thickness_with(
+    path: &ShapePath,
+    definition: &[StrokeThicknessPoint],
+    (start, end): (f32, f32),
+    n: usize,
+) -> Vec<(Point, f32, f32)> {
+    let cumulative = path.cumulative_lengths();
+    let length = cumulative.last().copied().unwrap_or(0.);
+    (0..n)
+        .filter_map(|i| {
+            let fraction = match n {
+                1 => 0.,
+                _ => i as f32 / (n - 1) as f32,
+            };
+            let param = path.param_at_length_in(&cumulative, fraction * length);
+            let point = path.point_at_param(param)?;
+            let loc = start + (end - start) * fraction;
+            Some((
+                point,
+                offset_at(definition, loc, true),
+                offset_at(definition, loc, false),
+            ))
+        })
+        .collect()
+}
 
-    Ok(StrokeThickness { definition, domain })
+/// Returns the outline of a pencil stroke as a polygon: the left side from the start of the path
+/// to its end, then the right side back to the start. The thickness is sampled at `n` points (see
+/// [sample_thickness_with]), and the ends are cut off straight.
+///
+/// Returns nothing if the path has fewer than two points or `n` is less than 2.
+#[cfg(feature = "std")]
+pub fn outline(
+    path: &ShapePath,
+    definition: &[StrokeThicknessPoint],
+    domain: (f32, f32),
+    n: usize,
+) -> Vec<Point> {
+    let samples = sample_thickness_with(path, definition, domain, n);
+    if samples.len() < 2 || path.segments.len() < 2 {
+        return Vec::new();
+    }
+    // the left normal of the direction between the neighboring samples (Y is up)
+    let last = samples.len() - 1;
+    let normals: Vec<Point> = (0..samples.len())
+        .map(|i| {
+            let (a, b) = (samples[i.saturating_sub(1)].0, samples[(i + 1).min(last)].0);
+            let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+            let length = dx.hypot(dy);
+            match length > 0. {
+                true => (-dy / length, dx / length),
+                false => (0., 0.),
+            }
+        })
+        .collect();
+    let offset = |(x, y): Point, (nx, ny): Point, d: f32| (x + nx * d, y + ny * d);
+    let left = samples
+        .iter()
+        .zip(&normals)
+        .map(|(&(point, left, _), &normal)| offset(point, normal, left));
+    let right = samples
+        .iter()
+        .zip(&normals)
+        .rev()
+        .map(|(&(point, _, right), &normal)| offset(point, normal, -right));
+    left.chain(right).collect()
 }
@@ -0,0 +1,24 @@
+//! Commonly used types, for glob importing with `use tvg::prelude::*`.
+//!
+//! The prelude is versioned. Items are never removed from or renamed in an existing version, so
+//! importing `tvg::prelude::v1::*` keeps compiling even if a later version changes. `tvg::prelude`
+//! itself always re-exports the latest version.
+
+/// The first version of the prelude.
+pub mod v1 {
+    pub use crate::color::Rgba;
+    pub use crate::document::{Art, Document};
+    pub use crate::read::{FileData, ReadError, ReadOptions};
+
+    #[cfg(all(feature = "std", feature = "layers"))]
+    pub use crate::geometry::Bounds;
+    #[cfg(feature = "layers")]
+    pub use crate::layer::{
+        ComponentType, LayerData, PathSegment, Point, ShapeComponent, ShapeComponentData,
+        ShapePath, ShapeType, VectorShape,
+    };
+    #[cfg(feature = "palette")]
+    pub use crate::palette::{ColorData, PaletteColor, PaletteData};
+}
+
+pub use v1::*;
@@ -0,0 +1,421 @@
+//! Indented, human-readable trees of file data, e.g. for dumping files.
+//!
+//! [FileData], [LayerData], [VectorShape] and [PaletteData] implement [Display] with the default
+//! options. To change the options, use [PrettyPrint::pretty]:
+//!
+//! ```
+//! # fn example(data: &[tvg::read::FileData]) {
+//! use tvg::pretty::PrettyPrint;
+//!
+//! for tag in data {
+//!     print!("{}", tag.pretty().max_points(8));
+//! }
+//! # }
+//! ```
+
+#[cfg(feature = "layers")]
+use crate::document::Art;
+#[cfg(feature = "layers")]
+use crate::layer::{
+    ComponentInfo, LayerData, LayerTrailer, PathSegment, ShapeComponent, ShapeComponentData,
+    ShapePath, VectorShape,
+};
+#[cfg(feature = "palette")]
+use crate::palette::{ColorData, PaletteData};
+#[cfg(feature = "pencil")]
+use crate::pencil::{StrokeThickness, StrokeThicknessSide};
+use crate::read::FileData;
+use core::fmt::{self, Display, Formatter};
+
+/// Options for [Pretty].
+#[derive(Debug, Clone, Copy, Default)]
+pub struct PrettyOptions {
+    /// The most path segments and thickness points that are shown for each path. The rest are
+    /// only counted. All of them are shown if this is None.
+    pub max_points: Option<usize>,
+}
+
+/// Formats a value as an indented tree. See [PrettyPrint::pretty].
+pub struct Pretty<'a, T: ?Sized> {
+    value: &'a T,
+    options: PrettyOptions,
+}
+
+impl<T: ?Sized> Pretty<'_, T> {
+    /// Shows at most `max` path segments and thickness points for each path.
+    pub fn max_points(mut self, max: usize) -> Self {
+        self.options.max_points = Some(max);
+        self
+    }
+
+    pub fn options(mut self, options: PrettyOptions) -> Self {
+        self.options = options;
+        self
+    }
+}
+
+impl<T: PrettyPrint + ?Sized> Display for Pretty<'_, T> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.value.write_tree(&mut TreeWriter {
+            f,
+            depth: 0,
+            options: self.options,
+        })
+    }
+}
+
+/// Values that can be formatted as an indented tree.
+pub trait PrettyPrint {
+    /// Writes the value as one or more lines.
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result;
+
+    /// Returns a [Display] wrapper that formats the value as an indented tree.
+    fn pretty(&self) -> Pretty<'_, Self> {
+        Pretty {
+            value: self,
+            options: PrettyOptions::default(),
+        }
+    }
+}
+
+/// Writes indented lines. See [PrettyPrint::write_tree].
+pub struct TreeWriter<'a, 'b> {
+    f: &'a mut Formatter<'b>,
+    depth: usize,
+    options: PrettyOptions,
+}
+
+impl TreeWriter<'_, '_> {
+    pub fn options(&self) -> PrettyOptions {
+        self.options
+    }
+
+    /// Writes a line at the current depth.
+    pub fn line(&mut self, args: fmt::Arguments) -> fmt::Result {
+        writeln!(self.f, "{:indent$}{args}", "", indent = self.depth * 2)
+    }
+
+    /// Writes lines one level deeper.
+    pub fn nested(&mut self, inner: impl FnOnce(&mut Self) -> fmt::Result) -> fmt::Result {
+        self.depth += 1;
+        let result = inner(self);
+        self.depth -= 1;
+        result
+    }
+
+    /// Writes the items that [PrettyOptions::max_points] allows, and counts the rest.
+    #[cfg(feature = "layers")]
+    fn points<T>(
+        &mut self,
+        items: &[T],
+        mut write: impl FnMut(&mut Self, &T) -> fmt::Result,
+    ) -> fmt::Result {
+        let shown = self
+            .options
+            .max_points
+            .unwrap_or(items.len())
+            .min(items.len());
+        for item in &items[..shown] {
+            write(self, item)?;
+        }
+        if shown < items.len() {
+            self.line(format_args!("… {} more", items.len() - shown))?;
+        }
+        Ok(())
+    }
+}
+
+/// Formats bytes as space-separated hexadecimal digits.
+struct Hex<'a>(&'a [u8]);
+
+impl Display for Hex<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        for (i, byte) in self.0.iter().enumerate() {
+            if i > 0 {
+                write!(f, " ")?;
+            }
+            write!(f, "{byte:02x}")?;
+        }
+        Ok(())
+    }
+}
+
+impl PrettyPrint for FileData {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        match self {
+            FileData::Certificate(text) => {
+                w.line(format_args!("certificate: {} bytes", text.len()))
+            }
+            FileData::Signature(data) => w.line(format_args!("signature: {}", Hex(data))),
+            FileData::Crea(value) => w.line(format_args!("crea: {value}")),
+            FileData::Endt => w.line(format_args!("end")),
+            FileData::Main(tags) => {
+                w.line(format_args!("main"))?;
+                w.nested(|w| tags.iter().try_for_each(|tag| tag.write_tree(w)))
+            }
+            FileData::MainOffsets { offsets, unknown } => {
+                w.line(format_args!("table of contents"))?;
+                w.nested(|w| {
+                    for (tag, offset) in offsets {
+                        w.line(format_args!("{tag:?}: {offset}"))?;
+                    }
+                    if let Some(unknown) = unknown {
+                        w.line(format_args!("unknown: {}", Hex(&unknown.0)))?;
+                    }
+                    Ok(())
+                })
+            }
+            FileData::Identity {
+                device,
+                software_name,
+                unknown,
+                extra,
+            } => {
+                w.line(format_args!("identity"))?;
+                w.nested(|w| {
+                    w.line(format_args!("device: {device:?}"))?;
+                    w.line(format_args!("software: {software_name:?}"))?;
+                    if let Some(unknown) = unknown {
+                        w.line(format_args!("unknown: {}", Hex(&unknown.0)))?;
+                    }
+                    if let Some(extra) = extra {
+                        w.line(format_args!("extra: {}", Hex(&extra.0)))?;
+                    }
+                    Ok(())
+                })
+            }
+            #[cfg(feature = "layers")]
+            FileData::LayerUnderlay(layer) => write_layer(w, Art::Underlay, layer),
+            #[cfg(feature = "layers")]
+            FileData::LayerColor(layer) => write_layer(w, Art::Color, layer),
+            #[cfg(feature = "layers")]
+            FileData::LayerLine(layer) => write_layer(w, Art::Line, layer),
+            #[cfg(feature = "layers")]
+            FileData::LayerOverlay(layer) => write_layer(w, Art::Overlay, layer),
+            #[cfg(feature = "palette")]
+            FileData::Palette(palette) => {
+                w.line(format_args!("palette"))?;
+                w.nested(|w| palette.write_tree(w))
+            }
+            FileData::Unreadable { tag, data, error } => {
+                w.line(format_args!("unreadable tag {tag:08x}: {} bytes", data.0.len()))?;
+                w.nested(|w| w.line(format_args!("error: {error}")))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "layers")]
+fn write_layer(w: &mut TreeWriter, art: Art, layer: &LayerData) -> fmt::Result {
+    w.line(format_args!("{} layer", art.name()))?;
+    w.nested(|w| layer.write_tree(w))
+}
+
+#[cfg(feature = "layers")]
+impl PrettyPrint for LayerData {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        match self {
+            LayerData::Empty => w.line(format_args!("empty")),
+            LayerData::Vector { shapes, trailer } => {
+                w.line(format_args!("shapes: {}", shapes.len()))?;
+                for shape in shapes {
+                    shape.write_tree(w)?;
+                }
+                match trailer {
+                    _ if *trailer == Default::default() => (),
+                    LayerTrailer::Tagged {
+                        marker,
+                        tag,
+                        len,
+                        value,
+                        data,
+                    } => w.line(format_args!(
+                        "trailer: marker {marker}, tag {tag:08x}, length {len}, value {value:?}, \
+                         data {}",
+                        Hex(&data.0),
+                    ))?,
+                    LayerTrailer::Raw(data) => w.line(format_args!("trailer: {}", Hex(&data.0)))?,
+                }
+                Ok(())
+            }
+            LayerData::Unknown { ty, data } => w.line(format_args!(
+                "unknown layer type {ty}: {} bytes",
+                data.0.len()
+            )),
+        }
+    }
+}
+
+#[cfg(feature = "layers")]
+impl PrettyPrint for VectorShape {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        w.line(format_args!("{:?} shape", self.ty))?;
+        w.nested(|w| {
+            self.components
+                .iter()
+                .try_for_each(|component| component.write_tree(w))
+        })
+    }
+}
+
+#[cfg(feature = "layers")]
+impl PrettyPrint for ShapeComponent {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        w.line(format_args!("component"))?;
+        w.nested(|w| {
+            for tag in &self.tags {
+                tag.write_tree(w)?;
+            }
+            if let Some(trailer) = &self.trailer {
+                w.line(format_args!("trailer: {}", Hex(&trailer.0)))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "layers")]
+impl PrettyPrint for ShapeComponentData {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        match self {
+            ShapeComponentData::Info(info) => info.write_tree(w),
+            ShapeComponentData::Path(path) => path.write_tree(w),
+            #[cfg(feature = "pencil")]
+            ShapeComponentData::Thickness(thickness) => thickness.write_tree(w),
+            ShapeComponentData::Tgti(data) => w.line(format_args!("tGTI: {}", Hex(&data.0))),
+            ShapeComponentData::Unknown { tag, data } => {
+                w.line(format_args!("unknown tag {tag:08x}: {}", Hex(&data.0)))
+            }
+        }
+    }
+}
+
+#[cfg(feature = "layers")]
+impl PrettyPrint for ComponentInfo {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        match self.color_id {
+            Some(id) => w.line(format_args!("{:?}, color {id:016x}", self.ty))?,
+            None => w.line(format_args!("{:?}, no color", self.ty))?,
+        }
+        w.nested(|w| {
+            if let Some(value) = self.pencil_value {
+                w.line(format_args!("pencil value: {value}"))?;
+            }
+            if let Some(prefix) = self.unknown_prefix.as_ref().filter(|p| !p.0.is_empty()) {
+                w.line(format_args!("unknown prefix: {}", Hex(&prefix.0)))?;
+            }
+            if let Some(suffix) = self.unknown_suffix.as_ref().filter(|s| !s.0.is_empty()) {
+                w.line(format_args!("unknown suffix: {}", Hex(&suffix.0)))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "layers")]
+impl PrettyPrint for ShapePath {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        w.line(format_args!("path: {} segments", self.segments.len()))?;
+        w.nested(|w| {
+            w.points(&self.segments, |w, segment| match segment {
+                PathSegment::Line((x, y)) => w.line(format_args!("line ({x}, {y})")),
+                PathSegment::Cubic(c1, c2, p) => w.line(format_args!(
+                    "cubic ({}, {}) ({}, {}) ({}, {})",
+                    c1.0, c1.1, c2.0, c2.1, p.0, p.1
+                )),
+            })?;
+            if let Some(extra) = &self.extra {
+                w.line(format_args!("extra: {}", Hex(&extra.0)))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "pencil")]
+impl PrettyPrint for StrokeThickness {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        let (start, end) = self.domain;
+        w.line(format_args!("thickness: domain {start} to {end}"))?;
+        w.nested(|w| {
+            if let Some(definition) = &self.definition {
+                w.points(definition, |w, point| {
+                    w.line(format_args!(
+                        "at {}: left {}, right {}",
+                        point.loc,
+                        Side(&point.left),
+                        Side(&point.right),
+                    ))
+                })?;
+            }
+            if let Some(unknown) = &self.unknown {
+                w.line(format_args!("unknown: {}", Hex(&unknown.0)))?;
+            }
+            Ok(())
+        })
+    }
+}
+
+#[cfg(feature = "pencil")]
+struct Side<'a>(&'a StrokeThicknessSide);
+
+#[cfg(feature = "pencil")]
+impl Display for Side<'_> {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        let StrokeThicknessSide {
+            offset,
+            ctrl_back: (bx, by),
+            ctrl_fwd: (fx, fy),
+        } = self.0;
+        write!(f, "{offset} ({bx}, {by}) ({fx}, {fy})")
+    }
+}
+
+#[cfg(feature = "palette")]
+impl PrettyPrint for PaletteData {
+    fn write_tree(&self, w: &mut TreeWriter) -> fmt::Result {
+        w.line(format_args!("colors: {}", self.colors.len()))?;
+        for color in &self.colors {
+            w.line(format_args!("color"))?;
+            w.nested(|w| {
+                color.tags.iter().try_for_each(|tag| match tag {
+                    ColorData::ColorRgba(rgba) => w.line(format_args!("{}", rgba.to_css_hex())),
+                    ColorData::ColorId { id, name, palette } => {
+                        w.line(format_args!("{id:016x} {name:?} from {palette:?}"))
+                    }
+                    ColorData::Unknown { tag, data } => {
+                        w.line(format_args!("unknown tag {tag:08x}: {}", Hex(&data.0)))
+                    }
+                })
+            })?;
+        }
+        Ok(())
+    }
+}
+
+impl Display for FileData {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.pretty().fmt(f)
+    }
+}
+
+#[cfg(feature = "layers")]
+impl Display for LayerData {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.pretty().fmt(f)
+    }
+}
+
+#[cfg(feature = "layers")]
+impl Display for VectorShape {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.pretty().fmt(f)
+    }
+}
+
+#[cfg(feature = "palette")]
+impl Display for PaletteData {
+    fn fmt(&self, f: &mut Formatter) -> fmt::Result {
+        self.pretty().fmt(f)
+    }
+}
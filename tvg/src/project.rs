@@ -0,0 +1,480 @@
+//! The Harmony scene around a drawing: the palettes in its palette lists, and the entry of its
+//! element in the scene file.
+//!
+//! Drawings are stored in scene folders like this:
+//!
+//! ```text
+//! scene/
+//!   scene.xstage          the scene, with an entry for each element
+//!   PALETTE_LIST          the scene palettes
+//!   palette-library/
+//!     colors.plt
+//!   elements/
+//!     bg/
+//!       PALETTE_LIST      the element palettes, if it has any
+//!       palette-library/
+//!       bg-1.tvg
+//! ```
+//!
+//! Each drawing contains a copy of the palette colors that were available when it was saved (see
+//! [Document::palette]), but Harmony shows the colors in the palettes of the palette lists, which
+//! may have changed since. [Project::apply] updates the palette of a document to match.
+//!
+//! The formats of `PALETTE_LIST` and `.plt` files are inferred from files saved by Harmony. Only
+//! solid colors are read from palettes. Environment and job palettes, which are stored in the
+//! Harmony database rather than the scene folder, aren't loaded.
+
+use crate::color::Rgba;
+use crate::document::Document;
+use crate::palette::{PaletteColor, PaletteData};
+use crate::sequence::parse_file_name;
+use std::collections::BTreeSet;
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// Name of the files that list the palettes of a scene or element.
+pub const PALETTE_LIST: &str = "PALETTE_LIST";
+
+#[derive(Debug, Error)]
+pub enum ProjectError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("not a drawing file name: {0}")]
+    NotADrawing(PathBuf),
+    #[error("not in a Harmony scene folder: {0}")]
+    NotInScene(PathBuf),
+    #[error("{path}:{line}: {message}")]
+    Syntax {
+        path: PathBuf,
+        line: usize,
+        message: String,
+    },
+    #[error("invalid XML in {0}: {1}")]
+    Xml(PathBuf, roxmltree::Error),
+}
+
+/// An error in the contents of a palette file.
+#[derive(Debug, Error)]
+#[error("line {line}: {message}")]
+pub struct SyntaxError {
+    /// The line number, starting at 1.
+    pub line: usize,
+    pub message: String,
+}
+
+/// Which palette list a palette is in.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PaletteScope {
+    Element,
+    Scene,
+}
+
+/// A palette from a palette list.
+#[derive(Debug, Clone)]
+pub struct ProjectPalette {
+    /// The file name without `.plt`, which drawings store as the project palette name of each
+    /// color (see [PaletteColor::project]).
+    pub name: String,
+    pub path: PathBuf,
+    pub scope: PaletteScope,
+    pub palette: PaletteData,
+}
+
+/// The entry of an element in the scene file.
+#[derive(Debug, Clone, PartialEq)]
+pub struct ElementInfo {
+    pub id: Option<String>,
+    pub name: String,
+    /// The name of the element folder in `elements/`.
+    pub folder: Option<String>,
+    /// The size of the element's field chart, e.g. 12.
+    pub field_chart: Option<f32>,
+    /// The drawing names listed for the element, in the scene's order.
+    pub drawings: Vec<String>,
+}
+
+/// The scene around a drawing. See the [module documentation](self).
+#[derive(Debug, Clone)]
+pub struct Project {
+    /// The scene folder.
+    pub scene_dir: PathBuf,
+    /// The scene file (`.xstage`), if there is one.
+    pub scene_file: Option<PathBuf>,
+    /// The element name, from the drawing's file name.
+    pub element_name: String,
+    /// The element's entry in the scene file, if there is one.
+    pub element: Option<ElementInfo>,
+    /// The palettes in the order colors are looked up in: those of the element's palette list,
+    /// then those of the scene's.
+    pub palettes: Vec<ProjectPalette>,
+    /// Palettes that are listed, but whose files don't exist.
+    pub missing_palettes: Vec<PathBuf>,
+}
+
+impl Project {
+    /// Finds the scene folder around a drawing file and loads its palettes and the element's
+    /// entry in the scene file.
+    ///
+    /// The drawing can be in `elements/<element>/` of the scene folder, or anywhere inside a
+    /// folder that contains a scene file. It doesn't have to exist, only its name and location
+    /// are used.
+    pub fn for_drawing(path: impl AsRef<Path>) -> Result<Self, ProjectError> {
+        // relative paths are made absolute, so the folders above the current one are searched too
+        let path = &std::path::absolute(path)?;
+        let not_a_drawing = || ProjectError::NotADrawing(path.to_path_buf());
+        let file_name = path.file_name().and_then(|name| name.to_str());
+        let (element_name, _) = file_name
+            .and_then(parse_file_name)
+            .ok_or_else(not_a_drawing)?;
+        let element_dir = path.parent().ok_or_else(not_a_drawing)?;
+        let scene_dir = find_scene_dir(element_dir)?
+            .ok_or_else(|| ProjectError::NotInScene(path.to_path_buf()))?;
+        let scene_file = find_scene_file(&scene_dir)?;
+
+        let element = match &scene_file {
+            Some(scene_file) => read_element_info(scene_file, element_name)?,
+            None => None,
+        };
+
+        let mut project = Project {
+            scene_dir: scene_dir.clone(),
+            scene_file,
+            element_name: element_name.to_string(),
+            element,
+            palettes: Vec::new(),
+            missing_palettes: Vec::new(),
+        };
+        // a drawing directly in the scene folder has no element palette list of its own
+        if element_dir != scene_dir {
+            project.load_palette_list(element_dir, PaletteScope::Element)?;
+        }
+        project.load_palette_list(&scene_dir, PaletteScope::Scene)?;
+        Ok(project)
+    }
+
+    fn load_palette_list(&mut self, dir: &Path, scope: PaletteScope) -> Result<(), ProjectError> {
+        let list = dir.join(PALETTE_LIST);
+        if !list.is_file() {
+            return Ok(());
+        }
+        for path in read_palette_list(&list)? {
+            if !path.is_file() {
+                self.missing_palettes.push(path);
+                continue;
+            }
+            let name = path
+                .file_stem()
+                .map(|stem| stem.to_string_lossy().into_owned())
+                .unwrap_or_default();
+            let palette = read_palette_file(&path, &name)?;
+            self.palettes.push(ProjectPalette {
+                name,
+                path,
+                scope,
+                palette,
+            });
+        }
+        Ok(())
+    }
+
+    /// Returns the color with the given ID from the first palette that has it.
+    pub fn find_color(&self, id: u64) -> Option<&PaletteColor> {
+        self.palettes
+            .iter()
+            .find_map(|palette| palette.palette.find(id))
+    }
+
+    /// Updates the palette of a document to the colors of the project: colors whose value or name
+    /// differs from the project's are replaced with the project's version, and colors that shapes
+    /// use but that are missing from the palette are added from the project. Returns the number
+    /// of colors that were replaced or added.
+    ///
+    /// Documents without a palette are left as they are.
+    pub fn apply(&self, document: &mut Document) -> usize {
+        let used: BTreeSet<u64> = document
+            .shapes()
+            .flat_map(|shape| shape.shape.color_ids())
+            .collect();
+        let Some(palette) = document.palette_mut() else {
+            return 0;
+        };
+
+        let mut changed = 0;
+        for color in &mut palette.colors {
+            let Some(project_color) = color.id().and_then(|id| self.find_color(id)) else {
+                continue;
+            };
+            if color.rgba() != project_color.rgba() || color.name() != project_color.name() {
+                *color = project_color.clone();
+                changed += 1;
+            }
+        }
+        for id in used {
+            if palette.find(id).is_some() {
+                continue;
+            }
+            if let Some(project_color) = self.find_color(id) {
+                palette.colors.push(project_color.clone());
+                changed += 1;
+            }
+        }
+        changed
+    }
+}
+
+/// Returns the scene folder: the folder that contains `elements/` if the element folder is in
+/// one, or the closest folder with a scene file otherwise.
+fn find_scene_dir(element_dir: &Path) -> Result<Option<PathBuf>, ProjectError> {
+    if element_dir.parent().and_then(Path::file_name) == Some("elements".as_ref()) {
+        return Ok(element_dir
+            .parent()
+            .and_then(Path::parent)
+            .map(Path::to_path_buf));
+    }
+    for dir in element_dir.ancestors() {
+        if find_scene_file(dir)?.is_some() {
+            return Ok(Some(dir.to_path_buf()));
+        }
+    }
+    Ok(None)
+}
+
+/// Returns the scene file in a folder: the one named after the folder if there are several, or
+/// the first by name otherwise.
+fn find_scene_file(dir: &Path) -> Result<Option<PathBuf>, ProjectError> {
+    let entries = match std::fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) if e.kind() == io::ErrorKind::NotFound => return Ok(None),
+        Err(e) => return Err(e.into()),
+    };
+    let mut files = Vec::new();
+    for entry in entries {
+        let path = entry?.path();
+        if path
+            .extension()
+            .is_some_and(|extension| extension == "xstage")
+            && path.is_file()
+        {
+            files.push(path);
+        }
+    }
+    files.sort();
+    let named_after_dir = files.iter().position(|file| {
+        file.file_stem()
+            .is_some_and(|stem| Some(stem) == dir.file_name())
+    });
+    Ok(match named_after_dir {
+        Some(i) => Some(files.swap_remove(i)),
+        None => files.into_iter().next(),
+    })
+}
+
+/// Reads a `PALETTE_LIST` file, and returns the paths of the palettes in it. See
+/// [parse_palette_list].
+pub fn read_palette_list(path: &Path) -> Result<Vec<PathBuf>, ProjectError> {
+    let text = std::fs::read_to_string(path)?;
+    let dir = path.parent().unwrap_or(Path::new(""));
+    Ok(parse_palette_list(&text, dir))
+}
+
+/// Parses the contents of a `PALETTE_LIST` file in `dir`, and returns the paths of the palettes
+/// in it.
+///
+/// After a header line, each line contains the path of a palette relative to the folder of the
+/// list, usually without the `.plt` extension, optionally followed by a palette ID.
+pub fn parse_palette_list(text: &str, dir: &Path) -> Vec<PathBuf> {
+    let mut palettes = Vec::new();
+    for line in text.lines() {
+        let line = line.trim();
+        if line.is_empty() || line.starts_with("ToonBoomAnimationInc") {
+            continue;
+        }
+        let line = match line.rsplit_once(char::is_whitespace) {
+            Some((rest, id)) if id.starts_with("0x") => rest.trim_end(),
+            _ => line,
+        };
+        let mut palette = dir.join(line);
+        if palette
+            .extension()
+            .is_none_or(|extension| extension != "plt")
+        {
+            palette.as_mut_os_string().push(".plt");
+        }
+        palettes.push(palette);
+    }
+    palettes
+}
+
+/// Reads the solid colors of a palette file (`.plt`). See [parse_palette_file].
+pub fn read_palette_file(path: &Path, name: &str) -> Result<PaletteData, ProjectError> {
+    let text = std::fs::read_to_string(path)?;
+    parse_palette_file(&text, name).map_err(|e| ProjectError::Syntax {
+        path: path.to_path_buf(),
+        line: e.line,
+        message: e.message,
+    })
+}
+
+/// Parses the solid colors of a palette file (`.plt`). `name` is the project palette name the
+/// colors get (see [PaletteColor::project]), which is usually the file name without `.plt`.
+///
+/// After a header line, each color is a line like `Solid "Skin tone" 0x0a4f2d5b7c000001 242 199
+/// 165 255`, with the kind of color, the name (in quotes if it contains spaces), the color ID and
+/// the RGBA value. Other kinds of colors, like gradients, are skipped.
+pub fn parse_palette_file(text: &str, name: &str) -> Result<PaletteData, SyntaxError> {
+    let syntax_error = |line: usize, message: String| SyntaxError {
+        line: line + 1,
+        message,
+    };
+
+    let mut lines = text.lines().enumerate();
+    match lines.next() {
+        Some((_, header)) if header.starts_with("ToonBoomAnimationInc PaletteFile") => (),
+        _ => return Err(syntax_error(0, "not a Harmony palette file".into())),
+    }
+
+    let mut colors = Vec::new();
+    for (i, line) in lines {
+        let fields = split_fields(line);
+        if fields.first().map(String::as_str) != Some("Solid") {
+            continue;
+        }
+        let [_, color_name, id, r, g, b, a] = &fields[..] else {
+            return Err(syntax_error(
+                i,
+                format!("expected 7 fields, found {}", fields.len()),
+            ));
+        };
+        let id = id
+            .strip_prefix("0x")
+            .and_then(|hex| u64::from_str_radix(hex, 16).ok())
+            .ok_or_else(|| syntax_error(i, format!("invalid color ID: {id}")))?;
+        let channel = |c: &String| {
+            c.parse::<u8>()
+                .map_err(|_| syntax_error(i, format!("invalid color channel: {c}")))
+        };
+        let rgba = Rgba::new(channel(r)?, channel(g)?, channel(b)?, channel(a)?);
+        colors.push(PaletteColor::solid(id, rgba, color_name.as_str(), name));
+    }
+    Ok(PaletteData { colors })
+}
+
+/// Splits a line at whitespace, keeping text in double quotes together (without the quotes).
+fn split_fields(line: &str) -> Vec<String> {
+    let mut fields = Vec::new();
+    let mut chars = line.chars().peekable();
+    while let Some(&c) = chars.peek() {
+        if c.is_whitespace() {
+            chars.next();
+            continue;
+        }
+        let mut field = String::new();
+        if c == '"' {
+            chars.next();
+            field.extend(chars.by_ref().take_while(|&c| c != '"'));
+        } else {
+            while let Some(c) = chars.next_if(|c| !c.is_whitespace()) {
+                field.push(c);
+            }
+        }
+        fields.push(field);
+    }
+    fields
+}
+
+/// Reads the entry of an element from a scene file, if it has one.
+fn read_element_info(
+    scene_file: &Path,
+    element: &str,
+) -> Result<Option<ElementInfo>, ProjectError> {
+    let text = std::fs::read_to_string(scene_file)?;
+    let xml = roxmltree::Document::parse(&text)
+        .map_err(|e| ProjectError::Xml(scene_file.to_path_buf(), e))?;
+    let node = xml.descendants().find(|node| {
+        node.has_tag_name("element") && node.attribute("elementName") == Some(element)
+    });
+    Ok(node.map(|node| ElementInfo {
+        id: node.attribute("id").map(String::from),
+        name: element.to_string(),
+        folder: node.attribute("elementFolder").map(String::from),
+        field_chart: node
+            .attribute("fieldChart")
+            .and_then(|size| size.parse().ok()),
+        drawings: node
+            .descendants()
+            .filter(|node| node.has_tag_name("dwg"))
+            .filter_map(|node| node.attribute("name").map(String::from))
+            .collect(),
+    }))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn split_fields_keeps_quoted_names() {
+        assert_eq!(
+            split_fields(r#"Solid  "Skin tone" 0x0a 242	199"#),
+            ["Solid", "Skin tone", "0x0a", "242", "199"]
+        );
+        assert_eq!(split_fields(r#"Solid "" 0x0a"#), ["Solid", "", "0x0a"]);
+        assert!(split_fields("   ").is_empty());
+    }
+
+    #[test]
+    fn palette_list_paths() {
+        let text = "ToonBoomAnimationInc PaletteList 1\n\
+            palette-library/colors\n\
+            palette-library/Skin Tones 0x0a4f2d5b7c000001\n\
+            palette-library/lines.plt\n\
+            \n\
+            palette-library/shadows.plt 0x0a4f2d5b7c000002\n";
+        assert_eq!(
+            parse_palette_list(text, Path::new("scene")),
+            [
+                "palette-library/colors.plt",
+                "palette-library/Skin Tones.plt",
+                "palette-library/lines.plt",
+                "palette-library/shadows.plt",
+            ]
+            .map(|path| Path::new("scene").join(path))
+        );
+    }
+
+    #[test]
+    fn palette_file_colors() {
+        let text = "ToonBoomAnimationInc PaletteFile 2\n\
+            Solid \"Skin tone\" 0x0a4f2d5b7c000001 242 199 165 255\n\
+            LinearGradient Sky 0x0a4f2d5b7c000002 0 0 0 255\n\
+            Solid Black 0x0a4f2d5b7c000003 0 0 0 255\n";
+        let palette = parse_palette_file(text, "colors").unwrap();
+        assert_eq!(palette.colors.len(), 2);
+
+        let color = &palette.colors[0];
+        assert_eq!(color.id(), Some(0x0a4f2d5b7c000001));
+        assert_eq!(color.name(), Some("Skin tone"));
+        assert_eq!(color.project(), Some("colors"));
+        assert_eq!(color.rgba(), Some(Rgba::new(242, 199, 165, 255)));
+        assert_eq!(palette.colors[1].name(), Some("Black"));
+    }
+
+    #[test]
+    fn palette_file_errors() {
+        let text = "ToonBoomAnimationInc PaletteFile 2\n\
+            Solid Black 0x0a4f2d5b7c000003 0 0 0 255\n\
+            Solid \"Dark red\" 0x0a4f2d5b7c000004 128 0 0\n";
+        let error = parse_palette_file(text, "colors").unwrap_err();
+        assert_eq!(error.line, 3);
+        assert_eq!(error.message, "expected 7 fields, found 6");
+
+        let error = parse_palette_file("Solid Black 0x01 0 0 0 255\n", "colors").unwrap_err();
+        assert_eq!(error.line, 1);
+        assert_eq!(error.message, "not a Harmony palette file");
+
+        let text = "ToonBoomAnimationInc PaletteFile 2\nSolid Black 0x01 0 0 256 255\n";
+        let error = parse_palette_file(text, "colors").unwrap_err();
+        assert_eq!(error.message, "invalid color channel: 256");
+    }
+}
@@ -1,11 +1,28 @@
+#[cfg(feature = "layers")]
+use crate::document::Art;
+#[cfg(feature = "layers")]
 use crate::layer::{LayerData, read_layer_data};
+#[cfg(feature = "palette")]
 use crate::palette::{PaletteData, read_palette_data};
-use crate::util::read_encoded_data;
-use byteorder::{LE, ReadBytesExt};
+use crate::util::{all_values, read_encoded_data, Bytes, RawBlock};
+use byteorder::LE;
 use num_enum::{IntoPrimitive, TryFromPrimitive};
-use std::io::{self, BufRead, Read};
+use crate::io::{self, Read, ReadBytesExt};
 use thiserror::Error;
 use crate::eof_reader::EofReader;
+use alloc::ffi::{CString, FromVecWithNulError};
+use alloc::string::{FromUtf16Error, String};
+use alloc::{format, vec, vec::Vec};
+use crate::trace;
+#[cfg(feature = "std")]
+use crate::parse_trace::{ParseTrace, TraceEventKind};
+use crate::read_forensic::read_tags_forensic;
+#[cfg(feature = "tokio")]
+pub use crate::read_async::{read_async, read_async_with_options};
+pub use crate::read_captured::{read_captured, CapturedTag, RawTag};
+pub use crate::read_cursor::{Framing, TagCursor};
+pub use crate::read_incremental::{is_complete, IncrementalReader};
+pub use crate::read_located::{read_located, LocatedReadError};
 
 pub const MAGIC: [u8; 8] = *b"OTVGfull";
 pub const TVG_VERSION: u32 = 1009;
@@ -35,14 +52,230 @@ pub enum ReadError {
     #[error("unknown encoding: {0:08x?}")]
     UnknownEncoding(u32),
     #[error("c string error in {0}: {1}")]
-    CStringError(&'static str, std::ffi::FromVecWithNulError),
+    CStringError(&'static str, FromVecWithNulError),
     #[error("utf8 error in {0}: {1}")]
-    Utf8Error(&'static str, std::str::Utf8Error),
+    Utf8Error(&'static str, core::str::Utf8Error),
     #[error("utf16 error in {0}: {1}")]
-    Utf16Error(&'static str, std::string::FromUtf16Error),
+    Utf16Error(&'static str, FromUtf16Error),
+    #[error("invalid length of {0}: {1}")]
+    InvalidLength(&'static str, u32),
+    #[error("{what} exceeds the limit: {value} > {limit}")]
+    LimitExceeded {
+        what: &'static str,
+        value: u64,
+        limit: u64,
+    },
+    #[error("file ends in the middle of a tag ({0} bytes left)")]
+    TruncatedTag(usize),
+    #[error("zlib-compressed data can't be decoded without a zlib backend feature")]
+    ZlibUnavailable,
+}
+
+impl ReadError {
+    /// The name of the variant in snake case, e.g. to tell errors apart without matching on them.
+    pub fn kind(&self) -> &'static str {
+        match self {
+            ReadError::Io(_) => "io",
+            ReadError::UnexpectedMagic(_) => "unexpected_magic",
+            ReadError::UnexpectedVersion(_) => "unexpected_version",
+            ReadError::UnknownMystery(_) => "unknown_mystery",
+            ReadError::UnknownFileTag(_) => "unknown_file_tag",
+            ReadError::UnknownLayerTag(_) => "unknown_layer_tag",
+            ReadError::UnknownShapeType(_) => "unknown_shape_type",
+            ReadError::UnknownComponentType(_) => "unknown_component_type",
+            ReadError::UnknownComponentTag(_) => "unknown_component_tag",
+            ReadError::UnknownPaletteTag(_) => "unknown_palette_tag",
+            ReadError::UnknownEncoding(_) => "unknown_encoding",
+            ReadError::CStringError(..) => "c_string_error",
+            ReadError::Utf8Error(..) => "utf8_error",
+            ReadError::Utf16Error(..) => "utf16_error",
+            ReadError::InvalidLength(..) => "invalid_length",
+            ReadError::LimitExceeded { .. } => "limit_exceeded",
+            ReadError::TruncatedTag(_) => "truncated_tag",
+            ReadError::ZlibUnavailable => "zlib_unavailable",
+        }
+    }
+}
+
+/// Options for reading TVG files.
+///
+/// The limits protect against files that claim huge sizes to make the reader allocate too much
+/// memory. The defaults are far above anything observed in real files.
+#[derive(Debug, Clone)]
+pub struct ReadOptions {
+    /// Maximum size of a decoded (e.g. decompressed) block, in bytes. Default: 256 MiB.
+    pub max_decoded_size: u64,
+    /// Maximum number of shapes in a layer. Default: 1 000 000.
+    pub max_shape_count: u32,
+    /// Maximum number of components in a shape. Default: 1 000 000.
+    pub max_component_count: u32,
+    /// Maximum number of points in a path or thickness definition. Default: 10 000 000.
+    pub max_point_count: u32,
+    /// Maximum number of colors in a palette. Default: 1 000 000.
+    pub max_color_count: u32,
+    /// How unexpected data is handled. Default: [ParseProfile::Strict].
+    pub profile: ParseProfile,
+    /// If true, [read_captured] keeps a copy of the bytes of each tag, and not just their
+    /// location.
+    pub capture_raw: bool,
+    /// If set, the time taken by each tag and by decompression is recorded here. Only
+    /// [read_with_options] and the functions built on it record tags; all readers record
+    /// decompression.
+    #[cfg(feature = "std")]
+    pub trace: Option<ParseTrace>,
+}
+
+/// How the reader handles data that differs from what it expects.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum ParseProfile {
+    /// Any unexpected value is an error.
+    #[default]
+    Strict,
+    /// Unexpected values in fields of a fixed size are ignored, since they don't change how the
+    /// rest of the data is read. Values that have been seen to vary between files are read as
+    /// usual, but the others (like the `0x79` before the palette colors, or the zeros around the
+    /// `tGTB` domain) are discarded.
+    Compatible,
+    /// Like [ParseProfile::Strict], but a tag that can't be read is kept as
+    /// [FileData::Unreadable], with its bytes and the error, and reading continues with the next
+    /// tag. Regions that are skipped because we don't know what they mean are kept as raw bytes
+    /// (see [ReadOptions::preserve_unknown]), and unexpected values that are kept anyway, like
+    /// the values in the file header, aren't an error.
+    ///
+    /// If the end of a tag can't be determined (e.g. because it's an unknown tag), the rest of the
+    /// data is kept as one unreadable tag. Only [read_with_options] and the functions built on it
+    /// recover from errors like this; the incremental, async and random access readers don't.
+    Forensic,
+}
+
+impl ReadOptions {
+    /// Returns the default options with a different profile.
+    pub fn with_profile(profile: ParseProfile) -> Self {
+        ReadOptions {
+            profile,
+            ..Default::default()
+        }
+    }
+
+    /// Whether regions of the file that are skipped because we don't know what they mean are
+    /// kept as raw bytes on the corresponding structure, so that they can be written back out.
+    /// Only with [ParseProfile::Forensic].
+    pub fn preserve_unknown(&self) -> bool {
+        self.profile == ParseProfile::Forensic
+    }
+
+    /// Whether data that differs from what has been observed in files so far is an error even if
+    /// it's kept as it is, like the values in the file header and the layer trailer. Only with
+    /// [ParseProfile::Strict].
+    pub fn is_strict(&self) -> bool {
+        self.profile == ParseProfile::Strict
+    }
+
+    /// Handles an unexpected value in a field of a fixed size, which is only an error if the
+    /// profile doesn't tolerate it.
+    pub(crate) fn unexpected(&self, message: impl FnOnce() -> String) -> Result<(), ReadError> {
+        match self.profile {
+            ParseProfile::Compatible => {
+                trace::debug!(message = %message(), "ignored unexpected value");
+                Ok(())
+            }
+            ParseProfile::Strict | ParseProfile::Forensic => {
+                Err(ReadError::UnknownMystery(message()))
+            }
+        }
+    }
+}
+
+impl Default for ReadOptions {
+    fn default() -> Self {
+        ReadOptions {
+            max_decoded_size: 256 << 20,
+            max_shape_count: 1_000_000,
+            max_component_count: 1_000_000,
+            max_point_count: 10_000_000,
+            max_color_count: 1_000_000,
+            profile: ParseProfile::Strict,
+            capture_raw: false,
+            #[cfg(feature = "std")]
+            trace: None,
+        }
+    }
+}
+
+/// Returns [ReadError::LimitExceeded] if `value` is greater than `limit`.
+pub(crate) fn check_limit(
+    what: &'static str,
+    value: impl Into<u64>,
+    limit: impl Into<u64>,
+) -> Result<(), ReadError> {
+    let (value, limit) = (value.into(), limit.into());
+    if value > limit {
+        return Err(ReadError::LimitExceeded { what, value, limit });
+    }
+    Ok(())
+}
+
+pub fn read<R>(input: R) -> Result<Vec<FileData>, ReadError>
+where
+    R: Read,
+{
+    read_with_options(input, &ReadOptions::default())
+}
+
+pub fn read_with_options<R>(
+    input: R,
+    options: &ReadOptions,
+) -> Result<Vec<FileData>, ReadError>
+where
+    R: Read,
+{
+    Ok(read_with_header(input, options)?.1)
+}
+
+/// Reads a file, and also returns its header.
+pub fn read_with_header<R>(
+    mut input: R,
+    options: &ReadOptions,
+) -> Result<(FileHeader, Vec<FileData>), ReadError>
+where
+    R: Read,
+{
+    let header = read_header(&mut input, options)?;
+
+    if options.profile == ParseProfile::Forensic {
+        let mut data = Vec::new();
+        input.read_to_end(&mut data)?;
+        return Ok((header, read_tags_forensic(&data, options)));
+    }
+
+    let tags = read_tags(&mut EofReader::new(input)?, options)?;
+
+    Ok((header, tags))
 }
 
-pub fn read<R>(mut input: R) -> Result<Vec<FileData>, ReadError>
+/// The values after the TVG version in the header of files that have been seen so far: (2, 1) in
+/// most files, and (1, 1) in some files from older Harmony versions. What they mean isn't known
+/// yet (see `notes/header.md`).
+pub const KNOWN_HEADER_VALUES: [(u32, u32); 2] = [(2, 1), (1, 1)];
+
+/// The file header after the magic bytes.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct FileHeader {
+    /// Always [TVG_VERSION], since other versions aren't supported.
+    pub version: u32,
+    /// The two u32s after the version. See [KNOWN_HEADER_VALUES].
+    pub unknown: (u32, u32),
+}
+
+/// Reads the file header.
+///
+/// Values after the version that aren't in [KNOWN_HEADER_VALUES] are only an error with
+/// [ParseProfile::Strict] (see [ReadOptions::is_strict]), since none are known to change the
+/// layout of the rest of the file.
+pub fn read_header<R>(mut input: R, options: &ReadOptions) -> Result<FileHeader, ReadError>
 where
     R: Read,
 {
@@ -60,20 +293,141 @@ where
 
     let thing_1 = input.read_u32::<LE>()?;
     let thing_2 = input.read_u32::<LE>()?;
-    if thing_1 != 2 || thing_2 != 1 {
+    if options.is_strict() && !KNOWN_HEADER_VALUES.contains(&(thing_1, thing_2)) {
         return Err(ReadError::UnknownMystery(format!(
-            "unexpected mystery values after the TVG version: {}, {} (expected 2, 1)",
-            thing_1, thing_2
+            "unexpected mystery values after the TVG version: {}, {} (expected one of {:?})",
+            thing_1, thing_2, KNOWN_HEADER_VALUES
         )));
     }
+    Ok(FileHeader {
+        version: tvg_version,
+        unknown: (thing_1, thing_2),
+    })
+}
 
-    let tags = read_tags(&mut EofReader::new(input)?)?;
+/// The encoded block of a tag, as stored in the file. See [read_blocks].
+#[derive(Debug, Clone)]
+pub struct TagBlock {
+    pub tag: FileTag,
+    /// Whether the tag is in the main data.
+    pub in_main: bool,
+    pub block: RawBlock,
+}
 
-    Ok(tags)
+/// Reads the encoded blocks of all tags that have one, without decoding them, e.g. to see how
+/// well they're compressed.
+///
+/// The main data is decoded to read the blocks inside it, which follow the main data block.
+/// Tags without an encoded block (like `CERT`) are skipped.
+pub fn read_blocks<R>(mut input: R, options: &ReadOptions) -> Result<Vec<TagBlock>, ReadError>
+where
+    R: Read,
+{
+    read_header(&mut input, options)?;
+    let mut blocks = Vec::new();
+    read_tag_blocks(&mut EofReader::new(input)?, false, options, &mut blocks)?;
+    Ok(blocks)
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
+fn read_tag_blocks<R: Read>(
+    input: &mut EofReader<R>,
+    in_main: bool,
+    options: &ReadOptions,
+    blocks: &mut Vec<TagBlock>,
+) -> Result<(), ReadError> {
+    loop {
+        let mut peek_buf = [0; 4];
+        match input.peek(&mut peek_buf)? {
+            0 => break Ok(()),
+            1 if peek_buf[0] == 0 => break Ok(()),
+            4 => (),
+            read => return Err(ReadError::TruncatedTag(read)),
+        }
+
+        let tag = u32::from_be_bytes(peek_buf);
+        let tag = FileTag::try_from(tag).map_err(|e| ReadError::UnknownFileTag(e.number))?;
+        match tag {
+            FileTag::Cert | FileTag::Endt | FileTag::Ttoc | FileTag::Sign => {
+                read_tag(&mut *input, options)?;
+            }
+            _ => {
+                input.read_u32::<byteorder::BE>()?;
+                let block = RawBlock::read(&mut *input, options)?;
+                let data = (tag == FileTag::MainData)
+                    .then(|| block.clone().decode(options))
+                    .transpose()?;
+                blocks.push(TagBlock {
+                    tag,
+                    in_main,
+                    block,
+                });
+                if let Some(data) = data {
+                    read_tag_blocks(&mut EofReader::new(&data[..])?, true, options, blocks)?;
+                }
+            }
+        }
+    }
+}
+
+/// Reads only the first tag of a kind, e.g. to show the palette before the layers are read.
+///
+/// Other tags are skipped without decoding their blocks, but the main data block still has to be
+/// decoded to find the tags inside it. The offsets in `TTOC` would allow skipping to a tag
+/// directly, but they don't match the tag positions in any file seen so far, so they aren't used.
+pub fn read_one<R>(
+    mut input: R,
+    tag: FileTag,
+    options: &ReadOptions,
+) -> Result<Option<FileData>, ReadError>
+where
+    R: Read,
+{
+    read_header(&mut input, options)?;
+    find_tag(&mut EofReader::new(input)?, tag, options)
+}
+
+fn find_tag<R: Read>(
+    input: &mut EofReader<R>,
+    wanted: FileTag,
+    options: &ReadOptions,
+) -> Result<Option<FileData>, ReadError> {
+    loop {
+        let mut peek_buf = [0; 4];
+        match input.peek(&mut peek_buf)? {
+            0 => break Ok(None),
+            1 if peek_buf[0] == 0 => break Ok(None),
+            4 => (),
+            read => return Err(ReadError::TruncatedTag(read)),
+        }
+
+        let tag = u32::from_be_bytes(peek_buf);
+        let tag = FileTag::try_from(tag).map_err(|e| ReadError::UnknownFileTag(e.number))?;
+        if tag == wanted {
+            return read_tag(&mut *input, options);
+        }
+        match tag {
+            FileTag::Cert | FileTag::Endt | FileTag::Ttoc | FileTag::Sign => {
+                read_tag(&mut *input, options)?;
+            }
+            _ => {
+                input.read_u32::<byteorder::BE>()?;
+                let block = RawBlock::read(&mut *input, options)?;
+                if tag == FileTag::MainData {
+                    let data = block.decode(options)?;
+                    let found = find_tag(&mut EofReader::new(&data[..])?, wanted, options)?;
+                    if found.is_some() {
+                        return Ok(found);
+                    }
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, TryFromPrimitive, IntoPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[repr(u32)]
 pub enum FileTag {
     /// `CERT`: contains a certificate unique to the license
@@ -102,8 +456,25 @@ pub enum FileTag {
     Sign = 0x5349474e,
 }
 
+all_values!(FileTag {
+    Cert,
+    MainData,
+    Endt,
+    Tvci,
+    Crea,
+    LayerUnderlay,
+    LayerColor,
+    LayerLine,
+    LayerOverlay,
+    Palette,
+    Ttoc,
+    Sign,
+});
+
 #[derive(Debug, Clone, Copy, PartialEq, Eq, TryFromPrimitive, IntoPrimitive)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[repr(u32)]
 pub enum EncodingTag {
     /// `UNCO`: uncompressed data
@@ -112,8 +483,12 @@ pub enum EncodingTag {
     Zlib = 0x5a4c4942,
 }
 
+all_values!(EncodingTag { Unco, Zlib });
+
 #[derive(Debug, Clone)]
 #[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
 #[cfg_attr(feature = "serde", serde(tag = "type", content = "content", rename_all = "snake_case"))]
 pub enum FileData {
     Certificate(String),
@@ -121,44 +496,161 @@ pub enum FileData {
     Crea(u32),
     Endt,
     Main(Vec<FileData>),
-    MainOffsets(Vec<(FileTag, u32)>),
+    MainOffsets {
+        offsets: Vec<(FileTag, u32)>,
+        /// The 8 bytes that follow the offsets, if [ReadOptions::preserve_unknown].
+        unknown: Option<Bytes>,
+    },
     Identity {
         device: String,
         software_name: String,
+        /// The 13 bytes before the device name, if [ReadOptions::preserve_unknown].
+        unknown: Option<Bytes>,
+        /// Any bytes after the software name, if [ReadOptions::preserve_unknown] and there
+        /// are any (see `notes/tvci.md`).
+        extra: Option<Bytes>,
     },
+    #[cfg(feature = "layers")]
     LayerUnderlay(LayerData),
+    #[cfg(feature = "layers")]
     LayerColor(LayerData),
+    #[cfg(feature = "layers")]
     LayerLine(LayerData),
+    #[cfg(feature = "layers")]
     LayerOverlay(LayerData),
+    #[cfg(feature = "palette")]
     Palette(PaletteData),
+    /// A tag that couldn't be read, with [ParseProfile::Forensic].
+    Unreadable {
+        /// The tag number, which may not be a known [FileTag].
+        tag: u32,
+        /// The data after the tag number.
+        data: Bytes,
+        /// Why it couldn't be read.
+        error: String,
+    },
 }
 
-fn read_tags<R: Read>(input: &mut EofReader<R>) -> Result<Vec<FileData>, ReadError>
-{
-    let mut tags = Vec::new();
-    loop {
-        if input.is_at_eof() {
-            break Ok(tags);
+impl FileData {
+    /// Returns the tag that this data is stored in, or None for an unreadable tag that isn't a
+    /// known one.
+    pub fn tag(&self) -> Option<FileTag> {
+        Some(match self {
+            FileData::Certificate(_) => FileTag::Cert,
+            FileData::Signature(_) => FileTag::Sign,
+            FileData::Crea(_) => FileTag::Crea,
+            FileData::Endt => FileTag::Endt,
+            FileData::Main(_) => FileTag::MainData,
+            FileData::MainOffsets { .. } => FileTag::Ttoc,
+            FileData::Identity { .. } => FileTag::Tvci,
+            #[cfg(feature = "layers")]
+            FileData::LayerUnderlay(_) => FileTag::LayerUnderlay,
+            #[cfg(feature = "layers")]
+            FileData::LayerColor(_) => FileTag::LayerColor,
+            #[cfg(feature = "layers")]
+            FileData::LayerLine(_) => FileTag::LayerLine,
+            #[cfg(feature = "layers")]
+            FileData::LayerOverlay(_) => FileTag::LayerOverlay,
+            #[cfg(feature = "palette")]
+            FileData::Palette(_) => FileTag::Palette,
+            FileData::Unreadable { tag, .. } => return FileTag::try_from(*tag).ok(),
+        })
+    }
+
+    /// Wraps layer data in the variant for its art layer.
+    #[cfg(feature = "layers")]
+    pub fn from_layer(art: Art, layer: LayerData) -> Self {
+        match art {
+            Art::Underlay => FileData::LayerUnderlay(layer),
+            Art::Color => FileData::LayerColor(layer),
+            Art::Line => FileData::LayerLine(layer),
+            Art::Overlay => FileData::LayerOverlay(layer),
+        }
+    }
+
+    /// Returns the art layer and its data, if this is one of the layer variants.
+    ///
+    /// Each art layer has its own variant (like the tags in the file), which this saves matching
+    /// on one by one.
+    #[cfg(feature = "layers")]
+    pub fn layer(&self) -> Option<(Art, &LayerData)> {
+        match self {
+            FileData::LayerUnderlay(layer) => Some((Art::Underlay, layer)),
+            FileData::LayerColor(layer) => Some((Art::Color, layer)),
+            FileData::LayerLine(layer) => Some((Art::Line, layer)),
+            FileData::LayerOverlay(layer) => Some((Art::Overlay, layer)),
+            _ => None,
+        }
+    }
+
+    /// Like [FileData::layer], but mutable.
+    #[cfg(feature = "layers")]
+    pub fn layer_mut(&mut self) -> Option<(Art, &mut LayerData)> {
+        match self {
+            FileData::LayerUnderlay(layer) => Some((Art::Underlay, layer)),
+            FileData::LayerColor(layer) => Some((Art::Color, layer)),
+            FileData::LayerLine(layer) => Some((Art::Line, layer)),
+            FileData::LayerOverlay(layer) => Some((Art::Overlay, layer)),
+            _ => None,
+        }
+    }
+
+    /// Like [FileData::layer], but takes ownership of the data.
+    #[cfg(feature = "layers")]
+    pub fn into_layer(self) -> Option<(Art, LayerData)> {
+        match self {
+            FileData::LayerUnderlay(layer) => Some((Art::Underlay, layer)),
+            FileData::LayerColor(layer) => Some((Art::Color, layer)),
+            FileData::LayerLine(layer) => Some((Art::Line, layer)),
+            FileData::LayerOverlay(layer) => Some((Art::Overlay, layer)),
+            _ => None,
         }
+    }
+}
 
+fn read_tags<R: Read>(
+    input: &mut EofReader<R>,
+    options: &ReadOptions,
+) -> Result<Vec<FileData>, ReadError> {
+    let mut tags = Vec::new();
+    loop {
         let mut peek_buf = [0; 4];
-        let read = input.peek_tag(&mut peek_buf)?;
-        if read == 1 && peek_buf[0] == 0 {
+        match input.peek(&mut peek_buf)? {
+            0 => break Ok(tags),
             // trailing 0 byte at EOF
-            break Ok(tags);
+            1 if peek_buf[0] == 0 => break Ok(tags),
+            4 => (),
+            read => return Err(ReadError::TruncatedTag(read)),
         }
 
-        tags.push(read_tag(&mut *input)?);
+        #[cfg(feature = "std")]
+        let started = (options.trace.as_ref())
+            .map(|trace| (trace, std::time::Instant::now(), input.position()));
+        let tag = read_tag(&mut *input, options)?;
+        #[cfg(feature = "std")]
+        if let Some((trace, start, position)) = started {
+            let kind = TraceEventKind::Tag(u32::from_be_bytes(peek_buf));
+            trace.record(kind, start, input.position() - position, None);
+        }
+        if let Some(tag) = tag {
+            tags.push(tag);
+        }
     }
 }
 
-fn read_tag<R>(mut input: R) -> Result<FileData, ReadError>
+/// Reads a tag. Returns None if the tag was skipped because decoding it is disabled.
+pub(crate) fn read_tag<R>(
+    mut input: R,
+    options: &ReadOptions,
+) -> Result<Option<FileData>, ReadError>
 where
     R: Read,
 {
     let tag = input.read_u32::<byteorder::BE>()?;
+    trace::span!("tag", tag = %format_args!("{tag:08x}"));
+    trace::debug!(known = ?FileTag::try_from(tag).ok(), "reading tag");
 
-    match FileTag::try_from(tag) {
+    let data = match FileTag::try_from(tag) {
         Ok(FileTag::Cert) => {
             let len = input.read_u32::<LE>()?;
             let mut reader = (&mut input).take(len as u64);
@@ -166,15 +658,14 @@ where
             // mystery thing
             let thing = reader.read_u32::<LE>()?;
             if thing != 1 {
-                return Err(ReadError::UnknownMystery(format!(
-                    "unexpected CERT header bytes: {} (expected 1)",
-                    thing
-                )));
+                options.unexpected(|| {
+                    format!("unexpected CERT header bytes: {} (expected 1)", thing)
+                })?;
             }
             let cert_len = reader.read_u32::<LE>()?;
+            check_limit("certificate length", cert_len, options.max_decoded_size)?;
 
-            let mut cert = Vec::new();
-            cert.resize(cert_len as usize, 0);
+            let mut cert = vec![0; cert_len as usize];
             reader.read_exact(&mut cert)?;
             let cert = String::from_utf8(cert)
                 .map_err(|e| ReadError::Utf8Error("certificate", e.utf8_error()))?;
@@ -182,54 +673,77 @@ where
             Ok(FileData::Certificate(cert))
         }
         Ok(FileTag::MainData) => {
-            let data = read_encoded_data(&mut input)?;
-            Ok(FileData::Main(read_tags(&mut EofReader::new(io::Cursor::new(data))?)?))
+            let data = read_encoded_data(&mut input, options)?;
+            let mut reader = EofReader::new(&data[..])?;
+            Ok(FileData::Main(read_tags(&mut reader, options)?))
         }
         Ok(FileTag::Endt) => Ok(FileData::Endt),
         Ok(FileTag::Crea) => {
-            let data = read_encoded_data(&mut input)?;
-            let mut buf_read = io::BufReader::new(io::Cursor::new(data));
-            let thing = buf_read.read_u32::<LE>()?;
+            let data = read_encoded_data(&mut input, options)?;
+            let thing = (&data[..]).read_u32::<LE>()?;
             if thing != 2 {
-                return Err(ReadError::UnknownMystery(format!(
-                    "unexpected CREA value: {} (expected 2)",
-                    thing
-                )));
+                options.unexpected(|| format!("unexpected CREA value: {} (expected 2)", thing))?;
             }
             // TODO: check EOF?
             Ok(FileData::Crea(thing))
         }
         Ok(FileTag::Tvci) => {
-            let data = read_encoded_data(&mut input)?;
-            let mut buf_read = io::BufReader::new(io::Cursor::new(data));
+            let data = read_encoded_data(&mut input, options)?;
+            let mut data = &data[..];
             // skip 13 mystery bytes
-            buf_read.read_exact(&mut [0; 13])?;
+            let mut unknown = [0; 13];
+            data.read_exact(&mut unknown)?;
 
-            let mut device = Vec::new();
-            buf_read.read_until(0, &mut device)?;
-            let mut name = Vec::new();
-            buf_read.read_until(0, &mut name)?;
+            let device = read_until_nul(&mut data);
+            let name = read_until_nul(&mut data);
 
-            let device = std::ffi::CString::from_vec_with_nul(device)
+            let device = CString::from_vec_with_nul(device)
                 .map_err(|e| ReadError::CStringError("tvci device", e))?
                 .into_string()
                 .map_err(|e| ReadError::Utf8Error("tvci device", e.utf8_error()))?;
-            let name = std::ffi::CString::from_vec_with_nul(name)
+            let name = CString::from_vec_with_nul(name)
                 .map_err(|e| ReadError::CStringError("tvci software name", e))?
                 .into_string()
                 .map_err(|e| ReadError::Utf8Error("tvci software name", e.utf8_error()))?;
 
-            // TODO: check EOF?
             Ok(FileData::Identity {
                 device,
                 software_name: name,
+                unknown: options.preserve_unknown().then(|| Bytes(unknown.into())),
+                extra: (options.preserve_unknown() && !data.is_empty()).then(|| Bytes(data.into())),
             })
         }
-        Ok(FileTag::LayerUnderlay) => Ok(FileData::LayerUnderlay(read_layer_data(&mut input)?)),
-        Ok(FileTag::LayerColor) => Ok(FileData::LayerColor(read_layer_data(&mut input)?)),
-        Ok(FileTag::LayerLine) => Ok(FileData::LayerLine(read_layer_data(&mut input)?)),
-        Ok(FileTag::LayerOverlay) => Ok(FileData::LayerOverlay(read_layer_data(&mut input)?)),
-        Ok(FileTag::Palette) => Ok(FileData::Palette(read_palette_data(&mut input)?)),
+        #[cfg(feature = "layers")]
+        Ok(FileTag::LayerUnderlay) => {
+            Ok(FileData::LayerUnderlay(read_layer_data(&mut input, options)?))
+        }
+        #[cfg(feature = "layers")]
+        Ok(FileTag::LayerColor) => Ok(FileData::LayerColor(read_layer_data(&mut input, options)?)),
+        #[cfg(feature = "layers")]
+        Ok(FileTag::LayerLine) => Ok(FileData::LayerLine(read_layer_data(&mut input, options)?)),
+        #[cfg(feature = "layers")]
+        Ok(FileTag::LayerOverlay) => {
+            Ok(FileData::LayerOverlay(read_layer_data(&mut input, options)?))
+        }
+        #[cfg(not(feature = "layers"))]
+        Ok(
+            FileTag::LayerUnderlay
+            | FileTag::LayerColor
+            | FileTag::LayerLine
+            | FileTag::LayerOverlay,
+        ) => {
+            read_encoded_data(&mut input, options)?;
+            trace::debug!("skipped layer (layers feature disabled)");
+            return Ok(None);
+        }
+        #[cfg(feature = "palette")]
+        Ok(FileTag::Palette) => Ok(FileData::Palette(read_palette_data(&mut input, options)?)),
+        #[cfg(not(feature = "palette"))]
+        Ok(FileTag::Palette) => {
+            read_encoded_data(&mut input, options)?;
+            trace::debug!("skipped palette (palette feature disabled)");
+            return Ok(None);
+        }
         Ok(FileTag::Ttoc) => {
             let count = input.read_u32::<LE>()?;
             let mut offsets = Vec::new();
@@ -246,9 +760,13 @@ where
             }
 
             // read 8 mystery bytes
-            input.read_exact(&mut [0; 8])?;
+            let mut unknown = [0; 8];
+            input.read_exact(&mut unknown)?;
 
-            Ok(FileData::MainOffsets(offsets))
+            Ok(FileData::MainOffsets {
+                offsets,
+                unknown: options.preserve_unknown().then(|| Bytes(unknown.into())),
+            })
         }
         Ok(FileTag::Sign) => {
             // let's hope it's always 74 bytes!
@@ -257,5 +775,120 @@ where
             Ok(FileData::Signature(data.into()))
         }
         Err(tag) => Err(ReadError::UnknownFileTag(tag.number)),
+    };
+    data.map(Some)
+}
+
+/// Returns the data up to and including the next 0 byte, or all of it if there is none (like
+/// `BufRead::read_until`).
+fn read_until_nul(data: &mut &[u8]) -> Vec<u8> {
+    let len = data.iter().position(|b| *b == 0).map_or(data.len(), |i| i + 1);
+    let (string, rest) = data.split_at(len);
+    *data = rest;
+    string.to_vec()
+}
+
+#[cfg(all(test, feature = "std", feature = "layers"))]
+mod tests {
+    use super::*;
+    use crate::testgen::{generate, TestDrawing, TestLayer};
+
+    /// Line art with `shapes` shapes, each with a path of `segments` segments after its first
+    /// point.
+    fn drawing(shapes: usize, segments: usize) -> TestDrawing {
+        TestDrawing {
+            encoding: EncodingTag::Unco,
+            layers: vec![TestLayer::new(Art::Line, shapes, segments)],
+            ..Default::default()
+        }
+    }
+
+    /// Reads the drawing with the default limits, and then with `options`, and returns which
+    /// limit was exceeded.
+    fn exceeded(drawing: &TestDrawing, options: ReadOptions) -> &'static str {
+        let file = generate(drawing).unwrap();
+        read(&file[..]).expect("within the default limits");
+        match read_with_options(&file[..], &options) {
+            Err(ReadError::LimitExceeded { what, .. }) => what,
+            result => panic!("expected a limit to be exceeded, got {result:?}"),
+        }
+    }
+
+    #[test]
+    fn decoded_size_limit() {
+        let options = ReadOptions {
+            max_decoded_size: 64,
+            ..Default::default()
+        };
+        assert_eq!(exceeded(&drawing(1, 1), options), "encoded data length");
+    }
+
+    #[test]
+    fn decompressed_size_limit() {
+        if crate::zlib::BACKEND.is_none() {
+            return;
+        }
+        // the colors are evenly spaced, so they compress well
+        let drawing = TestDrawing {
+            encoding: EncodingTag::Zlib,
+            palette_colors: 2000,
+            ..Default::default()
+        };
+        let options = ReadOptions {
+            max_decoded_size: 20_000,
+            ..Default::default()
+        };
+        assert_eq!(exceeded(&drawing, options), "decompressed length");
+    }
+
+    #[test]
+    fn shape_count_limit() {
+        let options = ReadOptions {
+            max_shape_count: 4,
+            ..Default::default()
+        };
+        let file = generate(&drawing(4, 1)).unwrap();
+        read_with_options(&file[..], &options).unwrap();
+        assert_eq!(exceeded(&drawing(5, 1), options), "shape count");
+    }
+
+    #[test]
+    fn component_count_limit() {
+        let options = ReadOptions {
+            max_component_count: 0,
+            ..Default::default()
+        };
+        assert_eq!(exceeded(&drawing(1, 1), options), "component count");
+    }
+
+    #[test]
+    fn point_count_limit() {
+        // the first point and one point per line, or three per cubic
+        let options = ReadOptions {
+            max_point_count: 10,
+            ..Default::default()
+        };
+        assert_eq!(exceeded(&drawing(1, 10), options), "path point count");
+    }
+
+    #[cfg(feature = "pencil")]
+    #[test]
+    fn thickness_point_count_limit() {
+        // a path of one point, with a thickness definition of two
+        let options = ReadOptions {
+            max_point_count: 1,
+            ..Default::default()
+        };
+        assert_eq!(exceeded(&drawing(1, 0), options), "thickness point count");
+    }
+
+    #[cfg(feature = "palette")]
+    #[test]
+    fn color_count_limit() {
+        let options = ReadOptions {
+            max_color_count: 3,
+            ..Default::default()
+        };
+        assert_eq!(exceeded(&drawing(0, 0), options), "palette color count");
     }
 }
@@ -0,0 +1,145 @@
+//! Reading from async streams, e.g. files fetched over the network.
+//!
+//! The stream is read one top-level tag at a time: each tag's bytes are collected (their length
+//! is always known from the tag header) and then decoded with the regular reader. This way, only
+//! one tag is buffered at a time, though in practice most of a file is the main data tag, which
+//! has to be buffered to be decompressed anyway.
+
+use crate::read::{
+    check_limit, read_header, read_tag, EncodingTag, FileData, FileTag, ReadError, ReadOptions,
+};
+use byteorder::{ByteOrder, BE, LE};
+use tokio::io::{AsyncRead, AsyncReadExt};
+
+/// Length of the file header: magic, version, and two mystery u32s.
+const HEADER_LEN: usize = 8 + 4 + 4 + 4;
+
+/// Length of a `SIGN` tag after the tag itself.
+const SIGN_LEN: usize = 74;
+
+/// Reads a TVG file from an async stream. See [read_async_with_options].
+pub async fn read_async<R>(input: R) -> Result<Vec<FileData>, ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    read_async_with_options(input, &ReadOptions::default()).await
+}
+
+/// Reads a TVG file from an async stream, without waiting for the whole file first.
+///
+/// This reads the same data as [read_with_options](crate::read::read_with_options).
+pub async fn read_async_with_options<R>(
+    mut input: R,
+    options: &ReadOptions,
+) -> Result<Vec<FileData>, ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut header = [0; HEADER_LEN];
+    input.read_exact(&mut header).await?;
+    read_header(&header[..], options)?;
+
+    let mut tags = Vec::new();
+    loop {
+        let mut tag = [0; 4];
+        match read_up_to(&mut input, &mut tag).await? {
+            0 => break,
+            // trailing 0 byte at EOF
+            1 if tag[0] == 0 => break,
+            4 => (),
+            read => return Err(ReadError::TruncatedTag(read)),
+        }
+
+        let mut data = tag.to_vec();
+        read_tag_body(&mut input, BE::read_u32(&tag), &mut data, options).await?;
+        if let Some(tag) = read_tag(&data[..], options)? {
+            tags.push(tag);
+        }
+    }
+    Ok(tags)
+}
+
+/// Reads into the buffer until it's full or the stream ends, and returns how much was read.
+async fn read_up_to<R>(input: &mut R, buf: &mut [u8]) -> Result<usize, ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    let mut read = 0;
+    while read < buf.len() {
+        match input.read(&mut buf[read..]).await? {
+            0 => break,
+            n => read += n,
+        }
+    }
+    Ok(read)
+}
+
+/// Appends `len` bytes from the stream to `out`.
+async fn read_into<R>(input: &mut R, len: usize, out: &mut Vec<u8>) -> Result<(), ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    let start = out.len();
+    out.resize(start + len, 0);
+    input.read_exact(&mut out[start..]).await?;
+    Ok(())
+}
+
+/// Appends a little-endian u32 from the stream to `out`, and returns it.
+async fn read_u32_into<R>(input: &mut R, out: &mut Vec<u8>) -> Result<u32, ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    read_into(input, 4, out).await?;
+    Ok(LE::read_u32(&out[out.len() - 4..]))
+}
+
+/// Appends the rest of a tag (everything after the tag itself) from the stream to `out`.
+async fn read_tag_body<R>(
+    input: &mut R,
+    tag: u32,
+    out: &mut Vec<u8>,
+    options: &ReadOptions,
+) -> Result<(), ReadError>
+where
+    R: AsyncRead + Unpin,
+{
+    match FileTag::try_from(tag) {
+        Ok(FileTag::Cert) => {
+            let len = read_u32_into(input, out).await?;
+            check_limit("certificate length", len, options.max_decoded_size)?;
+            read_into(input, len as usize, out).await
+        }
+        Ok(FileTag::Endt) => Ok(()),
+        Ok(FileTag::Ttoc) => {
+            let count = read_u32_into(input, out).await?;
+            let len = count as u64 * 8 + 8;
+            check_limit("table of contents length", len, options.max_decoded_size)?;
+            read_into(input, len as usize, out).await
+        }
+        Ok(FileTag::Sign) => read_into(input, SIGN_LEN, out).await,
+        Ok(
+            FileTag::MainData
+            | FileTag::Tvci
+            | FileTag::Crea
+            | FileTag::LayerUnderlay
+            | FileTag::LayerColor
+            | FileTag::LayerLine
+            | FileTag::LayerOverlay
+            | FileTag::Palette,
+        ) => {
+            read_into(input, 4, out).await?;
+            let encoding = BE::read_u32(&out[out.len() - 4..]);
+            match EncodingTag::try_from(encoding) {
+                // both are followed by the length of the rest of the block
+                Ok(EncodingTag::Unco | EncodingTag::Zlib) => {
+                    let len = read_u32_into(input, out).await?;
+                    check_limit("encoded data length", len, options.max_decoded_size)?;
+                    read_into(input, len as usize, out).await
+                }
+                Err(tag) => Err(ReadError::UnknownEncoding(tag.number)),
+            }
+        }
+        Err(tag) => Err(ReadError::UnknownFileTag(tag.number)),
+    }
+}
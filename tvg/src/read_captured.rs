@@ -0,0 +1,85 @@
+//! Reading with the raw bytes of each tag, for inspectors that show the bytes a value came from,
+//! and for writing unmodified tags back out exactly as they were.
+
+use crate::io;
+use crate::read::{read_header, read_tag, FileData, FileTag, ReadError, ReadOptions};
+use crate::read_incremental::{tag_len, HEADER_LEN};
+use crate::util::{read_encoded_data, Bytes};
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, BE};
+
+/// A tag and where its bytes are.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct CapturedTag {
+    pub data: FileData,
+    pub raw: RawTag,
+}
+
+/// The bytes of a tag, including the tag number.
+#[derive(Debug, Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct RawTag {
+    /// Offset of the tag in the file, or in the decoded main data for tags inside it.
+    ///
+    /// Tags in `ZLIB`-encoded main data only exist after decompressing it, so their offsets can't
+    /// be offsets in the file.
+    pub offset: usize,
+    pub len: usize,
+    /// A copy of the bytes, if [ReadOptions::capture_raw] is set.
+    pub bytes: Option<Bytes>,
+    /// For the main data tag, the tags inside it, in the same order as in [FileData::Main].
+    pub inner: Vec<RawTag>,
+}
+
+/// Reads a file like [read_with_options](crate::read::read_with_options), but also returns the
+/// byte range of each tag.
+pub fn read_captured(data: &[u8], options: &ReadOptions) -> Result<Vec<CapturedTag>, ReadError> {
+    read_header(data.get(..HEADER_LEN).unwrap_or(data), options)?;
+    let tags = read_tags(&data[HEADER_LEN..], HEADER_LEN, options)?;
+    Ok(tags
+        .into_iter()
+        .map(|(data, raw)| CapturedTag { data, raw })
+        .collect())
+}
+
+/// Reads a sequence of tags. `base` is the offset of `data` in its container.
+fn read_tags(
+    data: &[u8],
+    base: usize,
+    options: &ReadOptions,
+) -> Result<Vec<(FileData, RawTag)>, ReadError> {
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let rest = &data[pos..];
+        let len = tag_len(rest, options)?;
+        let Some(tag_data) = len.and_then(|len| rest.get(..len)) else {
+            return match rest.len() {
+                // trailing 0 byte at EOF
+                1 if rest[0] == 0 => Ok(tags),
+                len if len < 4 => Err(ReadError::TruncatedTag(len)),
+                _ => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+            };
+        };
+
+        let mut raw = RawTag {
+            offset: base + pos,
+            len: tag_data.len(),
+            bytes: options.capture_raw.then(|| Bytes(tag_data.into())),
+            inner: Vec::new(),
+        };
+        if BE::read_u32(tag_data) == u32::from(FileTag::MainData) {
+            let main = read_encoded_data(&tag_data[4..], options)?;
+            let (inner, inner_raw) = read_tags(&main, 0, options)?.into_iter().unzip();
+            raw.inner = inner_raw;
+            tags.push((FileData::Main(inner), raw));
+        } else if let Some(tag) = read_tag(tag_data, options)? {
+            tags.push((tag, raw));
+        }
+        pos += tag_data.len();
+    }
+    Ok(tags)
+}
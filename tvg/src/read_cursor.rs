@@ -0,0 +1,196 @@
+//! Walking the tags of a file without interpreting their contents, for working out what unknown
+//! tags contain.
+
+use crate::read::{read_header, EncodingTag, FileTag, ReadError, ReadOptions};
+use crate::read_incremental::{tag_len, HEADER_LEN};
+use crate::util::read_encoded_data;
+use alloc::borrow::Cow;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, BE, LE};
+
+/// How tags are laid out.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Framing {
+    /// Tags at the top level of a file and in the main data: a big-endian tag number, followed by
+    /// an encoded block for most tags (see [FileTag]). Unknown tags are assumed to have an
+    /// encoded block if one follows the tag number.
+    File,
+    /// Tags like the ones in shapes and palette colors (`TGVS`, `TGBP`, `TCSC`, ...): a
+    /// big-endian tag number, a little-endian length and that many bytes.
+    Chunks,
+}
+
+/// A position in a sequence of tags.
+///
+/// ```
+/// # fn example(file: &[u8]) -> Result<(), tvg::read::ReadError> {
+/// use tvg::read::TagCursor;
+///
+/// let mut cursor = TagCursor::new(file)?;
+/// while !cursor.is_empty() {
+///     println!("{:08x}: {} bytes", cursor.tag().unwrap(), cursor.len());
+///     if let Some(mut inner) = cursor.enter()? {
+///         while !inner.is_empty() {
+///             println!("  {:08x}: {} bytes", inner.tag().unwrap(), inner.len());
+///             inner.advance()?;
+///         }
+///     }
+///     cursor.advance()?;
+/// }
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug, Clone)]
+pub struct TagCursor<'a> {
+    data: Cow<'a, [u8]>,
+    framing: Framing,
+    options: ReadOptions,
+    /// Offset of the current tag.
+    pos: usize,
+    /// Length of the current tag, or 0 at the end.
+    len: usize,
+}
+
+impl<'a> TagCursor<'a> {
+    /// Checks the file header, and starts at the first tag after it.
+    pub fn new(file: &'a [u8]) -> Result<Self, ReadError> {
+        read_header(
+            file.get(..HEADER_LEN).unwrap_or(file),
+            &ReadOptions::default(),
+        )?;
+        Self::from_cow(Cow::Borrowed(file), HEADER_LEN, Framing::File)
+    }
+
+    /// Starts at the first tag in `data`, which contains only tags.
+    pub fn with_framing(data: &'a [u8], framing: Framing) -> Result<Self, ReadError> {
+        Self::from_cow(Cow::Borrowed(data), 0, framing)
+    }
+
+    fn from_cow(data: Cow<'a, [u8]>, pos: usize, framing: Framing) -> Result<Self, ReadError> {
+        let mut cursor = TagCursor {
+            data,
+            framing,
+            options: ReadOptions::default(),
+            pos,
+            len: 0,
+        };
+        cursor.len = cursor.current_len()?;
+        Ok(cursor)
+    }
+
+    /// Returns the length of the tag at `pos`.
+    fn current_len(&self) -> Result<usize, ReadError> {
+        let rest = &self.data[self.pos..];
+        match (self.framing, rest.len()) {
+            (_, 0) => return Ok(0),
+            // trailing 0 byte at EOF
+            (Framing::File, 1) if rest[0] == 0 => return Ok(0),
+            (_, len) if len < 4 => return Err(ReadError::TruncatedTag(len)),
+            _ => (),
+        }
+
+        let len = match self.framing {
+            Framing::File => match FileTag::try_from(BE::read_u32(rest)) {
+                Ok(_) => tag_len(rest, &self.options)?,
+                Err(tag) => {
+                    let encoding = rest.get(4..8).map(BE::read_u32);
+                    if encoding.is_none_or(|encoding| EncodingTag::try_from(encoding).is_err()) {
+                        return Err(ReadError::UnknownFileTag(tag.number));
+                    }
+                    rest.get(8..12).map(|len| 12 + LE::read_u32(len) as usize)
+                }
+            },
+            Framing::Chunks => rest.get(4..8).map(|len| 8 + LE::read_u32(len) as usize),
+        };
+        match len {
+            Some(len) if len <= rest.len() => Ok(len),
+            _ => Err(ReadError::TruncatedTag(rest.len())),
+        }
+    }
+
+    /// Moves to the next tag. Returns false if there is none.
+    pub fn advance(&mut self) -> Result<bool, ReadError> {
+        self.pos += self.len;
+        self.len = 0;
+        self.len = self.current_len()?;
+        Ok(self.len > 0)
+    }
+
+    /// Returns true at the end, where there are no more tags.
+    pub fn is_empty(&self) -> bool {
+        self.len == 0
+    }
+
+    /// The tag number, or None at the end.
+    pub fn tag(&self) -> Option<u32> {
+        (!self.is_empty()).then(|| BE::read_u32(&self.data[self.pos..]))
+    }
+
+    /// The tag, if it's a known file tag.
+    pub fn file_tag(&self) -> Option<FileTag> {
+        self.tag().and_then(|tag| FileTag::try_from(tag).ok())
+    }
+
+    /// The offset of the tag in the file or data the cursor was created with, or in the decoded
+    /// data of the tag that was [entered](TagCursor::enter).
+    pub fn offset(&self) -> usize {
+        self.pos
+    }
+
+    /// The length of the tag in bytes, including the tag number.
+    pub fn len(&self) -> usize {
+        self.len
+    }
+
+    /// All bytes of the tag, including the tag number.
+    pub fn bytes(&self) -> &[u8] {
+        &self.data[self.pos..self.pos + self.len]
+    }
+
+    /// The bytes after the tag number, and after the length for [Framing::Chunks].
+    pub fn body(&self) -> &[u8] {
+        let header = match (self.is_empty(), self.framing) {
+            (true, _) => 0,
+            (false, Framing::File) => 4,
+            (false, Framing::Chunks) => 8,
+        };
+        &self.bytes()[header..]
+    }
+
+    /// The encoding of the tag's encoded block, if it has one.
+    pub fn encoding(&self) -> Option<EncodingTag> {
+        if self.framing != Framing::File || self.len < 12 {
+            return None;
+        }
+        match self.file_tag() {
+            Some(FileTag::Cert | FileTag::Endt | FileTag::Ttoc | FileTag::Sign) => None,
+            _ => EncodingTag::try_from(BE::read_u32(self.body())).ok(),
+        }
+    }
+
+    /// Decodes the tag's encoded block, if it has one.
+    pub fn decoded(&self) -> Result<Option<Vec<u8>>, ReadError> {
+        match self.encoding() {
+            Some(_) => Ok(Some(read_encoded_data(self.body(), &self.options)?)),
+            None => Ok(None),
+        }
+    }
+
+    /// Returns a cursor at the first of the tags inside this one, if it's known to contain tags:
+    /// the main data tag, which is decoded first, and any tag with [Framing::Chunks], whose
+    /// body is assumed to be a sequence of chunks.
+    ///
+    /// Many chunks don't contain only tags, e.g. `TGLY` starts with a 6 byte header, and the
+    /// `TGSD` tags in `TGVS` are each followed by an extra byte. To walk those, use
+    /// [TagCursor::with_framing] on the right part of the [body](TagCursor::body).
+    pub fn enter(&self) -> Result<Option<TagCursor<'_>>, ReadError> {
+        match self.framing {
+            Framing::File if self.file_tag() == Some(FileTag::MainData) => {
+                let data = read_encoded_data(self.body(), &self.options)?;
+                TagCursor::from_cow(Cow::Owned(data), 0, Framing::File).map(Some)
+            }
+            Framing::File => Ok(None),
+            Framing::Chunks => TagCursor::with_framing(self.body(), Framing::Chunks).map(Some),
+        }
+    }
+}
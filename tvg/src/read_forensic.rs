@@ -0,0 +1,70 @@
+//! Reading with [ParseProfile::Forensic](crate::read::ParseProfile::Forensic), which keeps tags
+//! that can't be read instead of stopping at the first error.
+
+use crate::io;
+use crate::read::{read_tag, FileData, FileTag, ReadError, ReadOptions};
+use crate::read_incremental::tag_len;
+use crate::trace;
+use crate::util::{read_encoded_data, Bytes};
+use alloc::string::ToString;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, BE};
+
+/// Reads a sequence of tags. Tags that can't be read become [FileData::Unreadable].
+pub(crate) fn read_tags_forensic(data: &[u8], options: &ReadOptions) -> Vec<FileData> {
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let rest = &data[pos..];
+        let len = match tag_len(rest, options) {
+            Ok(Some(len)) if len <= rest.len() => len,
+            // trailing 0 byte at EOF
+            Ok(_) if rest == [0] => break,
+            Ok(_) => {
+                let error = match rest.len() {
+                    len if len < 4 => ReadError::TruncatedTag(len),
+                    _ => io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+                };
+                tags.push(unreadable(rest, error));
+                break;
+            }
+            // the end of the tag is unknown, so nothing after it can be read either
+            Err(error) => {
+                tags.push(unreadable(rest, error));
+                break;
+            }
+        };
+        let tag_data = &rest[..len];
+
+        let is_main = BE::read_u32(tag_data) == u32::from(FileTag::MainData);
+        let result = if is_main {
+            // read the main data the same way, so one bad layer doesn't lose the others
+            read_encoded_data(&tag_data[4..], options)
+                .map(|main| Some(FileData::Main(read_tags_forensic(&main, options))))
+        } else {
+            read_tag(tag_data, options)
+        };
+        match result {
+            Ok(Some(tag)) => tags.push(tag),
+            Ok(None) => (),
+            Err(error) => tags.push(unreadable(tag_data, error)),
+        }
+        pos += len;
+    }
+    tags
+}
+
+/// Keeps the bytes of a tag that couldn't be read. If there aren't enough bytes for a tag number,
+/// the tag number is 0 and the data is all of them.
+fn unreadable(data: &[u8], error: ReadError) -> FileData {
+    trace::debug!(%error, len = data.len(), "kept unreadable tag");
+    let (tag, data) = match data.len() {
+        4.. => (BE::read_u32(data), &data[4..]),
+        _ => (0, data),
+    };
+    FileData::Unreadable {
+        tag,
+        data: Bytes(data.into()),
+        error: error.to_string(),
+    }
+}
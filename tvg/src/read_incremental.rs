@@ -0,0 +1,246 @@
+//! Reading from data that arrives in chunks, without blocking on the whole file.
+//!
+//! Like [read_async](crate::read::read_async), this collects one top-level tag at a time and
+//! decodes it with the regular reader once it's complete, but the caller pushes data in instead
+//! of the reader pulling it.
+
+use crate::io;
+use crate::read::{
+    check_limit, read_header, read_tag, EncodingTag, FileData, FileHeader, FileTag, ReadError,
+    ReadOptions,
+};
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, BE, LE};
+
+/// Length of the file header: magic, version, and two mystery u32s.
+pub(crate) const HEADER_LEN: usize = 8 + 4 + 4 + 4;
+
+/// Length of a `SIGN` tag after the tag itself.
+const SIGN_LEN: usize = 74;
+
+/// A TVG reader that's fed data in chunks.
+///
+/// Each top-level tag is decoded as soon as all of its data has arrived. Most of a file is the
+/// main data tag, though, so most of the work happens when its last chunk is fed.
+///
+/// ```
+/// # fn example(chunks: &[&[u8]]) -> Result<(), tvg::read::ReadError> {
+/// use tvg::read::IncrementalReader;
+///
+/// let mut reader = IncrementalReader::new(Default::default());
+/// for chunk in chunks {
+///     reader.feed(chunk)?;
+/// }
+/// let tags = reader.finish()?;
+/// # Ok(())
+/// # }
+/// ```
+#[derive(Debug)]
+pub struct IncrementalReader {
+    options: ReadOptions,
+    /// Data that hasn't been decoded yet.
+    buffer: Vec<u8>,
+    /// Total length of the data that has been decoded.
+    consumed: usize,
+    header: Option<FileHeader>,
+    tags: Vec<FileData>,
+}
+
+impl IncrementalReader {
+    pub fn new(options: ReadOptions) -> Self {
+        IncrementalReader {
+            options,
+            buffer: Vec::new(),
+            consumed: 0,
+            header: None,
+            tags: Vec::new(),
+        }
+    }
+
+    /// Adds data, and decodes all tags that are complete.
+    ///
+    /// After an error, the reader shouldn't be used anymore.
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), ReadError> {
+        self.buffer.extend_from_slice(chunk);
+
+        let mut pos = 0;
+        if self.header.is_none() {
+            if self.buffer.len() < HEADER_LEN {
+                return Ok(());
+            }
+            self.header = Some(read_header(&self.buffer[..HEADER_LEN], &self.options)?);
+            pos = HEADER_LEN;
+        }
+
+        while let Some(len) = tag_len(&self.buffer[pos..], &self.options)? {
+            if self.buffer.len() - pos < len {
+                break;
+            }
+            if let Some(tag) = read_tag(&self.buffer[pos..pos + len], &self.options)? {
+                self.tags.push(tag);
+            }
+            pos += len;
+        }
+
+        self.buffer.drain(..pos);
+        self.consumed += pos;
+        Ok(())
+    }
+
+    /// Number of bytes that have been decoded. Data that belongs to an incomplete tag isn't
+    /// counted until the tag is complete.
+    pub fn bytes_read(&self) -> usize {
+        self.consumed
+    }
+
+    /// Number of bytes that have been fed, including data that hasn't been decoded yet.
+    pub fn bytes_fed(&self) -> usize {
+        self.consumed + self.buffer.len()
+    }
+
+    /// The file header, once it has been read.
+    pub fn header(&self) -> Option<FileHeader> {
+        self.header
+    }
+
+    /// The tags that have been decoded so far.
+    pub fn tags(&self) -> &[FileData] {
+        &self.tags
+    }
+
+    /// Returns all tags, or an error if the data ended in the middle of a tag.
+    pub fn finish(self) -> Result<Vec<FileData>, ReadError> {
+        let header_read = self.header.is_some();
+        match self.buffer.len() {
+            0 if header_read => Ok(self.tags),
+            // trailing 0 byte at EOF
+            1 if header_read && self.buffer[0] == 0 => Ok(self.tags),
+            len if header_read && len < 4 => Err(ReadError::TruncatedTag(len)),
+            _ => Err(io::Error::from(io::ErrorKind::UnexpectedEof).into()),
+        }
+    }
+}
+
+/// Returns whether `data` looks like a whole TVG file: a valid header, followed by complete
+/// top-level tags up to a `SIGN` tag at the end (and the optional trailing 0 byte).
+///
+/// Harmony writes `SIGN` last, so this is useful to tell whether a file that's being written has
+/// been saved completely. Files without a signature (see
+/// [Document::strip_signing](crate::document::Document::strip_signing)) are never complete.
+pub fn is_complete(data: &[u8]) -> bool {
+    let options = ReadOptions::default();
+    let Some(header) = data.get(..HEADER_LEN) else {
+        return false;
+    };
+    if read_header(header, &options).is_err() {
+        return false;
+    }
+    let mut rest = &data[HEADER_LEN..];
+    loop {
+        let len = match tag_len(rest, &options) {
+            Ok(Some(len)) if len <= rest.len() => len,
+            _ => return false,
+        };
+        let (tag, after) = rest.split_at(len);
+        if BE::read_u32(tag) == u32::from(FileTag::Sign) {
+            return after.is_empty() || after == [0];
+        }
+        rest = after;
+    }
+}
+
+/// Returns the total length of the tag at the start of `data`, or None if there isn't enough
+/// data to tell yet.
+pub(crate) fn tag_len(data: &[u8], options: &ReadOptions) -> Result<Option<usize>, ReadError> {
+    let u32_at = |offset: usize| data.get(offset..offset + 4).map(LE::read_u32);
+    let Some(tag) = data.get(..4).map(BE::read_u32) else {
+        return Ok(None);
+    };
+
+    let len = match FileTag::try_from(tag) {
+        Ok(FileTag::Cert) => u32_at(4).map(|len| {
+            check_limit("certificate length", len, options.max_decoded_size)?;
+            Ok(8 + len as usize)
+        }),
+        Ok(FileTag::Endt) => Some(Ok(4)),
+        Ok(FileTag::Ttoc) => u32_at(4).map(|count| {
+            let len = count as u64 * 8 + 8;
+            check_limit("table of contents length", len, options.max_decoded_size)?;
+            Ok(8 + len as usize)
+        }),
+        Ok(FileTag::Sign) => Some(Ok(4 + SIGN_LEN)),
+        Ok(
+            FileTag::MainData
+            | FileTag::Tvci
+            | FileTag::Crea
+            | FileTag::LayerUnderlay
+            | FileTag::LayerColor
+            | FileTag::LayerLine
+            | FileTag::LayerOverlay
+            | FileTag::Palette,
+        ) => {
+            let Some(encoding) = data.get(4..8).map(BE::read_u32) else {
+                return Ok(None);
+            };
+            match EncodingTag::try_from(encoding) {
+                // both are followed by the length of the rest of the block
+                Ok(EncodingTag::Unco | EncodingTag::Zlib) => u32_at(8).map(|len| {
+                    check_limit("encoded data length", len, options.max_decoded_size)?;
+                    Ok(12 + len as usize)
+                }),
+                Err(tag) => return Err(ReadError::UnknownEncoding(tag.number)),
+            }
+        }
+        Err(tag) => return Err(ReadError::UnknownFileTag(tag.number)),
+    };
+    len.transpose()
+}
+
+#[cfg(all(test, feature = "std", feature = "layers"))]
+mod tests {
+    use super::*;
+    use crate::document::Art;
+    use crate::read::read;
+    use crate::testgen::{generate, TestDrawing, TestLayer};
+
+    fn read_in_chunks(file: &[u8], chunk_len: usize) -> Result<Vec<FileData>, ReadError> {
+        let mut reader = IncrementalReader::new(ReadOptions::default());
+        for chunk in file.chunks(chunk_len) {
+            reader.feed(chunk)?;
+        }
+        assert_eq!(reader.bytes_fed(), file.len());
+        reader.finish()
+    }
+
+    #[test]
+    fn chunks_read_like_whole_file() {
+        for encoding in [EncodingTag::Unco, EncodingTag::Zlib] {
+            if encoding == EncodingTag::Zlib && crate::zlib::BACKEND.is_none() {
+                continue;
+            }
+            let file = generate(&TestDrawing {
+                encoding,
+                layers: vec![
+                    TestLayer::new(Art::Line, 5, 20),
+                    TestLayer::new(Art::Color, 3, 10),
+                ],
+                ..Default::default()
+            })
+            .unwrap();
+            let expected = format!("{:?}", read(&file[..]).unwrap());
+            for chunk_len in [1, 3, 64, 1000, file.len()] {
+                let tags = read_in_chunks(&file, chunk_len).unwrap();
+                assert_eq!(format!("{tags:?}"), expected, "{chunk_len}-byte chunks");
+            }
+        }
+    }
+
+    #[test]
+    fn truncated_file_is_an_error() {
+        // ends in the middle of the signature
+        let file = generate(&TestDrawing::default()).unwrap();
+        let mut reader = IncrementalReader::new(ReadOptions::default());
+        reader.feed(&file[..file.len() - 10]).unwrap();
+        assert!(reader.finish().is_err());
+    }
+}
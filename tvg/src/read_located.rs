@@ -0,0 +1,116 @@
+//! Reading with error locations, for tools that point at the bytes a read error came from.
+
+use crate::io;
+use crate::read::{read_header, read_tag, FileData, FileTag, ReadError, ReadOptions};
+use crate::read_incremental::{tag_len, HEADER_LEN};
+use crate::util::RawBlock;
+use alloc::vec::Vec;
+use byteorder::{ByteOrder, BE};
+use thiserror::Error;
+
+/// A read error, and where in the file it happened.
+#[derive(Debug, Error)]
+#[error("{error} (at offset {offset})")]
+pub struct LocatedReadError {
+    #[source]
+    pub error: ReadError,
+    /// Offset of the tag that contains the error, or 0 if the header is wrong.
+    ///
+    /// Tags in `ZLIB`-encoded main data only exist after decompressing it, so errors in them point
+    /// at the main data tag.
+    pub offset: usize,
+    /// The tags that contain the error, outermost first. Empty if the header is wrong.
+    pub tag_path: Vec<FileTag>,
+}
+
+/// Reads a file like [read_with_options](crate::read::read_with_options), but returns where an
+/// error happened.
+pub fn read_located(data: &[u8], options: &ReadOptions) -> Result<Vec<FileData>, LocatedReadError> {
+    let header = data.get(..HEADER_LEN).unwrap_or(data);
+    read_header(header, options).map_err(|error| LocatedReadError {
+        error,
+        offset: 0,
+        tag_path: Vec::new(),
+    })?;
+    read_tags(
+        &data[HEADER_LEN..],
+        &|pos| HEADER_LEN + pos,
+        &mut Vec::new(),
+        options,
+    )
+}
+
+/// Reads a sequence of tags. `file_offset` maps positions in `data` to offsets in the file.
+fn read_tags(
+    data: &[u8],
+    file_offset: &dyn Fn(usize) -> usize,
+    path: &mut Vec<FileTag>,
+    options: &ReadOptions,
+) -> Result<Vec<FileData>, LocatedReadError> {
+    let located = |error, pos, path: &[FileTag]| LocatedReadError {
+        error,
+        offset: file_offset(pos),
+        tag_path: path.to_vec(),
+    };
+
+    let mut tags = Vec::new();
+    let mut pos = 0;
+    while pos < data.len() {
+        let rest = &data[pos..];
+        let len = tag_len(rest, options).map_err(|error| located(error, pos, path))?;
+        let Some(tag_data) = len.and_then(|len| rest.get(..len)) else {
+            let error = match rest.len() {
+                // trailing 0 byte at EOF
+                1 if rest[0] == 0 => break,
+                len if len < 4 => ReadError::TruncatedTag(len),
+                _ => io::Error::from(io::ErrorKind::UnexpectedEof).into(),
+            };
+            return Err(located(error, pos, path));
+        };
+
+        match read_tag(tag_data, options) {
+            Ok(Some(tag)) => tags.push(tag),
+            Ok(None) => (),
+            Err(error) => {
+                // tag_len has already checked that the tag is known
+                if let Ok(tag) = FileTag::try_from(BE::read_u32(tag_data)) {
+                    path.push(tag);
+                    if tag == FileTag::MainData {
+                        locate_in_main(&tag_data[4..], pos, file_offset, path, options)?;
+                    }
+                }
+                return Err(located(error, pos, path));
+            }
+        }
+        pos += tag_data.len();
+    }
+    Ok(tags)
+}
+
+/// Reads the tags in the main data block at `pos`, to find the one that contains an error.
+fn locate_in_main(
+    block: &[u8],
+    pos: usize,
+    file_offset: &dyn Fn(usize) -> usize,
+    path: &mut Vec<FileTag>,
+    options: &ReadOptions,
+) -> Result<(), LocatedReadError> {
+    // errors in the block itself are errors in the main data tag
+    let Ok(block) = RawBlock::read(block, options) else {
+        return Ok(());
+    };
+    let is_stored = block.stated_len.is_none();
+    let Ok(data) = block.decode(options) else {
+        return Ok(());
+    };
+
+    // uncompressed data starts after the tag, the encoding, and the length
+    let inner_offset = |inner: usize| {
+        if is_stored {
+            file_offset(pos + 12 + inner)
+        } else {
+            file_offset(pos)
+        }
+    };
+    read_tags(&data, &inner_offset, path, options).map(|_| ())
+}
@@ -0,0 +1,582 @@
+//! Reconstruction of fill regions from the stroke graph.
+//!
+//! Harmony fills are bounded by strokes (including invisible ones). This computes the planar map
+//! of all stroke center lines: strokes are split where they cross, stroke ends that don't bound
+//! anything are removed, and the faces of the resulting graph are the regions. Each region is
+//! then given the color of the topmost fill that covers it.
+
+use crate::document::Document;
+use crate::geometry::{self, Bounds};
+use crate::layer::{LayerData, PathSegment, Point, ShapeComponentData, ShapePath, ShapeType};
+use std::collections::{HashMap, HashSet};
+
+#[derive(Debug, Clone)]
+pub struct RegionOptions {
+    /// Flattening tolerance for stroke and fill paths, in drawing units.
+    pub tolerance: f32,
+    /// Vertices closer than this are merged, in drawing units.
+    pub snap: f32,
+    /// Regions with a smaller area (in square drawing units) are dropped.
+    pub min_area: f32,
+}
+
+impl Default for RegionOptions {
+    fn default() -> Self {
+        RegionOptions {
+            tolerance: geometry::FLATTEN_TOLERANCE,
+            snap: geometry::CLOSE_EPSILON,
+            min_area: 1.,
+        }
+    }
+}
+
+/// A closed region of the stroke graph.
+#[derive(Debug, Clone)]
+pub struct FillRegion {
+    /// The outer boundary, counterclockwise (in Y-up coordinates). The first point is not
+    /// repeated at the end.
+    pub outline: Vec<Point>,
+    /// Boundaries of strokes that are completely inside the region, clockwise.
+    pub holes: Vec<Vec<Point>>,
+    /// The color of the topmost fill that covers the region, or None if it's not filled.
+    pub color_id: Option<u64>,
+    /// The area of the region, excluding holes.
+    pub area: f32,
+}
+
+impl FillRegion {
+    /// Returns the outline and holes as closed paths, which can be filled using either the
+    /// even-odd or the nonzero rule.
+    pub fn paths(&self) -> Vec<ShapePath> {
+        std::iter::once(&self.outline)
+            .chain(&self.holes)
+            .map(|ring| ShapePath {
+                segments: ring
+                    .iter()
+                    .chain(ring.first())
+                    .map(|p| PathSegment::Line(*p))
+                    .collect(),
+                extra: None,
+            })
+            .collect()
+    }
+}
+
+/// Computes the regions of all art layers together.
+pub fn compute_regions(document: &Document) -> Vec<FillRegion> {
+    compute_regions_with_options(document, &RegionOptions::default())
+}
+
+/// Like [compute_regions], but with custom options.
+pub fn compute_regions_with_options(
+    document: &Document,
+    options: &RegionOptions,
+) -> Vec<FillRegion> {
+    let shapes = document.layers().flat_map(|(_, layer)| match layer {
+        LayerData::Vector { shapes, .. } => &shapes[..],
+        _ => &[][..],
+    });
+
+    let mut strokes = Vec::new();
+    let mut fills = Vec::new();
+    for shape in shapes {
+        let mut paths = Vec::new();
+        let mut color_id = None;
+        for tag in shape.components.iter().flat_map(|c| &c.tags) {
+            match tag {
+                ShapeComponentData::Info(info) => color_id = color_id.or(info.color_id),
+                ShapeComponentData::Path(path) => {
+                    let points = geometry::flatten(path, options.tolerance);
+                    paths.push(points.into_iter().map(to_f64).collect::<Vec<_>>());
+                }
+                _ => (),
+            }
+        }
+        match shape.ty {
+            ShapeType::Stroke | ShapeType::Line => strokes.extend(paths),
+            ShapeType::Fill => fills.push(Fill::new(paths, color_id)),
+            _ => (),
+        }
+    }
+
+    let mut graph = Graph::new(options.snap as f64);
+    for (a, b) in split_at_intersections(&strokes) {
+        graph.add_edge(a, b);
+    }
+    graph.prune_dangling();
+
+    let mut faces = Vec::new();
+    let mut outer_boundaries = Vec::new();
+    for cycle in graph.cycles() {
+        let ring: Vec<P> = cycle.iter().map(|&i| graph.vertices[i]).collect();
+        let area = signed_area(&ring);
+        if area > 0. {
+            faces.push((ring, area, graph.component[cycle[0]]));
+        } else if area < 0. {
+            outer_boundaries.push((ring, -area, graph.component[cycle[0]]));
+        }
+    }
+
+    // the outer boundary of a connected part of the graph is a hole in the smallest face of
+    // another part that contains it
+    let mut holes = vec![Vec::new(); faces.len()];
+    for (ring, area, component) in outer_boundaries {
+        let containing = faces
+            .iter()
+            .enumerate()
+            .filter(|(_, (face, face_area, face_component))| {
+                *face_component != component
+                    && *face_area > area
+                    && geometry::contains_even_odd([&face[..]], ring[0])
+            })
+            .min_by(|(_, a), (_, b)| a.1.total_cmp(&b.1));
+        if let Some((i, _)) = containing {
+            holes[i].push(ring);
+        }
+    }
+
+    faces
+        .into_iter()
+        .zip(holes)
+        .filter_map(|((outline, area, _), holes)| {
+            let area = area - holes.iter().map(|h| -signed_area(h)).sum::<f64>();
+            if area < options.min_area as f64 {
+                return None;
+            }
+            let color_id = interior_point(&outline, &holes).and_then(|p| {
+                fills
+                    .iter()
+                    .rev()
+                    .find(|fill| fill.contains(p))
+                    .and_then(|fill| fill.color_id)
+            });
+            let to_points = |ring: Vec<P>| ring.into_iter().map(to_f32).collect();
+            Some(FillRegion {
+                outline: to_points(outline),
+                holes: holes.into_iter().map(to_points).collect(),
+                color_id,
+                area: area as f32,
+            })
+        })
+        .collect()
+}
+
+/// Points are converted to f64 to keep intersections precise.
+type P = (f64, f64);
+
+fn to_f64(p: Point) -> P {
+    (p.0 as f64, p.1 as f64)
+}
+
+fn to_f32(p: P) -> Point {
+    (p.0 as f32, p.1 as f32)
+}
+
+fn sub(a: P, b: P) -> P {
+    (a.0 - b.0, a.1 - b.1)
+}
+
+fn cross(a: P, b: P) -> f64 {
+    a.0 * b.1 - a.1 * b.0
+}
+
+fn dot(a: P, b: P) -> f64 {
+    a.0 * b.0 + a.1 * b.1
+}
+
+fn lerp(a: P, b: P, t: f64) -> P {
+    (a.0 + (b.0 - a.0) * t, a.1 + (b.1 - a.1) * t)
+}
+
+fn signed_area(ring: &[P]) -> f64 {
+    let mut area = 0.;
+    for (i, a) in ring.iter().enumerate() {
+        let b = ring[(i + 1) % ring.len()];
+        area += cross(*a, b);
+    }
+    area / 2.
+}
+
+/// Returns a point well inside a region: the middle of the widest horizontal span at one of a few
+/// heights.
+fn interior_point(outline: &[P], holes: &[Vec<P>]) -> Option<P> {
+    let (min_y, max_y) = outline.iter().fold((f64::MAX, f64::MIN), |(min, max), p| {
+        (min.min(p.1), max.max(p.1))
+    });
+
+    let mut best: Option<(f64, P)> = None;
+    for fraction in [0.5, 0.25, 0.75, 0.125, 0.375, 0.625, 0.875] {
+        let y = min_y + (max_y - min_y) * fraction;
+        let mut xs = Vec::new();
+        for ring in std::iter::once(outline).chain(holes.iter().map(|h| &h[..])) {
+            for (i, a) in ring.iter().enumerate() {
+                let b = ring[(i + 1) % ring.len()];
+                if (a.1 > y) != (b.1 > y) {
+                    xs.push(a.0 + (y - a.1) * (b.0 - a.0) / (b.1 - a.1));
+                }
+            }
+        }
+        xs.sort_by(f64::total_cmp);
+        for span in xs.chunks_exact(2) {
+            let width = span[1] - span[0];
+            if best.is_none_or(|(best_width, _)| width > best_width) {
+                best = Some((width, ((span[0] + span[1]) / 2., y)));
+            }
+        }
+    }
+    best.map(|(_, p)| p)
+}
+
+struct Fill {
+    paths: Vec<Vec<P>>,
+    bounds: Option<Bounds>,
+    color_id: Option<u64>,
+}
+
+impl Fill {
+    fn new(paths: Vec<Vec<P>>, color_id: Option<u64>) -> Self {
+        let points: Vec<Point> = paths.iter().flatten().copied().map(to_f32).collect();
+        Fill {
+            bounds: Bounds::from_points(&points),
+            paths,
+            color_id,
+        }
+    }
+
+    fn contains(&self, p: P) -> bool {
+        let Some(bounds) = self.bounds else {
+            return false;
+        };
+        let (x, y) = to_f32(p);
+        if x < bounds.min.0 || x > bounds.max.0 || y < bounds.min.1 || y > bounds.max.1 {
+            return false;
+        }
+        geometry::contains_even_odd(self.paths.iter().map(|path| &path[..]), p)
+    }
+}
+
+/// Splits the segments of polylines wherever they touch another segment.
+fn split_at_intersections(polylines: &[Vec<P>]) -> Vec<(P, P)> {
+    let segments: Vec<(P, P)> = polylines
+        .iter()
+        .flat_map(|line| line.windows(2).map(|w| (w[0], w[1])))
+        .filter(|(a, b)| a != b)
+        .collect();
+
+    // split positions along each segment
+    let mut splits: Vec<Vec<f64>> = vec![vec![0., 1.]; segments.len()];
+
+    // sweep along x so only segments with overlapping x ranges are compared
+    let min_x = |(a, b): (P, P)| a.0.min(b.0);
+    let max_x = |(a, b): (P, P)| a.0.max(b.0);
+    let mut order: Vec<usize> = (0..segments.len()).collect();
+    order.sort_by(|a, b| min_x(segments[*a]).total_cmp(&min_x(segments[*b])));
+
+    for (k, &i) in order.iter().enumerate() {
+        let a = segments[i];
+        for &j in &order[k + 1..] {
+            let b = segments[j];
+            if min_x(b) > max_x(a) {
+                break;
+            }
+            if a.0 .1.max(a.1 .1) < b.0 .1.min(b.1 .1) || b.0 .1.max(b.1 .1) < a.0 .1.min(a.1 .1) {
+                continue;
+            }
+            for (t, u) in intersect(a, b) {
+                splits[i].push(t);
+                splits[j].push(u);
+            }
+        }
+    }
+
+    let mut out = Vec::new();
+    for ((a, b), mut ts) in segments.into_iter().zip(splits) {
+        ts.sort_by(f64::total_cmp);
+        ts.dedup();
+        out.extend(ts.windows(2).map(|t| (lerp(a, b, t[0]), lerp(a, b, t[1]))));
+    }
+    out
+}
+
+/// Returns the positions along both segments where they touch. Collinear overlapping segments
+/// touch at the end points inside the other segment.
+fn intersect((a0, a1): (P, P), (b0, b1): (P, P)) -> Vec<(f64, f64)> {
+    const EPSILON: f64 = 1e-9;
+    let r = sub(a1, a0);
+    let s = sub(b1, b0);
+    let q = sub(b0, a0);
+    let denom = cross(r, s);
+    let in_range = |t: f64| (-EPSILON..=1. + EPSILON).contains(&t);
+
+    if denom.abs() > EPSILON * dot(r, r).sqrt() * dot(s, s).sqrt() {
+        let t = cross(q, s) / denom;
+        let u = cross(q, r) / denom;
+        if in_range(t) && in_range(u) {
+            return vec![(t.clamp(0., 1.), u.clamp(0., 1.))];
+        }
+        return Vec::new();
+    }
+
+    // parallel; check whether they're on the same line
+    if cross(q, r).abs() > 1e-6 * dot(r, r).sqrt() {
+        return Vec::new();
+    }
+    let project_a = |p: P| dot(sub(p, a0), r) / dot(r, r);
+    let project_b = |p: P| dot(sub(p, b0), s) / dot(s, s);
+    let mut out = Vec::new();
+    for (t, u) in [
+        (project_a(b0), 0.),
+        (project_a(b1), 1.),
+        (0., project_b(a0)),
+        (1., project_b(a1)),
+    ] {
+        if in_range(t) && in_range(u) {
+            out.push((t.clamp(0., 1.), u.clamp(0., 1.)));
+        }
+    }
+    out
+}
+
+/// An undirected graph with vertices merged within a snapping distance.
+struct Graph {
+    snap: f64,
+    vertices: Vec<P>,
+    grid: HashMap<(i64, i64), Vec<usize>>,
+    edges: HashSet<(usize, usize)>,
+    adjacent: Vec<Vec<usize>>,
+    /// The connected part of the graph that each vertex belongs to (set by [Graph::cycles]).
+    component: Vec<usize>,
+}
+
+impl Graph {
+    fn new(snap: f64) -> Self {
+        Graph {
+            snap: snap.max(1e-6),
+            vertices: Vec::new(),
+            grid: HashMap::new(),
+            edges: HashSet::new(),
+            adjacent: Vec::new(),
+            component: Vec::new(),
+        }
+    }
+
+    fn vertex(&mut self, p: P) -> usize {
+        let cell = (
+            (p.0 / self.snap).floor() as i64,
+            (p.1 / self.snap).floor() as i64,
+        );
+        for dx in -1..=1 {
+            for dy in -1..=1 {
+                let Some(candidates) = self.grid.get(&(cell.0 + dx, cell.1 + dy)) else {
+                    continue;
+                };
+                for &i in candidates {
+                    let v = self.vertices[i];
+                    if (v.0 - p.0).hypot(v.1 - p.1) <= self.snap {
+                        return i;
+                    }
+                }
+            }
+        }
+        let i = self.vertices.len();
+        self.vertices.push(p);
+        self.adjacent.push(Vec::new());
+        self.grid.entry(cell).or_default().push(i);
+        i
+    }
+
+    fn add_edge(&mut self, a: P, b: P) {
+        let a = self.vertex(a);
+        let b = self.vertex(b);
+        if a != b && self.edges.insert((a.min(b), a.max(b))) {
+            self.adjacent[a].push(b);
+            self.adjacent[b].push(a);
+        }
+    }
+
+    /// Removes edges that lead to a dead end, since they can't bound a region.
+    fn prune_dangling(&mut self) {
+        let mut queue: Vec<usize> = (0..self.vertices.len())
+            .filter(|&i| self.adjacent[i].len() == 1)
+            .collect();
+        while let Some(i) = queue.pop() {
+            let Some(&j) = self.adjacent[i].first() else {
+                continue;
+            };
+            self.adjacent[i].clear();
+            self.adjacent[j].retain(|&k| k != i);
+            if self.adjacent[j].len() == 1 {
+                queue.push(j);
+            }
+        }
+    }
+
+    /// Returns all face boundaries as cycles of vertex indices.
+    ///
+    /// Bounded faces are counterclockwise. Each connected part of the graph also has one
+    /// clockwise cycle around its outside.
+    fn cycles(&mut self) -> Vec<Vec<usize>> {
+        // sort neighbors counterclockwise
+        for i in 0..self.vertices.len() {
+            let v = self.vertices[i];
+            let angle = |j: &usize| {
+                let d = sub(self.vertices[*j], v);
+                d.1.atan2(d.0)
+            };
+            let mut adjacent = std::mem::take(&mut self.adjacent[i]);
+            adjacent.sort_by(|a, b| angle(a).total_cmp(&angle(b)));
+            self.adjacent[i] = adjacent;
+        }
+
+        self.component = vec![usize::MAX; self.vertices.len()];
+        for start in 0..self.vertices.len() {
+            if self.component[start] != usize::MAX {
+                continue;
+            }
+            let mut stack = vec![start];
+            self.component[start] = start;
+            while let Some(i) = stack.pop() {
+                for &j in &self.adjacent[i] {
+                    if self.component[j] == usize::MAX {
+                        self.component[j] = start;
+                        stack.push(j);
+                    }
+                }
+            }
+        }
+
+        let mut visited = HashSet::new();
+        let mut cycles = Vec::new();
+        for start in 0..self.vertices.len() {
+            for &next in &self.adjacent[start] {
+                if visited.contains(&(start, next)) {
+                    continue;
+                }
+                let mut cycle = Vec::new();
+                let (mut a, mut b) = (start, next);
+                while visited.insert((a, b)) {
+                    cycle.push(a);
+                    // keep the face on the left by taking the next edge clockwise from the one
+                    // we came from
+                    let adjacent = &self.adjacent[b];
+                    let back = adjacent.iter().position(|&i| i == a).unwrap_or(0);
+                    let c = adjacent[(back + adjacent.len() - 1) % adjacent.len()];
+                    (a, b) = (b, c);
+                }
+                cycles.push(cycle);
+            }
+        }
+        cycles
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::layer::{ComponentInfo, ComponentType, LayerTrailer, ShapeComponent, VectorShape};
+    use crate::read::FileData;
+
+    fn shape(ty: ShapeType, color_id: Option<u64>, points: &[Point]) -> VectorShape {
+        let info = ComponentInfo {
+            ty: match ty {
+                ShapeType::Fill => ComponentType::Fill,
+                _ => ComponentType::Stroke,
+            },
+            color_id,
+            pencil_value: None,
+            unknown_prefix: None,
+            unknown_suffix: None,
+        };
+        let path = ShapePath {
+            segments: points.iter().map(|p| PathSegment::Line(*p)).collect(),
+            extra: None,
+        };
+        VectorShape {
+            ty,
+            components: vec![ShapeComponent {
+                tags: vec![
+                    ShapeComponentData::Info(info),
+                    ShapeComponentData::Path(path),
+                ],
+                trailer: None,
+            }],
+        }
+    }
+
+    fn stroke(points: &[Point]) -> VectorShape {
+        shape(ShapeType::Stroke, None, points)
+    }
+
+    fn square(min: f32, max: f32) -> Vec<Point> {
+        vec![(min, min), (max, min), (max, max), (min, max), (min, min)]
+    }
+
+    fn regions(shapes: Vec<VectorShape>) -> Vec<FillRegion> {
+        let document = Document::from(vec![FileData::LayerColor(LayerData::Vector {
+            shapes,
+            trailer: LayerTrailer::default(),
+        })]);
+        let options = RegionOptions {
+            min_area: 0.,
+            ..Default::default()
+        };
+        compute_regions_with_options(&document, &options)
+    }
+
+    #[test]
+    fn crossing_strokes_form_a_filled_region() {
+        let regions = regions(vec![
+            shape(ShapeType::Fill, Some(7), &square(-5., 15.)),
+            stroke(&[(-2., 0.), (10., 0.), (10., 12.)]),
+            stroke(&[(12., 10.), (0., 10.), (0., -2.)]),
+        ]);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].color_id, Some(7));
+        assert!((regions[0].area - 100.).abs() < 1e-3);
+        assert!(regions[0].holes.is_empty());
+    }
+
+    #[test]
+    fn dangling_stroke_is_pruned() {
+        let regions = regions(vec![
+            stroke(&square(0., 10.)),
+            stroke(&[(10., 5.), (20., 5.)]),
+        ]);
+        assert_eq!(regions.len(), 1);
+        assert_eq!(regions[0].color_id, None);
+        assert!((regions[0].area - 100.).abs() < 1e-3);
+        assert!(regions[0].outline.iter().all(|p| p.0 <= 10.));
+    }
+
+    #[test]
+    fn disconnected_inner_loop_is_a_hole() {
+        let mut regions = regions(vec![stroke(&square(0., 30.)), stroke(&square(10., 20.))]);
+        regions.sort_by(|a, b| a.area.total_cmp(&b.area));
+        assert_eq!(regions.len(), 2);
+        assert!((regions[0].area - 100.).abs() < 1e-3);
+        assert!(regions[0].holes.is_empty());
+        assert!((regions[1].area - 800.).abs() < 1e-3);
+        assert_eq!(regions[1].holes.len(), 1);
+        assert!(
+            signed_area(
+                &regions[1].holes[0]
+                    .iter()
+                    .copied()
+                    .map(to_f64)
+                    .collect::<Vec<_>>()
+            ) < 0.
+        );
+    }
+
+    #[test]
+    fn collinear_overlaps_make_no_empty_faces() {
+        let regions = regions(vec![
+            stroke(&square(0., 10.)),
+            stroke(&[(3., 0.), (8., 0.)]),
+            stroke(&[(-4., 10.), (6., 10.)]),
+            stroke(&square(0., 10.)),
+        ]);
+        assert_eq!(regions.len(), 1);
+        assert!((regions[0].area - 100.).abs() < 1e-3);
+    }
+}
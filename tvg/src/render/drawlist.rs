@@ -0,0 +1,151 @@
+//! Flat lists of draw commands, for drawing documents with other renderers such as GPU pipelines
+//! or game engines.
+//!
+//! Curves are flattened into polylines, and layers and shapes into a single list in drawing
+//! order, so a renderer only has to fill polygons (using the even-odd rule) and stroke polylines.
+
+use super::{InvisibleStrokes, RenderOptions};
+use crate::color::Rgba;
+use crate::document::{find_layer, find_palette, Art, Document};
+use crate::export::{self, ConversionReport, Item};
+use crate::geometry::{self, Bounds};
+use crate::layer::{LayerData, Point};
+
+/// A list of draw commands, bottom to top.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrawList {
+    /// The drawn area, in drawing units.
+    pub view_box: Bounds,
+    /// Maps drawing units to pixels (with Y pointing down, unless [RenderOptions::mapping] says
+    /// otherwise) as the 2×3 affine matrix `[a, b, c, d, e, f]`, so `x' = a x + c y + e` and
+    /// `y' = b x + d y + f`.
+    ///
+    /// This is the same for all commands, since shapes don't have transforms of their own.
+    pub transform: [f32; 6],
+    /// Size of the drawn area in pixels.
+    pub size: (u32, u32),
+    pub commands: Vec<DrawCommand>,
+}
+
+/// Something to draw.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+pub struct DrawCommand {
+    pub geometry: DrawGeometry,
+    /// The color. Missing colors and colors that aren't solid colors are black, and invisible
+    /// strokes are [RenderOptions::guide_color].
+    pub color: Rgba,
+    /// The palette color ID, if there is one. Invisible strokes don't have one.
+    pub color_id: Option<u64>,
+    /// The art layer the command comes from, e.g. to apply
+    /// [RenderOptions::underlay_opacity] to the whole layer.
+    pub layer: Art,
+    /// Position in the drawing order, starting at 0 for the bottom command. Also the index of the
+    /// command in [DrawList::commands].
+    pub z: u32,
+}
+
+/// The geometry of a command, in drawing units.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "serde", serde(tag = "type", rename_all = "snake_case"))]
+pub enum DrawGeometry {
+    /// Polygons filled together using the even-odd rule. Polygons are closed implicitly.
+    Fill { polygons: Vec<Vec<Point>> },
+    /// Polylines stroked with round caps and joins.
+    Stroke {
+        polylines: Vec<Vec<Point>>,
+        width: f32,
+    },
+}
+
+/// Builds a draw list with the default [RenderOptions].
+pub fn build(document: &Document) -> DrawList {
+    build_with_options(document, &RenderOptions::default()).0
+}
+
+/// Builds a draw list of what [render](super::render) would draw with the same options.
+///
+/// Curves are flattened to a quarter pixel at the scale of [RenderOptions::mapping]. Layer opacity
+/// and the background aren't part of the commands, and [RenderOptions::quality] isn't used.
+pub fn build_with_options(
+    document: &Document,
+    options: &RenderOptions,
+) -> (DrawList, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let data = &document.tags;
+    let view_box = options.view_box(data);
+    options.check_pixel_scale(view_box, &mut report);
+    let size = options.image_size(view_box);
+    let mapping = options.pixel_mapping(view_box);
+    let transform = mapping.transform(view_box);
+    let tolerance = 0.25 / mapping.scale;
+
+    let palette = find_palette(data);
+    let show_guides = options.invisible_strokes != InvisibleStrokes::Hidden;
+    let mut commands = Vec::new();
+    for art in export::layer_order(&options.layers, options.line_over_color) {
+        let Some(LayerData::Vector { shapes, .. }) = find_layer(data, art) else {
+            continue;
+        };
+        for shape in shapes {
+            let mut guides = Vec::new();
+            let items = export::shape_items_with_guides(
+                shape,
+                show_guides.then_some(&mut guides),
+                &mut report,
+            );
+            for item in items {
+                let (Item::Fill { paths, color } | Item::Stroke { paths, color, .. }) = &item;
+                let rgba =
+                    export::resolve_rgba(palette, *color, &mut report).unwrap_or(Rgba::BLACK);
+                let polylines = paths
+                    .iter()
+                    .map(|path| geometry::flatten(path, tolerance))
+                    .collect();
+                let geometry = match item {
+                    Item::Fill { .. } => DrawGeometry::Fill {
+                        polygons: polylines,
+                    },
+                    Item::Stroke { .. } => DrawGeometry::Stroke {
+                        polylines,
+                        width: options.stroke_width,
+                    },
+                };
+                commands.push(DrawCommand {
+                    geometry,
+                    color: rgba,
+                    color_id: *color,
+                    layer: art,
+                    z: commands.len() as u32,
+                });
+            }
+
+            if guides.is_empty() {
+                continue;
+            }
+            let polylines: Vec<_> = guides
+                .iter()
+                .map(|path| geometry::flatten(path, tolerance))
+                .collect();
+            if let Some(geometry) = options.guide_geometry(&polylines, view_box) {
+                commands.push(DrawCommand {
+                    geometry,
+                    color: options.guide_color,
+                    color_id: None,
+                    layer: art,
+                    z: commands.len() as u32,
+                });
+            }
+        }
+    }
+
+    let list = DrawList {
+        view_box,
+        transform,
+        size,
+        commands,
+    };
+    (list, report)
+}
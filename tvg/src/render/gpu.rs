@@ -0,0 +1,512 @@
+//! Drawing documents on the GPU with [wgpu].
+//!
+//! This draws the commands of a [draw list](super::drawlist) using the stencil buffer, so there
+//! is no tessellation: each fill is drawn as triangle fans that invert the stencil (which gives
+//! the even-odd rule), each stroke as segment quads and round joins that set it, and then a
+//! rectangle over the command's bounds is drawn in its color wherever the stencil is set.
+//!
+//! With [RenderQuality::High], edges are anti-aliased with 4× MSAA.
+//!
+//! The same code works with WebGPU on the web, so a viewer could draw with this instead of
+//! uploading images rendered on the CPU.
+
+use super::drawlist::{self, DrawGeometry, DrawList};
+use super::{RenderOptions, RenderQuality};
+use crate::color::Rgba;
+use crate::document::Document;
+use crate::export::{self, ConversionReport};
+use crate::geometry::Bounds;
+use crate::layer::Point;
+use core::f32::consts::TAU;
+use core::ops::Range;
+use wgpu::util::DeviceExt;
+
+const SHADER: &str = r#"
+struct Globals {
+    // first row of the transform to pixels, and the width in pixels
+    row_x: vec4<f32>,
+    // second row of the transform to pixels, and the height in pixels
+    row_y: vec4<f32>,
+}
+
+@group(0) @binding(0) var<uniform> globals: Globals;
+
+struct VertexOutput {
+    @builtin(position) position: vec4<f32>,
+    @location(0) color: vec4<f32>,
+}
+
+@vertex
+fn vs_main(@location(0) position: vec2<f32>, @location(1) color: vec4<f32>) -> VertexOutput {
+    let pixel = vec2(
+        dot(globals.row_x.xyz, vec3(position, 1.0)),
+        dot(globals.row_y.xyz, vec3(position, 1.0)),
+    );
+    let size = vec2(globals.row_x.w, globals.row_y.w);
+    var out: VertexOutput;
+    out.position = vec4(pixel.x / size.x * 2.0 - 1.0, 1.0 - pixel.y / size.y * 2.0, 0.0, 1.0);
+    out.color = color;
+    return out;
+}
+
+@fragment
+fn fs_main(in: VertexOutput) -> @location(0) vec4<f32> {
+    return in.color;
+}
+"#;
+
+const STENCIL_FORMAT: wgpu::TextureFormat = wgpu::TextureFormat::Depth24PlusStencil8;
+
+/// Position in drawing units and color (not premultiplied).
+const VERTEX_SIZE: usize = 6 * 4;
+
+/// Number of triangles in a round join or cap.
+const DISC_SEGMENTS: usize = 16;
+
+/// Draws documents into wgpu textures.
+pub struct WgpuRenderer {
+    device: wgpu::Device,
+    queue: wgpu::Queue,
+    format: wgpu::TextureFormat,
+    bind_group_layout: wgpu::BindGroupLayout,
+    shader: wgpu::ShaderModule,
+    pipelines: Option<Pipelines>,
+    targets: Option<Targets>,
+    pub options: RenderOptions,
+}
+
+struct Pipelines {
+    sample_count: u32,
+    fill: wgpu::RenderPipeline,
+    stroke: wgpu::RenderPipeline,
+    cover: wgpu::RenderPipeline,
+}
+
+/// Textures that depend on the size of the output.
+struct Targets {
+    size: (u32, u32),
+    sample_count: u32,
+    stencil: wgpu::TextureView,
+    /// The multisampled color texture, if there is more than one sample.
+    color: Option<wgpu::TextureView>,
+}
+
+/// Vertex ranges to draw for a command.
+struct CommandRanges {
+    stencil: Range<u32>,
+    cover: Range<u32>,
+    is_fill: bool,
+}
+
+impl WgpuRenderer {
+    /// Creates a renderer for textures of the given format.
+    pub fn new(device: wgpu::Device, queue: wgpu::Queue, format: wgpu::TextureFormat) -> Self {
+        let shader = device.create_shader_module(wgpu::ShaderModuleDescriptor {
+            label: Some("tvg shader"),
+            source: wgpu::ShaderSource::Wgsl(SHADER.into()),
+        });
+        let bind_group_layout = device.create_bind_group_layout(&wgpu::BindGroupLayoutDescriptor {
+            label: Some("tvg globals"),
+            entries: &[wgpu::BindGroupLayoutEntry {
+                binding: 0,
+                visibility: wgpu::ShaderStages::VERTEX,
+                ty: wgpu::BindingType::Buffer {
+                    ty: wgpu::BufferBindingType::Uniform,
+                    has_dynamic_offset: false,
+                    min_binding_size: None,
+                },
+                count: None,
+            }],
+        });
+        WgpuRenderer {
+            device,
+            queue,
+            format,
+            bind_group_layout,
+            shader,
+            pipelines: None,
+            targets: None,
+            options: RenderOptions::default(),
+        }
+    }
+
+    /// Returns the size a texture needs to have to render the document, in pixels.
+    pub fn target_size(&self, document: &Document) -> (u32, u32) {
+        let view_box = self.options.view_box(&document.tags);
+        self.options.image_size(view_box)
+    }
+
+    /// Draws the document into a texture view, replacing its contents.
+    ///
+    /// The view should be [target_size](Self::target_size) large, and have the format given to
+    /// [new](Self::new). Underlay opacity is applied to each shape rather than to the layer as a
+    /// whole, so overlapping underlay shapes look a bit different than with
+    /// [render](super::render).
+    pub fn render(&mut self, document: &Document, view: &wgpu::TextureView) -> ConversionReport {
+        let (list, report) = drawlist::build_with_options(document, &self.options);
+        let sample_count = match self.options.quality {
+            RenderQuality::Fast => 1,
+            RenderQuality::High => 4,
+        };
+        self.prepare(list.size, sample_count);
+        let (Some(pipelines), Some(targets)) = (&self.pipelines, &self.targets) else {
+            unreachable!("prepared above");
+        };
+
+        let (vertices, ranges) = self.vertices(&list);
+        let globals = globals(&list);
+        let uniform_buffer = self
+            .device
+            .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                label: Some("tvg globals"),
+                contents: &globals,
+                usage: wgpu::BufferUsages::UNIFORM,
+            });
+        let bind_group = self.device.create_bind_group(&wgpu::BindGroupDescriptor {
+            label: Some("tvg globals"),
+            layout: &self.bind_group_layout,
+            entries: &[wgpu::BindGroupEntry {
+                binding: 0,
+                resource: uniform_buffer.as_entire_binding(),
+            }],
+        });
+        let vertex_buffer = (!vertices.is_empty()).then(|| {
+            self.device
+                .create_buffer_init(&wgpu::util::BufferInitDescriptor {
+                    label: Some("tvg vertices"),
+                    contents: &vertices,
+                    usage: wgpu::BufferUsages::VERTEX,
+                })
+        });
+
+        let background = match self.options.background {
+            Some(background) => {
+                let [r, g, b, a] = self.color(background, 1.);
+                wgpu::Color {
+                    r: r as f64,
+                    g: g as f64,
+                    b: b as f64,
+                    a: a as f64,
+                }
+            }
+            None => wgpu::Color::TRANSPARENT,
+        };
+        let (color_view, resolve_target) = match &targets.color {
+            Some(color) => (color, Some(view)),
+            None => (view, None),
+        };
+
+        let mut encoder = self
+            .device
+            .create_command_encoder(&wgpu::CommandEncoderDescriptor {
+                label: Some("tvg render"),
+            });
+        {
+            let mut pass = encoder.begin_render_pass(&wgpu::RenderPassDescriptor {
+                label: Some("tvg render"),
+                color_attachments: &[Some(wgpu::RenderPassColorAttachment {
+                    view: color_view,
+                    resolve_target,
+                    ops: wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(background),
+                        store: wgpu::StoreOp::Store,
+                    },
+                })],
+                depth_stencil_attachment: Some(wgpu::RenderPassDepthStencilAttachment {
+                    view: &targets.stencil,
+                    depth_ops: None,
+                    stencil_ops: Some(wgpu::Operations {
+                        load: wgpu::LoadOp::Clear(0),
+                        store: wgpu::StoreOp::Discard,
+                    }),
+                }),
+                timestamp_writes: None,
+                occlusion_query_set: None,
+            });
+            if let Some(vertex_buffer) = &vertex_buffer {
+                pass.set_bind_group(0, &bind_group, &[]);
+                pass.set_vertex_buffer(0, vertex_buffer.slice(..));
+                pass.set_stencil_reference(1);
+                for command in &ranges {
+                    match command.is_fill {
+                        true => pass.set_pipeline(&pipelines.fill),
+                        false => pass.set_pipeline(&pipelines.stroke),
+                    }
+                    pass.draw(command.stencil.clone(), 0..1);
+                    pass.set_pipeline(&pipelines.cover);
+                    pass.draw(command.cover.clone(), 0..1);
+                }
+            }
+        }
+        self.queue.submit([encoder.finish()]);
+        report
+    }
+
+    /// Creates the pipelines and textures, if they don't fit the output anymore.
+    fn prepare(&mut self, size: (u32, u32), sample_count: u32) {
+        if self.pipelines.as_ref().map(|p| p.sample_count) != Some(sample_count) {
+            self.pipelines = Some(self.create_pipelines(sample_count));
+        }
+        let targets = self.targets.as_ref();
+        if targets.map(|t| (t.size, t.sample_count)) != Some((size, sample_count)) {
+            self.targets = Some(self.create_targets(size, sample_count));
+        }
+    }
+
+    fn create_pipelines(&self, sample_count: u32) -> Pipelines {
+        let layout = self
+            .device
+            .create_pipeline_layout(&wgpu::PipelineLayoutDescriptor {
+                label: Some("tvg"),
+                bind_group_layouts: &[&self.bind_group_layout],
+                push_constant_ranges: &[],
+            });
+        let pipeline = |label, stencil: wgpu::StencilFaceState, write_color: bool| {
+            let color_target = wgpu::ColorTargetState {
+                format: self.format,
+                blend: Some(wgpu::BlendState::ALPHA_BLENDING),
+                write_mask: match write_color {
+                    true => wgpu::ColorWrites::ALL,
+                    false => wgpu::ColorWrites::empty(),
+                },
+            };
+            self.device
+                .create_render_pipeline(&wgpu::RenderPipelineDescriptor {
+                    label: Some(label),
+                    layout: Some(&layout),
+                    vertex: wgpu::VertexState {
+                        module: &self.shader,
+                        entry_point: Some("vs_main"),
+                        compilation_options: Default::default(),
+                        buffers: &[wgpu::VertexBufferLayout {
+                            array_stride: VERTEX_SIZE as wgpu::BufferAddress,
+                            step_mode: wgpu::VertexStepMode::Vertex,
+                            attributes: &wgpu::vertex_attr_array![
+                                0 => Float32x2,
+                                1 => Float32x4,
+                            ],
+                        }],
+                    },
+                    primitive: wgpu::PrimitiveState {
+                        topology: wgpu::PrimitiveTopology::TriangleList,
+                        cull_mode: None,
+                        ..Default::default()
+                    },
+                    depth_stencil: Some(wgpu::DepthStencilState {
+                        format: STENCIL_FORMAT,
+                        depth_write_enabled: false,
+                        depth_compare: wgpu::CompareFunction::Always,
+                        stencil: wgpu::StencilState {
+                            front: stencil,
+                            back: stencil,
+                            read_mask: 1,
+                            write_mask: 1,
+                        },
+                        bias: Default::default(),
+                    }),
+                    multisample: wgpu::MultisampleState {
+                        count: sample_count,
+                        ..Default::default()
+                    },
+                    fragment: Some(wgpu::FragmentState {
+                        module: &self.shader,
+                        entry_point: Some("fs_main"),
+                        compilation_options: Default::default(),
+                        targets: &[Some(color_target)],
+                    }),
+                    multiview: None,
+                    cache: None,
+                })
+        };
+        let stencil_op = |compare, pass_op| wgpu::StencilFaceState {
+            compare,
+            fail_op: wgpu::StencilOperation::Keep,
+            depth_fail_op: wgpu::StencilOperation::Keep,
+            pass_op,
+        };
+        use wgpu::{CompareFunction, StencilOperation};
+        Pipelines {
+            sample_count,
+            // every covering triangle flips the stencil, so odd coverage is inside
+            fill: pipeline(
+                "tvg fill",
+                stencil_op(CompareFunction::Always, StencilOperation::Invert),
+                false,
+            ),
+            stroke: pipeline(
+                "tvg stroke",
+                stencil_op(CompareFunction::Always, StencilOperation::Replace),
+                false,
+            ),
+            // also clears the stencil for the next command
+            cover: pipeline(
+                "tvg cover",
+                stencil_op(CompareFunction::Equal, StencilOperation::Zero),
+                true,
+            ),
+        }
+    }
+
+    fn create_targets(&self, size: (u32, u32), sample_count: u32) -> Targets {
+        let texture = |label, format, usage| {
+            self.device
+                .create_texture(&wgpu::TextureDescriptor {
+                    label: Some(label),
+                    size: wgpu::Extent3d {
+                        width: size.0.max(1),
+                        height: size.1.max(1),
+                        depth_or_array_layers: 1,
+                    },
+                    mip_level_count: 1,
+                    sample_count,
+                    dimension: wgpu::TextureDimension::D2,
+                    format,
+                    usage,
+                    view_formats: &[],
+                })
+                .create_view(&Default::default())
+        };
+        let usage = wgpu::TextureUsages::RENDER_ATTACHMENT;
+        Targets {
+            size,
+            sample_count,
+            stencil: texture("tvg stencil", STENCIL_FORMAT, usage),
+            color: (sample_count > 1).then(|| texture("tvg multisampled", self.format, usage)),
+        }
+    }
+
+    /// Collects the vertices of all commands.
+    fn vertices(&self, list: &DrawList) -> (Vec<u8>, Vec<CommandRanges>) {
+        let mut vertices = Vertices::default();
+        let mut ranges = Vec::with_capacity(list.commands.len());
+        // strokes are at least a pixel wide, like in the CPU renderer
+        let min_radius = 0.5 / self.options.pixel_scale(list.view_box);
+        for command in &list.commands {
+            let opacity = export::layer_opacity(command.layer, self.options.underlay_opacity);
+            vertices.color = self.color(command.color, opacity);
+
+            let start = vertices.len();
+            let mut bounds: Option<Bounds> = None;
+            let mut include = |point| match &mut bounds {
+                Some(bounds) => bounds.include(point),
+                None => bounds = Some(Bounds::from_point(point)),
+            };
+            let is_fill = match &command.geometry {
+                DrawGeometry::Fill { polygons } => {
+                    for polygon in polygons {
+                        let Some(&first) = polygon.first() else {
+                            continue;
+                        };
+                        for pair in polygon[1..].windows(2) {
+                            vertices.triangle(first, pair[0], pair[1]);
+                        }
+                        polygon.iter().for_each(|&point| include(point));
+                    }
+                    true
+                }
+                DrawGeometry::Stroke { polylines, width } => {
+                    let radius = (width / 2.).max(min_radius);
+                    for polyline in polylines {
+                        for pair in polyline.windows(2) {
+                            vertices.segment(pair[0], pair[1], radius);
+                        }
+                        for &point in polyline {
+                            vertices.disc(point, radius);
+                            include((point.0 - radius, point.1 - radius));
+                            include((point.0 + radius, point.1 + radius));
+                        }
+                    }
+                    false
+                }
+            };
+            let stencil = start..vertices.len();
+            let Some(Bounds { min, max }) = bounds.filter(|_| !stencil.is_empty()) else {
+                continue;
+            };
+            vertices.triangle(min, (max.0, min.1), max);
+            vertices.triangle(min, max, (min.0, max.1));
+            ranges.push(CommandRanges {
+                stencil: stencil.clone(),
+                cover: stencil.end..vertices.len(),
+                is_fill,
+            });
+        }
+        (vertices.data, ranges)
+    }
+
+    /// Converts a color for the output format, with its alpha multiplied by `opacity`.
+    fn color(&self, color: Rgba, opacity: f32) -> [f32; 4] {
+        let [r, g, b, a] = match self.format.is_srgb() {
+            // the GPU converts linear colors to sRGB when writing them
+            true => color.to_linear(),
+            false => color.to_f32(),
+        };
+        [r, g, b, a * opacity]
+    }
+}
+
+/// Packs the transform from the draw list into the `Globals` uniform of the shader.
+fn globals(list: &DrawList) -> Vec<u8> {
+    let [a, b, c, d, e, f] = list.transform;
+    let (width, height) = list.size;
+    [a, c, e, width.max(1) as f32, b, d, f, height.max(1) as f32]
+        .iter()
+        .flat_map(|v| v.to_ne_bytes())
+        .collect()
+}
+
+#[derive(Default)]
+struct Vertices {
+    data: Vec<u8>,
+    color: [f32; 4],
+}
+
+impl Vertices {
+    fn len(&self) -> u32 {
+        (self.data.len() / VERTEX_SIZE) as u32
+    }
+
+    fn vertex(&mut self, point: Point) {
+        for v in [point.0, point.1].iter().chain(&self.color) {
+            self.data.extend_from_slice(&v.to_ne_bytes());
+        }
+    }
+
+    fn triangle(&mut self, a: Point, b: Point, c: Point) {
+        self.vertex(a);
+        self.vertex(b);
+        self.vertex(c);
+    }
+
+    /// A rectangle around a line segment, without the caps.
+    fn segment(&mut self, a: Point, b: Point, radius: f32) {
+        let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+        let len = (dx * dx + dy * dy).sqrt();
+        if len <= 0. {
+            return;
+        }
+        let (nx, ny) = (-dy / len * radius, dx / len * radius);
+        let corners = [
+            (a.0 + nx, a.1 + ny),
+            (b.0 + nx, b.1 + ny),
+            (b.0 - nx, b.1 - ny),
+            (a.0 - nx, a.1 - ny),
+        ];
+        self.triangle(corners[0], corners[1], corners[2]);
+        self.triangle(corners[0], corners[2], corners[3]);
+    }
+
+    /// A polygon approximating a circle, for round joins and caps.
+    fn disc(&mut self, center: Point, radius: f32) {
+        let at = |i: usize| {
+            let angle = i as f32 / DISC_SEGMENTS as f32 * TAU;
+            (
+                center.0 + angle.cos() * radius,
+                center.1 + angle.sin() * radius,
+            )
+        };
+        for i in 0..DISC_SEGMENTS {
+            self.triangle(center, at(i), at(i + 1));
+        }
+    }
+}
@@ -0,0 +1,753 @@
+//! Rasterization of drawings into RGBA images.
+//!
+//! This is a simple CPU rasterizer, without anti-aliasing unless [RenderQuality::High] is used.
+//! Like the SVG exporter, fills use the even-odd rule, and pencil strokes are drawn as their center
+//! lines with a constant width.
+
+pub mod drawlist;
+#[cfg(feature = "wgpu")]
+pub mod gpu;
+mod raster;
+
+use crate::color::Rgba;
+use crate::document::{find_layer, find_palette, Art};
+use crate::export::{
+    self, ConversionReport, ConversionWarning, CoordinateMapping, Framing, Item, Origin,
+};
+use crate::geometry::{self, Bounds};
+use crate::layer::{LayerData, Point, VectorShape};
+use crate::palette::PaletteData;
+use crate::read::FileData;
+use drawlist::DrawGeometry;
+
+/// An image with 8-bit RGBA pixels (not premultiplied), stored row by row from the top.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RgbaImage {
+    pub width: u32,
+    pub height: u32,
+    pub data: Vec<u8>,
+}
+
+impl RgbaImage {
+    /// Creates a transparent image.
+    ///
+    /// # Panics
+    /// If the image data would be larger than the address space.
+    pub fn new(width: u32, height: u32) -> Self {
+        let len = (width as usize)
+            .checked_mul(height as usize)
+            .and_then(|pixels| pixels.checked_mul(4))
+            .unwrap_or_else(|| panic!("image too large: {width}x{height}"));
+        RgbaImage {
+            width,
+            height,
+            data: vec![0; len],
+        }
+    }
+
+    /// Returns the pixel at the given position.
+    pub fn pixel(&self, x: u32, y: u32) -> Rgba {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let p = &self.data[i..i + 4];
+        Rgba::new(p[0], p[1], p[2], p[3])
+    }
+
+    /// Draws a color over the pixel at the given position.
+    pub fn blend_pixel(&mut self, x: u32, y: u32, Rgba { r, g, b, a }: Rgba) {
+        let i = (y as usize * self.width as usize + x as usize) * 4;
+        let dst = &mut self.data[i..i + 4];
+        let src_a = a as f32 / 255.;
+        let dst_a = dst[3] as f32 / 255.;
+        let out_a = src_a + dst_a * (1. - src_a);
+        if out_a <= 0. {
+            return;
+        }
+        for (d, s) in dst.iter_mut().zip([r, g, b]) {
+            let c = (s as f32 * src_a + *d as f32 * dst_a * (1. - src_a)) / out_a;
+            *d = c.round() as u8;
+        }
+        dst[3] = (out_a * 255.).round() as u8;
+    }
+
+    /// Returns a copy scaled down to fit into a `max_px` by `max_px` box, with each pixel the
+    /// average of the pixels it covers. Images that already fit are copied as they are.
+    pub fn scaled_to_fit(&self, max_px: u32) -> RgbaImage {
+        let max_px = max_px.max(1);
+        let longest = self.width.max(self.height);
+        if longest <= max_px {
+            return self.clone();
+        }
+        let scale = |size: u32| ((size as u64 * max_px as u64 / longest as u64) as u32).max(1);
+        let mut out = RgbaImage::new(scale(self.width), scale(self.height));
+        // the range of source pixels that pixel `i` of `n` covers
+        let span = |i: u32, n: u32, size: u32| {
+            let start = (i as u64 * size as u64 / n as u64) as u32;
+            let end = ((i as u64 + 1) * size as u64 / n as u64) as u32;
+            start..end.max(start + 1)
+        };
+        for y in 0..out.height {
+            for x in 0..out.width {
+                // color channels are weighted by alpha, so transparent pixels don't darken edges
+                let (mut sum, mut alpha, mut count) = ([0u64; 3], 0u64, 0u64);
+                for sy in span(y, out.height, self.height) {
+                    for sx in span(x, out.width, self.width) {
+                        let Rgba { r, g, b, a } = self.pixel(sx, sy);
+                        for (sum, c) in sum.iter_mut().zip([r, g, b]) {
+                            *sum += c as u64 * a as u64;
+                        }
+                        alpha += a as u64;
+                        count += 1;
+                    }
+                }
+                let i = (y as usize * out.width as usize + x as usize) * 4;
+                if alpha > 0 {
+                    let [r, g, b] = sum.map(|sum| (sum / alpha) as u8);
+                    let a = (alpha / count) as u8;
+                    out.data[i..i + 4].copy_from_slice(&[r, g, b, a]);
+                }
+            }
+        }
+        out
+    }
+
+    /// Draws an image of the same size over this one, with its alpha multiplied by `opacity`.
+    pub fn blend_image(&mut self, other: &RgbaImage, opacity: f32) {
+        for y in 0..self.height.min(other.height) {
+            for x in 0..self.width.min(other.width) {
+                let rgba = other.pixel(x, y);
+                if rgba.a > 0 {
+                    let a = (rgba.a as f32 * opacity).round() as u8;
+                    self.blend_pixel(x, y, rgba.with_alpha(a));
+                }
+            }
+        }
+    }
+}
+
+#[derive(Debug, Clone)]
+pub struct RenderOptions {
+    /// The area of the drawing to render, in drawing units. Defaults to the framed area.
+    pub view_box: Option<Bounds>,
+    /// How to frame the drawing if there's no explicit view box.
+    pub framing: Framing,
+    /// Padding around the drawing, in drawing units. Only used if there is no explicit view box.
+    pub padding: f32,
+    /// How drawing units map to pixels. The origin is always the top left corner of the image,
+    /// so [CoordinateMapping::origin] is ignored.
+    pub mapping: CoordinateMapping,
+    /// Stroke width of pencil lines, in drawing units.
+    pub stroke_width: f32,
+    /// Background color. Transparent if None.
+    pub background: Option<Rgba>,
+    /// The art layers to render. Other layers are hidden, and also don't count for framing.
+    pub layers: Vec<Art>,
+    /// Opacity of the underlay art, from 0 to 1.
+    pub underlay_opacity: f32,
+    /// If true (the default), line art is drawn over color art, like in Harmony's default
+    /// camera view. If false, color art is drawn over line art.
+    pub line_over_color: bool,
+    /// Whether edges are anti-aliased.
+    pub quality: RenderQuality,
+    /// Whether and how invisible strokes are drawn, e.g. to see the edges of the regions that
+    /// fills are bounded by.
+    pub invisible_strokes: InvisibleStrokes,
+    /// Color of invisible strokes, if they're drawn.
+    pub guide_color: Rgba,
+    /// Width of invisible strokes, if they're drawn, in drawing units.
+    pub guide_width: f32,
+    /// The most pixels the image can have. If it would have more at the scale of
+    /// [RenderOptions::mapping], it's rendered at a smaller scale instead (see
+    /// [RenderOptions::pixel_scale]).
+    pub max_pixels: u64,
+}
+
+/// How invisible strokes are drawn. These are the strokes of components with
+/// [ComponentType::Stroke](crate::layer::ComponentType::Stroke), which Harmony doesn't draw, but
+/// which act as the boundaries of fill regions.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum InvisibleStrokes {
+    /// Not at all, like in Harmony.
+    #[default]
+    Hidden,
+    /// As thin fills along their center lines.
+    Filled,
+    /// As dashed lines, with dashes and gaps [DASH_LENGTH] times as long as the lines are wide.
+    Dashed,
+}
+
+/// Length of the dashes of [InvisibleStrokes::Dashed], relative to [RenderOptions::guide_width].
+pub const DASH_LENGTH: f32 = 4.;
+
+/// The most dashes drawn for one invisible stroke. Parts that would need more are drawn solid,
+/// since dashes that small can't be told apart anyway.
+const MAX_DASHES: usize = 100_000;
+
+/// The default [RenderOptions::max_pixels]: 4096 × 4096, or 64 MiB of image data.
+pub const MAX_PIXELS: u64 = 1 << 24;
+
+/// How carefully [render] draws edges.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum RenderQuality {
+    /// One sample per pixel, so edges are aliased. Good enough for previews.
+    #[default]
+    Fast,
+    /// 16 samples per pixel (4 by 4), for anti-aliased edges. Rendering takes a few times longer
+    /// than with [RenderQuality::Fast] (see `benches/render.rs`).
+    High,
+}
+
+impl RenderQuality {
+    /// Number of samples per pixel in each direction.
+    fn samples(self) -> u32 {
+        match self {
+            RenderQuality::Fast => 1,
+            RenderQuality::High => 4,
+        }
+    }
+}
+
+impl Default for RenderOptions {
+    fn default() -> Self {
+        RenderOptions {
+            view_box: None,
+            framing: Framing::Bounds,
+            padding: 10.,
+            mapping: CoordinateMapping::new(0.25),
+            stroke_width: 4.,
+            background: None,
+            layers: Art::ALL.to_vec(),
+            underlay_opacity: 1.,
+            line_over_color: true,
+            quality: RenderQuality::Fast,
+            invisible_strokes: InvisibleStrokes::Hidden,
+            guide_color: Rgba::new(0, 160, 255, 255),
+            guide_width: 1.,
+            max_pixels: MAX_PIXELS,
+        }
+    }
+}
+
+impl RenderOptions {
+    /// Returns the rendered area of a drawing, in drawing units.
+    pub fn view_box(&self, data: &[FileData]) -> Bounds {
+        let layers = Art::ALL
+            .into_iter()
+            .filter(|art| self.layers.contains(art))
+            .filter_map(|art| find_layer(data, art));
+        export::frame(
+            layers,
+            self.view_box,
+            self.framing,
+            self.padding + self.stroke_width / 2.,
+        )
+    }
+
+    /// Returns the size of the rendered image in pixels for a view box.
+    pub fn image_size(&self, view_box: Bounds) -> (u32, u32) {
+        image_size(view_box, self.pixel_scale(view_box))
+    }
+
+    /// Returns the scale of the rendered image for a view box: the scale of
+    /// [RenderOptions::mapping], or a smaller one if the image would have more than
+    /// [RenderOptions::max_pixels] pixels otherwise.
+    pub fn pixel_scale(&self, view_box: Bounds) -> f32 {
+        let max_pixels = self.max_pixels.max(1);
+        let mut scale = self.mapping.scale;
+        // the size is rounded up and is at least a pixel, so this may take a few tries
+        for _ in 0..64 {
+            let (width, height) = image_size(view_box, scale);
+            let pixels = width as u64 * height as u64;
+            if pixels <= max_pixels {
+                break;
+            }
+            scale *= ((max_pixels as f64 / pixels as f64).sqrt() * 0.999) as f32;
+        }
+        scale
+    }
+
+    /// The mapping from drawing units to pixels in the image for a view box.
+    pub(crate) fn pixel_mapping(&self, view_box: Bounds) -> CoordinateMapping {
+        CoordinateMapping {
+            origin: Origin::Corner,
+            scale: self.pixel_scale(view_box),
+            ..self.mapping
+        }
+    }
+
+    /// Warns if the image for a view box is rendered at a smaller scale than
+    /// [RenderOptions::mapping] to stay within [RenderOptions::max_pixels].
+    pub(crate) fn check_pixel_scale(&self, view_box: Bounds, report: &mut ConversionReport) {
+        if self.pixel_scale(view_box) < self.mapping.scale {
+            report.warn(ConversionWarning::ImageScaledDown);
+        }
+    }
+
+    /// Turns the flattened paths of invisible strokes into what's drawn for them, or None if
+    /// they're hidden. Dashes are only made for the parts within the view box.
+    pub(crate) fn guide_geometry(
+        &self,
+        polylines: &[Vec<Point>],
+        view_box: Bounds,
+    ) -> Option<DrawGeometry> {
+        let width = self.guide_width;
+        match self.invisible_strokes {
+            InvisibleStrokes::Hidden => None,
+            InvisibleStrokes::Filled => Some(DrawGeometry::Fill {
+                polygons: polylines
+                    .iter()
+                    .flat_map(|polyline| thin_outline(polyline, width))
+                    .collect(),
+            }),
+            InvisibleStrokes::Dashed => Some(DrawGeometry::Stroke {
+                polylines: polylines
+                    .iter()
+                    .flat_map(|polyline| {
+                        dashes(polyline, width * DASH_LENGTH, view_box.inflate(width))
+                    })
+                    .collect(),
+                width,
+            }),
+        }
+    }
+}
+
+fn image_size(view_box: Bounds, scale: f32) -> (u32, u32) {
+    let size = |length: f32| ((length * scale).ceil() as u32).max(1);
+    (size(view_box.width()), size(view_box.height()))
+}
+
+/// Returns polygons that cover a polyline with the given width: one polygon around an open
+/// polyline, or the two sides of a closed one, which make a band when filled with the even-odd
+/// rule.
+fn thin_outline(polyline: &[Point], width: f32) -> Vec<Vec<Point>> {
+    let mut points = polyline.to_vec();
+    points.dedup();
+    let closed = points.len() > 2 && points.first() == points.last();
+    if closed {
+        points.pop();
+    }
+    if points.len() < 2 {
+        return Vec::new();
+    }
+
+    let count = points.len();
+    let direction = |i: usize| {
+        let d = geometry::sub(points[(i + 1) % count], points[i]);
+        geometry::scale(d, 1. / geometry::length(d))
+    };
+    // offset of each point to its left side, mitered at corners
+    let offsets: Vec<Point> = (0..count)
+        .map(|i| {
+            let (before, after) = match i {
+                0 if !closed => (direction(0), direction(0)),
+                i if i == count - 1 && !closed => (direction(i - 1), direction(i - 1)),
+                i => (direction((i + count - 1) % count), direction(i)),
+            };
+            let normal = |(x, y): Point| (-y, x);
+            let sum = geometry::add(normal(before), normal(after));
+            let miter = geometry::scale(sum, 1. / geometry::length(sum).max(f32::EPSILON));
+            // limit the miter length at sharp corners
+            let cos = geometry::dot(miter, normal(after)).max(0.25);
+            geometry::scale(miter, width / 2. / cos)
+        })
+        .collect();
+
+    let left = points
+        .iter()
+        .zip(&offsets)
+        .map(|(&p, &o)| geometry::add(p, o));
+    let right = points
+        .iter()
+        .zip(&offsets)
+        .map(|(&p, &o)| geometry::sub(p, o));
+    if closed {
+        vec![left.collect(), right.collect()]
+    } else {
+        vec![left.chain(right.rev()).collect()]
+    }
+}
+
+/// Splits the part of a polyline within `clip` into dashes of the given length, with gaps of the
+/// same length. Returns nothing if the length isn't finite and positive.
+fn dashes(polyline: &[Point], length: f32, clip: Bounds) -> Vec<Vec<Point>> {
+    let length = length as f64;
+    if !(length.is_finite() && length > 0.) {
+        return Vec::new();
+    }
+
+    let mut dashes: Vec<Vec<Point>> = Vec::new();
+    // where the last dash ended, to continue it around corners
+    let mut dash_end = None;
+    // distance along the polyline to the start of the current segment
+    let mut start = 0.;
+    for pair in polyline.windows(2) {
+        let (a, b) = (to_f64(pair[0]), to_f64(pair[1]));
+        let segment = (b.0 - a.0).hypot(b.1 - a.1);
+        if !segment.is_finite() || segment == 0. {
+            continue;
+        }
+        // positions are computed from the distance along the polyline in f64, so they stay
+        // exact for long strokes far from the origin
+        let point = |distance: f64| {
+            let t = (distance - start) / segment;
+            (
+                (a.0 + (b.0 - a.0) * t) as f32,
+                (a.1 + (b.1 - a.1) * t) as f32,
+            )
+        };
+
+        if let Some((t0, t1)) = clip_segment(a, b, clip) {
+            let (from, to) = (start + t0 * segment, start + t1 * segment);
+            // dash k goes from 2k to 2k + 1 dash lengths
+            let first = (from / (2. * length)).floor();
+            let last = (to / (2. * length)).floor();
+            if dashes.len() as f64 + (last - first) >= MAX_DASHES as f64 {
+                dashes.push(vec![point(from), point(to)]);
+                dash_end = None;
+            } else {
+                for k in first as i64..=last as i64 {
+                    let dash_from = (2 * k) as f64 * length;
+                    let (dash_from, dash_to) = (dash_from.max(from), (dash_from + length).min(to));
+                    if dash_from >= dash_to {
+                        continue;
+                    }
+                    match dashes.last_mut() {
+                        Some(dash) if dash_end == Some(dash_from) => dash.push(point(dash_to)),
+                        _ => dashes.push(vec![point(dash_from), point(dash_to)]),
+                    }
+                    dash_end = Some(dash_to);
+                }
+            }
+        }
+        start += segment;
+    }
+    dashes
+}
+
+fn to_f64(p: Point) -> (f64, f64) {
+    (p.0 as f64, p.1 as f64)
+}
+
+/// Returns the range of positions (0 to 1) along the segment from `a` to `b` that is inside the
+/// bounds, or None if it's completely outside.
+fn clip_segment(a: (f64, f64), b: (f64, f64), bounds: Bounds) -> Option<(f64, f64)> {
+    let (min, max) = (to_f64(bounds.min), to_f64(bounds.max));
+    let d = (b.0 - a.0, b.1 - a.1);
+    let (mut t0, mut t1) = (0_f64, 1_f64);
+    // Liang–Barsky: each side limits the range from one end
+    for (p, q) in [
+        (-d.0, a.0 - min.0),
+        (d.0, max.0 - a.0),
+        (-d.1, a.1 - min.1),
+        (d.1, max.1 - a.1),
+    ] {
+        if p == 0. {
+            if q < 0. {
+                return None;
+            }
+        } else if p < 0. {
+            t0 = t0.max(q / p);
+        } else {
+            t1 = t1.min(q / p);
+        }
+    }
+    (t0 <= t1).then_some((t0, t1))
+}
+
+/// Renders a TVG file.
+pub fn render(data: &[FileData], options: &RenderOptions) -> (RgbaImage, ConversionReport) {
+    let mut report = ConversionReport::default();
+    let view_box = options.view_box(data);
+    options.check_pixel_scale(view_box, &mut report);
+    let (width, height) = options.image_size(view_box);
+    let mut image = RgbaImage::new(width, height);
+
+    if let Some(background) = options.background {
+        for y in 0..height {
+            for x in 0..width {
+                image.blend_pixel(x, y, background);
+            }
+        }
+    }
+
+    let palette = find_palette(data);
+    for art in export::layer_order(&options.layers, options.line_over_color) {
+        let Some(LayerData::Vector { shapes, .. }) = find_layer(data, art) else {
+            continue;
+        };
+        match export::layer_opacity(art, options.underlay_opacity) {
+            opacity if opacity >= 1. => {
+                draw_shapes(&mut image, shapes, palette, view_box, options, &mut report)
+            }
+            opacity if opacity > 0. => {
+                // drawn separately, so overlapping shapes don't show through each other
+                let mut layer = RgbaImage::new(width, height);
+                draw_shapes(&mut layer, shapes, palette, view_box, options, &mut report);
+                image.blend_image(&layer, opacity);
+            }
+            _ => (),
+        }
+    }
+
+    (image, report)
+}
+
+fn draw_shapes(
+    image: &mut RgbaImage,
+    shapes: &[VectorShape],
+    palette: Option<&PaletteData>,
+    view_box: Bounds,
+    options: &RenderOptions,
+    report: &mut ConversionReport,
+) {
+    let mapping = options.pixel_mapping(view_box);
+    let to_pixels = |point: Point| mapping.apply(view_box, point);
+    let samples = options.quality.samples();
+    // flatten to a quarter sample
+    let tolerance = 0.25 / (mapping.scale * samples as f32);
+
+    let show_guides = options.invisible_strokes != InvisibleStrokes::Hidden;
+    for shape in shapes {
+        let mut guides = Vec::new();
+        let items =
+            export::shape_items_with_guides(shape, show_guides.then_some(&mut guides), report);
+        for item in items {
+            let (Item::Fill { paths, color } | Item::Stroke { paths, color, .. }) = &item;
+            let rgba = export::resolve_rgba(palette, *color, report).unwrap_or(Rgba::BLACK);
+            let polylines: Vec<Vec<Point>> = paths
+                .iter()
+                .map(|path| {
+                    geometry::flatten(path, tolerance)
+                        .into_iter()
+                        .map(to_pixels)
+                        .collect()
+                })
+                .collect();
+
+            match item {
+                Item::Fill { .. } => raster::fill_even_odd(image, &polylines, rgba, samples),
+                Item::Stroke { .. } => {
+                    let width = options.stroke_width * mapping.scale;
+                    raster::stroke(image, &polylines, width, rgba, samples)
+                }
+            }
+        }
+
+        if guides.is_empty() {
+            continue;
+        }
+        let polylines: Vec<_> = guides
+            .iter()
+            .map(|path| geometry::flatten(path, tolerance))
+            .collect();
+        let lines_to_pixels = |lines: Vec<Vec<Point>>| -> Vec<Vec<Point>> {
+            lines
+                .into_iter()
+                .map(|line| line.into_iter().map(to_pixels).collect())
+                .collect()
+        };
+        let rgba = options.guide_color;
+        match options.guide_geometry(&polylines, view_box) {
+            Some(DrawGeometry::Fill { polygons }) => {
+                raster::fill_even_odd(image, &lines_to_pixels(polygons), rgba, samples)
+            }
+            Some(DrawGeometry::Stroke { polylines, width }) => {
+                let width = width * mapping.scale;
+                raster::stroke(image, &lines_to_pixels(polylines), width, rgba, samples)
+            }
+            None => (),
+        }
+    }
+}
+
+#[cfg(feature = "png")]
+fn png_encoder<W: std::io::Write>(w: W, width: u32, height: u32) -> png::Encoder<'static, W> {
+    let mut encoder = png::Encoder::new(w, width, height);
+    encoder.set_color(png::ColorType::Rgba);
+    encoder.set_depth(png::BitDepth::Eight);
+    encoder
+}
+
+#[cfg(feature = "png")]
+impl RgbaImage {
+    /// Decodes a PNG file. Images in other color types and bit depths are converted to 8-bit RGBA,
+    /// and only the first frame of animated PNGs is decoded.
+    pub fn read_png(r: impl std::io::Read) -> Result<RgbaImage, png::DecodingError> {
+        let mut decoder = png::Decoder::new(r);
+        decoder.set_transformations(png::Transformations::normalize_to_color8());
+        let mut reader = decoder.read_info()?;
+        let mut buffer = vec![0; reader.output_buffer_size()];
+        let info = reader.next_frame(&mut buffer)?;
+        let pixels = &buffer[..info.buffer_size()];
+        let data = match info.color_type {
+            png::ColorType::Rgba => pixels.to_vec(),
+            png::ColorType::Rgb => pixels
+                .chunks_exact(3)
+                .flat_map(|p| [p[0], p[1], p[2], 255])
+                .collect(),
+            png::ColorType::GrayscaleAlpha => pixels
+                .chunks_exact(2)
+                .flat_map(|p| [p[0], p[0], p[0], p[1]])
+                .collect(),
+            // indexed images are expanded to RGB(A) by the transformations
+            png::ColorType::Grayscale | png::ColorType::Indexed => {
+                pixels.iter().flat_map(|&v| [v, v, v, 255]).collect()
+            }
+        };
+        Ok(RgbaImage {
+            width: info.width,
+            height: info.height,
+            data,
+        })
+    }
+
+    /// Encodes the image as a PNG file.
+    pub fn write_png(&self, w: impl std::io::Write) -> Result<(), png::EncodingError> {
+        let mut writer = png_encoder(w, self.width, self.height).write_header()?;
+        writer.write_image_data(&self.data)?;
+        writer.finish()
+    }
+}
+
+/// Encodes images as an endlessly looping animated PNG file. All frames must have the same size.
+#[cfg(feature = "png")]
+pub fn write_apng(
+    frames: &[RgbaImage],
+    frame_delay_ms: u16,
+    w: impl std::io::Write,
+) -> Result<(), png::EncodingError> {
+    let Some(first) = frames.first() else {
+        let error = std::io::Error::new(std::io::ErrorKind::InvalidInput, "no frames");
+        return Err(png::EncodingError::IoError(error));
+    };
+    let mut encoder = png_encoder(w, first.width, first.height);
+    encoder.set_animated(frames.len() as u32, 0)?;
+    encoder.set_frame_delay(frame_delay_ms, 1000)?;
+    let mut writer = encoder.write_header()?;
+    for frame in frames {
+        writer.write_image_data(&frame.data)?;
+    }
+    writer.finish()
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::document::Document;
+    use crate::layer::{
+        ComponentInfo, ComponentType, LayerTrailer, PathSegment, ShapeComponent,
+        ShapeComponentData, ShapePath, ShapeType,
+    };
+
+    /// A document with one shape with one component along the points.
+    fn document(ty: ShapeType, component: ComponentType, points: &[Point]) -> Document {
+        let info = ComponentInfo {
+            ty: component,
+            color_id: None,
+            pencil_value: None,
+            unknown_prefix: None,
+            unknown_suffix: None,
+        };
+        let path = ShapePath {
+            segments: points.iter().map(|p| PathSegment::Line(*p)).collect(),
+            extra: None,
+        };
+        let shape = VectorShape {
+            ty,
+            components: vec![ShapeComponent {
+                tags: vec![
+                    ShapeComponentData::Info(info),
+                    ShapeComponentData::Path(path),
+                ],
+                trailer: None,
+            }],
+        };
+        Document::from(vec![FileData::LayerLine(LayerData::Vector {
+            shapes: vec![shape],
+            trailer: LayerTrailer::default(),
+        })])
+    }
+
+    fn invisible_stroke(points: &[Point]) -> Document {
+        document(ShapeType::Stroke, ComponentType::Stroke, points)
+    }
+
+    fn bounds(min: f32, max: f32) -> Bounds {
+        Bounds {
+            min: (min, min),
+            max: (max, max),
+        }
+    }
+
+    #[test]
+    fn dashes_continue_around_corners() {
+        let dashes = dashes(&[(0., 0.), (2., 0.), (2., 8.)], 3., bounds(-10., 10.));
+        assert_eq!(
+            dashes,
+            [vec![(0., 0.), (2., 0.), (2., 1.)], vec![(2., 4.), (2., 7.)]]
+        );
+    }
+
+    #[test]
+    fn no_dashes_without_length() {
+        let line = [(0., 0.), (10., 0.)];
+        assert!(dashes(&line, 0., bounds(-10., 10.)).is_empty());
+        assert!(dashes(&line, f32::NAN, bounds(-10., 10.)).is_empty());
+    }
+
+    #[test]
+    fn dashes_are_clipped() {
+        let dashes = dashes(&[(0., 0.), (1e9, 0.)], 4., bounds(-5., 5.));
+        assert_eq!(dashes, [vec![(0., 0.), (4., 0.)]]);
+    }
+
+    #[test]
+    fn dashed_guides_with_zero_width() {
+        let options = RenderOptions {
+            invisible_strokes: InvisibleStrokes::Dashed,
+            guide_width: 0.,
+            ..Default::default()
+        };
+        let document = invisible_stroke(&[(0., 0.), (100., 0.), (100., 100.)]);
+        let (image, _) = render(&document.tags, &options);
+        assert!(image.data.iter().all(|&c| c == 0));
+    }
+
+    #[test]
+    fn dashed_guides_far_away() {
+        let options = RenderOptions {
+            view_box: Some(bounds(0., 10.)),
+            invisible_strokes: InvisibleStrokes::Dashed,
+            ..Default::default()
+        };
+        let document = invisible_stroke(&[(5., 5.), (1e9, 5.)]);
+        let (image, _) = render(&document.tags, &options);
+        assert!(image.data.iter().any(|&c| c != 0));
+    }
+
+    #[test]
+    fn huge_drawing_is_scaled_down() {
+        let points = [(0., 0.), (1e9, 0.), (1e9, 10.), (0., 10.)];
+        let document = document(ShapeType::Fill, ComponentType::Fill, &points);
+        let (image, report) = render(&document.tags, &RenderOptions::default());
+        assert!(image.width as u64 * image.height as u64 <= MAX_PIXELS);
+        assert!(report
+            .warnings
+            .iter()
+            .any(|(warning, _)| *warning == ConversionWarning::ImageScaledDown));
+
+        let options = RenderOptions {
+            max_pixels: 100,
+            ..Default::default()
+        };
+        let view_box = options.view_box(&document.tags);
+        let (width, height) = options.image_size(view_box);
+        assert!(width as u64 * height as u64 <= 100);
+    }
+
+    #[test]
+    #[should_panic(expected = "image too large")]
+    fn image_size_overflow() {
+        RgbaImage::new(u32::MAX, u32::MAX);
+    }
+}
@@ -0,0 +1,162 @@
+//! Scanline rasterization with a grid of `samples` by `samples` sample points per pixel. With one
+//! sample, pixels are covered if their center is inside, so there's no anti-aliasing.
+
+use super::RgbaImage;
+use crate::color::Rgba;
+use crate::layer::Point;
+
+/// Fills polygons using the even-odd rule. Polygons are closed implicitly.
+pub fn fill_even_odd(image: &mut RgbaImage, polygons: &[Vec<Point>], rgba: Rgba, samples: u32) {
+    let edges: Vec<(Point, Point)> = polygons
+        .iter()
+        .filter(|polygon| polygon.len() >= 3)
+        .flat_map(|polygon| {
+            let next = polygon.iter().skip(1).chain(polygon.first());
+            polygon.iter().copied().zip(next.copied())
+        })
+        .filter(|(a, b)| a.1 != b.1)
+        .collect();
+    if edges.is_empty() {
+        return;
+    }
+
+    let (min_y, max_y) = edges
+        .iter()
+        .fold((f32::INFINITY, f32::NEG_INFINITY), |(min, max), e| {
+            (min.min(e.0 .1).min(e.1 .1), max.max(e.0 .1).max(e.1 .1))
+        });
+    let n = samples.max(1);
+    let nf = n as f32;
+    // in sample rows, which are pixel rows if there's one sample
+    let y_start = sample_start(min_y, nf, image.height * n);
+    let y_end = sample_start(max_y, nf, image.height * n);
+
+    let mut crossings = Vec::new();
+    // number of covered samples in each pixel of the current row
+    let mut coverage = vec![0_u32; image.width as usize];
+    let mut row = y_start / n;
+    for sample_y in y_start..y_end {
+        if sample_y / n != row {
+            blend_coverage(image, row, &mut coverage, rgba, n * n);
+            row = sample_y / n;
+        }
+
+        let center = (sample_y as f32 + 0.5) / nf;
+        crossings.clear();
+        for &((x0, y0), (x1, y1)) in &edges {
+            // half-open so that vertices on the scanline are only counted once
+            if (y0 <= center) != (y1 <= center) {
+                crossings.push(x0 + (center - y0) / (y1 - y0) * (x1 - x0));
+            }
+        }
+        crossings.sort_by(f32::total_cmp);
+
+        for span in crossings.chunks_exact(2) {
+            let x_start = sample_start(span[0], nf, image.width * n);
+            let x_end = sample_start(span[1], nf, image.width * n);
+            for sample_x in x_start..x_end {
+                coverage[(sample_x / n) as usize] += 1;
+            }
+        }
+    }
+    if y_start < y_end {
+        blend_coverage(image, row, &mut coverage, rgba, n * n);
+    }
+}
+
+/// Draws a row of coverage counts out of `total` samples, and resets them.
+fn blend_coverage(image: &mut RgbaImage, y: u32, coverage: &mut [u32], rgba: Rgba, total: u32) {
+    for (x, count) in coverage.iter_mut().enumerate() {
+        if *count > 0 {
+            let a = (rgba.a as u32 * (*count).min(total) + total / 2) / total;
+            image.blend_pixel(x as u32, y, rgba.with_alpha(a as u8));
+            *count = 0;
+        }
+    }
+}
+
+/// Strokes polylines with round caps and joins.
+pub fn stroke(
+    image: &mut RgbaImage,
+    polylines: &[Vec<Point>],
+    width: f32,
+    rgba: Rgba,
+    samples: u32,
+) {
+    let radius = (width / 2.).max(0.5);
+    let points = polylines.iter().flatten();
+    let Some((min_x, min_y, max_x, max_y)) = points.fold(None, |bounds, &(x, y)| {
+        let (x0, y0, x1, y1) = bounds.unwrap_or((x, y, x, y));
+        Some((x0.min(x), y0.min(y), x1.max(x), y1.max(y)))
+    }) else {
+        return;
+    };
+    let n = samples.max(1);
+    let nf = n as f32;
+    let (width, height) = (image.width * n, image.height * n);
+    // in samples, aligned to pixels
+    let x_start = sample_start(min_x - radius, nf, width) / n * n;
+    let x_end = sample_start(max_x + radius, nf, width).div_ceil(n) * n;
+    let y_start = sample_start(min_y - radius, nf, height) / n * n;
+    let y_end = sample_start(max_y + radius, nf, height).div_ceil(n) * n;
+    if x_start >= x_end || y_start >= y_end {
+        return;
+    }
+
+    // collect coverage first, so overlapping segments don't blend the color twice
+    let mask_width = (x_end - x_start) as usize;
+    let mut mask = vec![false; mask_width * (y_end - y_start) as usize];
+    for polyline in polylines {
+        let segments = polyline.windows(2).map(|w| (w[0], w[1])).chain(
+            polyline
+                .first()
+                .filter(|_| polyline.len() == 1)
+                .map(|&p| (p, p)),
+        );
+        for (a, b) in segments {
+            let sx_start = sample_start(a.0.min(b.0) - radius, nf, width).max(x_start);
+            let sx_end = sample_start(a.0.max(b.0) + radius, nf, width).min(x_end);
+            let sy_start = sample_start(a.1.min(b.1) - radius, nf, height).max(y_start);
+            let sy_end = sample_start(a.1.max(b.1) + radius, nf, height).min(y_end);
+            for y in sy_start..sy_end {
+                for x in sx_start..sx_end {
+                    let p = ((x as f32 + 0.5) / nf, (y as f32 + 0.5) / nf);
+                    if distance_to_segment(p, a, b) <= radius {
+                        let i = (y - y_start) as usize * mask_width + (x - x_start) as usize;
+                        mask[i] = true;
+                    }
+                }
+            }
+        }
+    }
+
+    let mut coverage = vec![0_u32; image.width as usize];
+    for y in (y_start..y_end).step_by(n as usize) {
+        for sample_y in y..y + n {
+            let row = (sample_y - y_start) as usize * mask_width;
+            for x in x_start..x_end {
+                if mask[row + (x - x_start) as usize] {
+                    coverage[(x / n) as usize] += 1;
+                }
+            }
+        }
+        blend_coverage(image, y / n, &mut coverage, rgba, n * n);
+    }
+}
+
+/// Returns the first sample whose center is at or after `pos` (in pixels), with `n` samples per
+/// pixel, clamped to `0..=size` (in samples).
+fn sample_start(pos: f32, n: f32, size: u32) -> u32 {
+    (pos * n - 0.5).ceil().clamp(0., size as f32) as u32
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let len_sq = dx * dx + dy * dy;
+    let t = if len_sq > 0. {
+        (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / len_sq).clamp(0., 1.)
+    } else {
+        0.
+    };
+    (p.0 - (a.0 + t * dx)).hypot(p.1 - (a.1 + t * dy))
+}
@@ -0,0 +1,228 @@
+//! Round-trip checks for writers: read a file, write it back out, read the result and compare it
+//! with the original.
+//!
+//! There's no writer in this crate yet (see `notes/writer.md`), so the function that writes is
+//! passed in. Any writer has to pass this for every file it's meant to support.
+//!
+//! ```
+//! # fn example(file: &[u8], write: fn(&[tvg::read::FileData]) -> Result<Vec<u8>, String>) {
+//! tvg::roundtrip::assert_roundtrip_with(file, write);
+//! # }
+//! ```
+//!
+//! When the result differs, the failure lists the structural differences (see [diff]) and shows
+//! the first bytes that differ, as a short hexdump. The bytes are compared in the decoded blocks
+//! of the tags where possible, since compressed data can't be compared meaningfully.
+
+use crate::diff::{diff, DiffOptions, Difference};
+use crate::read::{
+    read_blocks, read_with_options, FileData, FileTag, ParseProfile, ReadError, ReadOptions,
+};
+use std::fmt::{self, Write};
+use thiserror::Error;
+
+/// Number of bytes shown in a [Divergence].
+const WINDOW: usize = 64;
+const BYTES_PER_LINE: usize = 16;
+
+/// Why a round trip failed.
+#[derive(Debug)]
+pub struct RoundtripFailure {
+    pub error: RoundtripError,
+    /// The first bytes where the written file differs from the original, if it was written.
+    pub divergence: Option<Box<Divergence>>,
+}
+
+#[derive(Debug, Error)]
+pub enum RoundtripError {
+    #[error("failed to read the original: {0}")]
+    Read(ReadError),
+    #[error("failed to read tag {tag:08x} of the original: {error}")]
+    Unreadable { tag: u32, error: String },
+    #[error("failed to write: {0}")]
+    Write(String),
+    #[error("failed to read the written file: {0}")]
+    Reread(ReadError),
+    #[error("the written file is different:\n{}", list_differences(.0))]
+    Changed(Vec<Difference>),
+}
+
+fn list_differences(differences: &[Difference]) -> String {
+    let lines: Vec<_> = differences.iter().map(|d| d.to_string()).collect();
+    lines.join("\n")
+}
+
+impl fmt::Display for RoundtripFailure {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(f, "{}", self.error)?;
+        if let Some(divergence) = &self.divergence {
+            write!(f, "\n\n{divergence}")?;
+        }
+        Ok(())
+    }
+}
+
+impl std::error::Error for RoundtripFailure {}
+
+/// A region of bytes that differs between the original and the written file.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct Divergence {
+    /// The decoded block of this tag, or the whole file if None.
+    pub tag: Option<FileTag>,
+    /// Offset of the first byte that differs.
+    pub offset: usize,
+    /// Offset of the first byte shown.
+    pub start: usize,
+    /// The bytes shown, starting at `start`. Can be shorter than the other side, or empty, if
+    /// its data ends earlier.
+    pub original: Vec<u8>,
+    pub written: Vec<u8>,
+}
+
+impl Divergence {
+    /// Finds the first difference between two blocks of data, if there is one.
+    fn find(tag: Option<FileTag>, original: &[u8], written: &[u8]) -> Option<Self> {
+        let offset = match original.iter().zip(written).position(|(a, b)| a != b) {
+            Some(offset) => offset,
+            None if original.len() != written.len() => original.len().min(written.len()),
+            None => return None,
+        };
+        // a line of context before the line with the difference
+        let start = (offset / BYTES_PER_LINE * BYTES_PER_LINE).saturating_sub(BYTES_PER_LINE);
+        let window = |data: &[u8]| {
+            let end = (start + WINDOW).min(data.len());
+            data[start.min(end)..end].to_vec()
+        };
+        Some(Divergence {
+            tag,
+            offset,
+            start,
+            original: window(original),
+            written: window(written),
+        })
+    }
+}
+
+impl fmt::Display for Divergence {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self.tag {
+            Some(tag) => writeln!(
+                f,
+                "first difference in the {tag:?} block at {:#x}:",
+                self.offset
+            )?,
+            None => writeln!(f, "first difference in the file at {:#x}:", self.offset)?,
+        }
+        writeln!(f, "original:")?;
+        write_hexdump(f, self.start, &self.original, &self.written)?;
+        writeln!(f, "written:")?;
+        write_hexdump(f, self.start, &self.written, &self.original)
+    }
+}
+
+/// Writes the lines of a hexdump of `data`, each followed by a line that marks the bytes that
+/// differ from `other` with `^^`.
+fn write_hexdump(f: &mut fmt::Formatter, start: usize, data: &[u8], other: &[u8]) -> fmt::Result {
+    if data.is_empty() {
+        return writeln!(f, "  (ends before {start:#x})");
+    }
+    for (i, line) in data.chunks(BYTES_PER_LINE).enumerate() {
+        let line_start = i * BYTES_PER_LINE;
+        let mut bytes = String::new();
+        let mut markers = String::new();
+        for (j, byte) in line.iter().enumerate() {
+            write!(bytes, " {byte:02x}")?;
+            let differs = other.get(line_start + j) != Some(byte);
+            markers.push_str(if differs { " ^^" } else { "   " });
+        }
+        writeln!(f, "  {:08x} {bytes}", start + line_start)?;
+        if markers.contains('^') {
+            writeln!(f, "  {:8} {}", "", markers.trim_end())?;
+        }
+    }
+    Ok(())
+}
+
+/// Reads a file, writes it with `write`, reads the result and compares it with the original.
+///
+/// The files are read with [ParseProfile::Forensic], so unknown data is preserved (see
+/// [ReadOptions::preserve_unknown]) and the writer has to write it back out as well. Tags of the
+/// original that can't be read are still an error. Points have to be exactly the same.
+pub fn roundtrip<E: fmt::Display>(
+    input: &[u8],
+    write: impl FnOnce(&[FileData]) -> Result<Vec<u8>, E>,
+) -> Result<(), RoundtripFailure> {
+    let options = ReadOptions::with_profile(ParseProfile::Forensic);
+    let fail = |error, divergence| RoundtripFailure { error, divergence };
+
+    let original =
+        read_with_options(input, &options).map_err(|e| fail(RoundtripError::Read(e), None))?;
+    if let Some((tag, error)) = first_unreadable(&original) {
+        return Err(fail(RoundtripError::Unreadable { tag, error }, None));
+    }
+    let written = write(&original).map_err(|e| fail(RoundtripError::Write(e.to_string()), None))?;
+    let divergence = first_divergence(input, &written, &options);
+    let reread = match read_with_options(&written[..], &options) {
+        Ok(reread) => reread,
+        Err(e) => return Err(fail(RoundtripError::Reread(e), divergence)),
+    };
+
+    let exact = DiffOptions { epsilon: 0. };
+    let differences = diff(&original, &reread, &exact);
+    if !differences.is_empty() {
+        return Err(fail(RoundtripError::Changed(differences), divergence));
+    }
+    Ok(())
+}
+
+/// Returns the tag number and error of the first tag that couldn't be read, including tags in
+/// the main data.
+fn first_unreadable(tags: &[FileData]) -> Option<(u32, String)> {
+    tags.iter().find_map(|tag| match tag {
+        FileData::Unreadable { tag, error, .. } => Some((*tag, error.clone())),
+        FileData::Main(tags) => first_unreadable(tags),
+        _ => None,
+    })
+}
+
+/// Like [roundtrip], but panics with the failure, for use in tests.
+#[track_caller]
+pub fn assert_roundtrip_with<E: fmt::Display>(
+    input: &[u8],
+    write: impl FnOnce(&[FileData]) -> Result<Vec<u8>, E>,
+) {
+    if let Err(failure) = roundtrip(input, write) {
+        panic!("round trip failed: {failure}");
+    }
+}
+
+/// Finds the first bytes that differ, in the decoded blocks of the tags if the files have the same
+/// tags, and in the whole files otherwise.
+///
+/// The main data block contains the other blocks, so it's only compared if they're all the same
+/// (e.g. when only a tag without a block of its own, like `TTOC`, differs).
+fn first_divergence(
+    original: &[u8],
+    written: &[u8],
+    options: &ReadOptions,
+) -> Option<Box<Divergence>> {
+    let blocks = |data: &[u8]| read_blocks(data, options).ok();
+    if let (Some(a), Some(b)) = (blocks(original), blocks(written)) {
+        let same_tags = a.len() == b.len() && a.iter().zip(&b).all(|(a, b)| a.tag == b.tag);
+        if same_tags {
+            let mut pairs: Vec<_> = a.into_iter().zip(b).collect();
+            // the main data last
+            pairs.sort_by_key(|(a, _)| a.tag == FileTag::MainData);
+            for (a, b) in pairs {
+                let tag = a.tag;
+                let (Ok(a), Ok(b)) = (a.block.decode(options), b.block.decode(options)) else {
+                    break;
+                };
+                if let Some(divergence) = Divergence::find(Some(tag), &a, &b) {
+                    return Some(Box::new(divergence));
+                }
+            }
+        }
+    }
+    Divergence::find(None, original, written).map(Box::new)
+}
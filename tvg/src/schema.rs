@@ -0,0 +1,12 @@
+//! JSON Schema for the serde representation of the document model.
+//!
+//! Consumers of the JSON/msgpack dumps (e.g. the web viewer) can feed this into a code generator
+//! to get typed bindings instead of guessing at the structure.
+
+use crate::read::FileData;
+use schemars::schema::RootSchema;
+
+/// Returns a JSON Schema describing the serialized output of [crate::read::read].
+pub fn document_schema() -> RootSchema {
+    schemars::schema_for!(Vec<FileData>)
+}
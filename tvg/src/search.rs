@@ -0,0 +1,134 @@
+//! Finding the drawings in a folder tree that use a palette color, e.g. before retiring a swatch.
+//!
+//! Only the palette of each file is read (see [read_one]), which is much faster than reading
+//! whole drawings. A drawing's palette contains the colors that were available when it was saved,
+//! so it can include colors that no shape in the drawing uses.
+
+use crate::palette::PaletteColor;
+use crate::read::{read_one, FileData, FileTag, ReadError, ReadOptions};
+use std::fs::File;
+use std::io::{self, BufReader};
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+/// The color to look for.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum ColorQuery {
+    Id(u64),
+    /// A color name. Names are compared exactly.
+    Name(String),
+}
+
+impl ColorQuery {
+    /// Parses a query: a color ID in hex, as 16 digits (like in `tvg dump`) or with a `0x`
+    /// prefix, and a name otherwise.
+    pub fn parse(query: &str) -> Self {
+        let hex = match query.strip_prefix("0x") {
+            Some(hex) => Some(hex),
+            None if query.len() == 16 => Some(query),
+            None => None,
+        };
+        match hex.and_then(|hex| u64::from_str_radix(hex, 16).ok()) {
+            Some(id) => ColorQuery::Id(id),
+            None => ColorQuery::Name(query.to_string()),
+        }
+    }
+
+    pub fn matches(&self, color: &PaletteColor) -> bool {
+        match self {
+            ColorQuery::Id(id) => color.id() == Some(*id),
+            ColorQuery::Name(name) => color.name() == Some(name.as_str()),
+        }
+    }
+}
+
+/// A palette color in a file.
+#[derive(Debug, Clone)]
+pub struct ColorMatch {
+    pub path: PathBuf,
+    pub color: PaletteColor,
+}
+
+#[derive(Debug, Error)]
+pub enum SearchError {
+    #[error("failed to list {0}: {1}")]
+    Io(PathBuf, io::Error),
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, ReadError),
+}
+
+/// What a search found.
+#[derive(Debug, Default)]
+pub struct SearchResults {
+    /// Matching colors, ordered by path.
+    pub matches: Vec<ColorMatch>,
+    /// Folders and files that couldn't be read. The search continues after errors.
+    pub errors: Vec<SearchError>,
+}
+
+/// Searches all `.tvg` files in a folder and its subfolders for colors that match a query.
+pub fn search(root: impl AsRef<Path>, query: &ColorQuery, options: &ReadOptions) -> SearchResults {
+    let mut results = SearchResults::default();
+    search_dir(root.as_ref(), query, options, &mut results);
+    results
+}
+
+fn search_dir(dir: &Path, query: &ColorQuery, options: &ReadOptions, results: &mut SearchResults) {
+    let entries = match list_dir(dir) {
+        Ok(entries) => entries,
+        Err(e) => {
+            results.errors.push(SearchError::Io(dir.to_path_buf(), e));
+            return;
+        }
+    };
+    for (path, is_dir) in entries {
+        if is_dir {
+            search_dir(&path, query, options, results);
+        } else if is_tvg(&path) {
+            match search_file(&path, query, options) {
+                Ok(colors) => {
+                    let matches = colors.into_iter().map(|color| ColorMatch {
+                        path: path.clone(),
+                        color,
+                    });
+                    results.matches.extend(matches);
+                }
+                Err(e) => results.errors.push(SearchError::Read(path, e)),
+            }
+        }
+    }
+}
+
+/// Lists a folder as (path, is a folder), sorted by path. Symlinks to folders aren't followed, so
+/// there are no cycles.
+fn list_dir(dir: &Path) -> io::Result<Vec<(PathBuf, bool)>> {
+    let mut entries = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        entries.push((entry.path(), entry.file_type()?.is_dir()));
+    }
+    entries.sort();
+    Ok(entries)
+}
+
+fn is_tvg(path: &Path) -> bool {
+    path.extension()
+        .is_some_and(|extension| extension.eq_ignore_ascii_case("tvg"))
+}
+
+/// Returns the matching colors in the palette of a file.
+fn search_file(
+    path: &Path,
+    query: &ColorQuery,
+    options: &ReadOptions,
+) -> Result<Vec<PaletteColor>, ReadError> {
+    let file = BufReader::new(File::open(path)?);
+    let Some(FileData::Palette(palette)) = read_one(file, FileTag::Palette, options)? else {
+        return Ok(Vec::new());
+    };
+    Ok(palette
+        .colors
+        .into_iter()
+        .filter(|color| query.matches(color))
+        .collect())
+}
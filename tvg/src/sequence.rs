@@ -0,0 +1,444 @@
+//! Drawing sequences: Harmony elements stored as a folder with one TVG file per drawing.
+//!
+//! Drawing files are named `<element>-<drawing>.tvg`, e.g. `bg-1.tvg`, `bg-2.tvg`, `bg-2a.tvg`.
+
+use crate::document::Document;
+#[cfg(feature = "export-lottie")]
+use crate::export::lottie::{self, LottieOptions};
+#[cfg(feature = "export-pdf")]
+use crate::export::pdf::{self, PdfOptions};
+#[cfg(feature = "export-svg")]
+use crate::export::svg::{self, SvgOptions};
+#[cfg(any(
+    feature = "export-svg",
+    feature = "export-lottie",
+    feature = "export-pdf",
+    feature = "png"
+))]
+use crate::export::ConversionReport;
+#[cfg(any(feature = "export-svg", feature = "png"))]
+use crate::geometry::Bounds;
+use crate::palette::{ColorConflict, ConflictPolicy, PaletteColor, PaletteData};
+use crate::read::ReadError;
+#[cfg(feature = "png")]
+use crate::render::{self, RenderOptions};
+#[cfg(feature = "png")]
+use crate::thumbnail::{self, Preview, PreviewSource};
+use std::cmp::Ordering;
+use std::collections::{BTreeMap, HashSet};
+use std::io;
+use std::path::{Path, PathBuf};
+use thiserror::Error;
+
+#[derive(Debug, Error)]
+pub enum SequenceError {
+    #[error("io error: {0}")]
+    Io(#[from] io::Error),
+    #[error("failed to read {0}: {1}")]
+    Read(PathBuf, ReadError),
+    #[error("no drawings found")]
+    NoDrawings,
+    #[error("folder contains drawings of multiple elements: {0:?}")]
+    MultipleElements(Vec<String>),
+    #[cfg(feature = "png")]
+    #[error("png error: {0}")]
+    Png(#[from] png::EncodingError),
+}
+
+/// File format for exporting frames.
+#[cfg(any(feature = "export-svg", feature = "png"))]
+#[derive(Debug, Clone)]
+pub enum FrameFormat {
+    #[cfg(feature = "export-svg")]
+    Svg(SvgOptions),
+    #[cfg(feature = "png")]
+    Png(RenderOptions),
+}
+
+/// A drawing in a sequence.
+#[derive(Debug, Clone)]
+pub struct Frame {
+    /// The drawing name, e.g. `2a` for `bg-2a.tvg`.
+    pub name: String,
+    pub path: PathBuf,
+    pub document: Document,
+}
+
+/// All drawings of an element, ordered by drawing name.
+#[derive(Debug, Clone)]
+pub struct DrawingSequence {
+    /// The element name, e.g. `bg` for `bg-1.tvg`.
+    pub element: String,
+    pub frames: Vec<Frame>,
+}
+
+/// Splits a file name into the element name and the drawing name.
+/// Returns None if it's not a TVG file or the name has no drawing name.
+pub fn parse_file_name(file_name: &str) -> Option<(&str, &str)> {
+    let stem = file_name.strip_suffix(".tvg")?;
+    let (element, drawing) = stem.rsplit_once('-')?;
+    if element.is_empty() || drawing.is_empty() {
+        return None;
+    }
+    Some((element, drawing))
+}
+
+/// Compares drawing names such that numbers are sorted by value (`2` < `10`), and suffixes come
+/// after the plain number (`2` < `2a` < `3`).
+pub fn compare_drawing_names(a: &str, b: &str) -> Ordering {
+    fn split(name: &str) -> (Option<u64>, &str) {
+        let digits = name.len() - name.trim_start_matches(|c: char| c.is_ascii_digit()).len();
+        (name[..digits].parse().ok(), &name[digits..])
+    }
+    let (a_num, a_rest) = split(a);
+    let (b_num, b_rest) = split(b);
+    match (a_num, b_num) {
+        (Some(a_num), Some(b_num)) => a_num.cmp(&b_num).then_with(|| a_rest.cmp(b_rest)),
+        // numbered drawings first
+        (Some(_), None) => Ordering::Less,
+        (None, Some(_)) => Ordering::Greater,
+        (None, None) => a.cmp(b),
+    }
+    .then_with(|| a.cmp(b))
+}
+
+/// Lists the drawing files in a folder as (element, drawing name, path).
+fn list_drawings(dir: &Path) -> Result<Vec<(String, String, PathBuf)>, SequenceError> {
+    let mut drawings = Vec::new();
+    for entry in std::fs::read_dir(dir)? {
+        let entry = entry?;
+        if !entry.file_type()?.is_file() {
+            continue;
+        }
+        let file_name = entry.file_name();
+        let Some((element, drawing)) = file_name.to_str().and_then(parse_file_name) else {
+            continue;
+        };
+        drawings.push((element.to_string(), drawing.to_string(), entry.path()));
+    }
+    Ok(drawings)
+}
+
+/// Returns the element of the drawings. Fails if there are none or they're of several elements.
+fn single_element(drawings: &[(String, String, PathBuf)]) -> Result<String, SequenceError> {
+    let mut elements: Vec<_> = drawings
+        .iter()
+        .map(|(element, ..)| element.clone())
+        .collect();
+    elements.sort();
+    elements.dedup();
+    match &elements[..] {
+        [] => Err(SequenceError::NoDrawings),
+        [element] => Ok(element.clone()),
+        _ => Err(SequenceError::MultipleElements(elements)),
+    }
+}
+
+impl DrawingSequence {
+    /// Loads all drawings in a folder. Fails if it contains drawings of more than one element.
+    pub fn load(dir: impl AsRef<Path>) -> Result<Self, SequenceError> {
+        let drawings = list_drawings(dir.as_ref())?;
+        let element = single_element(&drawings)?;
+        Self::load_drawings(element, drawings)
+    }
+
+    /// Loads all drawings of an element in a folder.
+    pub fn load_element(dir: impl AsRef<Path>, element: &str) -> Result<Self, SequenceError> {
+        let drawings = list_drawings(dir.as_ref())?
+            .into_iter()
+            .filter(|(e, ..)| e == element)
+            .collect();
+        Self::load_drawings(element.to_string(), drawings)
+    }
+
+    fn load_drawings(
+        element: String,
+        mut drawings: Vec<(String, String, PathBuf)>,
+    ) -> Result<Self, SequenceError> {
+        if drawings.is_empty() {
+            return Err(SequenceError::NoDrawings);
+        }
+        drawings.sort_by(|(_, a, _), (_, b, _)| compare_drawing_names(a, b));
+
+        let mut frames = Vec::with_capacity(drawings.len());
+        for (_, name, path) in drawings {
+            let file = std::fs::File::open(&path)?;
+            let document = Document::read(io::BufReader::new(file))
+                .map_err(|e| SequenceError::Read(path.clone(), e))?;
+            frames.push(Frame {
+                name,
+                path,
+                document,
+            });
+        }
+
+        Ok(DrawingSequence { element, frames })
+    }
+
+    /// Returns an iterator over the frames, in order.
+    pub fn frames(&self) -> impl Iterator<Item = &Frame> {
+        self.frames.iter()
+    }
+
+    /// Returns a frame by drawing name.
+    pub fn frame(&self, name: &str) -> Option<&Frame> {
+        self.frames.iter().find(|frame| frame.name == name)
+    }
+
+    /// Returns the union of all palettes in the sequence. If several drawings contain a color
+    /// with the same ID, the first one is used.
+    pub fn palette(&self) -> Vec<&PaletteColor> {
+        let mut seen = HashSet::new();
+        self.frames
+            .iter()
+            .filter_map(|frame| frame.document.palette())
+            .flat_map(|palette| &palette.colors)
+            .filter(|color| seen.insert(color.id()))
+            .collect()
+    }
+
+    /// Merges the palettes of all drawings into one, in drawing order (see [PaletteData::merge]).
+    ///
+    /// Also returns the color IDs that change in each drawing, in the same order as
+    /// [DrawingSequence::frames]. With [ConflictPolicy::Keep], these are always empty, and the
+    /// colors that have IDs are the same as in [DrawingSequence::palette].
+    pub fn merged_palette(
+        &self,
+        policy: ConflictPolicy,
+    ) -> Result<(PaletteData, Vec<BTreeMap<u64, u64>>), ColorConflict> {
+        let mut merged = PaletteData { colors: Vec::new() };
+        let mut remaps = Vec::with_capacity(self.frames.len());
+        for frame in &self.frames {
+            let remap = match frame.document.palette() {
+                Some(palette) => merged.merge(palette, policy)?,
+                None => BTreeMap::new(),
+            };
+            remaps.push(remap);
+        }
+        Ok((merged, remaps))
+    }
+
+    /// Resolves a color ID used in `frame`.
+    ///
+    /// Each drawing contains a copy of the palette colors it uses, but if it doesn't contain the
+    /// color, it's looked up in the other drawings of the sequence.
+    pub fn resolve_color<'a>(&'a self, frame: &'a Frame, id: u64) -> Option<&'a PaletteColor> {
+        let find_color = |frame: &'a Frame| frame.document.palette()?.find(id);
+        find_color(frame).or_else(|| self.frames.iter().find_map(find_color))
+    }
+}
+
+#[cfg(any(feature = "export-svg", feature = "png"))]
+impl DrawingSequence {
+    /// Returns the union of the view boxes of all frames, so that they line up when exported.
+    /// Fails if there are no frames.
+    fn common_view_box(
+        &self,
+        view_box: impl Fn(&Document) -> Bounds,
+    ) -> Result<Bounds, SequenceError> {
+        self.frames
+            .iter()
+            .map(|frame| view_box(&frame.document))
+            .reduce(Bounds::union)
+            .ok_or(SequenceError::NoDrawings)
+    }
+
+    /// Exports every frame into a folder as `<element>-<drawing>.svg` or `.png`.
+    ///
+    /// Unless a view box is set explicitly, all frames use the same view box, which contains all
+    /// frames. Returns the paths of the written files. Fails if there are no frames.
+    pub fn export_frames(
+        &self,
+        dir: impl AsRef<Path>,
+        format: &FrameFormat,
+    ) -> Result<(Vec<PathBuf>, ConversionReport), SequenceError> {
+        if self.frames.is_empty() {
+            return Err(SequenceError::NoDrawings);
+        }
+        let mut report = ConversionReport::default();
+        let mut paths = Vec::with_capacity(self.frames.len());
+
+        match format {
+            #[cfg(feature = "export-svg")]
+            FrameFormat::Svg(options) => {
+                let view_box = self.common_view_box(|doc| options.view_box(&doc.tags))?;
+                let options = SvgOptions {
+                    view_box: Some(view_box),
+                    ..options.clone()
+                };
+                for frame in &self.frames {
+                    let (svg, frame_report) = svg::to_svg(&frame.document.tags, &options);
+                    report.merge(frame_report);
+                    let path = dir.as_ref().join(self.frame_file_name(frame, "svg"));
+                    std::fs::write(&path, svg)?;
+                    paths.push(path);
+                }
+            }
+            #[cfg(feature = "png")]
+            FrameFormat::Png(options) => {
+                for (frame, image) in self
+                    .frames
+                    .iter()
+                    .zip(self.render_frames(options, &mut report))
+                {
+                    let path = dir.as_ref().join(self.frame_file_name(frame, "png"));
+                    let file = std::fs::File::create(&path)?;
+                    image.write_png(io::BufWriter::new(file))?;
+                    paths.push(path);
+                }
+            }
+        }
+
+        Ok((paths, report))
+    }
+
+    fn frame_file_name(&self, frame: &Frame, extension: &str) -> String {
+        format!("{}-{}.{extension}", self.element, frame.name)
+    }
+
+    /// Exports the sequence as an endlessly looping animated SVG, showing each frame for
+    /// `frame_duration` seconds. Fails if there are no frames.
+    #[cfg(feature = "export-svg")]
+    pub fn to_animated_svg(
+        &self,
+        options: &SvgOptions,
+        frame_duration: f32,
+    ) -> Result<(String, ConversionReport), SequenceError> {
+        let mut report = ConversionReport::default();
+        let view_box = self.common_view_box(|doc| options.view_box(&doc.tags))?;
+
+        let n = self.frames.len();
+        let duration = frame_duration * n as f32;
+        let key_times: Vec<_> = (0..n).map(|i| (i as f32 / n as f32).to_string()).collect();
+        let key_times = key_times.join(";");
+
+        let mut out = String::new();
+        svg::write_svg_start(&mut out, view_box, options);
+        for (i, frame) in self.frames.iter().enumerate() {
+            let values: Vec<_> = (0..n)
+                .map(|j| if i == j { "inline" } else { "none" })
+                .collect();
+            out.push_str(&format!(
+                "<g class=\"frame\" display=\"none\">\n<animate attributeName=\"display\" \
+                 values=\"{}\" keyTimes=\"{key_times}\" dur=\"{duration}s\" \
+                 calcMode=\"discrete\" repeatCount=\"indefinite\"/>\n",
+                values.join(";")
+            ));
+            svg::write_drawing(
+                &mut out,
+                &frame.document.tags,
+                view_box,
+                options,
+                &mut report,
+            );
+            out.push_str("</g>\n");
+        }
+        out.push_str("</svg>\n");
+        Ok((out, report))
+    }
+
+    /// Renders all frames with the same view box. Returns no images if there are no frames.
+    #[cfg(feature = "png")]
+    pub fn render_frames(
+        &self,
+        options: &RenderOptions,
+        report: &mut ConversionReport,
+    ) -> Vec<render::RgbaImage> {
+        let Ok(view_box) = self.common_view_box(|doc| options.view_box(&doc.tags)) else {
+            return Vec::new();
+        };
+        let options = RenderOptions {
+            view_box: Some(view_box),
+            ..options.clone()
+        };
+        self.frames
+            .iter()
+            .map(|frame| {
+                let (image, frame_report) = render::render(&frame.document.tags, &options);
+                report.merge(frame_report);
+                image
+            })
+            .collect()
+    }
+
+    /// Exports the sequence as an endlessly looping animated PNG. Fails if there are no frames.
+    #[cfg(feature = "png")]
+    pub fn write_apng(
+        &self,
+        options: &RenderOptions,
+        frame_delay_ms: u16,
+        w: impl io::Write,
+    ) -> Result<ConversionReport, SequenceError> {
+        if self.frames.is_empty() {
+            return Err(SequenceError::NoDrawings);
+        }
+        let mut report = ConversionReport::default();
+        let frames = self.render_frames(options, &mut report);
+        render::write_apng(&frames, frame_delay_ms, w)?;
+        Ok(report)
+    }
+}
+
+#[cfg(feature = "png")]
+impl DrawingSequence {
+    /// Returns a preview of each frame that fits into a `max_px` by `max_px` box, in order: the
+    /// companion preview of the drawing file if it has one, and otherwise the drawing rendered
+    /// with `options` (see [thumbnail::preview]).
+    pub fn previews(&self, max_px: u32, options: &RenderOptions) -> Vec<Preview> {
+        self.frames
+            .iter()
+            .map(|frame| {
+                thumbnail::read_companion(&frame.path, max_px).unwrap_or_else(|| Preview {
+                    image: thumbnail::generate(&frame.document, max_px, options),
+                    source: PreviewSource::Rendered,
+                })
+            })
+            .collect()
+    }
+
+    /// Returns a preview of each drawing in a folder as (drawing name, preview), ordered by drawing
+    /// name, without loading the sequence.
+    ///
+    /// Only drawings without a companion preview are read and rendered, so this is much faster
+    /// than [DrawingSequence::load] for element folders that have them. Fails if the folder
+    /// contains drawings of more than one element.
+    pub fn load_previews(
+        dir: impl AsRef<Path>,
+        max_px: u32,
+        options: &RenderOptions,
+    ) -> Result<Vec<(String, Preview)>, SequenceError> {
+        let mut drawings = list_drawings(dir.as_ref())?;
+        single_element(&drawings)?;
+        drawings.sort_by(|(_, a, _), (_, b, _)| compare_drawing_names(a, b));
+
+        let mut previews = Vec::with_capacity(drawings.len());
+        for (_, name, path) in drawings {
+            let preview = match thumbnail::preview(&path, max_px, options) {
+                Ok(preview) => preview,
+                Err(thumbnail::PreviewError::Io(e)) => return Err(e.into()),
+                Err(thumbnail::PreviewError::Read(e)) => return Err(SequenceError::Read(path, e)),
+            };
+            previews.push((name, preview));
+        }
+        Ok(previews)
+    }
+}
+
+#[cfg(feature = "export-lottie")]
+impl DrawingSequence {
+    /// Exports the sequence as a Lottie animation, with one drawing per frame.
+    pub fn to_lottie(&self, options: &LottieOptions) -> (String, ConversionReport) {
+        let frames: Vec<_> = self.frames.iter().map(|f| &f.document.tags[..]).collect();
+        lottie::frames_to_lottie(&frames, options)
+    }
+}
+
+#[cfg(feature = "export-pdf")]
+impl DrawingSequence {
+    /// Exports the sequence as a PDF document with one page per drawing.
+    pub fn to_pdf(&self, options: &PdfOptions) -> (Vec<u8>, ConversionReport) {
+        let pages: Vec<_> = self.frames.iter().map(|f| &f.document.tags[..]).collect();
+        pdf::pages_to_pdf(&pages, options)
+    }
+}
+
@@ -0,0 +1,276 @@
+//! Reducing the number of points in paths, e.g. for over-dense drawings captured from tablets.
+
+use crate::document::{Art, Document};
+use crate::geometry::{add, cubic_point, dot, fit_cubic, length, scale, sub, Cubic};
+use crate::layer::{LayerData, PathSegment, Point, ShapeComponentData, ShapePath};
+
+/// Points sampled on each cubic when checking how well a merged cubic matches the originals.
+const SAMPLES_PER_CUBIC: usize = 8;
+
+/// The most cubics that are merged into one, which keeps simplification linear in the number of
+/// segments. Nearly straight runs could otherwise be merged for a very long time.
+const MAX_MERGED_CUBICS: usize = 32;
+
+/// Point counts before and after simplifying a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct SimplifyReport {
+    pub paths: usize,
+    /// Number of points before simplifying, counting cubic control points.
+    pub points_before: usize,
+    /// Number of points after simplifying, counting cubic control points.
+    pub points_after: usize,
+}
+
+impl Document {
+    /// Simplifies every path in every layer. See [ShapePath::simplify].
+    pub fn simplify(&mut self, tolerance: f32) -> SimplifyReport {
+        let mut report = SimplifyReport::default();
+        for art in Art::ALL {
+            let Some(LayerData::Vector { shapes, .. }) = self.layer_mut(art) else {
+                continue;
+            };
+            let paths = shapes
+                .iter_mut()
+                .flat_map(|shape| &mut shape.components)
+                .flat_map(|component| &mut component.tags)
+                .filter_map(|tag| match tag {
+                    ShapeComponentData::Path(path) => Some(path),
+                    _ => None,
+                });
+            for path in paths {
+                let simplified = path.simplify(tolerance);
+                report.paths += 1;
+                report.points_before += path.point_count();
+                report.points_after += simplified.point_count();
+                *path = simplified;
+            }
+        }
+        report
+    }
+}
+
+impl ShapePath {
+    /// Returns a copy of the path with fewer points, which differs from the original by at most
+    /// about `tolerance` drawing units.
+    ///
+    /// Runs of line segments are simplified with the Ramer–Douglas–Peucker algorithm, and runs of
+    /// cubic segments are merged into fewer cubics where they can be refitted within the
+    /// tolerance. Points where a line meets a cubic are always kept, as are corners between
+    /// cubics.
+    ///
+    /// Pencil thickness (`tGTB`) is specified along the whole path, so it still applies, but
+    /// may shift slightly if the length of the path changes. Extra bytes are dropped, since it's
+    /// unknown whether they depend on the points.
+    pub fn simplify(&self, tolerance: f32) -> ShapePath {
+        let Some((first, mut rest)) = self.segments.split_first() else {
+            return self.clone();
+        };
+        let mut segments = vec![first.clone()];
+        let mut current = segment_end(first);
+
+        while let Some(segment) = rest.first() {
+            let is_line = matches!(segment, PathSegment::Line(_));
+            let run_len = rest
+                .iter()
+                .take_while(|segment| matches!(segment, PathSegment::Line(_)) == is_line)
+                .count();
+            let (run, next) = rest.split_at(run_len);
+
+            if is_line {
+                let mut points = vec![current];
+                points.extend(run.iter().map(segment_end));
+                let kept = douglas_peucker(&points, tolerance);
+                segments.extend(kept[1..].iter().map(|&i| PathSegment::Line(points[i])));
+            } else {
+                let mut cubics = Vec::with_capacity(run.len());
+                for segment in run {
+                    if let PathSegment::Cubic(c1, c2, p) = *segment {
+                        cubics.push([current, c1, c2, p]);
+                        current = p;
+                    }
+                }
+                segments.extend(
+                    merge_cubics(&cubics, tolerance)
+                        .into_iter()
+                        .map(|[_, c1, c2, p]| PathSegment::Cubic(c1, c2, p)),
+                );
+            }
+
+            current = segment_end(&run[run.len() - 1]);
+            rest = next;
+        }
+
+        ShapePath {
+            segments,
+            extra: None,
+        }
+    }
+}
+
+fn segment_end(segment: &PathSegment) -> Point {
+    match *segment {
+        PathSegment::Line(p) | PathSegment::Cubic(_, _, p) => p,
+    }
+}
+
+fn distance_to_segment(p: Point, a: Point, b: Point) -> f32 {
+    let ab = sub(b, a);
+    let len_sq = dot(ab, ab);
+    if len_sq == 0. {
+        return length(sub(p, a));
+    }
+    let t = (dot(sub(p, a), ab) / len_sq).clamp(0., 1.);
+    length(sub(p, add(a, scale(ab, t))))
+}
+
+/// Returns the indices of the points to keep, including the first and last.
+fn douglas_peucker(points: &[Point], tolerance: f32) -> Vec<usize> {
+    let mut keep = vec![false; points.len()];
+    keep[0] = true;
+    keep[points.len() - 1] = true;
+
+    // explicit stack, since paths can have tens of thousands of points
+    let mut ranges = vec![(0, points.len() - 1)];
+    while let Some((start, end)) = ranges.pop() {
+        let farthest = (start + 1..end)
+            .map(|i| {
+                (
+                    i,
+                    distance_to_segment(points[i], points[start], points[end]),
+                )
+            })
+            .max_by(|a, b| a.1.total_cmp(&b.1));
+        if let Some((i, distance)) = farthest {
+            if distance > tolerance {
+                keep[i] = true;
+                ranges.push((start, i));
+                ranges.push((i, end));
+            }
+        }
+    }
+
+    (0..points.len()).filter(|&i| keep[i]).collect()
+}
+
+/// Merges consecutive cubics where one cubic can replace several.
+fn merge_cubics(cubics: &[Cubic], tolerance: f32) -> Vec<Cubic> {
+    let mut out = Vec::new();
+    let mut start = 0;
+    let mut merged = cubics[0];
+    for end in 1..cubics.len() {
+        let fitted = if end - start < MAX_MERGED_CUBICS {
+            fit_run(&cubics[start..=end], tolerance)
+        } else {
+            None
+        };
+        match fitted {
+            Some(cubic) => merged = cubic,
+            None => {
+                out.push(merged);
+                start = end;
+                merged = cubics[end];
+            }
+        }
+    }
+    out.push(merged);
+    out
+}
+
+/// Fits one cubic to a run of cubics, keeping the end points and end tangents.
+fn fit_run(run: &[Cubic], tolerance: f32) -> Option<Cubic> {
+    let mut points = vec![run[0][0]];
+    for cubic in run {
+        points.extend((1..=SAMPLES_PER_CUBIC).map(|i| {
+            let t = i as f32 / SAMPLES_PER_CUBIC as f32;
+            cubic_point(cubic[0], cubic[1], cubic[2], cubic[3], t)
+        }));
+    }
+
+    let first = run[0];
+    let last = run[run.len() - 1];
+    let start_tangent = tangent(first[0], [first[1], first[2], first[3]])?;
+    let end_tangent = tangent(last[3], [last[2], last[1], last[0]])?;
+
+    let (cubic, error, _) = fit_cubic(&points, start_tangent, end_tangent);
+    (error <= tolerance).then_some(cubic)
+}
+
+/// Returns the unit direction from `from` to the first of `towards` that's a different point.
+fn tangent(from: Point, towards: [Point; 3]) -> Option<Point> {
+    towards.iter().find_map(|&p| {
+        let d = sub(p, from);
+        let len = length(d);
+        (len > 0.).then(|| scale(d, 1. / len))
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::geometry::flatten;
+    use crate::testgen::{generate, Curves, TestDrawing, TestLayer};
+
+    /// Largest distance from a point of `a` to the polyline `b`.
+    fn deviation(a: &[Point], b: &[Point]) -> f32 {
+        a.iter()
+            .map(|&p| {
+                b.windows(2)
+                    .map(|w| distance_to_segment(p, w[0], w[1]))
+                    .fold(f32::INFINITY, f32::min)
+            })
+            .fold(0., f32::max)
+    }
+
+    fn paths(curves: Curves) -> Vec<ShapePath> {
+        let file = generate(&TestDrawing {
+            layers: vec![TestLayer {
+                curves,
+                ..TestLayer::new(Art::Line, 4, 60)
+            }],
+            ..Default::default()
+        })
+        .unwrap();
+        let document = Document::read(&file[..]).unwrap();
+        let Some(LayerData::Vector { shapes, .. }) = document.layer(Art::Line) else {
+            panic!("no line art");
+        };
+        shapes
+            .iter()
+            .flat_map(|shape| &shape.components)
+            .flat_map(|component| &component.tags)
+            .filter_map(|tag| match tag {
+                ShapeComponentData::Path(path) => Some(path.clone()),
+                _ => None,
+            })
+            .collect()
+    }
+
+    #[test]
+    fn within_tolerance() {
+        for curves in [Curves::Lines, Curves::Cubics, Curves::Mixed] {
+            for tolerance in [0.5, 5., 50.] {
+                let (mut before, mut after) = (0, 0);
+                for path in paths(curves) {
+                    let simplified = path.simplify(tolerance);
+                    before += path.point_count();
+                    after += simplified.point_count();
+
+                    // merged cubics are only checked at a few points, hence "about"
+                    let original = flatten(&path, 0.05);
+                    let simple = flatten(&simplified, 0.05);
+                    let d = deviation(&original, &simple).max(deviation(&simple, &original));
+                    assert!(
+                        d <= tolerance * 1.1 + 0.1,
+                        "{curves:?} with tolerance {tolerance} deviates by {d}"
+                    );
+                }
+                assert!(after <= before);
+                if tolerance >= 5. {
+                    assert!(after < before, "{curves:?} with tolerance {tolerance}");
+                }
+            }
+        }
+    }
+}
@@ -0,0 +1,250 @@
+//! A structured description of the TVG format, as far as this crate understands it.
+//!
+//! Tag lists and values are taken from the same enums and constants the parser uses, through
+//! their `ALL` arrays, which have to list every variant. The notes are matched exhaustively on
+//! those enums, so adding a tag without describing it won't compile.
+
+use crate::layer::{ComponentType, ShapeComponentTag, ShapeType, LAYER_TRAILER};
+use crate::palette::PaletteColorTag;
+use crate::read::{EncodingTag, FileTag, MAGIC, TVG_VERSION};
+use std::fmt::{self, Write};
+
+/// Describes the TVG format.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct FormatSpec {
+    /// File magic bytes.
+    pub magic: String,
+    /// The only supported file version.
+    pub version: u32,
+    /// Tags at the top level of the file and in the main data.
+    pub file_tags: Vec<TagSpec>,
+    /// Encodings for encoded data blocks.
+    pub encodings: Vec<TagSpec>,
+    /// Tags inside `TGVS` shape components.
+    pub component_tags: Vec<TagSpec>,
+    /// Tags inside palette colors.
+    pub palette_tags: Vec<TagSpec>,
+    /// Known shape types (`TGLY`).
+    pub shape_types: Vec<ValueSpec>,
+    /// Known component types (first byte of `TGSD`).
+    pub component_types: Vec<ValueSpec>,
+    /// The usual bytes at the end of vector layer data: a 0 byte, then a `TGRV` tag with a length
+    /// and mystery data.
+    pub layer_trailer: Vec<u8>,
+}
+
+/// Describes a tag.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct TagSpec {
+    /// The name of the enum variant in this crate.
+    pub name: String,
+    /// The tag as ASCII, with non-printable bytes escaped.
+    pub fourcc: String,
+    /// The tag value, as read in big endian.
+    pub value: u32,
+    /// What the tag is for.
+    pub description: &'static str,
+    /// Regions in the tag whose meaning is unknown.
+    pub mystery: &'static [&'static str],
+}
+
+/// Describes a numeric value of a type field.
+#[derive(Debug, Clone)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize))]
+pub struct ValueSpec {
+    /// The name of the enum variant in this crate.
+    pub name: String,
+    pub value: u32,
+}
+
+fn fourcc(value: u32) -> String {
+    value
+        .to_be_bytes()
+        .iter()
+        .map(|b| match b {
+            0x20..=0x7e => (*b as char).to_string(),
+            b => format!("<{b:02x}>"),
+        })
+        .collect()
+}
+
+fn tag_spec<T: fmt::Debug + Copy + Into<u32>>(
+    tag: T,
+    (description, mystery): (&'static str, &'static [&'static str]),
+) -> TagSpec {
+    TagSpec {
+        name: format!("{tag:?}"),
+        fourcc: fourcc(tag.into()),
+        value: tag.into(),
+        description,
+        mystery,
+    }
+}
+
+fn file_tag_notes(tag: FileTag) -> (&'static str, &'static [&'static str]) {
+    match tag {
+        FileTag::Cert => (
+            "certificate unique to the license",
+            &["u32 before the certificate length (always 1)"],
+        ),
+        FileTag::MainData => ("encoded container for the main drawing data tags", &[]),
+        FileTag::Endt => ("no contents", &["purpose"]),
+        FileTag::Tvci => (
+            "encoded information about the software that created the file",
+            &[
+                "13 bytes before the device name",
+                "bytes after the software name, if any",
+            ],
+        ),
+        FileTag::Crea => ("encoded u32, always 2", &["purpose"]),
+        FileTag::LayerUnderlay => ("encoded underlay art layer", &[]),
+        FileTag::LayerColor => ("encoded color art layer", &[]),
+        FileTag::LayerLine => ("encoded line art layer", &[]),
+        FileTag::LayerOverlay => ("encoded overlay art layer", &[]),
+        FileTag::Palette => ("encoded color palette", &["u32 0x79 after the color count"]),
+        FileTag::Ttoc => (
+            "index of offsets in the main data",
+            &["8 bytes after the offsets"],
+        ),
+        FileTag::Sign => (
+            "74 bytes of signature or checksum",
+            &["the entire contents"],
+        ),
+    }
+}
+
+fn encoding_notes(tag: EncodingTag) -> (&'static str, &'static [&'static str]) {
+    match tag {
+        EncodingTag::Unco => ("uncompressed: u32 length, data", &[]),
+        EncodingTag::Zlib => (
+            "zlib: u32 length (including the next field), u32 decompressed length, data",
+            &[],
+        ),
+    }
+}
+
+fn component_tag_notes(tag: ShapeComponentTag) -> (&'static str, &'static [&'static str]) {
+    match tag {
+        ShapeComponentTag::Tgsd => (
+            "component metadata: component type, color ID; followed by a continuation byte",
+            &[
+                "most bytes between the component type and the color ID",
+                "bytes after the color ID",
+                "u32 after the continuation byte, if it is 0",
+            ],
+        ),
+        ShapeComponentTag::Tgbp => (
+            "Bézier path: u32 point count, segment type bitstream, f32 points",
+            &[],
+        ),
+        ShapeComponentTag::Tgtb => (
+            "pencil thickness: optional thickness path definition and its domain",
+            &["u32 after the definition type"],
+        ),
+        ShapeComponentTag::Tgti => ("related to the pencil", &["the entire contents"]),
+    }
+}
+
+fn palette_tag_notes(tag: PaletteColorTag) -> (&'static str, &'static [&'static str]) {
+    match tag {
+        PaletteColorTag::Tcsc => ("RGBA color value", &[]),
+        PaletteColorTag::ColorId => (
+            "color name (UTF-16), u64 color ID, palette name (UTF-16)",
+            &[],
+        ),
+    }
+}
+
+/// Returns a description of the format.
+pub fn describe() -> FormatSpec {
+    FormatSpec {
+        magic: String::from_utf8_lossy(&MAGIC).into_owned(),
+        version: TVG_VERSION,
+        file_tags: FileTag::ALL
+            .into_iter()
+            .map(|tag| tag_spec(tag, file_tag_notes(tag)))
+            .collect(),
+        encodings: EncodingTag::ALL
+            .into_iter()
+            .map(|tag| tag_spec(tag, encoding_notes(tag)))
+            .collect(),
+        component_tags: ShapeComponentTag::ALL
+            .into_iter()
+            .map(|tag| tag_spec(tag, component_tag_notes(tag)))
+            .collect(),
+        palette_tags: PaletteColorTag::ALL
+            .into_iter()
+            .map(|tag| tag_spec(tag, palette_tag_notes(tag)))
+            .collect(),
+        shape_types: ShapeType::ALL
+            .into_iter()
+            .map(|ty| ValueSpec {
+                name: format!("{ty:?}"),
+                value: u16::from(ty).into(),
+            })
+            .collect(),
+        component_types: ComponentType::ALL
+            .into_iter()
+            .map(|ty| ValueSpec {
+                name: format!("{ty:?}"),
+                value: u8::from(ty).into(),
+            })
+            .collect(),
+        layer_trailer: LAYER_TRAILER.to_vec(),
+    }
+}
+
+impl FormatSpec {
+    /// Renders the description as Markdown.
+    pub fn to_markdown(&self) -> String {
+        let mut out = String::new();
+        writeln!(out, "# TVG format").unwrap();
+        writeln!(out, "magic: `{}`, version: {}", self.magic, self.version).unwrap();
+
+        let sections = [
+            ("file tags", &self.file_tags),
+            ("encodings", &self.encodings),
+            ("shape component tags", &self.component_tags),
+            ("palette color tags", &self.palette_tags),
+        ];
+        for (title, tags) in sections {
+            writeln!(out, "\n## {title}").unwrap();
+            writeln!(out, "| tag | value | name | description | unknown |").unwrap();
+            writeln!(out, "| --- | --- | --- | --- | --- |").unwrap();
+            for tag in tags {
+                writeln!(
+                    out,
+                    "| `{}` | `{:08x}` | {} | {} | {} |",
+                    tag.fourcc,
+                    tag.value,
+                    tag.name,
+                    tag.description,
+                    tag.mystery.join("; "),
+                )
+                .unwrap();
+            }
+        }
+
+        let values = [
+            ("shape types", &self.shape_types),
+            ("component types", &self.component_types),
+        ];
+        for (title, values) in values {
+            writeln!(out, "\n## {title}").unwrap();
+            for value in values {
+                writeln!(out, "- `{}`: {}", value.value, value.name).unwrap();
+            }
+        }
+
+        writeln!(out, "\n## layer trailer").unwrap();
+        writeln!(
+            out,
+            "`{:?}`",
+            crate::util::Bytes(self.layer_trailer.clone())
+        )
+        .unwrap();
+        out
+    }
+}
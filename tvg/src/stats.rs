@@ -0,0 +1,189 @@
+//! Summary statistics of a file, for finding drawings that are unusually expensive to render.
+
+use crate::document::{Art, Document};
+use crate::geometry::{layer_bounds, Bounds};
+use crate::layer::{LayerData, ShapeComponentData, VectorShape};
+use crate::read::{read_blocks, EncodingTag, FileTag, ReadError, ReadOptions};
+use std::collections::BTreeMap;
+
+/// Statistics of a file. See [analyze].
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct Stats {
+    /// Statistics of each art layer in the file, in drawing order.
+    pub layers: Vec<LayerStats>,
+    /// Number of colors in the palette.
+    pub palette_colors: usize,
+    /// Bounds of all shapes in all layers.
+    pub bounds: Option<Bounds>,
+    /// Sizes of the encoded tags, in file order. Only available from [analyze_file].
+    pub tags: Vec<TagSize>,
+}
+
+/// Statistics of an art layer.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct LayerStats {
+    pub layer: Art,
+    pub shapes: usize,
+    pub components: usize,
+    pub paths: usize,
+    /// Number of points in all paths, counting cubic control points.
+    pub points: usize,
+    /// The largest number of points in a single shape.
+    pub max_shape_points: usize,
+    pub bounds: Option<Bounds>,
+}
+
+/// The size of an encoded tag.
+#[derive(Debug, Clone, PartialEq)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct TagSize {
+    pub tag: FileTag,
+    /// Whether the tag is inside the main data tag.
+    pub in_main: bool,
+    pub encoding: EncodingTag,
+    /// Size in the file, including the encoding header.
+    pub stored: usize,
+    /// Size after decoding.
+    pub decoded: usize,
+}
+
+/// How a color is used in a drawing. See [Document::color_usage].
+#[derive(Debug, Clone, PartialEq, Default)]
+#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+#[cfg_attr(feature = "schema", derive(schemars::JsonSchema))]
+pub struct ColorUsage {
+    /// Number of shapes with a component in this color.
+    pub shape_count: usize,
+    /// Total area of the fill shapes in this color, in square drawing units (see
+    /// [VectorShape::area]). Overlapping fills are counted twice, and strokes not at all.
+    pub area_estimate: f32,
+    /// The art layers that contain shapes in this color, in drawing order.
+    pub layers: Vec<Art>,
+    /// Whether the color is in the palette. Shapes that use a color that isn't are drawn with a
+    /// fallback color.
+    pub in_palette: bool,
+}
+
+impl Document {
+    /// Returns how each color is used: first all colors in the palette in palette order, including
+    /// unused ones (with a shape count of 0), then colors that shapes use but the palette doesn't
+    /// contain, ordered by ID.
+    pub fn color_usage(&self) -> Vec<(u64, ColorUsage)> {
+        let mut used: BTreeMap<u64, ColorUsage> = BTreeMap::new();
+        for shape in self.shapes() {
+            let mut ids: Vec<u64> = shape.shape.color_ids().collect();
+            ids.sort_unstable();
+            ids.dedup();
+            if ids.is_empty() {
+                continue;
+            }
+            let area = shape.shape.area();
+            for id in ids {
+                let usage = used.entry(id).or_default();
+                usage.shape_count += 1;
+                usage.area_estimate += area;
+                if !usage.layers.contains(&shape.layer) {
+                    usage.layers.push(shape.layer);
+                }
+            }
+        }
+
+        let palette_ids = self
+            .palette()
+            .into_iter()
+            .flat_map(|palette| &palette.colors)
+            .filter_map(|color| color.id());
+        let mut usage = Vec::new();
+        for id in palette_ids {
+            let mut color = used.remove(&id).unwrap_or_default();
+            color.in_palette = true;
+            usage.push((id, color));
+        }
+        usage.extend(used);
+        usage
+    }
+}
+
+/// Collects statistics of a document.
+///
+/// Tag sizes aren't known after reading, so [Stats::tags] is empty. Use [analyze_file] to get
+/// them too.
+pub fn analyze(doc: &Document) -> Stats {
+    let layers: Vec<_> = doc
+        .layers()
+        .map(|(art, layer)| layer_stats(art, layer))
+        .collect();
+    let bounds = layers
+        .iter()
+        .filter_map(|layer| layer.bounds)
+        .reduce(Bounds::union);
+
+    Stats {
+        layers,
+        palette_colors: doc.palette().map_or(0, |palette| palette.colors.len()),
+        bounds,
+        tags: Vec::new(),
+    }
+}
+
+/// Reads a file and collects its statistics, including tag sizes.
+pub fn analyze_file(data: &[u8], options: &ReadOptions) -> Result<Stats, ReadError> {
+    let doc = Document::read_with_options(data, options)?;
+    let mut stats = analyze(&doc);
+    stats.tags = read_blocks(data, options)?
+        .into_iter()
+        .map(|block| TagSize {
+            tag: block.tag,
+            in_main: block.in_main,
+            encoding: block.block.encoding,
+            stored: block.block.encoded_len(),
+            decoded: block.block.decoded_len(),
+        })
+        .collect();
+    Ok(stats)
+}
+
+fn layer_stats(layer: Art, data: &LayerData) -> LayerStats {
+    let mut stats = LayerStats {
+        layer,
+        shapes: 0,
+        components: 0,
+        paths: 0,
+        points: 0,
+        max_shape_points: 0,
+        bounds: layer_bounds(data),
+    };
+    if let LayerData::Vector { shapes, .. } = data {
+        for shape in shapes {
+            let points = shape_points(shape);
+            stats.shapes += 1;
+            stats.components += shape.components.len();
+            stats.paths += shape
+                .components
+                .iter()
+                .flat_map(|component| &component.tags)
+                .filter(|tag| matches!(tag, ShapeComponentData::Path(_)))
+                .count();
+            stats.points += points;
+            stats.max_shape_points = stats.max_shape_points.max(points);
+        }
+    }
+    stats
+}
+
+fn shape_points(shape: &VectorShape) -> usize {
+    shape
+        .components
+        .iter()
+        .flat_map(|component| &component.tags)
+        .filter_map(|tag| match tag {
+            ShapeComponentData::Path(path) => Some(path.point_count()),
+            _ => None,
+        })
+        .sum()
+}
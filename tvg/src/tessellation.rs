@@ -0,0 +1,104 @@
+//! Conversions to [lyon] paths, and fill tessellation of shapes.
+//!
+//! Unlike [mesh](crate::mesh), this combines all contours of a fill shape, so holes are
+//! preserved.
+
+use crate::color::Rgba;
+use crate::export::{self, ConversionReport, Item};
+use crate::layer::{PathSegment, Point, ShapePath, ShapeType, VectorShape};
+use crate::palette::PaletteData;
+use lyon::math::point;
+use lyon::tessellation::{
+    BuffersBuilder, FillOptions, FillTessellator, TessellationError, VertexBuffers,
+};
+
+fn to_lyon(p: Point) -> lyon::math::Point {
+    point(p.0, p.1)
+}
+
+/// Adds a path to a lyon path builder as a subpath.
+///
+/// The subpath is closed if the path ends where it starts.
+fn add_path(builder: &mut lyon::path::path::Builder, path: &ShapePath) {
+    let mut segments = path.segments.iter();
+    let start = match segments.next() {
+        Some(PathSegment::Line(p) | PathSegment::Cubic(_, _, p)) => *p,
+        None => return,
+    };
+    builder.begin(to_lyon(start));
+    let mut end = start;
+    for segment in segments {
+        match *segment {
+            PathSegment::Line(p) => {
+                builder.line_to(to_lyon(p));
+                end = p;
+            }
+            PathSegment::Cubic(c1, c2, p) => {
+                builder.cubic_bezier_to(to_lyon(c1), to_lyon(c2), to_lyon(p));
+                end = p;
+            }
+        }
+    }
+    builder.end(end == start);
+}
+
+impl From<&ShapePath> for lyon::path::Path {
+    fn from(path: &ShapePath) -> Self {
+        let mut builder = lyon::path::Path::builder();
+        add_path(&mut builder, path);
+        builder.build()
+    }
+}
+
+impl From<ShapePath> for lyon::path::Path {
+    fn from(path: ShapePath) -> Self {
+        (&path).into()
+    }
+}
+
+/// A vertex of a tessellated shape.
+#[derive(Debug, Clone, Copy, PartialEq)]
+pub struct ColoredVertex {
+    pub position: Point,
+    /// The RGBA color of the shape. Black if the color couldn't be resolved.
+    pub color: Rgba,
+}
+
+/// Tessellates a fill shape, appending the triangles to `output`.
+///
+/// All paths of the shape are filled together, so `options` should use the even-odd fill rule
+/// (which is lyon's default). Shapes that aren't fills are skipped.
+pub fn fill_shape(
+    shape: &VectorShape,
+    palette: Option<&PaletteData>,
+    tessellator: &mut FillTessellator,
+    options: &FillOptions,
+    output: &mut VertexBuffers<ColoredVertex, u32>,
+) -> Result<ConversionReport, TessellationError> {
+    let mut report = ConversionReport::default();
+    if shape.ty != ShapeType::Fill {
+        return Ok(report);
+    }
+    for item in export::shape_items(shape, &mut report) {
+        let Item::Fill { paths, color } = item else {
+            continue;
+        };
+        let color = export::resolve_rgba(palette, color, &mut report).unwrap_or(Rgba::BLACK);
+
+        let mut builder = lyon::path::Path::builder();
+        for path in paths {
+            add_path(&mut builder, path);
+        }
+        let path = builder.build();
+
+        let mut buffers = BuffersBuilder::new(output, |vertex: lyon::tessellation::FillVertex| {
+            let position = vertex.position();
+            ColoredVertex {
+                position: (position.x, position.y),
+                color,
+            }
+        });
+        tessellator.tessellate_path(&path, options, &mut buffers)?;
+    }
+    Ok(report)
+}
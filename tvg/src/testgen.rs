@@ -0,0 +1,344 @@
+//! Generating valid TVG files with a given structure, for tests, fuzzing seeds and benchmarks,
+//! since real files from studios can't be shipped with them.
+//!
+//! The files have the same tags in the same order as files saved by Harmony, but the values that
+//! aren't understood yet (like the `TTOC` offsets and the signature) are zeros, so Harmony itself
+//! may not accept them.
+//!
+//! ```
+//! # fn example() -> Result<(), tvg::read::ReadError> {
+//! use tvg::document::{Art, Document};
+//! use tvg::testgen::{generate, TestDrawing, TestLayer};
+//!
+//! let drawing = TestDrawing {
+//!     layers: vec![TestLayer::new(Art::Line, 10, 20)],
+//!     ..Default::default()
+//! };
+//! let file = generate(&drawing)?;
+//! let document = Document::read(&file[..])?;
+//! # Ok(())
+//! # }
+//! ```
+
+use crate::document::Art;
+use crate::layer::{ComponentType, ShapeType, LAYER_TRAILER};
+use crate::read::{EncodingTag, FileTag, ReadError, MAGIC, TVG_VERSION};
+use crate::util::write_encoded_data;
+
+/// What to generate.
+#[derive(Debug, Clone)]
+pub struct TestDrawing {
+    /// The encoding of all encoded blocks. `ZLIB` needs a zlib backend. Default: `ZLIB` if there
+    /// is a backend, and `UNCO` otherwise.
+    pub encoding: EncodingTag,
+    /// Number of palette colors. Their IDs go from 1 to this number, and shapes use them in turn.
+    /// Default: 4.
+    pub palette_colors: usize,
+    /// The art layers with shapes. Art layers that aren't in the list are empty; if one is in
+    /// the list more than once, the first one is used. Default: none.
+    pub layers: Vec<TestLayer>,
+    /// If true, the tags that don't contain drawing data (`CERT`, `TVCI`, `CREA`, `TTOC`, `ENDT`
+    /// and `SIGN`) are included, with placeholder contents. Default: true.
+    pub metadata: bool,
+    /// Seed for the point coordinates. The same options and seed always generate the same file.
+    pub seed: u64,
+}
+
+impl Default for TestDrawing {
+    fn default() -> Self {
+        TestDrawing {
+            encoding: match crate::zlib::BACKEND {
+                Some(_) => EncodingTag::Zlib,
+                None => EncodingTag::Unco,
+            },
+            palette_colors: 4,
+            layers: Vec::new(),
+            metadata: true,
+            seed: 0,
+        }
+    }
+}
+
+/// The shapes of an art layer.
+#[derive(Debug, Clone)]
+pub struct TestLayer {
+    pub art: Art,
+    pub shapes: usize,
+    /// Number of segments in the path of each shape, after the first point.
+    pub segments: usize,
+    pub curves: Curves,
+    /// The component type of the shapes: fills are fill shapes, and the others are strokes.
+    pub component: ComponentType,
+    /// If true, pencil strokes have a thickness definition (`tGTB`).
+    pub thickness: bool,
+}
+
+impl TestLayer {
+    /// Pencil strokes with mixed curves.
+    pub fn new(art: Art, shapes: usize, segments: usize) -> Self {
+        TestLayer {
+            art,
+            shapes,
+            segments,
+            curves: Curves::Mixed,
+            component: ComponentType::Pencil,
+            thickness: true,
+        }
+    }
+}
+
+/// The kinds of path segments.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Default)]
+pub enum Curves {
+    Lines,
+    Cubics,
+    /// Lines and cubics in a random order.
+    #[default]
+    Mixed,
+}
+
+/// Generates a file. Returns an error only if the encoding is `ZLIB` and there's no zlib backend.
+pub fn generate(drawing: &TestDrawing) -> Result<Vec<u8>, ReadError> {
+    // xorshift never leaves 0
+    let mut rng = Rng((drawing.seed ^ 0x9e37_79b9_7f4a_7c15).max(1));
+    let encoding = drawing.encoding;
+
+    let mut main = Vec::new();
+    if drawing.metadata {
+        let mut tvci = vec![0; 13];
+        tvci.extend_from_slice(b"TESTGEN\0tvg testgen\0");
+        tag(&mut main, FileTag::Tvci);
+        write_encoded_data(encoding, &tvci, &mut main)?;
+        tag(&mut main, FileTag::Crea);
+        write_encoded_data(encoding, &2_u32.to_le_bytes(), &mut main)?;
+    }
+    tag(&mut main, FileTag::Palette);
+    write_encoded_data(encoding, &palette(drawing.palette_colors), &mut main)?;
+    for art in Art::ALL {
+        let layer = drawing.layers.iter().find(|layer| layer.art == art);
+        let data = match layer {
+            Some(layer) => vector_layer(layer, drawing.palette_colors, &mut rng),
+            None => 0_u16.to_le_bytes().to_vec(),
+        };
+        tag(&mut main, art.file_tag());
+        write_encoded_data(encoding, &data, &mut main)?;
+    }
+
+    let mut out = MAGIC.to_vec();
+    u32_le(&mut out, TVG_VERSION);
+    // the values that most files have (see read::KNOWN_HEADER_VALUES)
+    u32_le(&mut out, 2);
+    u32_le(&mut out, 1);
+    if drawing.metadata {
+        let certificate = b"testgen certificate";
+        tag(&mut out, FileTag::Cert);
+        u32_le(&mut out, 8 + certificate.len() as u32);
+        u32_le(&mut out, 1);
+        u32_le(&mut out, certificate.len() as u32);
+        out.extend_from_slice(certificate);
+    }
+    tag(&mut out, FileTag::MainData);
+    write_encoded_data(encoding, &main, &mut out)?;
+    if drawing.metadata {
+        // no offsets, and the 8 unknown bytes
+        tag(&mut out, FileTag::Ttoc);
+        u32_le(&mut out, 0);
+        out.extend_from_slice(&[0; 8]);
+        tag(&mut out, FileTag::Endt);
+        tag(&mut out, FileTag::Sign);
+        out.extend_from_slice(&[0; 74]);
+        out.push(0);
+    }
+    Ok(out)
+}
+
+/// A small xorshift generator, so that files don't depend on a random number crate.
+struct Rng(u64);
+
+impl Rng {
+    fn next(&mut self) -> u64 {
+        self.0 ^= self.0 << 13;
+        self.0 ^= self.0 >> 7;
+        self.0 ^= self.0 << 17;
+        self.0
+    }
+
+    /// A number in `-range..range`.
+    fn coordinate(&mut self, range: f32) -> f32 {
+        ((self.next() >> 40) as f32 / (1 << 24) as f32 * 2. - 1.) * range
+    }
+}
+
+fn u16_le(out: &mut Vec<u8>, value: u16) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn u32_le(out: &mut Vec<u8>, value: u32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn f32_le(out: &mut Vec<u8>, value: f32) {
+    out.extend_from_slice(&value.to_le_bytes());
+}
+
+fn tag(out: &mut Vec<u8>, tag: FileTag) {
+    out.extend_from_slice(&u32::from(tag).to_be_bytes());
+}
+
+/// Appends a tag with a length-prefixed body, like the tags in shapes and palette colors.
+fn inner_tag(out: &mut Vec<u8>, tag: &[u8; 4], body: &[u8]) {
+    out.extend_from_slice(tag);
+    u32_le(out, body.len() as u32);
+    out.extend_from_slice(body);
+}
+
+fn utf16(out: &mut Vec<u8>, string: &str) {
+    let chars: Vec<u16> = string.encode_utf16().collect();
+    u32_le(out, chars.len() as u32);
+    for c in chars {
+        u16_le(out, c);
+    }
+}
+
+fn palette(colors: usize) -> Vec<u8> {
+    let mut out = Vec::new();
+    u32_le(&mut out, colors as u32);
+    u32_le(&mut out, 0x79);
+    for i in 0..colors {
+        u16_le(&mut out, 0);
+        let rgba = [(i * 37) as u8, (i * 91) as u8, (i * 13) as u8, 255];
+        inner_tag(&mut out, b"TCSC", &rgba);
+
+        let mut id = Vec::new();
+        utf16(&mut id, &format!("Color {}", i + 1));
+        id.extend_from_slice(&(i as u64 + 1).to_le_bytes());
+        utf16(&mut id, "testgen");
+        inner_tag(&mut out, b"TCID", &id);
+
+        out.extend_from_slice(&0x79_00_00_00_u32.to_be_bytes());
+    }
+    out
+}
+
+fn vector_layer(layer: &TestLayer, colors: usize, rng: &mut Rng) -> Vec<u8> {
+    let mut out = Vec::new();
+    u16_le(&mut out, 0x0100);
+    u32_le(&mut out, layer.shapes as u32);
+    for i in 0..layer.shapes {
+        let color_id = match colors {
+            0 => 0,
+            _ => (i % colors) as u64 + 1,
+        };
+        let shape = shape(layer, color_id, rng);
+        u32_le(&mut out, 2);
+        inner_tag(&mut out, b"TGLY", &shape);
+    }
+    out.extend_from_slice(LAYER_TRAILER);
+    out
+}
+
+fn shape(layer: &TestLayer, color_id: u64, rng: &mut Rng) -> Vec<u8> {
+    let ty = match layer.component {
+        ComponentType::Fill => ShapeType::Fill,
+        _ => ShapeType::Stroke,
+    };
+    let mut component = Vec::new();
+    inner_tag(&mut component, b"TGSD", &info(layer.component, color_id));
+    component.push(1);
+    inner_tag(&mut component, b"TGBP", &path(layer, rng));
+    if layer.thickness && layer.component == ComponentType::Pencil {
+        inner_tag(&mut component, b"tGTB", &thickness());
+    }
+
+    let mut out = Vec::new();
+    u16_le(&mut out, ty.into());
+    u32_le(&mut out, 1);
+    inner_tag(&mut out, b"TGVS", &component);
+    out
+}
+
+/// The contents of a `TGSD` tag, laid out like in Harmony files.
+fn info(component: ComponentType, color_id: u64) -> Vec<u8> {
+    let mut out = vec![component.into()];
+    match component {
+        ComponentType::Fill => {
+            out.push(1);
+            out.extend_from_slice(&[0; 14]);
+            out.extend_from_slice(&color_id.to_le_bytes());
+            out.extend_from_slice(&[0; 16]);
+        }
+        ComponentType::Pencil => {
+            out.extend_from_slice(&[0x00, 0x00, 0x20, 0x41]);
+            out.extend_from_slice(&color_id.to_le_bytes());
+            out.extend_from_slice(&[0; 8]);
+        }
+        ComponentType::Unknown1 | ComponentType::Stroke => out.extend_from_slice(&[0; 8]),
+    }
+    out
+}
+
+/// The contents of a `TGBP` tag: a random walk of `segments` segments after a starting point.
+fn path(layer: &TestLayer, rng: &mut Rng) -> Vec<u8> {
+    let is_cubic: Vec<bool> = (0..layer.segments)
+        .map(|_| match layer.curves {
+            Curves::Lines => false,
+            Curves::Cubics => true,
+            Curves::Mixed => rng.next() & 1 == 1,
+        })
+        .collect();
+    let points = 1 + is_cubic
+        .iter()
+        .map(|&c| if c { 3 } else { 1 })
+        .sum::<usize>();
+
+    // segment types from LSB to MSB: 1 for a line, 001 for a cubic (see notes/curve-instr.md)
+    let mut bits = vec![true];
+    for &cubic in &is_cubic {
+        match cubic {
+            true => bits.extend([false, false, true]),
+            false => bits.push(true),
+        }
+    }
+    let mut types = vec![0_u8; bits.len().div_ceil(8)];
+    for (i, bit) in bits.into_iter().enumerate() {
+        if bit {
+            types[i / 8] |= 1 << (i % 8);
+        }
+    }
+
+    let mut out = Vec::new();
+    u32_le(&mut out, points as u32);
+    out.extend(types);
+    let (mut x, mut y) = (rng.coordinate(1000.), rng.coordinate(1000.));
+    for _ in 0..points {
+        x += rng.coordinate(20.);
+        y += rng.coordinate(20.);
+        f32_le(&mut out, x);
+        f32_le(&mut out, y);
+    }
+    out
+}
+
+/// The contents of a `tGTB` tag with a constant thickness over the whole stroke.
+fn thickness() -> Vec<u8> {
+    let mut out = vec![1];
+    u32_le(&mut out, 7);
+    u16_le(&mut out, 0xcf);
+    u32_le(&mut out, 2);
+    for loc in [0., 1.] {
+        f32_le(&mut out, loc);
+        // offset, back control point and forward control point of each side
+        for _ in 0..2 {
+            for value in [2., 0.3, 2., 0.3, 2.] {
+                f32_le(&mut out, value);
+            }
+        }
+    }
+    out.extend_from_slice(&[0; 5]);
+    // the domain, each bound followed by 8 zero bytes
+    for bound in [0., 1.] {
+        f32_le(&mut out, bound);
+        out.extend_from_slice(&[0; 8]);
+    }
+    out
+}
@@ -0,0 +1,251 @@
+//! Fit-to-box previews of drawings, e.g. for asset browsers.
+//!
+//! With the `png` feature, previews can also come from companion images that some element
+//! folders carry next to the drawings (see [companion_paths]), which is much faster than reading
+//! and rendering the drawing.
+
+use crate::document::Document;
+use crate::read::ReadError;
+use crate::render::{self, RenderOptions, RgbaImage};
+use std::collections::hash_map::DefaultHasher;
+use std::collections::{HashMap, VecDeque};
+use std::hash::{Hash, Hasher};
+#[cfg(feature = "png")]
+use std::path::{Path, PathBuf};
+#[cfg(feature = "png")]
+use thiserror::Error;
+
+/// Renders a preview of a document that fits into a `max_px` by `max_px` box, with `options`
+/// otherwise (see [options]).
+///
+/// With the default options, the longer side of the drawing's bounds will be `max_px` pixels
+/// long, and the background is transparent. For messy drawings,
+/// [Framing::Significant](crate::export::Framing::Significant) leaves out stray dots, so the
+/// drawing itself comes out larger.
+pub fn generate(document: &Document, max_px: u32, options: &RenderOptions) -> RgbaImage {
+    render::render(&document.tags, &self::options(document, max_px, options)).0
+}
+
+/// Returns a copy of `options` with the scale and view box set so that the framed area of the
+/// document (see [RenderOptions::framing]) fits into a `max_px` by `max_px` box.
+pub fn options(document: &Document, max_px: u32, options: &RenderOptions) -> RenderOptions {
+    let max_px = max_px.max(1);
+    let mut options = options.clone();
+    // padding and stroke width are in drawing units, so the view box doesn't depend on the scale
+    let view_box = options.view_box(&document.tags);
+    let longest = view_box.width().max(view_box.height());
+    options.mapping.scale = max_px as f32 / longest;
+    // the image size is rounded up, which may overshoot by a pixel due to float rounding
+    let fits = |options: &RenderOptions| {
+        let (width, height) = options.image_size(view_box);
+        width.max(height) <= max_px
+    };
+    while !fits(&options) {
+        options.mapping.scale *= 1. - f32::EPSILON * 16.;
+    }
+    options.view_box = Some(view_box);
+    options
+}
+
+/// Returns the hash that [ThumbnailCache] uses to identify a file.
+///
+/// This is only meant for in-memory caches; it may change between versions.
+pub fn file_hash(file: &[u8]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+    file.hash(&mut hasher);
+    hasher.finish()
+}
+
+/// An in-memory cache of thumbnails, keyed by the hash of the file contents.
+///
+/// Since files are identified by their contents, renamed or moved files don't need to be
+/// rendered again, and changed files never show a stale thumbnail. All thumbnails are rendered
+/// with the same options.
+#[derive(Debug, Clone)]
+pub struct ThumbnailCache {
+    max_px: u32,
+    options: RenderOptions,
+    capacity: usize,
+    thumbnails: HashMap<u64, RgbaImage>,
+    /// File hashes from least to most recently inserted.
+    order: VecDeque<u64>,
+}
+
+impl ThumbnailCache {
+    /// Creates an empty cache for thumbnails of size `max_px` that holds at most `capacity`
+    /// thumbnails (but at least one). The oldest thumbnails are evicted first.
+    ///
+    /// Thumbnails are rendered with `options` (see [generate]).
+    pub fn new(max_px: u32, capacity: usize, options: RenderOptions) -> Self {
+        ThumbnailCache {
+            max_px,
+            options,
+            capacity: capacity.max(1),
+            thumbnails: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The thumbnail size.
+    pub fn max_px(&self) -> u32 {
+        self.max_px
+    }
+
+    /// The options thumbnails are rendered with.
+    pub fn options(&self) -> &RenderOptions {
+        &self.options
+    }
+
+    /// Returns the cached thumbnail of a file.
+    pub fn get(&self, file: &[u8]) -> Option<&RgbaImage> {
+        self.thumbnails.get(&file_hash(file))
+    }
+
+    /// Returns the thumbnail of a file, reading and rendering it if it's not cached.
+    pub fn get_or_generate(&mut self, file: &[u8]) -> Result<&RgbaImage, ReadError> {
+        let hash = file_hash(file);
+        if !self.thumbnails.contains_key(&hash) {
+            let document = Document::read(file)?;
+            self.insert(hash, generate(&document, self.max_px, &self.options));
+        }
+        Ok(&self.thumbnails[&hash])
+    }
+
+    /// Adds a thumbnail for the file with the given hash (see [file_hash]).
+    pub fn insert(&mut self, hash: u64, thumbnail: RgbaImage) {
+        if self.thumbnails.insert(hash, thumbnail).is_none() {
+            self.order.push_back(hash);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(oldest) = self.order.pop_front() {
+                self.thumbnails.remove(&oldest);
+            }
+        }
+    }
+
+    /// Removes the thumbnail of a file. Returns it if it was cached.
+    pub fn remove(&mut self, file: &[u8]) -> Option<RgbaImage> {
+        let hash = file_hash(file);
+        self.order.retain(|h| *h != hash);
+        self.thumbnails.remove(&hash)
+    }
+
+    pub fn len(&self) -> usize {
+        self.thumbnails.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.thumbnails.is_empty()
+    }
+
+    pub fn clear(&mut self) {
+        self.thumbnails.clear();
+        self.order.clear();
+    }
+}
+
+/// Where a [Preview] came from.
+#[cfg(feature = "png")]
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum PreviewSource {
+    /// A companion image at this path.
+    Companion(PathBuf),
+    /// The drawing was rendered.
+    Rendered,
+}
+
+/// A preview of a drawing that fits into a `max_px` by `max_px` box.
+#[cfg(feature = "png")]
+#[derive(Debug, Clone)]
+pub struct Preview {
+    pub image: RgbaImage,
+    pub source: PreviewSource,
+}
+
+#[cfg(feature = "png")]
+#[derive(Debug, Error)]
+pub enum PreviewError {
+    #[error("io error: {0}")]
+    Io(#[from] std::io::Error),
+    #[error("failed to read the drawing: {0}")]
+    Read(#[from] ReadError),
+}
+
+/// Returns the paths where a companion preview of a drawing file may be, in the order they're
+/// checked: `.thumbnails/<file name>.png` and `.thumbnails/<stem>.png` in the drawing's folder,
+/// e.g. `.thumbnails/bg-1.tvg.png` and `.thumbnails/bg-1.png` for `bg-1.tvg`.
+///
+/// These are the layouts that have been seen in element folders; the format of the images isn't
+/// documented, so anything that doesn't decode as a PNG is ignored.
+#[cfg(feature = "png")]
+pub fn companion_paths(drawing: &Path) -> Vec<PathBuf> {
+    let (Some(dir), Some(file_name)) = (drawing.parent(), drawing.file_name()) else {
+        return Vec::new();
+    };
+    let dir = dir.join(".thumbnails");
+    let mut paths = Vec::new();
+    let mut with_png = file_name.to_owned();
+    with_png.push(".png");
+    paths.push(dir.join(with_png));
+    if let Some(stem) = drawing.file_stem() {
+        let mut stem = stem.to_owned();
+        stem.push(".png");
+        paths.push(dir.join(stem));
+    }
+    paths
+}
+
+/// Returns the first companion preview of a drawing file that exists and isn't older than the
+/// drawing, since previews aren't always updated when a drawing is edited elsewhere.
+#[cfg(feature = "png")]
+pub fn find_companion(drawing: &Path) -> Option<PathBuf> {
+    let modified = |path: &Path| std::fs::metadata(path).and_then(|m| m.modified()).ok();
+    let drawing_modified = modified(drawing);
+    companion_paths(drawing).into_iter().find(|path| {
+        path.is_file()
+            && match (modified(path), drawing_modified) {
+                (Some(preview), Some(drawing)) => preview >= drawing,
+                // can't tell, so assume it's up to date
+                _ => true,
+            }
+    })
+}
+
+/// Reads the companion preview of a drawing file, if it has one (see [find_companion]), scaled
+/// down to fit into a `max_px` by `max_px` box. Returns None if there is no companion preview or
+/// it can't be decoded.
+///
+/// This doesn't read the drawing itself.
+#[cfg(feature = "png")]
+pub fn read_companion(drawing: &Path, max_px: u32) -> Option<Preview> {
+    let path = find_companion(drawing)?;
+    let file = std::fs::File::open(&path).ok()?;
+    let image = RgbaImage::read_png(std::io::BufReader::new(file)).ok()?;
+    Some(Preview {
+        image: image.scaled_to_fit(max_px),
+        source: PreviewSource::Companion(path),
+    })
+}
+
+/// Returns a preview of a drawing file that fits into a `max_px` by `max_px` box: its companion
+/// preview if it has one (see [read_companion]), and otherwise the drawing rendered with
+/// `options` (see [generate]).
+///
+/// Companion previews are drawn by other software, so they can look different from rendered ones,
+/// e.g. with a background or at a different scale, and `options` don't apply to them.
+#[cfg(feature = "png")]
+pub fn preview(
+    drawing: &Path,
+    max_px: u32,
+    options: &RenderOptions,
+) -> Result<Preview, PreviewError> {
+    if let Some(preview) = read_companion(drawing, max_px) {
+        return Ok(preview);
+    }
+    let file = std::fs::File::open(drawing)?;
+    let document = Document::read(std::io::BufReader::new(file))?;
+    Ok(Preview {
+        image: generate(&document, max_px, options),
+        source: PreviewSource::Rendered,
+    })
+}
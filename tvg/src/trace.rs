@@ -0,0 +1,21 @@
+//! Diagnostics through [tracing](https://docs.rs/tracing), if the `tracing` feature is enabled.
+//!
+//! Without the feature, these macros expand to nothing, so their arguments are not evaluated.
+
+/// Emits a debug event.
+macro_rules! debug {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        tracing::debug!($($arg)*);
+    };
+}
+
+/// Enters a debug span until the end of the current block.
+macro_rules! span {
+    ($($arg:tt)*) => {
+        #[cfg(feature = "tracing")]
+        let _span = tracing::debug_span!($($arg)*).entered();
+    };
+}
+
+pub(crate) use {debug, span};
@@ -1,44 +1,173 @@
-use crate::read::{EncodingTag, ReadError};
-use byteorder::{ReadBytesExt, LE};
-use std::io::Read;
+use crate::read::{check_limit, EncodingTag, ReadError, ReadOptions};
+use crate::io::{self, Read, ReadBytesExt};
+use crate::zlib;
+#[cfg(feature = "std")]
+use crate::parse_trace::TraceEventKind;
+#[cfg(feature = "std")]
+use std::time::Instant;
+use alloc::{vec, vec::Vec};
+use byteorder::LE;
+
+/// Defines `ALL`, an array of all values of a fieldless enum, in the given order.
+///
+/// Every variant has to be listed: a missing one is a compile error (a non-exhaustive match), and
+/// one listed twice is an unreachable pattern.
+macro_rules! all_values {
+    ($name:ident { $($variant:ident),* $(,)? }) => {
+        impl $name {
+            /// All known values.
+            pub const ALL: [$name; [$(stringify!($variant)),*].len()] = [$($name::$variant),*];
+        }
+
+        const _: fn($name) = |value| match value {
+            $($name::$variant)|* => (),
+        };
+    };
+}
+
+pub(crate) use all_values;
 
 /// Reads encoded data into a buffer.
 /// Encoded data starts with a tag describing the encoding ([EncodingTag]) and is followed by the
 /// data length.
-pub(crate) fn read_encoded_data<R>(mut input: R) -> Result<Vec<u8>, ReadError>
+///
+/// The decoded size is limited by [ReadOptions::max_decoded_size].
+pub(crate) fn read_encoded_data<R>(input: R, options: &ReadOptions) -> Result<Vec<u8>, ReadError>
 where
     R: Read,
 {
-    let encoding_tag = input.read_u32::<byteorder::BE>()?;
-    match EncodingTag::try_from(encoding_tag) {
-        Ok(EncodingTag::Unco) => {
-            let len = input.read_u32::<LE>()?;
-            let mut data = Vec::new();
-            data.resize(len as usize, 0);
-            input.read_exact(&mut data)?;
-            Ok(data)
+    RawBlock::read(input, options)?.decode(options)
+}
+
+/// Appends encoded data, the reverse of [read_encoded_data]. `ZLIB` data is compressed with
+/// [zlib::compress].
+#[cfg(feature = "std")]
+pub(crate) fn write_encoded_data(
+    encoding: EncodingTag,
+    data: &[u8],
+    out: &mut Vec<u8>,
+) -> Result<(), ReadError> {
+    out.extend_from_slice(&u32::from(encoding).to_be_bytes());
+    match encoding {
+        EncodingTag::Unco => {
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(data);
+        }
+        EncodingTag::Zlib => {
+            let compressed = zlib::compress(data)?;
+            // the length includes the decompressed length
+            out.extend_from_slice(&(compressed.len() as u32 + 4).to_le_bytes());
+            out.extend_from_slice(&(data.len() as u32).to_le_bytes());
+            out.extend_from_slice(&compressed);
+        }
+    }
+    Ok(())
+}
+
+/// An encoded block as it's stored in the file, before decoding.
+///
+/// This is mostly useful to see how well the data compresses; [RawBlock::decode] returns the
+/// same data the reader would see.
+#[derive(Debug, Clone)]
+pub struct RawBlock {
+    pub encoding: EncodingTag,
+    /// The decompressed length stated in the header of ZLIB blocks.
+    pub stated_len: Option<u32>,
+    /// The data after the header (compressed, for ZLIB blocks).
+    pub data: Vec<u8>,
+}
+
+impl RawBlock {
+    /// Reads an encoded block without decoding it.
+    pub fn read<R>(mut input: R, options: &ReadOptions) -> Result<Self, ReadError>
+    where
+        R: Read,
+    {
+        let encoding_tag = input.read_u32::<byteorder::BE>()?;
+        match EncodingTag::try_from(encoding_tag) {
+            Ok(EncodingTag::Unco) => {
+                let len = input.read_u32::<LE>()?;
+                check_limit("encoded data length", len, options.max_decoded_size)?;
+                let mut data = vec![0; len as usize];
+                input.read_exact(&mut data)?;
+                Ok(RawBlock {
+                    encoding: EncodingTag::Unco,
+                    stated_len: None,
+                    data,
+                })
+            }
+            Ok(EncodingTag::Zlib) => {
+                let len = input.read_u32::<LE>()?;
+                check_limit("encoded data length", len, options.max_decoded_size)?;
+                let decompressed_len = input.read_u32::<LE>()?;
+                check_limit("decompressed length", decompressed_len, options.max_decoded_size)?;
+
+                // the length includes the decompressed length
+                let len = len.saturating_sub(4) as u64;
+                let mut data = Vec::new();
+                (&mut input).take(len).read_to_end(&mut data)?;
+                if (data.len() as u64) < len {
+                    return Err(io::Error::from(io::ErrorKind::UnexpectedEof).into());
+                }
+                Ok(RawBlock {
+                    encoding: EncodingTag::Zlib,
+                    stated_len: Some(decompressed_len),
+                    data,
+                })
+            }
+            Err(tag) => Err(ReadError::UnknownEncoding(tag.number)),
+        }
+    }
+
+    /// The size of the block in the file, including the encoding tag and the header.
+    pub fn encoded_len(&self) -> usize {
+        match self.encoding {
+            EncodingTag::Unco => 8 + self.data.len(),
+            EncodingTag::Zlib => 12 + self.data.len(),
+        }
+    }
+
+    /// The size of the decoded data, according to the header.
+    pub fn decoded_len(&self) -> usize {
+        match self.stated_len {
+            Some(len) => len as usize,
+            None => self.data.len(),
         }
-        Ok(EncodingTag::Zlib) => {
-            let len = input.read_u32::<LE>()?;
-            let decompressed_len = input.read_u32::<LE>()?;
+    }
 
-            let mut decoder =
-                libflate::zlib::Decoder::new((&mut input).take(len.saturating_sub(4) as u64))?;
-            let mut data = Vec::with_capacity(decompressed_len as usize);
-            decoder.read_to_end(&mut data)?;
-            Ok(data)
+    /// Decodes the block. The decoded size is limited by [ReadOptions::max_decoded_size].
+    pub fn decode(self, options: &ReadOptions) -> Result<Vec<u8>, ReadError> {
+        match self.encoding {
+            EncodingTag::Unco => Ok(self.data),
+            EncodingTag::Zlib => {
+                // the stated length may be wrong, so limit the actual output as well
+                let max_len = options.max_decoded_size;
+                #[cfg(feature = "std")]
+                let started = (options.trace.as_ref()).map(|trace| (trace, Instant::now()));
+                let data = zlib::decompress(&self.data, self.decoded_len(), max_len)?;
+                #[cfg(feature = "std")]
+                if let Some((trace, start)) = started {
+                    let (bytes, decoded) = (self.data.len() as u64, data.len() as u64);
+                    trace.record(TraceEventKind::Decompress, start, bytes, Some(decoded));
+                }
+                check_limit("decompressed data size", data.len() as u64, max_len)?;
+                Ok(data)
+            }
         }
-        Err(tag) => Err(ReadError::UnknownEncoding(tag.number)),
     }
 }
 
 /// Contains byte data (with appropriate debug formatting).
-#[derive(Clone)]
-#[cfg_attr(feature = "serde", derive(serde::Serialize, serde::Deserialize))]
+///
+/// With serde, this is a string of hexadecimal digits in human-readable formats like JSON, and a
+/// byte string in binary formats like msgpack. Arrays of numbers are also accepted when
+/// deserializing.
+#[derive(Clone, PartialEq, Eq)]
+#[cfg_attr(feature = "arbitrary", derive(arbitrary::Arbitrary))]
 pub struct Bytes(pub Vec<u8>);
 
-impl std::fmt::Debug for Bytes {
-    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+impl core::fmt::Debug for Bytes {
+    fn fmt(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
         let mut is_first = true;
         for byte in &self.0 {
             if is_first {
@@ -51,3 +180,117 @@ impl std::fmt::Debug for Bytes {
         Ok(())
     }
 }
+
+#[cfg(feature = "serde")]
+impl serde::Serialize for Bytes {
+    fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+    where
+        S: serde::Serializer,
+    {
+        if serializer.is_human_readable() {
+            let mut hex = alloc::string::String::with_capacity(self.0.len() * 2);
+            for byte in &self.0 {
+                let _ = core::fmt::Write::write_fmt(&mut hex, format_args!("{byte:02x}"));
+            }
+            serializer.serialize_str(&hex)
+        } else {
+            serializer.serialize_bytes(&self.0)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+impl<'de> serde::Deserialize<'de> for Bytes {
+    fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+    where
+        D: serde::Deserializer<'de>,
+    {
+        if deserializer.is_human_readable() {
+            deserializer.deserialize_any(BytesVisitor)
+        } else {
+            deserializer.deserialize_byte_buf(BytesVisitor)
+        }
+    }
+}
+
+#[cfg(feature = "serde")]
+struct BytesVisitor;
+
+#[cfg(feature = "serde")]
+impl<'de> serde::de::Visitor<'de> for BytesVisitor {
+    type Value = Bytes;
+
+    fn expecting(&self, f: &mut core::fmt::Formatter) -> core::fmt::Result {
+        write!(f, "bytes, hexadecimal digits, or an array of bytes")
+    }
+
+    fn visit_str<E>(self, hex: &str) -> Result<Bytes, E>
+    where
+        E: serde::de::Error,
+    {
+        let digit = |c: u8| match c {
+            b'0'..=b'9' => Ok(c - b'0'),
+            b'a'..=b'f' => Ok(c - b'a' + 10),
+            b'A'..=b'F' => Ok(c - b'A' + 10),
+            _ => Err(E::invalid_value(serde::de::Unexpected::Str(hex), &self)),
+        };
+        if !hex.len().is_multiple_of(2) {
+            return Err(E::invalid_length(hex.len(), &"an even number of hexadecimal digits"));
+        }
+        hex.as_bytes()
+            .chunks_exact(2)
+            .map(|pair| Ok(digit(pair[0])? << 4 | digit(pair[1])?))
+            .collect::<Result<_, _>>()
+            .map(Bytes)
+    }
+
+    fn visit_bytes<E>(self, bytes: &[u8]) -> Result<Bytes, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(Bytes(bytes.to_vec()))
+    }
+
+    fn visit_byte_buf<E>(self, bytes: Vec<u8>) -> Result<Bytes, E>
+    where
+        E: serde::de::Error,
+    {
+        Ok(Bytes(bytes))
+    }
+
+    fn visit_seq<A>(self, mut seq: A) -> Result<Bytes, A::Error>
+    where
+        A: serde::de::SeqAccess<'de>,
+    {
+        let mut bytes = Vec::with_capacity(seq.size_hint().unwrap_or(0).min(4096));
+        while let Some(byte) = seq.next_element()? {
+            bytes.push(byte);
+        }
+        Ok(Bytes(bytes))
+    }
+}
+
+/// Describes the human-readable form.
+#[cfg(feature = "schema")]
+impl schemars::JsonSchema for Bytes {
+    fn schema_name() -> String {
+        "Bytes".into()
+    }
+
+    fn json_schema(_: &mut schemars::gen::SchemaGenerator) -> schemars::schema::Schema {
+        use schemars::schema::{InstanceType, Metadata, SchemaObject, StringValidation};
+        SchemaObject {
+            metadata: Some(Box::new(Metadata {
+                description: Some("Bytes as hexadecimal digits".into()),
+                ..Default::default()
+            })),
+            instance_type: Some(InstanceType::String.into()),
+            string: Some(Box::new(StringValidation {
+                pattern: Some("^([0-9a-fA-F]{2})*$".into()),
+                ..Default::default()
+            })),
+            ..Default::default()
+        }
+        .into()
+    }
+}
@@ -0,0 +1,190 @@
+//! Checks for invariants that files written by Harmony are expected to satisfy.
+//!
+//! This is mostly useful for checking generated or modified documents before writing them.
+
+use crate::document::{Art, Document};
+use crate::layer::{LayerData, PathSegment, ShapeComponentData};
+use crate::palette::PaletteColor;
+use crate::read::{FileData, FileTag};
+use std::collections::HashSet;
+use std::fmt;
+
+/// The location of a shape component in a document.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct ComponentLocation {
+    pub art: Art,
+    /// Index of the shape in the layer.
+    pub shape: usize,
+    /// Index of the component in the shape.
+    pub component: usize,
+}
+
+impl fmt::Display for ComponentLocation {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        write!(
+            f,
+            "{:?} shape {} component {}",
+            self.art, self.shape, self.component
+        )
+    }
+}
+
+/// A broken invariant.
+#[derive(Debug, Clone, PartialEq)]
+pub enum ValidationIssue {
+    /// A component references a color that is not in the palette.
+    MissingColor {
+        location: ComponentLocation,
+        color_id: u64,
+    },
+    /// A path has no segments.
+    EmptyPath(ComponentLocation),
+    /// A path has a point with a NaN or infinite coordinate.
+    NonFinitePoint(ComponentLocation),
+    /// A stroke thickness domain is not within [0, 1], or ends before it starts.
+    #[cfg(feature = "pencil")]
+    InvalidThicknessDomain {
+        location: ComponentLocation,
+        domain: (f32, f32),
+    },
+    /// The TTOC lists a tag that is not in the main data.
+    TocTagMissing(FileTag),
+    /// The TTOC lists a tag more than once.
+    TocTagDuplicated(FileTag),
+    /// The TTOC offsets are not in the same order as the tags in the main data.
+    TocOffsetsOutOfOrder,
+}
+
+impl fmt::Display for ValidationIssue {
+    fn fmt(&self, f: &mut fmt::Formatter) -> fmt::Result {
+        match self {
+            ValidationIssue::MissingColor { location, color_id } => {
+                write!(f, "{location}: color not in palette: {color_id:016x}")
+            }
+            ValidationIssue::EmptyPath(location) => write!(f, "{location}: empty path"),
+            ValidationIssue::NonFinitePoint(location) => {
+                write!(f, "{location}: path has a non-finite point")
+            }
+            #[cfg(feature = "pencil")]
+            ValidationIssue::InvalidThicknessDomain { location, domain } => {
+                write!(f, "{location}: invalid thickness domain: {domain:?}")
+            }
+            ValidationIssue::TocTagMissing(tag) => {
+                write!(f, "TTOC lists {tag:?}, which is not in the main data")
+            }
+            ValidationIssue::TocTagDuplicated(tag) => write!(f, "TTOC lists {tag:?} twice"),
+            ValidationIssue::TocOffsetsOutOfOrder => {
+                write!(f, "TTOC offsets are not in main data order")
+            }
+        }
+    }
+}
+
+/// Checks a document for broken invariants. Returns an empty list if there are none.
+///
+/// TTOC offsets are only checked for consistency with the order of tags in the main data, since
+/// a document doesn't retain the byte positions of its tags.
+pub fn validate(document: &Document) -> Vec<ValidationIssue> {
+    let mut issues = Vec::new();
+    validate_colors_and_paths(document, &mut issues);
+    validate_toc(document, &mut issues);
+    issues
+}
+
+fn validate_colors_and_paths(document: &Document, issues: &mut Vec<ValidationIssue>) {
+    let color_ids: HashSet<u64> = document
+        .palette()
+        .iter()
+        .flat_map(|palette| &palette.colors)
+        .filter_map(PaletteColor::id)
+        .collect();
+
+    for (art, layer) in document.layers() {
+        let LayerData::Vector { shapes, .. } = layer else {
+            continue;
+        };
+        for (shape_index, shape) in shapes.iter().enumerate() {
+            for (component_index, component) in shape.components.iter().enumerate() {
+                let location = ComponentLocation {
+                    art,
+                    shape: shape_index,
+                    component: component_index,
+                };
+                for tag in &component.tags {
+                    match tag {
+                        ShapeComponentData::Info(info) => {
+                            if let Some(color_id) = info.color_id {
+                                if !color_ids.contains(&color_id) {
+                                    issues
+                                        .push(ValidationIssue::MissingColor { location, color_id });
+                                }
+                            }
+                        }
+                        ShapeComponentData::Path(path) => {
+                            if path.segments.is_empty() {
+                                issues.push(ValidationIssue::EmptyPath(location));
+                            }
+                            let is_finite = |p: &(f32, f32)| p.0.is_finite() && p.1.is_finite();
+                            let all_finite = path.segments.iter().all(|segment| match segment {
+                                PathSegment::Line(p) => is_finite(p),
+                                PathSegment::Cubic(c1, c2, p) => {
+                                    is_finite(c1) && is_finite(c2) && is_finite(p)
+                                }
+                            });
+                            if !all_finite {
+                                issues.push(ValidationIssue::NonFinitePoint(location));
+                            }
+                        }
+                        #[cfg(feature = "pencil")]
+                        ShapeComponentData::Thickness(thickness) => {
+                            let (start, end) = thickness.domain;
+                            let in_range = |t: f32| (0. ..=1.).contains(&t);
+                            if !in_range(start) || !in_range(end) || start > end {
+                                issues.push(ValidationIssue::InvalidThicknessDomain {
+                                    location,
+                                    domain: thickness.domain,
+                                });
+                            }
+                        }
+                        _ => (),
+                    }
+                }
+            }
+        }
+    }
+}
+
+fn validate_toc(document: &Document, issues: &mut Vec<ValidationIssue>) {
+    let Some(offsets) = document.tags.iter().find_map(|tag| match tag {
+        FileData::MainOffsets { offsets, .. } => Some(offsets),
+        _ => None,
+    }) else {
+        return;
+    };
+
+    let main_tags: Vec<Option<FileTag>> = document
+        .main()
+        .unwrap_or_default()
+        .iter()
+        .map(FileData::tag)
+        .collect();
+
+    let mut seen = HashSet::new();
+    // (position in main data, offset)
+    let mut positions = Vec::new();
+    for &(tag, offset) in offsets {
+        if !seen.insert(tag) {
+            issues.push(ValidationIssue::TocTagDuplicated(tag));
+            continue;
+        }
+        match main_tags.iter().position(|t| *t == Some(tag)) {
+            Some(position) => positions.push((position, offset)),
+            None => issues.push(ValidationIssue::TocTagMissing(tag)),
+        }
+    }
+
+    positions.sort_by_key(|(position, _)| *position);
+    if positions.windows(2).any(|w| w[0].1 >= w[1].1) {
+        issues.push(ValidationIssue::TocOffsetsOutOfOrder);
+    }
+}
@@ -0,0 +1,111 @@
+//! zlib compression, with the backend chosen by cargo features (`zlib-ng`, `miniz_oxide` or
+//! `libflate`, in that order of preference).
+
+use crate::read::ReadError;
+use alloc::vec::Vec;
+
+/// The name of the backend in use, if there is one.
+pub const BACKEND: Option<&str> = if cfg!(feature = "zlib-ng") {
+    Some("zlib-ng")
+} else if cfg!(feature = "miniz_oxide") {
+    Some("miniz_oxide")
+} else if cfg!(feature = "libflate") {
+    Some("libflate")
+} else {
+    None
+};
+
+/// Decompresses zlib data.
+///
+/// Stops after `max_len + 1` bytes of output, so that callers can tell when the data is larger
+/// than they're willing to accept.
+pub fn decompress(data: &[u8], size_hint: usize, max_len: u64) -> Result<Vec<u8>, ReadError> {
+    let limit = max_len.saturating_add(1);
+    // the stated size may be wrong, so don't allocate more than the limit up front
+    let capacity = size_hint.min(usize::try_from(limit).unwrap_or(usize::MAX));
+    imp::decompress(data, capacity, limit)
+}
+
+/// Compresses data in the zlib format.
+pub fn compress(data: &[u8]) -> Result<Vec<u8>, ReadError> {
+    imp::compress(data)
+}
+
+#[cfg(feature = "zlib-ng")]
+mod imp {
+    use super::*;
+    use flate2::{read::ZlibDecoder, write::ZlibEncoder, Compression};
+    use std::io::{Read, Write};
+
+    pub fn decompress(data: &[u8], capacity: usize, limit: u64) -> Result<Vec<u8>, ReadError> {
+        let mut out = Vec::with_capacity(capacity);
+        ZlibDecoder::new(data).take(limit).read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn compress(data: &[u8]) -> Result<Vec<u8>, ReadError> {
+        let mut encoder = ZlibEncoder::new(Vec::new(), Compression::default());
+        encoder.write_all(data)?;
+        Ok(encoder.finish()?)
+    }
+}
+
+#[cfg(all(feature = "miniz_oxide", not(feature = "zlib-ng")))]
+mod imp {
+    use super::*;
+    use crate::io;
+    use miniz_oxide::inflate::TINFLStatus;
+
+    pub fn decompress(data: &[u8], _capacity: usize, limit: u64) -> Result<Vec<u8>, ReadError> {
+        let limit = usize::try_from(limit).unwrap_or(usize::MAX);
+        match miniz_oxide::inflate::decompress_to_vec_zlib_with_limit(data, limit) {
+            Ok(out) => Ok(out),
+            // the output is cut off at the limit, like with the other backends
+            Err(e) if e.status == TINFLStatus::HasMoreOutput => Ok(e.output),
+            Err(e) if e.status == TINFLStatus::FailedCannotMakeProgress => {
+                Err(io::Error::from(io::ErrorKind::UnexpectedEof).into())
+            }
+            Err(_) => Err(io::Error::new(io::ErrorKind::InvalidData, "invalid zlib data").into()),
+        }
+    }
+
+    pub fn compress(data: &[u8]) -> Result<Vec<u8>, ReadError> {
+        Ok(miniz_oxide::deflate::compress_to_vec_zlib(data, 6))
+    }
+}
+
+#[cfg(all(
+    feature = "libflate",
+    not(any(feature = "zlib-ng", feature = "miniz_oxide"))
+))]
+mod imp {
+    use super::*;
+    use std::io::{Read, Write};
+
+    pub fn decompress(data: &[u8], capacity: usize, limit: u64) -> Result<Vec<u8>, ReadError> {
+        let mut out = Vec::with_capacity(capacity);
+        libflate::zlib::Decoder::new(data)?
+            .take(limit)
+            .read_to_end(&mut out)?;
+        Ok(out)
+    }
+
+    pub fn compress(data: &[u8]) -> Result<Vec<u8>, ReadError> {
+        let mut encoder = libflate::zlib::Encoder::new(Vec::new())?;
+        encoder.write_all(data)?;
+        Ok(encoder.finish().into_result()?)
+    }
+}
+
+#[cfg(not(any(feature = "zlib-ng", feature = "miniz_oxide", feature = "libflate")))]
+mod imp {
+    use super::*;
+
+    pub fn decompress(_: &[u8], _: usize, _: u64) -> Result<Vec<u8>, ReadError> {
+        Err(ReadError::ZlibUnavailable)
+    }
+
+    pub fn compress(_: &[u8]) -> Result<Vec<u8>, ReadError> {
+        Err(ReadError::ZlibUnavailable)
+    }
+}
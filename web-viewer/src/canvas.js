@@ -177,8 +177,8 @@ function DrawingLayer({ type, layer, palette }) {
         if (!layer || layer.type !== 'vector') return null;
 
         const items = [];
-        for (let i = 0; i < layer.content.length; i++) {
-            const shape = layer.content[i];
+        for (let i = 0; i < layer.content.shapes.length; i++) {
+            const shape = layer.content.shapes[i];
             const id = [type, i].join('/');
             const isHovering = sel.hovering === id;
             const isSelected = sel.selected === id;
@@ -2,10 +2,11 @@ import { render } from 'preact';
 import { useMemo, useRef, useState } from 'preact/hooks';
 import { html } from 'htm/preact';
 import { decode } from 'msgpackr';
-import initWasm, { readTVG } from '../tvg-wasm-out/tvg_wasm.js';
+import initWasm, { TvgParser } from '../tvg-wasm-out/tvg_wasm.js';
 import { FileMetadata } from './metadata.js';
 import { CanvasView } from './canvas.js';
 import { LayerDataViewer } from './layer-data.js';
+import { RasterPreview } from './preview.js';
 import { selectionContext, shapeVizContext } from './ctx.js';
 
 function LoadFile({ onLoad }) {
@@ -18,16 +19,8 @@ function LoadFile({ onLoad }) {
         const file = e.target.files[0];
         if (!file) return;
 
-        const fr = new FileReader();
-        fr.onload = () => {
-            onLoad(fr.result);
-            fileInput.current.value = null;
-        };
-        fr.onerror = () => {
-            alert('error reading file');
-            fileInput.current.value = null;
-        };
-        fr.readAsArrayBuffer(file);
+        onLoad(file);
+        fileInput.current.value = null;
     };
     const onDrop = (e) => {
         e.preventDefault();
@@ -35,10 +28,7 @@ function LoadFile({ onLoad }) {
         const file = e.dataTransfer.files[0];
         if (!file) return;
 
-        const fr = new FileReader();
-        fr.onload = () => onLoad(fr.result);
-        fr.onerror = () => alert('error reading file');
-        fr.readAsArrayBuffer(file);
+        onLoad(file);
     };
 
     return html`
@@ -66,7 +56,7 @@ function LoadFile({ onLoad }) {
     `;
 }
 
-function Tvg({ file }) {
+function Tvg({ file, data }) {
     const [hovering, setHovering] = useState(null);
     const [selected, setSelected] = useState(null);
     const selection = useMemo(() => ({
@@ -91,6 +81,7 @@ function Tvg({ file }) {
                     <div class="side-panel">
                         <${LayerDataViewer} file=${file} />
                         <${FileMetadata} file=${file} />
+                        <${RasterPreview} data=${data} />
                     </div>
                     <div class="canvas-container">
                         <${CanvasView} file=${file} />
@@ -103,18 +94,31 @@ function Tvg({ file }) {
 
 function Main() {
     const [tvg, setTvg] = useState(null);
+    const [data, setData] = useState(null);
     const [statusText, setStatusText] = useState(null);
 
-    const onLoad = async (data) => {
+    const onLoad = async (file) => {
         try {
             await initWasm();
 
             const t0 = Date.now();
-            const fileData = readTVG(new Uint8Array(data));
+            // parse while the file is being read, so that there's progress to show
+            const parser = new TvgParser(file.size);
+            const reader = file.stream().getReader();
+            const chunks = [];
+            while (true) {
+                const { done, value } = await reader.read();
+                if (done) break;
+                parser.feed(value);
+                chunks.push(value);
+                setStatusText(`loading… ${Math.round(parser.progress() * 100)}%`);
+            }
+            const fileData = parser.finish();
             const t1 = Date.now();
 
             const tvg = decode(fileData);
             console.log('read file', tvg);
+            setData(new Uint8Array(await new Blob(chunks).arrayBuffer()));
             setTvg(tvg);
             setStatusText(`file loaded in ${t1 - t0} ms`);
         } catch (err) {
@@ -125,7 +129,7 @@ function Main() {
 
     let contents = null;
     if (tvg) {
-        contents = html`<${Tvg} file=${tvg} />`;
+        contents = html`<${Tvg} file=${tvg} data=${data} />`;
     }
 
     return html`
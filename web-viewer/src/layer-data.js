@@ -38,7 +38,7 @@ function LayerData({ type, layer }) {
 function LayerContent({ type, layer }) {
     if (layer.type === 'vector') {
         const items = [];
-        for (const shape of layer.content) {
+        for (const shape of layer.content.shapes) {
             items.push(html`<${VectorShape} shape=${shape} id=${[type, items.length].join('/')} />`);
         }
 
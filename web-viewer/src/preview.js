@@ -0,0 +1,46 @@
+import { useEffect, useRef, useState } from 'preact/hooks';
+import { html } from 'htm/preact';
+import { renderToCanvas } from '../tvg-wasm-out/tvg_wasm.js';
+
+const LAYERS = ['underlay', 'color', 'line', 'overlay'];
+
+export function RasterPreview({ data }) {
+    const canvas = useRef();
+    const [layers, setLayers] = useState(LAYERS);
+    const [error, setError] = useState(null);
+
+    useEffect(() => {
+        try {
+            renderToCanvas(data, canvas.current, { scale: 0.2, layers });
+            setError(null);
+        } catch (err) {
+            console.error(err);
+            setError(err.toString());
+        }
+    }, [data, layers]);
+
+    const toggleLayer = (layer) => {
+        if (layers.includes(layer)) setLayers(layers.filter(item => item !== layer));
+        // keep the drawing order
+        else setLayers(LAYERS.filter(item => item === layer || layers.includes(item)));
+    };
+
+    return html`
+        <div class="raster-preview">
+            <div class="preview-layers">
+                ${LAYERS.map(layer => html`
+                    <label>
+                        <input
+                            type="checkbox"
+                            checked=${layers.includes(layer)}
+                            onChange=${() => toggleLayer(layer)}
+                        />
+                        ${layer}
+                    </label>
+                `)}
+            </div>
+            ${error}
+            <canvas ref=${canvas} />
+        </div>
+    `;
+}
@@ -1,11 +1,309 @@
+use serde::Serialize;
+use tvg::color::Rgba;
+use tvg::document::Art;
+use tvg::read::{FileData, FileTag, IncrementalReader, LocatedReadError, ReadError, ReadOptions};
+use tvg::render::RenderOptions;
 use wasm_bindgen::prelude::*;
+use wasm_bindgen::Clamped;
+use web_sys::{CanvasRenderingContext2d, HtmlCanvasElement, ImageData};
 
 fn err_to_js_value(err: impl std::error::Error) -> JsValue {
     JsValue::from_str(&err.to_string())
 }
 
+/// An error from reading a file.
+#[wasm_bindgen(getter_with_clone)]
+pub struct TvgError {
+    /// The kind of error in snake case, e.g. `"unknown_file_tag"`.
+    pub kind: String,
+    /// Offset of the tag that contains the error, if it's known.
+    pub offset: Option<usize>,
+    /// Names of the tags that contain the error, outermost first.
+    #[wasm_bindgen(js_name = "tagPath")]
+    pub tag_path: Vec<String>,
+    pub message: String,
+}
+
+#[wasm_bindgen]
+impl TvgError {
+    #[wasm_bindgen(js_name = "toString")]
+    pub fn display(&self) -> String {
+        let mut out = self.message.clone();
+        if let Some(offset) = self.offset {
+            out += &format!(" (at offset {offset:#x}");
+            if !self.tag_path.is_empty() {
+                out += &format!(", in {}", self.tag_path.join(" > "));
+            }
+            out += ")";
+        }
+        out
+    }
+}
+
+impl From<ReadError> for TvgError {
+    fn from(err: ReadError) -> Self {
+        TvgError {
+            kind: err.kind().into(),
+            offset: None,
+            tag_path: Vec::new(),
+            message: err.to_string(),
+        }
+    }
+}
+
+impl From<LocatedReadError> for TvgError {
+    fn from(err: LocatedReadError) -> Self {
+        TvgError {
+            offset: Some(err.offset),
+            tag_path: err.tag_path.iter().map(|tag| format!("{tag:?}")).collect(),
+            ..err.error.into()
+        }
+    }
+}
+
+fn read_err(err: impl Into<TvgError>) -> JsValue {
+    err.into().into()
+}
+
+/// Formats that data can be returned in.
+#[derive(Debug, Clone, Copy)]
+enum Format {
+    Msgpack,
+    Json,
+    Cbor,
+}
+
+impl Format {
+    fn parse(name: &str) -> Result<Self, JsValue> {
+        match name {
+            "msgpack" => Ok(Format::Msgpack),
+            "json" => Ok(Format::Json),
+            "cbor" => Ok(Format::Cbor),
+            _ => Err(JsValue::from_str(&format!("unknown format: {name}"))),
+        }
+    }
+
+    fn to_vec(self, value: &impl Serialize) -> Result<Vec<u8>, JsValue> {
+        match self {
+            Format::Msgpack => rmp_serde::to_vec_named(value).map_err(err_to_js_value),
+            Format::Json => serde_json::to_vec(value).map_err(err_to_js_value),
+            Format::Cbor => {
+                let mut out = Vec::new();
+                ciborium::into_writer(value, &mut out).map_err(err_to_js_value)?;
+                Ok(out)
+            }
+        }
+    }
+
+    /// Serializes a value as a string for JSON, and as a Uint8Array otherwise.
+    fn to_js_value(self, value: &impl Serialize) -> Result<JsValue, JsValue> {
+        match self {
+            Format::Json => {
+                let json = serde_json::to_string(value).map_err(err_to_js_value)?;
+                Ok(JsValue::from_str(&json))
+            }
+            Format::Msgpack | Format::Cbor => {
+                Ok(js_sys::Uint8Array::from(&self.to_vec(value)?[..]).into())
+            }
+        }
+    }
+}
+
+fn read(data: &[u8]) -> Result<Vec<FileData>, JsValue> {
+    tvg::read::read_located(data, &ReadOptions::default()).map_err(read_err)
+}
+
 #[wasm_bindgen(js_name = "readTVG")]
 pub fn read_tvg(data: &[u8]) -> Result<Vec<u8>, JsValue> {
-    let data = tvg::read::read(&mut std::io::Cursor::new(data)).map_err(err_to_js_value)?;
-    rmp_serde::to_vec_named(&data).map_err(err_to_js_value)
+    let data = read(data)?;
+    Format::Msgpack.to_vec(&data)
+}
+
+/// Reads a file and returns it as `"msgpack"` or `"cbor"` (both as a Uint8Array), or as `"json"`
+/// (as a string).
+#[wasm_bindgen(js_name = "readTVGAs")]
+pub fn read_tvg_as(data: &[u8], format: &str) -> Result<JsValue, JsValue> {
+    let format = Format::parse(format)?;
+    format.to_js_value(&read(data)?)
+}
+
+/// Reads only the palette. Returns `null` if there is none.
+#[wasm_bindgen(js_name = "readPalette")]
+pub fn read_palette(data: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let palette = match read_one(data, FileTag::Palette)? {
+        Some(FileData::Palette(palette)) => Some(palette),
+        _ => None,
+    };
+    Format::Msgpack.to_vec(&palette)
+}
+
+/// Reads only one art layer (`"underlay"`, `"color"`, `"line"` or `"overlay"`). Returns `null` if
+/// there is none.
+#[wasm_bindgen(js_name = "readLayer")]
+pub fn read_layer(data: &[u8], layer: &str) -> Result<Vec<u8>, JsValue> {
+    let layer = read_one(data, parse_art(layer)?.file_tag())?
+        .and_then(FileData::into_layer)
+        .map(|(_, layer)| layer);
+    Format::Msgpack.to_vec(&layer)
+}
+
+/// Reads only the identity (`TVCI`) tag. Returns `null` if there is none.
+#[wasm_bindgen(js_name = "readIdentity")]
+pub fn read_identity(data: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let identity = read_one(data, FileTag::Tvci)?;
+    Format::Msgpack.to_vec(&identity)
+}
+
+/// Reads a file and returns its outline, a tree of short descriptions of the tags, shapes and
+/// colors for the sidebar.
+#[wasm_bindgen(js_name = "readOutline")]
+pub fn read_outline(data: &[u8]) -> Result<Vec<u8>, JsValue> {
+    let document = tvg::document::Document::from(read(data)?);
+    Format::Msgpack.to_vec(&document.outline())
+}
+
+fn read_one(data: &[u8], tag: FileTag) -> Result<Option<FileData>, JsValue> {
+    tvg::read::read_one(data, tag, &ReadOptions::default()).map_err(read_err)
+}
+
+fn parse_art(name: &str) -> Result<Art, JsValue> {
+    Art::from_name(name).ok_or_else(|| JsValue::from_str(&format!("unknown layer: {name}")))
+}
+
+/// Renders a TVG file into a canvas, which is resized to fit the drawing.
+///
+/// `options` is an optional object with these optional properties:
+///
+/// - `scale`: pixels per drawing unit
+/// - `maxPixels`: the most pixels the canvas can have; larger drawings are rendered at a smaller
+///   scale. Default: 4096 × 4096
+/// - `strokeWidth`: width of pencil lines, in drawing units
+/// - `layers`: names of the art layers to show (`"underlay"`, `"color"`, `"line"`, `"overlay"`)
+/// - `background`: `[r, g, b, a]`, 0–255. Transparent if unset
+/// - `underlayOpacity`: opacity of the underlay art, 0–1
+/// - `lineOverColor`: whether line art is drawn over color art (the default) or below it
+#[wasm_bindgen(js_name = "renderToCanvas")]
+pub fn render_to_canvas(
+    data: &[u8],
+    canvas: &HtmlCanvasElement,
+    options: &JsValue,
+) -> Result<(), JsValue> {
+    let data = read(data)?;
+    let options = render_options(options)?;
+    let (image, _) = tvg::render::render(&data, &options);
+
+    canvas.set_width(image.width);
+    canvas.set_height(image.height);
+    let ctx: CanvasRenderingContext2d = canvas
+        .get_context("2d")?
+        .ok_or("canvas has no 2d context")?
+        .dyn_into()?;
+    let image_data = ImageData::new_with_u8_clamped_array_and_sh(
+        Clamped(&image.data),
+        image.width,
+        image.height,
+    )?;
+    ctx.put_image_data(&image_data, 0., 0.)
+}
+
+/// Returns a property of an options object, if it's set.
+fn option(options: &JsValue, key: &str) -> Result<Option<JsValue>, JsValue> {
+    if options.is_undefined() || options.is_null() {
+        return Ok(None);
+    }
+    let value = js_sys::Reflect::get(options, &JsValue::from_str(key))?;
+    Ok((!value.is_undefined() && !value.is_null()).then_some(value))
+}
+
+fn number_option(options: &JsValue, key: &str) -> Result<Option<f64>, JsValue> {
+    option(options, key)?
+        .map(|value| {
+            value
+                .as_f64()
+                .ok_or_else(|| format!("{key} must be a number").into())
+        })
+        .transpose()
+}
+
+fn render_options(options: &JsValue) -> Result<RenderOptions, JsValue> {
+    let mut out = RenderOptions::default();
+    if let Some(scale) = number_option(options, "scale")? {
+        out.mapping.scale = scale as f32;
+    }
+    if let Some(max_pixels) = number_option(options, "maxPixels")? {
+        out.max_pixels = max_pixels as u64;
+    }
+    if let Some(width) = number_option(options, "strokeWidth")? {
+        out.stroke_width = width as f32;
+    }
+    if let Some(layers) = option(options, "layers")? {
+        out.layers = js_sys::Array::from(&layers)
+            .iter()
+            .map(|layer| match layer.as_string() {
+                Some(name) => parse_art(&name),
+                None => Err(JsValue::from_str(&format!("unknown layer: {layer:?}"))),
+            })
+            .collect::<Result<_, _>>()?;
+    }
+    if let Some(background) = option(options, "background")? {
+        let channels: Vec<u8> = js_sys::Array::from(&background)
+            .iter()
+            .map(|channel| channel.as_f64().map(|c| c.clamp(0., 255.) as u8))
+            .collect::<Option<_>>()
+            .ok_or("background must be an array of numbers")?;
+        let [r, g, b, a] = channels[..] else {
+            return Err("background must have 4 channels".into());
+        };
+        out.background = Some(Rgba::new(r, g, b, a));
+    }
+    if let Some(opacity) = number_option(options, "underlayOpacity")? {
+        out.underlay_opacity = opacity as f32;
+    }
+    if let Some(line_over_color) = option(options, "lineOverColor")? {
+        out.line_over_color = line_over_color
+            .as_bool()
+            .ok_or("lineOverColor must be a boolean")?;
+    }
+    Ok(out)
+}
+
+/// Reads a file that arrives in chunks, e.g. from a stream, so that the page stays responsive
+/// and can show progress.
+#[wasm_bindgen(js_name = "TvgParser")]
+pub struct TvgParser {
+    reader: IncrementalReader,
+    total_len: usize,
+}
+
+#[wasm_bindgen(js_class = "TvgParser")]
+impl TvgParser {
+    /// Creates a parser for a file of the given size, which is only used for progress.
+    #[wasm_bindgen(constructor)]
+    pub fn new(total_len: usize) -> TvgParser {
+        TvgParser {
+            reader: IncrementalReader::new(Default::default()),
+            total_len,
+        }
+    }
+
+    pub fn feed(&mut self, chunk: &[u8]) -> Result<(), JsValue> {
+        self.reader.feed(chunk).map_err(read_err)
+    }
+
+    /// Returns how much of the file has been fed, from 0 to 1.
+    ///
+    /// Tags are parsed as soon as they're complete, but most of a file is the main data tag, so
+    /// this is mostly a measure of how much has been loaded.
+    pub fn progress(&self) -> f64 {
+        if self.total_len == 0 {
+            return 0.;
+        }
+        (self.reader.bytes_fed() as f64 / self.total_len as f64).min(1.)
+    }
+
+    /// Returns the file data in the same format as `readTVG`. The parser can't be used afterwards.
+    pub fn finish(self) -> Result<Vec<u8>, JsValue> {
+        let data = self.reader.finish().map_err(read_err)?;
+        Format::Msgpack.to_vec(&data)
+    }
 }